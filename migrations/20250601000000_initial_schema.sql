@@ -0,0 +1,16 @@
+-- Initial EGI database schema: the sites known to EGI and the I2S runs prepared for them.
+CREATE TABLE IF NOT EXISTS sites (
+    site_id TEXT PRIMARY KEY NOT NULL,
+    name TEXT,
+    latitude REAL,
+    longitude REAL,
+    altitude REAL
+);
+
+CREATE TABLE IF NOT EXISTS i2s_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    site_id TEXT NOT NULL REFERENCES sites(site_id),
+    run_date TEXT NOT NULL,
+    run_dir TEXT NOT NULL,
+    created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
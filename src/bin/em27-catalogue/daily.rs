@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use egi_rs::{
+    config::glob_igrams,
+    i2s_catalog::{make_catalog_entries, CatalogEntries, CatalogOptions, MainCatalogError},
+    utils::{
+        pattern_replacement::render_daily_pattern, progress::ProgressReporter, LineEnding,
+        LineEndingWriter,
+    },
+};
+use error_stack::ResultExt;
+use ggg_rs::i2s::{self, OpusCatalogueEntry};
+use log::{info, warn};
+
+use crate::DailyCli;
+
+/// Make catalogs for each date from `args.start_date` to `args.end_date`, inclusive.
+///
+/// The interferograms, coordinates, and met for each date are found using the patterns
+/// in `args.inputs`. Each date's catalog is written to the path given by rendering
+/// `args.output_pattern`, or all dates' catalogs are written to `args.combined_output`.
+pub(crate) fn catalog_daily(args: DailyCli) -> error_stack::Result<(), MainCatalogError> {
+    let mut combined_entries = vec![];
+    let mut glob_error_counts = vec![];
+    let mut n_catalogs = 0;
+    let mut next_run = args.catalog.start_run;
+    let continue_runs = args.continue_runs || args.combined_output.is_some();
+
+    let mut curr_date = args.start_date;
+    if args.end_date < curr_date {
+        warn!("Warning: end date is before start date, no days will be cataloged.");
+    }
+
+    while curr_date <= args.end_date {
+        let first_run = if continue_runs {
+            next_run
+        } else {
+            args.catalog.start_run
+        };
+        let Some((catalog, n_glob_errs)) = catalog_one_day(&args, curr_date, first_run)? else {
+            curr_date += chrono::Duration::days(1);
+            continue;
+        };
+        next_run = catalog.next_run_number;
+        let entries = catalog.entries;
+
+        if n_glob_errs > 0 {
+            glob_error_counts.push((curr_date, n_glob_errs));
+        }
+
+        if let Some(pattern) = &args.output_pattern {
+            let output = render_daily_pattern(pattern, curr_date, &args.site_id)
+                .map(PathBuf::from)
+                .change_context_lazy(|| {
+                    MainCatalogError::Inputs(format!(
+                        "OUTPUT_PATTERN is not valid (while processing {curr_date})"
+                    ))
+                })?;
+            write_catalog(&output, &entries, args.catalog.line_endings)?;
+            info!(
+                "Wrote {} entries for {curr_date} to {}",
+                entries.len(),
+                output.display()
+            );
+            n_catalogs += 1;
+        } else {
+            combined_entries.extend(entries);
+        }
+
+        curr_date += chrono::Duration::days(1);
+    }
+
+    if let Some(output) = &args.combined_output {
+        write_catalog(output, &combined_entries, args.catalog.line_endings)?;
+        info!(
+            "Wrote {} entries to {}",
+            combined_entries.len(),
+            output.display()
+        );
+    } else {
+        info!("Wrote {n_catalogs} daily catalogs");
+    }
+
+    for (date, n) in glob_error_counts {
+        warn!("Warning: there were {n} files on {date} that could not be checked against the glob pattern, double check the catalog for {date}");
+    }
+
+    Ok(())
+}
+
+/// Make the catalog entries for one date.
+///
+/// Returns `None` if the date has no interferogram directory and the user did not ask
+/// to error in that case, or if no interferograms match the glob pattern. Otherwise,
+/// returns the catalog and the number of files that could not be checked against the
+/// glob pattern. The run numbers in the catalog start from `first_run`.
+fn catalog_one_day(
+    args: &DailyCli,
+    curr_date: chrono::NaiveDate,
+    first_run: u32,
+) -> error_stack::Result<Option<(CatalogEntries, u64)>, MainCatalogError> {
+    let pattern_err = |name: &str| {
+        MainCatalogError::Inputs(format!(
+            "{name} is not valid (while processing {curr_date})"
+        ))
+    };
+
+    let igram_dir = args
+        .inputs
+        .igram_dir(curr_date, &args.site_id)
+        .change_context_lazy(|| pattern_err("IGRAM_PATTERN"))?;
+    if !igram_dir.is_dir() {
+        if args.no_skip_missing_dates {
+            return Err(MainCatalogError::Inputs(format!(
+                "The interferogram directory {} does not exist",
+                igram_dir.display()
+            ))
+            .into());
+        }
+        info!("Interferogram directory for {curr_date} missing, assuming no data");
+        return Ok(None);
+    }
+
+    let igram_glob = args
+        .inputs
+        .igram_glob(curr_date, &args.site_id)
+        .change_context_lazy(|| pattern_err("IGRAM_GLOB_PATTERN"))?;
+    let (mut interferograms, n_glob_errs) = glob_igrams(&igram_dir, &igram_glob, false)
+        .change_context_lazy(|| pattern_err("IGRAM_GLOB_PATTERN"))?;
+    if interferograms.is_empty() {
+        info!("No interferograms found for {curr_date}, skipping");
+        return Ok(None);
+    }
+    interferograms.sort();
+
+    let coordinate_file = args
+        .inputs
+        .coord_file(curr_date, &args.site_id)
+        .change_context_lazy(|| pattern_err("COORD_FILE_PATTERN"))?;
+    let met_source_file = args
+        .inputs
+        .met_file(curr_date, &args.site_id)
+        .change_context_lazy(|| pattern_err("MET_FILE_PATTERN"))?;
+
+    info!(
+        "Cataloging {} interferograms for {curr_date}",
+        interferograms.len()
+    );
+    let mut progress = ProgressReporter::new(&format!("Cataloging {curr_date}"));
+    let catalog = make_catalog_entries(
+        &coordinate_file,
+        &[met_source_file],
+        &interferograms,
+        &CatalogOptions {
+            first_run_number: first_run,
+            ..args.catalog.catalog_options(Some(&args.site_id))
+        },
+        Some(&mut |done, total| progress.update(done, total)),
+    )?;
+    progress.finish();
+    args.catalog.report_skips(&catalog, Some(curr_date));
+    Ok(Some((catalog, n_glob_errs)))
+}
+
+fn write_catalog(
+    output: &std::path::Path,
+    entries: &[OpusCatalogueEntry],
+    line_endings: LineEnding,
+) -> error_stack::Result<(), MainCatalogError> {
+    let f = std::fs::File::create(output)
+        .change_context_lazy(|| MainCatalogError::Output(output.to_path_buf()))?;
+    let mut f = LineEndingWriter::new(f, line_endings);
+    i2s::write_opus_catalogue_table(&mut f, entries, false)
+        .change_context_lazy(|| MainCatalogError::Output(output.to_path_buf()))?;
+    Ok(())
+}
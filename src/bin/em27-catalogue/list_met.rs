@@ -0,0 +1,65 @@
+use egi_rs::{
+    i2s_catalog::{load_met_for_igrams, CatalogOptions, MainCatalogError},
+    meteorology::MetEntry,
+};
+use error_stack::ResultExt;
+
+use crate::ListMetCli;
+
+/// Read the met data from `args.met_source_file` and print it as a table or JSON.
+///
+/// The entries go to stdout and the summary of how many entries were read and the
+/// time span they cover goes to stderr, so that the entries can be redirected to a file.
+pub(crate) fn list_met(args: ListMetCli) -> error_stack::Result<(), MainCatalogError> {
+    let options = CatalogOptions {
+        site_id: args.site_id.as_deref(),
+        detectors: args.detectors,
+        default_utc_offset: args.assume_utc_offset,
+        utc_offset_override: args.utc_offset,
+        ..Default::default()
+    };
+    let mut entries =
+        load_met_for_igrams(&args.met_source_file, &args.interferograms, &options)?.entries;
+    entries.sort_by_key(|m| m.datetime);
+
+    if args.json {
+        let mut stdout = std::io::stdout();
+        serde_json::to_writer_pretty(&mut stdout, &entries)
+            .change_context_lazy(|| MainCatalogError::Met(args.met_source_file.clone()))?;
+        println!();
+    } else {
+        print_met_table(&entries);
+    }
+
+    match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => eprintln!(
+            "{} met entries from {} to {}",
+            entries.len(),
+            first.datetime,
+            last.datetime
+        ),
+        _ => eprintln!("0 met entries"),
+    }
+    Ok(())
+}
+
+fn print_met_table(entries: &[MetEntry]) {
+    let fmt_opt = |v: Option<f64>| {
+        v.map(|v| format!("{v:.2}"))
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    println!(
+        "{:<25}  {:>10}  {:>11}  {:>8}",
+        "datetime", "pres (hPa)", "temp (degC)", "RH (%)"
+    );
+    for entry in entries {
+        println!(
+            "{:<25}  {:>10.2}  {:>11}  {:>8}",
+            entry.datetime.to_rfc3339(),
+            entry.pressure,
+            fmt_opt(entry.temperature),
+            fmt_opt(entry.humidity)
+        );
+    }
+}
@@ -1,17 +1,34 @@
 use std::{path::PathBuf, process::ExitCode};
 
-use clap::Parser;
-use clap_verbosity_flag::{Verbosity, WarnLevel};
-use egi_rs::i2s_catalog::{make_catalog_entries, MainCatalogError};
+use chrono::{DateTime, FixedOffset};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use egi_rs::{
+    config::{read_igram_list, DailyInputPatterns, DetectorSet, SiteIdLengthArgs},
+    i2s_catalog::{
+        check_met_coverage, make_catalog_entries, max_run_number_for_digits, CatalogEntries,
+        CatalogOptions, DuplicateCheck, InstrumentMetParams, MainCatalogError, MultiDateCheck,
+    },
+    i2s_top::{write_provenance_comments, CatalogProvenance},
+    utils::{logging::LogArgs, progress::ProgressReporter, LineEnding, LineEndingWriter},
+    CATALOG_FILL_FLOAT_F32,
+};
 use error_stack::ResultExt;
 use ggg_rs::i2s;
 
+mod daily;
+mod list_met;
+
 fn main() -> ExitCode {
     let clargs = Cli::parse();
+    if let Some(site_id) = clargs.site_id() {
+        if let Err(e) = clargs.site_id_length.validate(site_id) {
+            Cli::command()
+                .error(clap::error::ErrorKind::ValueValidation, e)
+                .exit();
+        }
+    }
 
-    env_logger::Builder::new()
-        .filter_level(clargs.verbose.log_level_filter())
-        .init();
+    clargs.logging.init_logger();
 
     log::debug!("Debug-level logging active");
     log::trace!("Trace-level logging active");
@@ -27,38 +44,481 @@ fn main() -> ExitCode {
 }
 
 fn driver(clargs: Cli) -> error_stack::Result<(), MainCatalogError> {
-    let catalogue_entries = make_catalog_entries(
-        &clargs.coordinate_file,
-        &clargs.surface_met_source_file,
-        &clargs.interferograms,
-        clargs.keep_if_missing_met,
+    match clargs.command {
+        Some(CatalogActions::Daily(args)) => return daily::catalog_daily(*args),
+        Some(CatalogActions::ListMet(args)) => return list_met::list_met(args),
+        None => (),
+    }
+
+    if clargs.check {
+        return check_driver(clargs);
+    }
+
+    let interferograms = clargs.all_interferograms()?;
+    let mut progress = ProgressReporter::new("Cataloging interferograms");
+    let catalog = make_catalog_entries(
+        clargs.coordinate_file(),
+        &clargs.surface_met_source_files,
+        &interferograms,
+        &clargs.catalog.catalog_options(clargs.site_id.as_deref()),
+        Some(&mut |done, total| progress.update(done, total)),
     )?;
+    progress.finish();
+    clargs.catalog.report_skips(&catalog, None);
+    if let Some(path_map) = &clargs.path_map {
+        catalog.write_path_map(path_map)?;
+    }
+    if let Some(met_flags) = &clargs.met_flags {
+        catalog.write_met_flags(met_flags)?;
+    }
+    let catalogue_entries = catalog.entries;
+    let provenance = clargs.provenance_comments.then(|| CatalogProvenance {
+        coordinate_file: clargs.coordinate_file(),
+        met_files: &clargs.surface_met_source_files,
+    });
 
-    let mut stdout = std::io::stdout();
-    i2s::write_opus_catalogue_table(&mut stdout, &catalogue_entries, false)
-        .change_context_lazy(|| MainCatalogError::Catalog)?;
+    if let Some(output) = &clargs.output {
+        let f = std::fs::File::create(output)
+            .change_context_lazy(|| MainCatalogError::Output(output.clone()))?;
+        let mut f = LineEndingWriter::new(f, clargs.catalog.line_endings);
+        if let Some(provenance) = &provenance {
+            write_provenance_comments(&mut f, provenance)
+                .change_context_lazy(|| MainCatalogError::Output(output.clone()))?;
+        }
+        i2s::write_opus_catalogue_table(&mut f, &catalogue_entries, false)
+            .change_context_lazy(|| MainCatalogError::Output(output.clone()))?;
+    } else {
+        let mut stdout = LineEndingWriter::new(std::io::stdout(), clargs.catalog.line_endings);
+        if let Some(provenance) = &provenance {
+            write_provenance_comments(&mut stdout, provenance)
+                .change_context_lazy(|| MainCatalogError::Catalog)?;
+        }
+        i2s::write_opus_catalogue_table(&mut stdout, &catalogue_entries, false)
+            .change_context_lazy(|| MainCatalogError::Catalog)?;
+    }
+    Ok(())
+}
+
+fn check_driver(clargs: Cli) -> error_stack::Result<(), MainCatalogError> {
+    let interferograms = clargs.all_interferograms()?;
+    let coverage = check_met_coverage(
+        clargs.coordinate_file(),
+        &clargs.surface_met_source_files,
+        &interferograms,
+        &clargs.catalog.catalog_options(clargs.site_id.as_deref()),
+    )?;
+
+    let fmt_span = |span: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>| {
+        span.map(|(first, last)| format!("{first} to {last}"))
+            .unwrap_or_else(|| "(no data)".to_string())
+    };
+
+    println!(
+        "{} interferograms, ZPD times {}",
+        coverage.n_igrams,
+        fmt_span(coverage.igram_time_span)
+    );
+    for met in coverage.met.iter() {
+        println!("Met source {}:", met.source_file.display());
+        println!("  met times {}", fmt_span(met.met_time_span));
+        println!(
+            "  {} interferograms inside, {} outside the met time domain",
+            met.n_inside, met.n_outside
+        );
+    }
+    println!(
+        "{} of {} interferograms have met data available",
+        coverage.n_covered, coverage.n_igrams
+    );
     Ok(())
 }
 
 /// Generate an I2S catalogue for EM27 interferograms
+///
+/// By default, this catalogs one list of interferograms given on the command line. Use the
+/// "daily" subcommand to catalog each date in a range of dates, finding the inputs for each
+/// date from path patterns.
 #[derive(Debug, clap::Parser)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<CatalogActions>,
+
     #[command(flatten)]
-    verbose: Verbosity<WarnLevel>,
+    logging: LogArgs,
 
-    /// Set this flag to include an interferogram even if there isn't surface met data available to match up with it.
-    /// The default is to skip it, since GGG requires surface pressure to perform the retrieval.
-    #[clap(long)]
-    keep_if_missing_met: bool,
+    #[command(flatten)]
+    site_id_length: SiteIdLengthArgs,
+
+    #[command(flatten)]
+    catalog: CatalogArgs,
 
     /// Path to a coordinates JSON file (required). See the documentation for [`CoordinateSource`] for allowed formats.
-    #[clap(long = "coords")]
-    coordinate_file: PathBuf,
+    #[clap(long = "coords", required = true)]
+    coordinate_file: Option<PathBuf>,
 
     /// Path to a surface met source description file (required). See the documentation for [`MetSource`] for allowed formats.
-    #[clap(long = "surf-met")]
-    surface_met_source_file: PathBuf,
+    /// This may be given multiple times, in which case each interferogram will use the first source (in the order given)
+    /// that has met data available at its ZPD time.
+    #[clap(long = "surf-met", required = true)]
+    surface_met_source_files: Vec<PathBuf>,
+
+    /// Check how many interferograms have surface met data available and print a summary,
+    /// rather than writing the catalogue.
+    #[clap(long, conflicts_with = "output")]
+    check: bool,
+
+    /// Path to write the catalogue to. If not given, the catalogue is written to stdout.
+    #[clap(short = 'o', long)]
+    output: Option<PathBuf>,
+
+    /// Also write a file mapping each cataloged interferogram's file name to its absolute path, to help
+    /// trace spectra back to their interferograms. If this path ends in ".json", the map is written as
+    /// JSON; otherwise it is written as a tab-separated table.
+    #[clap(long, conflicts_with = "check")]
+    path_map: Option<PathBuf>,
+
+    /// Also write a tab-separated file with three flag characters for each cataloged interferogram,
+    /// indicating whether its surface pressure, temperature, and humidity were interpolated ("I"),
+    /// held from the nearest met data point ("H"), or filled ("F").
+    #[clap(long, conflicts_with = "check")]
+    met_flags: Option<PathBuf>,
+
+    /// Write comment lines before the catalogue recording the EGI-RS version, when the
+    /// catalogue was made, and the coordinate and met files used. I2S ignores these lines.
+    #[clap(long, conflicts_with = "check")]
+    provenance_comments: bool,
+
+    /// The site ID of the EM27 (optional; two characters, unless --site-id-length is given). Only needed if a met source uses
+    /// it, e.g. an ExtScriptV1 source with the {SITE_ID} placeholder in its arguments.
+    #[clap(long)]
+    site_id: Option<String>,
+
+    /// A file listing interferograms to add to the catalogue, one path per line. Blank lines and lines
+    /// starting with "#" are ignored. Use this instead of (or as well as) giving the interferograms as
+    /// arguments when there are too many for the command line. The interferograms from the file come
+    /// after any given as arguments.
+    #[clap(long)]
+    igram_list: Option<PathBuf>,
 
     /// Paths to the interferograms to add to the catalogue.
     interferograms: Vec<PathBuf>,
 }
+
+impl Cli {
+    /// The site ID given to the main command or subcommand, if any.
+    fn site_id(&self) -> Option<&str> {
+        match &self.command {
+            Some(CatalogActions::Daily(args)) => Some(&args.site_id),
+            Some(CatalogActions::ListMet(args)) => args.site_id.as_deref(),
+            None => self.site_id.as_deref(),
+        }
+    }
+
+    /// The interferograms given as arguments followed by those in the `--igram-list` file, if given.
+    fn all_interferograms(&self) -> error_stack::Result<Vec<PathBuf>, MainCatalogError> {
+        let mut interferograms = self.interferograms.clone();
+        if let Some(list_file) = &self.igram_list {
+            let listed = read_igram_list(list_file).change_context_lazy(|| {
+                MainCatalogError::Inputs(format!(
+                    "could not read the interferogram list {}",
+                    list_file.display()
+                ))
+            })?;
+            log::info!(
+                "Read {} interferograms from {}",
+                listed.len(),
+                list_file.display()
+            );
+            interferograms.extend(listed);
+        }
+        Ok(interferograms)
+    }
+
+    fn coordinate_file(&self) -> &std::path::Path {
+        self.coordinate_file
+            .as_deref()
+            .expect("--coords should be required by the command line parser")
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum CatalogActions {
+    /// Make catalogues for a range of dates, finding the interferograms, coordinates,
+    /// and met for each date from path patterns.
+    Daily(Box<DailyCli>),
+
+    /// Print the met data read from a surface met source file, to check that the file is
+    /// configured correctly before making catalogues.
+    ListMet(ListMetCli),
+}
+
+#[derive(Debug, Args)]
+struct ListMetCli {
+    /// Path to the surface met source description file to read.
+    met_source_file: PathBuf,
+
+    /// Paths to interferograms to read the met data for. Some met sources use the interferograms'
+    /// ZPD times to infer the met time zone or to decide which met data to read, so these should be
+    /// interferograms you intend to catalogue.
+    #[clap(required = true)]
+    interferograms: Vec<PathBuf>,
+
+    /// Print the met entries as JSON rather than a table.
+    #[clap(long)]
+    json: bool,
+
+    /// The site ID of the EM27 (optional), see the main command.
+    #[clap(long)]
+    site_id: Option<String>,
+
+    /// The detector set of the EM27 (optional), see the main command.
+    #[clap(short = 'd', long)]
+    detectors: Option<DetectorSet>,
+
+    /// The UTC offset in hours to assume for interferograms whose header time does not include
+    /// a GMT offset, see the main command.
+    #[clap(long, allow_negative_numbers = true, value_parser = parse_utc_offset)]
+    assume_utc_offset: Option<FixedOffset>,
+
+    /// The UTC offset in hours to use for all interferograms, ignoring the GMT offset in their
+    /// headers, see the main command.
+    #[clap(long, allow_negative_numbers = true, value_parser = parse_utc_offset, conflicts_with = "assume_utc_offset")]
+    utc_offset: Option<FixedOffset>,
+}
+
+#[derive(Debug, Args)]
+struct DailyCli {
+    #[command(flatten)]
+    inputs: DailyInputPatterns,
+
+    #[command(flatten)]
+    catalog: CatalogArgs,
+
+    /// A path with a date placeholder to write each date's catalogue to, using the same
+    /// sort of patterns as IGRAM_PATTERN, e.g. "/data/{SITE_ID}/catalogs/{DATE:%Y%m%d}.txt".
+    /// Either this or --combined-output is required.
+    #[clap(short = 'o', long, required_unless_present = "combined_output")]
+    output_pattern: Option<String>,
+
+    /// Write the catalogue entries for all dates to this file, rather than one file per date.
+    #[clap(long, conflicts_with = "output_pattern")]
+    combined_output: Option<PathBuf>,
+
+    /// Start each date's run numbers where the previous date's ended (beginning from --start-run),
+    /// so that the daily catalogues can be concatenated. This is always done with --combined-output.
+    #[clap(long)]
+    continue_runs: bool,
+
+    /// If a date in the date range does not have an interferogram directory,
+    /// raise an error rather than continuing.
+    #[clap(short = 's', long)]
+    no_skip_missing_dates: bool,
+
+    /// The site ID of the EM27 (two characters, unless --site-id-length is given), used for the {SITE_ID} placeholder in the patterns
+    /// and passed to met sources that need it.
+    site_id: String,
+
+    /// The first date to catalogue, in YYYY-MM-DD format.
+    start_date: chrono::NaiveDate,
+
+    /// The last date to catalogue, in YYYY-MM-DD format.
+    end_date: chrono::NaiveDate,
+}
+
+/// Options controlling how the catalogue entries are made, common to all modes.
+#[derive(Debug, Args)]
+struct CatalogArgs {
+    /// Set this flag to include an interferogram even if there isn't surface met data available to match up with it.
+    /// The default is to skip it, since GGG requires surface pressure to perform the retrieval.
+    #[clap(long)]
+    keep_if_missing_met: bool,
+
+    /// Set this flag to include an interferogram even if its header does not have the instrument temperature,
+    /// using the fill value for it. The default is to stop with an error.
+    #[clap(long)]
+    keep_if_missing_tins: bool,
+
+    /// Set this flag to fail if the interferograms have ZPD times on more than one date. By default,
+    /// a warning is printed instead.
+    #[clap(long)]
+    strict_dates: bool,
+
+    /// Set this flag to fail if some interferograms appear to be copies of the same scan (same ZPD time and
+    /// file size), e.g. if the glob matched a renamed copy of an interferogram. By default, a warning is printed
+    /// and only the first of each set of copies is cataloged.
+    #[clap(long)]
+    strict_duplicates: bool,
+
+    /// How much to advance the run number between interferograms in the catalogue. The default of 2 assumes
+    /// each interferogram has a forward and a reverse scan; use 1 for forward-only acquisition.
+    #[clap(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    run_step: u32,
+
+    /// The run number of the first interferogram in the catalogue. Use this to continue the run numbers
+    /// of another catalogue, so that the two can be concatenated for one I2S run.
+    #[clap(long, default_value_t = 1)]
+    start_run: u32,
+
+    /// How many digits the run numbers have in the I2S spectrum names, i.e. the number of R's in the
+    /// spectrum name template. Cataloguing fails if the run numbers would not fit. The default of 4
+    /// matches the usual "RRRR".
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=9))]
+    run_digits: u32,
+
+    /// The parameter in the instrument status block of the interferogram headers to read the instrument
+    /// pressure (in hPa) from. If an interferogram does not have this parameter, the surface pressure is used.
+    #[clap(long, default_value = "PRS")]
+    instrument_pressure_param: String,
+
+    /// The parameter in the instrument status block of the interferogram headers to read the instrument
+    /// relative humidity (in percent) from. If an interferogram does not have this parameter, the surface
+    /// humidity is used.
+    #[clap(long, default_value = "HUM")]
+    instrument_humidity_param: String,
+
+    /// The parameter in the instrument status block of the interferogram headers to read the instrument
+    /// temperature from. Give a comma-separated list (or this option multiple times) to try several
+    /// parameters in order, for interferograms from different OPUS versions.
+    #[clap(long, default_value = "TSC", value_delimiter = ',')]
+    instrument_temperature_param: Vec<String>,
+
+    /// Set this flag to always use the surface pressure and humidity for the instrument pressure and humidity,
+    /// even if the interferogram headers include them.
+    #[clap(long)]
+    no_instrument_met: bool,
+
+    /// Only catalogue interferograms with ZPD times within this many hours of local solar noon, calculated
+    /// from the longitude in the coordinates. By default, interferograms at any time of day are catalogued.
+    #[clap(long, value_parser = parse_solar_window)]
+    solar_window: Option<f64>,
+
+    /// Only catalogue interferograms with a solar zenith angle of at most this many degrees at their
+    /// ZPD time and location, e.g. to leave out interferograms taken with the sun low in the sky. By
+    /// default, interferograms at any solar zenith angle are catalogued.
+    #[clap(long, value_parser = parse_max_sza)]
+    max_sza: Option<f64>,
+
+    /// Skip interferograms with fewer than this many data points in their primary channel, e.g. from
+    /// a truncated acquisition. The default of 1 only skips interferograms with no data points.
+    #[clap(long, default_value_t = 1)]
+    min_points: u32,
+
+    /// Which line endings to write the catalogue with, "lf" (the default) or "crlf". Use "crlf"
+    /// if your I2S build requires Windows-style line endings.
+    #[clap(long, value_enum, default_value_t = LineEnding::Lf)]
+    line_endings: LineEnding,
+
+    /// Set this flag to list every interferogram left out of the catalogue, and why, after the summary of how
+    /// many interferograms were cataloged. (The list is also printed at the info logging level.)
+    #[clap(long)]
+    verbose_skips: bool,
+
+    /// The value to write in the catalogue for any missing values (e.g. met data that could not be found).
+    #[clap(long, default_value_t = CATALOG_FILL_FLOAT_F32, allow_negative_numbers = true)]
+    fill_value: f32,
+
+    /// The detector set of the EM27, "single", "dual", or "midir" (optional). Only needed if
+    /// a met source uses it, e.g. an ExtScriptV1 source with the {DETECTORS} placeholder in
+    /// its arguments.
+    #[clap(short = 'd', long)]
+    detectors: Option<DetectorSet>,
+
+    /// The UTC offset in hours (e.g. 0 or -8) to assume for interferograms whose header time does not
+    /// include a GMT offset. By default, such interferograms cause an error, since assuming the wrong
+    /// offset would give the wrong ZPD times.
+    #[clap(long, allow_negative_numbers = true, value_parser = parse_utc_offset)]
+    assume_utc_offset: Option<FixedOffset>,
+
+    /// The UTC offset in hours (e.g. 0 or -8) to use for all interferograms, ignoring the GMT offset in
+    /// their headers. Use this if the headers have the wrong offset, e.g. because the acquisition computer's
+    /// time zone was set incorrectly.
+    #[clap(long, allow_negative_numbers = true, value_parser = parse_utc_offset, conflicts_with = "assume_utc_offset")]
+    utc_offset: Option<FixedOffset>,
+}
+
+impl CatalogArgs {
+    /// Print a summary of how many interferograms were cataloged and skipped to stderr (so that it
+    /// does not mix with a catalogue written to stdout), along with the skipped interferograms if
+    /// requested. `date` is included in the summary when cataloging multiple dates.
+    fn report_skips(&self, catalog: &CatalogEntries, date: Option<chrono::NaiveDate>) {
+        let prefix = date.map(|d| format!("{d}: ")).unwrap_or_default();
+        eprintln!("{prefix}{}", catalog.summary());
+        for (igram, reason) in catalog.skipped.iter() {
+            if self.verbose_skips {
+                eprintln!("  skipped {} because {reason}", igram.display());
+            } else {
+                log::info!("{prefix}skipped {} because {reason}", igram.display());
+            }
+        }
+    }
+
+    fn catalog_options<'a>(&'a self, site_id: Option<&'a str>) -> CatalogOptions<'a> {
+        CatalogOptions {
+            keep_if_missing_met: self.keep_if_missing_met,
+            keep_if_missing_tins: self.keep_if_missing_tins,
+            fill: self.fill_value,
+            site_id,
+            detectors: self.detectors,
+            multi_date_check: if self.strict_dates {
+                MultiDateCheck::Error
+            } else {
+                MultiDateCheck::Warn
+            },
+            duplicate_check: if self.strict_duplicates {
+                DuplicateCheck::Error
+            } else {
+                DuplicateCheck::Warn
+            },
+            run_step: self.run_step,
+            first_run_number: self.start_run,
+            max_run_number: max_run_number_for_digits(self.run_digits),
+            instrument_met_params: if self.no_instrument_met {
+                InstrumentMetParams::none()
+            } else {
+                InstrumentMetParams {
+                    pressure: Some(&self.instrument_pressure_param),
+                    humidity: Some(&self.instrument_humidity_param),
+                }
+            },
+            instrument_temperature_params: &self.instrument_temperature_param,
+            default_utc_offset: self.assume_utc_offset,
+            utc_offset_override: self.utc_offset,
+            solar_window: self.solar_window,
+            max_sza: self.max_sza,
+            min_points: self.min_points,
+        }
+    }
+}
+
+fn parse_solar_window(s: &str) -> Result<f64, String> {
+    let hours: f64 = s
+        .parse()
+        .map_err(|_| format!("'{s}' is not a number of hours"))?;
+    if (0.0..=12.0).contains(&hours) {
+        Ok(hours)
+    } else {
+        Err(format!("solar window {hours} h must be between 0 and 12"))
+    }
+}
+
+fn parse_max_sza(s: &str) -> Result<f64, String> {
+    let degrees: f64 = s
+        .parse()
+        .map_err(|_| format!("'{s}' is not a number of degrees"))?;
+    if (0.0..=180.0).contains(&degrees) {
+        Ok(degrees)
+    } else {
+        Err(format!(
+            "maximum SZA {degrees} deg must be between 0 and 180"
+        ))
+    }
+}
+
+fn parse_utc_offset(s: &str) -> Result<FixedOffset, String> {
+    let hours: f64 = s
+        .parse()
+        .map_err(|_| format!("'{s}' is not a number of hours"))?;
+    FixedOffset::east_opt((hours * 3600.0).round() as i32)
+        .ok_or_else(|| format!("UTC offset {hours} h is out of the allowed range (-24 to +24)"))
+}
@@ -4,20 +4,51 @@ use std::{
     process::{Command, Stdio},
 };
 
-use egi_rs::utils::{get_user_menu_selection, read_menu_file};
+use egi_rs::utils::{
+    find_em27_linelist_index, find_em27_window_index, get_user_menu_selection, read_menu_file,
+    Em27LinelistError, Em27WindowError,
+};
 use egi_rs::{default_files, utils::pattern_replacement::render_postproc_script_pattern};
 use error_stack::ResultExt;
 use ggg_rs::utils::get_ggg_path;
 
-use crate::CliError;
+use crate::{priors::check_priors_ready, CliError};
+
+/// How many lines of gsetup's output to include in the error if it fails.
+const GSETUP_OUTPUT_TAIL_LINES: usize = 20;
+
+/// The gsetup answer for ground-based spectra; this matches the "gnd" subdirectories
+/// we read the runlog and window menus from.
+const GROUND_GEOMETRY: &str = "g";
+
+/// One answer to give to a gsetup prompt, in the order that gsetup asks them.
+struct GsetupAnswer {
+    /// A description of the prompt being answered, for logging
+    prompt: &'static str,
+    /// The value to write to gsetup's stdin for this prompt
+    value: String,
+}
+
+impl GsetupAnswer {
+    fn new<V: ToString>(prompt: &'static str, value: V) -> Self {
+        Self {
+            prompt,
+            value: value.to_string(),
+        }
+    }
+
+    /// Join the answers into the text to write to gsetup's stdin.
+    fn to_stdin(answers: &[Self]) -> String {
+        answers.iter().map(|a| format!("{}\n", a.value)).collect()
+    }
+}
 
 pub(super) fn run_gsetup(
     run_dir: &Path,
     runlog_name: Option<&str>,
+    skip_priors_check: bool,
+    site_id_length: usize,
 ) -> error_stack::Result<(), CliError> {
-    // TODO: check that the priors are ready, download if needed, abort if some of the priors are
-    // not available yet.
-
     if !run_dir.exists() {
         std::fs::create_dir(run_dir).change_context_lazy(|| {
             CliError::other(format!(
@@ -37,22 +68,22 @@ pub(super) fn run_gsetup(
 
     // We will need the window menu to find the em27 window file; get that now so we don't
     // prompt the user if we can't finish the rest of the setup
-    let win_menu_file = ggg_path.join("windows").join("gnd").join("windows.men");
-    let window_options = read_menu_file(&win_menu_file).change_context_lazy(|| {
-        CliError::missing_input(format!("Could not read {}", win_menu_file.display()))
+    let em27_win_index = find_em27_window_index(&ggg_path).map_err(|e| {
+        let context = match &e {
+            Em27WindowError::ReadMenu(..) => CliError::missing_input(&e),
+            Em27WindowError::NotFound(_) => CliError::bad_input(&e),
+        };
+        error_stack::Report::new(e).change_context(context)
+    })?;
+
+    // Likewise for the EM27 linelist in the spectral region menu
+    let linelist_index = find_em27_linelist_index(&ggg_path).map_err(|e| {
+        let context = match &e {
+            Em27LinelistError::ReadMenu(..) => CliError::missing_input(&e),
+            Em27LinelistError::NotFound(_) => CliError::bad_input(&e),
+        };
+        error_stack::Report::new(e).change_context(context)
     })?;
-    let em27_win_index = window_options
-        .iter()
-        .find_map(|entry| {
-            // TODO: ensure that this window file is added to the windows/gnd directory and menu by
-            // a first-time setup function.
-            if entry.value == "em27.gnd" {
-                Some(entry.index)
-            } else {
-                None
-            }
-        })
-        .ok_or_else(|| CliError::bad_input("Could not find 'em27.gnd' in the windows menu file; have you run the EGI initialization on the current GGG installation?"))?;
 
     // We need to read the runlog menu to determine what value to pass to gsetup.
     let menu_file = ggg_path.join("runlogs").join("gnd").join("runlogs.men");
@@ -81,10 +112,49 @@ pub(super) fn run_gsetup(
         })?
     };
 
+    // We need the runlog and site ID to fill in the EM27 post_processing.sh once gsetup
+    // is done; get them now so that a bad runlog name fails before running gsetup.
+    let runlog_name = runlog_options
+        .iter()
+        .find_map(|entry| {
+            if entry.index != runlog_index {
+                return None;
+            }
+
+            // Get the runlog name as the part before the period. If no period (which shouldn't
+            // happen), assume that the full value is the runlog name.
+            if let Some((stem, _)) = entry.value.rsplit_once('.') {
+                Some(stem)
+            } else {
+                Some(&entry.value)
+            }
+        })
+        .expect("Failed to get the runlog with our previously found index");
+
+    let site_id = site_id_from_runlog(runlog_name, site_id_length)?;
+
+    // Make sure the priors are available before running gsetup, since GFIT cannot
+    // run without them.
+    if skip_priors_check {
+        log::info!("Skipping check that priors are available");
+    } else {
+        let runlog_value = runlog_options
+            .iter()
+            .find(|entry| entry.index == runlog_index)
+            .map(|entry| entry.value.as_str())
+            .ok_or_else(|| {
+                CliError::bad_input(format!("No runlog at index {runlog_index} in the menu"))
+            })?;
+        let runlog_file = ggg_path.join("runlogs").join("gnd").join(runlog_value);
+        check_priors_ready(&ggg_path, &runlog_file)?;
+    }
+
     let gsetup = ggg_path.join("bin").join("gsetup");
     let mut child = Command::new(gsetup)
         .current_dir(run_dir)
         .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .change_context_lazy(|| CliError::program_error("Error occurred while calling gsetup"))?;
 
@@ -96,7 +166,26 @@ pub(super) fn run_gsetup(
     // The example (https://doc.rust-lang.org/std/process/struct.Stdio.html) spawns a thread
     // to write to stdin, I assume this is to prevent a deadlock, or possibly to move the stdin
     // handle out of the parent so that wait_with_output doesn't close it.
-    let gsetup_input = format!("g\n{runlog_index}\n5\n{em27_win_index}\ny\n");
+    let gsetup_answers = [
+        GsetupAnswer::new("spectrum geometry", GROUND_GEOMETRY),
+        GsetupAnswer::new(
+            "runlog (index in $GGGPATH/runlogs/gnd/runlogs.men)",
+            runlog_index,
+        ),
+        GsetupAnswer::new(
+            "spectral region (index of atm.161 in $GGGPATH/linelist/linelist.men)",
+            linelist_index,
+        ),
+        GsetupAnswer::new(
+            "window list (index of em27.gnd in $GGGPATH/windows/gnd/windows.men)",
+            em27_win_index,
+        ),
+        GsetupAnswer::new("confirm setup", "y"),
+    ];
+    for answer in gsetup_answers.iter() {
+        log::debug!("gsetup prompt '{}' -> {}", answer.prompt, answer.value);
+    }
+    let gsetup_input = GsetupAnswer::to_stdin(&gsetup_answers);
     std::thread::spawn(move || {
         stdin
             .write_all(gsetup_input.as_bytes())
@@ -107,35 +196,35 @@ pub(super) fn run_gsetup(
         CliError::program_error("Error occurred while waiting for gsetup to finish")
     })?;
 
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
     if !output.status.success() {
-        // TODO: should get the gsetup output and print so the user knows what happened.
-        return Err(CliError::program_error("gsetup did not run successfully").into());
+        return Err(CliError::program_error(format!(
+            "gsetup did not run successfully ({}). The last lines of its output were:\n{}",
+            output.status,
+            output_tail(&stdout, &stderr, GSETUP_OUTPUT_TAIL_LINES)
+        ))
+        .into());
+    }
+    log::debug!("gsetup stdout:\n{stdout}");
+    log::debug!("gsetup stderr:\n{stderr}");
+
+    // If gsetup's prompts changed order, it may still exit successfully but will not have set up
+    // the run directory correctly. Its main product is multiggg.sh, so at least check that was created.
+    let multiggg = run_dir.join("multiggg.sh");
+    if !multiggg.is_file() {
+        return Err(CliError::program_error(format!(
+            "gsetup finished but did not create {}; the answers given to its prompts may not match what it asked. The last lines of its output were:\n{}",
+            multiggg.display(),
+            output_tail(&stdout, &stderr, GSETUP_OUTPUT_TAIL_LINES)
+        ))
+        .into());
     }
 
     // Finally we can overwrite the existing post_processing.sh in our run directory with the EM27
     // specific one. We need the runlog and site ID to substitute in, as well as the GGGPATH as a
     // string.
     let ggg_path_str = ggg_path.to_string_lossy();
-    let runlog_name = runlog_options
-        .iter()
-        .find_map(|entry| {
-            if entry.index != runlog_index {
-                return None;
-            }
-
-            // Get the runlog name as the part before the period. If no period (which shouldn't
-            // happen), assume that the full value is the runlog name.
-            if let Some((stem, _)) = entry.value.rsplit_once('.') {
-                Some(stem)
-            } else {
-                Some(&entry.value)
-            }
-        })
-        .expect("Failed to get the runlog with our previously found index");
-
-    let (i, _) = runlog_name.char_indices().nth(1)
-        .ok_or_else(|| CliError::bad_input(format!("Runlog name ({runlog_name}) too short; it did not have at least the two character site ID at the start")))?;
-    let site_id = &runlog_name[..i + 1];
 
     let postproc_script_contents = render_postproc_script_pattern(
         default_files::POSTPROC_SCRIPT,
@@ -160,3 +249,55 @@ pub(super) fn run_gsetup(
         })?;
     Ok(())
 }
+
+/// Get the site ID from the start of `runlog_name`, which must be at least `site_id_length` characters long.
+fn site_id_from_runlog(runlog_name: &str, site_id_length: usize) -> Result<&str, CliError> {
+    match runlog_name.char_indices().nth(site_id_length) {
+        Some((i, _)) => Ok(&runlog_name[..i]),
+        None if runlog_name.chars().count() == site_id_length => Ok(runlog_name),
+        None => Err(CliError::bad_input(format!(
+            "Runlog name ({runlog_name}) is too short; it should start with the {site_id_length} character site ID (use --site-id-length if your site IDs are not {site_id_length} characters)"
+        ))),
+    }
+}
+
+/// Get the last `n` lines of a program's output, with stdout followed by stderr.
+///
+/// Since stdout and stderr are captured separately, we cannot interleave them in the
+/// order they were printed, but stderr is usually the most relevant part when a program
+/// fails, so it goes last.
+fn output_tail(stdout: &str, stderr: &str, n: usize) -> String {
+    let lines: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+    let start = lines.len().saturating_sub(n);
+    lines[start..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{output_tail, site_id_from_runlog, GsetupAnswer};
+
+    #[test]
+    fn test_gsetup_answers_to_stdin() {
+        let answers = [
+            GsetupAnswer::new("geometry", "g"),
+            GsetupAnswer::new("runlog", 12),
+            GsetupAnswer::new("confirm", "y"),
+        ];
+        assert_eq!(GsetupAnswer::to_stdin(&answers), "g\n12\ny\n");
+    }
+
+    #[test]
+    fn test_site_id_from_runlog() {
+        assert_eq!(site_id_from_runlog("xx20240401", 2).unwrap(), "xx");
+        assert_eq!(site_id_from_runlog("x20240401", 1).unwrap(), "x");
+        assert_eq!(site_id_from_runlog("abc", 3).unwrap(), "abc");
+        assert!(site_id_from_runlog("ab", 3).is_err());
+    }
+
+    #[test]
+    fn test_output_tail() {
+        assert_eq!(output_tail("a\nb\nc\n", "d\n", 2), "c\nd");
+        assert_eq!(output_tail("a\nb\n", "", 5), "a\nb");
+        assert_eq!(output_tail("", "", 5), "");
+    }
+}
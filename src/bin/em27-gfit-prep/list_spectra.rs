@@ -1,9 +1,13 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
 
 use error_stack::ResultExt;
 
 use egi_rs::{
-    config::DailyCommonArgs,
+    config::{DailyCommonArgs, ExcludeDatesArgs, ExcludedDates},
     utils::{ensure_trailing_path_sep, pattern_replacement::render_daily_pattern},
 };
 use ggg_rs::{tccon::sort_spectra::sort_spectra_in_dirs, utils::iter_dates};
@@ -11,24 +15,66 @@ use log::{debug, info};
 
 use crate::CliError;
 
+/// Where to find the spectrum directories: the run directory pattern plus the name of
+/// the spectra subdirectory in each run directory (see [`DailyCommonArgs::spectra_subdir`]).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpecDirPattern<'a> {
+    pub(crate) run_dir_pattern: &'a str,
+    pub(crate) spectra_subdir: &'a str,
+}
+
+impl SpecDirPattern<'_> {
+    /// The spectrum directory for `date`.
+    fn render(
+        &self,
+        date: chrono::NaiveDate,
+        site_id: &str,
+    ) -> error_stack::Result<PathBuf, CliError> {
+        let run_dir = render_daily_pattern(self.run_dir_pattern, date, site_id)
+            .change_context_lazy(|| {
+                CliError::BadInput("The RUN_DIR_PATTERN was not valid".to_string())
+            })?;
+        Ok(PathBuf::from(run_dir).join(self.spectra_subdir))
+    }
+}
+
+/// List the spectrum directories for the given dates.
+///
+/// If `merge_into` is `None`, the directories are printed to stdout. Otherwise, the existing
+/// data partition file at that path (if any) is read, the directories not already in it are
+/// added (or uncommented, if present but commented out), and the result written back to
+/// that file. Existing lines, including comments, keep their order.
 pub(crate) fn print_daily_spec_dirs(
     site_id: &str,
     start_date: chrono::NaiveDate,
     end_date: chrono::NaiveDate,
-    run_dir_pattern: &str,
+    spec_dir_pattern: SpecDirPattern,
     allow_missing: bool,
+    exclude: &ExcludeDatesArgs,
+    merge_into: Option<&Path>,
 ) -> error_stack::Result<(), CliError> {
-    let spec_dirs = vec![];
+    let excluded = load_excluded_dates(exclude)?;
+    let spec_dirs = if let Some(part_file) = merge_into {
+        read_data_partition(part_file)?
+    } else {
+        vec![]
+    };
     let spec_dirs = add_spectrum_dirs_to_list(
         spec_dirs,
         site_id,
         start_date,
         end_date,
-        run_dir_pattern,
+        spec_dir_pattern,
         allow_missing,
+        &excluded,
     )?;
-    for dir in spec_dirs {
-        println!("{dir}");
+
+    if let Some(part_file) = merge_into {
+        write_data_partition(part_file, &spec_dirs)?;
+    } else {
+        for dir in spec_dirs {
+            println!("{dir}");
+        }
     }
     Ok(())
 }
@@ -39,6 +85,8 @@ pub(crate) fn print_daily_spec_dirs_json(
     end_date: chrono::NaiveDate,
     json_file: &Path,
     allow_missing: bool,
+    exclude: &ExcludeDatesArgs,
+    merge_into: Option<&Path>,
 ) -> error_stack::Result<(), CliError> {
     let common = DailyCommonArgs::read_from_path(json_file)
         .change_context_lazy(|| CliError::BadInput("Could not read JSON file".to_string()))?;
@@ -46,26 +94,60 @@ pub(crate) fn print_daily_spec_dirs_json(
         site_id,
         start_date,
         end_date,
-        &common.run_dir_pattern,
+        SpecDirPattern {
+            run_dir_pattern: &common.run_dir_pattern,
+            spectra_subdir: &common.spectra_subdir,
+        },
         allow_missing,
+        exclude,
+        merge_into,
     )
 }
 
+/// Read the lines of the data partition file at `part_file`, or return an empty list if it does not exist.
+fn read_data_partition(part_file: &Path) -> error_stack::Result<Vec<String>, CliError> {
+    if !part_file.exists() {
+        info!("{} does not exist, it will be created", part_file.display());
+        return Ok(vec![]);
+    }
+
+    let f = std::fs::File::open(part_file).change_context_lazy(|| {
+        CliError::bad_input(format!("Could not open {}", part_file.display()))
+    })?;
+    BufReader::new(f)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .change_context_lazy(|| {
+            CliError::bad_input(format!("Could not read {}", part_file.display()))
+        })
+}
+
+fn write_data_partition(
+    part_file: &Path,
+    data_partition: &[String],
+) -> error_stack::Result<(), CliError> {
+    let err = || CliError::other(format!("Could not write {}", part_file.display()));
+    let mut f = std::fs::File::create(part_file).change_context_lazy(err)?;
+    for line in data_partition {
+        writeln!(f, "{line}").change_context_lazy(err)?;
+    }
+    Ok(())
+}
+
 fn add_spectrum_dirs_to_list(
     mut data_partition: Vec<String>,
     site_id: &str,
     start_date: chrono::NaiveDate,
     end_date: chrono::NaiveDate,
-    run_dir_pattern: &str,
+    spec_dir_pattern: SpecDirPattern,
     allow_missing: bool,
+    excluded: &ExcludedDates,
 ) -> error_stack::Result<Vec<String>, CliError> {
     for curr_date in iter_dates(start_date, end_date) {
-        let spec_dir = render_daily_pattern(run_dir_pattern, curr_date, site_id)
-            .map(|s| PathBuf::from(s))
-            .change_context_lazy(|| {
-                CliError::BadInput("The RUN_DIR_PATTERN was not valid".to_string())
-            })?
-            .join("spectra");
+        if excluded.skip(curr_date) {
+            continue;
+        }
+        let spec_dir = spec_dir_pattern.render(curr_date, site_id)?;
 
         if !spec_dir.exists() {
             if allow_missing {
@@ -78,11 +160,8 @@ fn add_spectrum_dirs_to_list(
             }
         }
 
-        let spec_dir_str = ensure_trailing_path_sep(&spec_dir).ok_or_else(|| {
-            CliError::BadInput(format!(
-                "Could not encode {} to valid UTF-8",
-                spec_dir.display()
-            ))
+        let spec_dir_str = ensure_trailing_path_sep(&spec_dir).change_context_lazy(|| {
+            CliError::BadInput("The spectrum directories must be valid UTF-8".to_string())
         })?;
 
         if data_partition.contains(&spec_dir_str) {
@@ -101,6 +180,12 @@ fn add_spectrum_dirs_to_list(
     Ok(data_partition)
 }
 
+fn load_excluded_dates(exclude: &ExcludeDatesArgs) -> error_stack::Result<ExcludedDates, CliError> {
+    exclude
+        .load()
+        .change_context_lazy(|| CliError::bad_input("Invalid --exclude-dates"))
+}
+
 fn dir_in_commented_line(data_part: &[String], dir_str: &str) -> Option<usize> {
     for (i, s) in data_part.iter().enumerate() {
         if s.starts_with(':') && s.contains(dir_str) {
@@ -111,20 +196,34 @@ fn dir_in_commented_line(data_part: &[String], dir_str: &str) -> Option<usize> {
     return None;
 }
 
+/// Print the spectra in the spectrum directories for the given dates in acquisition time order.
+///
+/// The spectra from all the directories are sorted together, so the order is correct across
+/// day boundaries. If `unique` is `true`, spectra that appear in more than one directory (e.g.
+/// from overlapping run directories) are only printed the first time they occur in that order.
 pub(crate) fn print_daily_ordered_spectra(
     site_id: &str,
     start_date: chrono::NaiveDate,
     end_date: chrono::NaiveDate,
-    run_dir_pattern: &str,
+    spec_dir_pattern: SpecDirPattern,
     allow_missing: bool,
+    exclude: &ExcludeDatesArgs,
+    unique: bool,
 ) -> error_stack::Result<(), CliError> {
+    let excluded = load_excluded_dates(exclude)?;
     let spectra = list_ordered_spectra_daily(
         site_id,
         start_date,
         end_date,
-        run_dir_pattern,
+        spec_dir_pattern,
         allow_missing,
+        &excluded,
     )?;
+    let spectra = if unique {
+        dedup_spectra(spectra)
+    } else {
+        spectra
+    };
     for spec in spectra {
         println!("{spec}");
     }
@@ -135,18 +234,17 @@ fn list_ordered_spectra_daily(
     site_id: &str,
     start_date: chrono::NaiveDate,
     end_date: chrono::NaiveDate,
-    run_dir_pattern: &str,
+    spec_dir_pattern: SpecDirPattern,
     allow_missing: bool,
+    excluded: &ExcludedDates,
 ) -> error_stack::Result<Vec<String>, CliError> {
     let mut spec_dirs = vec![];
     info!("Searching for spectra between {start_date} and {end_date}");
     for curr_date in iter_dates(start_date, end_date) {
-        let spec_dir = render_daily_pattern(run_dir_pattern, curr_date, site_id)
-            .map(|s| PathBuf::from(s))
-            .change_context_lazy(|| {
-                CliError::BadInput("The RUN_DIR_PATTERN was not valid".to_string())
-            })?
-            .join("spectra");
+        if excluded.skip(curr_date) {
+            continue;
+        }
+        let spec_dir = spec_dir_pattern.render(curr_date, site_id)?;
 
         if !spec_dir.exists() {
             if allow_missing {
@@ -169,3 +267,96 @@ fn list_ordered_spectra_daily(
     })?;
     Ok(sorted_spec)
 }
+
+/// Remove repeated spectra from `spectra`, keeping the first instance of each, without
+/// otherwise changing the order.
+fn dedup_spectra(spectra: Vec<String>) -> Vec<String> {
+    let n_in = spectra.len();
+    let mut seen = HashSet::new();
+    let unique: Vec<String> = spectra
+        .into_iter()
+        .filter(|spec| {
+            let name = Path::new(spec)
+                .file_name()
+                .map(|n| n.to_os_string())
+                .unwrap_or_else(|| spec.into());
+            seen.insert(name)
+        })
+        .collect();
+
+    if unique.len() < n_in {
+        info!("Removed {} duplicate spectra", n_in - unique.len());
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_data_partition() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for day in ["20240401", "20240402", "20240403"] {
+            std::fs::create_dir_all(root.join(day).join("spectra")).unwrap();
+        }
+        let dir_str =
+            |day: &str| ensure_trailing_path_sep(&root.join(day).join("spectra")).unwrap();
+
+        let part_file = root.join("data_part.lst");
+        let existing = [
+            "/other/spectra/".to_string(),
+            format!(": {}", dir_str("20240402")),
+            "".to_string(),
+            dir_str("20240401"),
+        ];
+        std::fs::write(&part_file, existing.join("\n") + "\n").unwrap();
+
+        let pattern = format!("{}/{{DATE:%Y%m%d}}", root.display());
+        let pattern = SpecDirPattern {
+            run_dir_pattern: &pattern,
+            spectra_subdir: "spectra",
+        };
+        print_daily_spec_dirs(
+            "xx",
+            chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2024, 4, 4).unwrap(),
+            pattern,
+            true,
+            &ExcludeDatesArgs::default(),
+            Some(&part_file),
+        )
+        .unwrap();
+
+        let merged = read_data_partition(&part_file).unwrap();
+        assert_eq!(
+            merged,
+            vec![
+                "/other/spectra/".to_string(),
+                dir_str("20240402"),
+                "".to_string(),
+                dir_str("20240401"),
+                dir_str("20240403"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_spectra() {
+        let spectra = vec![
+            "xx20240401s0e00a.0001".to_string(),
+            "xx20240401s0e00a.0002".to_string(),
+            "/data/a/xx20240401s0e00a.0001".to_string(),
+            "xx20240402s0e00a.0001".to_string(),
+        ];
+        assert_eq!(
+            dedup_spectra(spectra),
+            vec![
+                "xx20240401s0e00a.0001".to_string(),
+                "xx20240401s0e00a.0002".to_string(),
+                "xx20240402s0e00a.0001".to_string(),
+            ]
+        );
+    }
+}
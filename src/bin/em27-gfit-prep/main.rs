@@ -1,25 +1,41 @@
 use std::{path::PathBuf, process::ExitCode};
 
-use clap::{Args, Parser, Subcommand};
-use clap_verbosity_flag::{Verbosity, WarnLevel};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use egi_rs::{
+    config::{
+        parse_spectra_subdir, resolve_jobs, ExcludeDatesArgs, SiteIdLengthArgs,
+        DEFAULT_SPECTRA_SUBDIR,
+    },
+    utils::logging::LogArgs,
+};
 
 mod gsetup;
 mod list_spectra;
+mod priors;
+mod run_gfit;
 
 fn main() -> ExitCode {
     let clargs = Cli::parse();
+    if let Some(site_id) = clargs.command.site_id() {
+        if let Err(e) = clargs.site_id_length.validate(site_id) {
+            Cli::command()
+                .error(clap::error::ErrorKind::ValueValidation, e)
+                .exit();
+        }
+    }
 
-    env_logger::Builder::new()
-        .filter_level(clargs.verbose.log_level_filter())
-        .init();
+    clargs.logging.init_logger();
 
+    let site_id_length = clargs.site_id_length.site_id_length;
     let res = match clargs.command {
         PrepActions::ListDataPartitionsDaily(clargs) => list_spectra::print_daily_spec_dirs(
-            &clargs.site_id,
-            clargs.start_date,
-            clargs.end_date,
-            &clargs.i2s_dir_pattern,
-            !clargs.no_skip_missing_dates,
+            &clargs.daily.site_id,
+            clargs.daily.start_date,
+            clargs.daily.end_date,
+            clargs.daily.spec_dir_pattern(),
+            !clargs.daily.no_skip_missing_dates,
+            &clargs.daily.exclude,
+            clargs.merge_into.as_deref(),
         ),
         PrepActions::ListDataPartitionsDailyJson(clargs) => {
             list_spectra::print_daily_spec_dirs_json(
@@ -28,17 +44,27 @@ fn main() -> ExitCode {
                 clargs.end_date,
                 &clargs.json_file,
                 !clargs.no_skip_missing_dates,
+                &clargs.exclude,
+                clargs.merge_into.as_deref(),
             )
         }
         PrepActions::ListSpectraDaily(clargs) => list_spectra::print_daily_ordered_spectra(
-            &clargs.site_id,
-            clargs.start_date,
-            clargs.end_date,
-            &clargs.i2s_dir_pattern,
-            !clargs.no_skip_missing_dates,
+            &clargs.daily.site_id,
+            clargs.daily.start_date,
+            clargs.daily.end_date,
+            clargs.daily.spec_dir_pattern(),
+            !clargs.daily.no_skip_missing_dates,
+            &clargs.daily.exclude,
+            clargs.unique,
+        ),
+        PrepActions::EgiGsetup(clargs) => gsetup::run_gsetup(
+            &clargs.run_dir,
+            clargs.runlog_name.as_deref(),
+            clargs.skip_priors_check,
+            site_id_length,
         ),
-        PrepActions::EgiGsetup(clargs) => {
-            gsetup::run_gsetup(&clargs.run_dir, clargs.runlog_name.as_deref())
+        PrepActions::Multiggg(clargs) => {
+            run_gfit::write_multiggg(&clargs.run_dir, resolve_jobs(clargs.jobs), clargs.launch)
         }
     };
 
@@ -56,14 +82,17 @@ struct Cli {
     command: PrepActions,
 
     #[command(flatten)]
-    verbose: Verbosity<WarnLevel>,
+    logging: LogArgs,
+
+    #[command(flatten)]
+    site_id_length: SiteIdLengthArgs,
 }
 
 #[derive(Debug, Subcommand)]
 enum PrepActions {
     /// List the directories to include in the data partition file given the
     /// I2S directory pattern explicitly.
-    ListDataPartitionsDaily(DailyCli),
+    ListDataPartitionsDaily(DataPartDailyCli),
 
     /// List the directories to include in the data partition file using the I2S JSON
     /// run files as input.
@@ -71,16 +100,32 @@ enum PrepActions {
 
     /// List the spectra for to process for specific days in the correct order
     /// given the I2S directory pattern explicitly.
-    ListSpectraDaily(DailyCli),
+    ListSpectraDaily(SpectraDailyCli),
 
     /// Prepare a GGG run directory, modified to work for EM27s, from a given
     /// or selected runlog.
     EgiGsetup(GsetupCli),
+
+    /// Write a multiggg.sh file to run GFIT on each window set up in a run
+    /// directory, and optionally run it.
+    Multiggg(MultigggCli),
+}
+
+impl PrepActions {
+    /// The site ID given to this subcommand, if it takes one.
+    fn site_id(&self) -> Option<&str> {
+        match self {
+            Self::ListDataPartitionsDaily(args) => Some(&args.daily.site_id),
+            Self::ListDataPartitionsDailyJson(args) => Some(&args.site_id),
+            Self::ListSpectraDaily(args) => Some(&args.daily.site_id),
+            Self::EgiGsetup(_) | Self::Multiggg(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
 pub(crate) struct DailyCli {
-    /// The two-letter site ID to use in spectrum names.
+    /// The site ID to use in spectrum names (two characters, unless --site-id-length is given).
     pub(crate) site_id: String,
 
     /// The first date to process, in YYYY-MM-DD format.
@@ -89,6 +134,9 @@ pub(crate) struct DailyCli {
     /// The last date to process, in YYYY-MM-DD format.
     pub(crate) end_date: chrono::NaiveDate,
 
+    #[command(flatten)]
+    pub(crate) exclude: ExcludeDatesArgs,
+
     /// A path, potentially with placeholders, where I2S was run.
     ///
     /// This uses curly braces to indicate a placeholder. The current date
@@ -99,18 +147,56 @@ pub(crate) struct DailyCli {
     /// as in {DATE}, it defaults to YYYY-MM-DD format.
     pub(crate) i2s_dir_pattern: String,
 
+    /// The name of the subdirectory of each I2S run directory that has the spectra.
+    /// This must match the --spectra-subdir given to em27-i2s-prep.
+    #[clap(long, default_value = DEFAULT_SPECTRA_SUBDIR, value_parser = parse_spectra_subdir)]
+    pub(crate) spectra_subdir: String,
+
     /// If a date in the date range does not have an interferogram directory,
     /// raise an error rather than continuing.
     #[clap(short = 's', long)]
     pub(crate) no_skip_missing_dates: bool,
 }
 
+impl DailyCli {
+    fn spec_dir_pattern(&self) -> list_spectra::SpecDirPattern<'_> {
+        list_spectra::SpecDirPattern {
+            run_dir_pattern: &self.i2s_dir_pattern,
+            spectra_subdir: &self.spectra_subdir,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct DataPartDailyCli {
+    #[command(flatten)]
+    pub(crate) daily: DailyCli,
+
+    /// An existing data partition file (e.g. $GGGPATH/config/data_part.lst) to merge the
+    /// directories into, rather than printing them. Directories already in the file are not
+    /// duplicated, commented out ones are uncommented, and all other lines are kept as they are.
+    #[clap(long)]
+    pub(crate) merge_into: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct SpectraDailyCli {
+    #[command(flatten)]
+    pub(crate) daily: DailyCli,
+
+    /// Only list each spectrum once, even if it is in more than one of the spectrum
+    /// directories (e.g. because run directories overlap). The first instance in
+    /// acquisition time order is kept.
+    #[clap(short = 'u', long)]
+    pub(crate) unique: bool,
+}
+
 #[derive(Debug, Args)]
 pub(crate) struct DailyJsonCli {
     /// Path to a JSON file used to run I2S for the days of interest
     json_file: PathBuf,
 
-    /// The two-letter site ID to use in spectrum names.
+    /// The site ID to use in spectrum names (two characters, unless --site-id-length is given).
     pub(crate) site_id: String,
 
     /// The first date to process, in YYYY-MM-DD format.
@@ -119,10 +205,19 @@ pub(crate) struct DailyJsonCli {
     /// The last date to process, in YYYY-MM-DD format.
     pub(crate) end_date: chrono::NaiveDate,
 
+    #[command(flatten)]
+    pub(crate) exclude: ExcludeDatesArgs,
+
     /// If a date in the date range does not have an interferogram directory,
     /// raise an error rather than continuing.
     #[clap(short = 's', long)]
     pub(crate) no_skip_missing_dates: bool,
+
+    /// An existing data partition file (e.g. $GGGPATH/config/data_part.lst) to merge the
+    /// directories into, rather than printing them. Directories already in the file are not
+    /// duplicated, commented out ones are uncommented, and all other lines are kept as they are.
+    #[clap(long)]
+    pub(crate) merge_into: Option<PathBuf>,
 }
 
 #[derive(Debug, Args)]
@@ -136,6 +231,28 @@ pub(crate) struct GsetupCli {
     /// given, then you will be prompted to choose the runlog.
     #[clap(short = 'r', long)]
     runlog_name: Option<String>,
+
+    /// Do not check that the .mod and .vmr prior files for the dates in the
+    /// runlog are present before running gsetup.
+    #[clap(long)]
+    skip_priors_check: bool,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct MultigggCli {
+    /// Run directory prepared by egi-gsetup (i.e. containing the .ggg files).
+    #[clap(short = 'd', long, default_value = ".")]
+    run_dir: PathBuf,
+
+    /// Number of GFIT processes to run at once with --launch. Values greater
+    /// than 1 require GNU parallel. If not given, `default_jobs` from
+    /// $GGGPATH/egi/egi_config.toml is used, or 1 if that is not set.
+    #[clap(short = 'j', long)]
+    jobs: Option<usize>,
+
+    /// Run GFIT after writing multiggg.sh.
+    #[clap(long)]
+    launch: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
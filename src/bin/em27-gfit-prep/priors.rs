@@ -0,0 +1,169 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use error_stack::ResultExt;
+use itertools::Itertools;
+
+use crate::CliError;
+
+/// GGG2020 priors are produced every three hours, so we need these hours for each date.
+const PRIOR_HOURS: [u32; 8] = [0, 3, 6, 9, 12, 15, 18, 21];
+
+/// Check that the model (`.mod`) and VMR (`.vmr`) prior files needed for all the
+/// dates in a runlog are present in `$GGGPATH/models/gnd` and `$GGGPATH/vmrs/gnd`.
+///
+/// # Errors
+/// - if the runlog cannot be read or parsed, or
+/// - if any date with spectra in the runlog is missing one or more prior files,
+///   in which case the error will list the dates missing priors.
+pub(super) fn check_priors_ready(
+    ggg_path: &Path,
+    runlog_file: &Path,
+) -> error_stack::Result<(), CliError> {
+    let f = std::fs::File::open(runlog_file).change_context_lazy(|| {
+        CliError::missing_input(format!("Could not open runlog {}", runlog_file.display()))
+    })?;
+    let dates = read_runlog_dates(BufReader::new(f)).map_err(|e| {
+        CliError::bad_input(format!(
+            "Could not get dates from runlog {}: {e}",
+            runlog_file.display()
+        ))
+    })?;
+
+    let models_dir = ggg_path.join("models").join("gnd");
+    let vmrs_dir = ggg_path.join("vmrs").join("gnd");
+    let missing = dates
+        .into_iter()
+        .filter(|&date| {
+            !priors_present(&models_dir, "mod", date) || !priors_present(&vmrs_dir, "vmr", date)
+        })
+        .collect_vec();
+
+    if missing.is_empty() {
+        log::info!("Priors for all dates in {} found", runlog_file.display());
+        Ok(())
+    } else {
+        let missing = missing.iter().map(|d| d.to_string()).join(", ");
+        Err(CliError::missing_input(format!(
+            "Priors (.mod and/or .vmr files) missing in {} or {} for the following dates: {missing}. Obtain the priors for these dates or use --skip-priors-check if you are sure they are available.",
+            models_dir.display(),
+            vmrs_dir.display()
+        ))
+        .into())
+    }
+}
+
+/// Check that there is one prior file with extension `ext` for each of the
+/// three-hourly times on `date` in `dir`.
+fn priors_present(dir: &Path, ext: &str, date: chrono::NaiveDate) -> bool {
+    PRIOR_HOURS.iter().all(|hour| {
+        let pattern = dir.join(format!("*_{}{hour:02}Z_*.{ext}", date.format("%Y%m%d")));
+        let Some(pattern) = pattern.to_str() else {
+            return false;
+        };
+        glob::glob(pattern)
+            .map(|mut paths| paths.any(|p| p.is_ok()))
+            .unwrap_or(false)
+    })
+}
+
+/// Get the unique dates of the spectra listed in a runlog.
+///
+/// This expects the GGG2020 runlog format: the first line gives the number of header lines,
+/// the last header line has the column names (which must include "year" and "day", the latter
+/// being the day of year), and lines starting with ":" or ";" are commented out spectra.
+fn read_runlog_dates<R: BufRead>(rdr: R) -> Result<BTreeSet<chrono::NaiveDate>, String> {
+    let mut lines = rdr.lines();
+    let first_line = lines
+        .next()
+        .ok_or_else(|| "runlog is empty".to_string())?
+        .map_err(|e| e.to_string())?;
+    let nhead: usize = first_line
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| {
+            format!("first line ({first_line}) does not start with the number of header lines")
+        })?;
+
+    let mut column_line = first_line;
+    for _ in 1..nhead {
+        column_line = lines
+            .next()
+            .ok_or_else(|| "runlog ended before the end of the header".to_string())?
+            .map_err(|e| e.to_string())?;
+    }
+
+    let columns = column_line
+        .split_whitespace()
+        .map(|c| c.to_ascii_lowercase())
+        .collect_vec();
+    let find_column = |name: &str| {
+        columns
+            .iter()
+            .position(|c| c == name)
+            .ok_or_else(|| format!("runlog does not have a '{name}' column"))
+    };
+    let iyear = find_column("year")?;
+    let iday = find_column("day")?;
+
+    let mut dates = BTreeSet::new();
+    for (iline, line) in lines.enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() || line.starts_with(':') || line.starts_with(';') {
+            continue;
+        }
+
+        let line_num = iline + nhead + 1;
+        let parts = line.split_whitespace().collect_vec();
+        let year: i32 = parts
+            .get(iyear)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("could not get the year on line {line_num}"))?;
+        let doy: u32 = parts
+            .get(iday)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("could not get the day of year on line {line_num}"))?;
+        let date = chrono::NaiveDate::from_yo_opt(year, doy)
+            .ok_or_else(|| format!("invalid year/day of year on line {line_num}"))?;
+        dates.insert(date);
+    }
+
+    Ok(dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_runlog_dates() {
+        let runlog = "3 5
+Runlog for testing
+  Spectrum_Name     Year  Day   Hour     oblat
+ xx20240101s0e00a.0001   2024    1   16.1   34.1
+ xx20240101s0e00a.0002   2024    1   16.2   34.1
+:xx20240103s0e00a.0001   2024    3   16.1   34.1
+ xx20240102s0e00a.0001   2024    2   16.1   34.1
+";
+        let dates = read_runlog_dates(runlog.as_bytes()).unwrap();
+        let expected = [
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+        ];
+        assert_eq!(dates.into_iter().collect_vec(), expected);
+    }
+
+    #[test]
+    fn test_read_runlog_missing_column() {
+        let runlog = "2 4
+  Spectrum_Name     Year   Hour     oblat
+ xx20240101s0e00a.0001   2024   16.1   34.1
+";
+        let e = read_runlog_dates(runlog.as_bytes());
+        assert!(e.is_err());
+    }
+}
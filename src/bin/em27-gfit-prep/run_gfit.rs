@@ -0,0 +1,155 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use error_stack::ResultExt;
+use ggg_rs::utils::get_ggg_path;
+
+use crate::CliError;
+
+/// Write a `multiggg.sh` file in `run_dir` that runs GFIT on each `.ggg` file in it,
+/// and optionally run it.
+///
+/// The file has one command per line, so it can be run serially with `bash multiggg.sh`
+/// or with GNU `parallel`. When `launch` is `true`, it is run with `parallel` if `jobs`
+/// is greater than 1 and with `bash` otherwise.
+pub(super) fn write_multiggg(
+    run_dir: &Path,
+    jobs: usize,
+    launch: bool,
+) -> error_stack::Result<(), CliError> {
+    let ggg_path = get_ggg_path().change_context_lazy(|| {
+        CliError::BadInput("Could not get GGGPATH environmental variable.".to_string())
+    })?;
+
+    let gfit = ggg_path.join("bin").join("gfit");
+    if !gfit.is_file() {
+        return Err(CliError::missing_input(format!(
+            "Could not find the gfit program at {}",
+            gfit.display()
+        ))
+        .into());
+    }
+
+    let ggg_files = find_ggg_files(run_dir)?;
+    if ggg_files.is_empty() {
+        return Err(CliError::missing_input(format!(
+            "No .ggg files found in {}; run egi-gsetup in that directory first",
+            run_dir.display()
+        ))
+        .into());
+    }
+
+    let multiggg = run_dir.join("multiggg.sh");
+    let mut f = std::fs::File::create(&multiggg).change_context_lazy(|| {
+        CliError::other(format!("Could not create {}", multiggg.display()))
+    })?;
+    for ggg_file in ggg_files.iter() {
+        writeln!(&mut f, "{}", gfit_command(&gfit, ggg_file)).change_context_lazy(|| {
+            CliError::other(format!("Failed to write to {}", multiggg.display()))
+        })?;
+    }
+    log::info!(
+        "Wrote {} gfit commands to {}",
+        ggg_files.len(),
+        multiggg.display()
+    );
+
+    if launch {
+        run_multiggg(run_dir, &multiggg, jobs)?;
+    }
+
+    Ok(())
+}
+
+/// Get the `.ggg` files in the run directory, sorted by name.
+fn find_ggg_files(run_dir: &Path) -> error_stack::Result<Vec<PathBuf>, CliError> {
+    if !run_dir.is_dir() {
+        return Err(CliError::missing_input(format!(
+            "Run directory {} does not exist",
+            run_dir.display()
+        ))
+        .into());
+    }
+
+    let entries = std::fs::read_dir(run_dir).change_context_lazy(|| {
+        CliError::other(format!("Could not list files in {}", run_dir.display()))
+    })?;
+
+    let mut ggg_files = vec![];
+    for entry in entries {
+        let path = entry
+            .change_context_lazy(|| {
+                CliError::other(format!("Could not list files in {}", run_dir.display()))
+            })?
+            .path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "ggg") {
+            ggg_files.push(path);
+        }
+    }
+    ggg_files.sort();
+    Ok(ggg_files)
+}
+
+/// Build the command to run GFIT for one window, e.g. `/ggg/bin/gfit co_4290.ggg>/dev/null`.
+///
+/// Only the file name of the `.ggg` file is used, since the commands are run from the
+/// run directory.
+fn gfit_command(gfit: &Path, ggg_file: &Path) -> String {
+    let ggg_name = ggg_file
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_else(|| ggg_file.to_string_lossy());
+    format!("{} {ggg_name}>/dev/null", gfit.display())
+}
+
+fn run_multiggg(run_dir: &Path, multiggg: &Path, jobs: usize) -> error_stack::Result<(), CliError> {
+    let mut cmd = if jobs > 1 {
+        let parallel = which::which("parallel").change_context_lazy(|| {
+            CliError::missing_input(format!(
+                "Could not find GNU parallel to run gfit with {jobs} jobs; install it or use --jobs 1"
+            ))
+        })?;
+        let mut cmd = Command::new(parallel);
+        cmd.arg("-j").arg(jobs.to_string()).arg("-a").arg(multiggg);
+        cmd
+    } else {
+        let mut cmd = Command::new("bash");
+        cmd.arg(multiggg);
+        cmd
+    };
+
+    log::info!("Running gfit in {}", run_dir.display());
+    let status = cmd.current_dir(run_dir).status().change_context_lazy(|| {
+        CliError::program_error(format!(
+            "Error occurred while running {}",
+            multiggg.display()
+        ))
+    })?;
+
+    if !status.success() {
+        return Err(CliError::program_error(format!(
+            "{} did not run successfully ({status})",
+            multiggg.display()
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gfit_command() {
+        let cmd = gfit_command(
+            Path::new("/ggg/bin/gfit"),
+            Path::new("/data/run/co_4290.ggg"),
+        );
+        assert_eq!(cmd, "/ggg/bin/gfit co_4290.ggg>/dev/null");
+    }
+}
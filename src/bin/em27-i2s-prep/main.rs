@@ -1,24 +1,38 @@
 use std::{path::PathBuf, process::ExitCode};
 
-use clap::{Args, Parser, Subcommand};
-use clap_verbosity_flag::{Verbosity, WarnLevel};
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 
 use error_stack::ResultExt;
+use itertools::Itertools;
 
-use egi_rs::config::DailyCommonArgs;
+use egi_rs::{
+    config::{site_config_path, DailyCommonArgs, ExcludeDatesArgs, SiteIdLengthArgs},
+    utils::logging::LogArgs,
+};
 
 mod run_daily;
+mod state;
 
 fn main() -> ExitCode {
-    let clargs = Cli::parse();
+    let matches = cli_command().get_matches();
+    let clargs = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = clargs.site_id_length.validate(clargs.command.site_id()) {
+        cli_command()
+            .error(clap::error::ErrorKind::ValueValidation, e)
+            .exit();
+    }
 
-    env_logger::Builder::new()
-        .filter_level(clargs.verbose.log_level_filter())
-        .init();
+    clargs.logging.init_logger();
 
     let res = match clargs.command {
-        PrepActions::Daily(args) => run_daily::prep_daily_i2s(args),
-        PrepActions::DailyJson(json_args) => run_daily::prep_daily_i2s_json(json_args),
+        PrepActions::Daily(mut args) => {
+            let daily_matches = matches
+                .subcommand_matches("daily")
+                .expect("the daily subcommand's arguments should be present");
+            args.apply_site_defaults(daily_matches)
+                .and_then(|_| run_daily::prep_daily_i2s(*args))
+        }
+        PrepActions::DailyJson(json_args) => run_daily::prep_daily_i2s_json(*json_args),
     };
 
     if let Err(e) = res {
@@ -37,37 +51,79 @@ enum CliError {
     IoError(String),
     #[error("The interferogram directory {} does not exist", .0.display())]
     MissingIgramDir(PathBuf),
+    #[error("The interferogram directory {} has no files matching {1}", .0.display())]
+    NoMatchingIgrams(PathBuf, String),
     #[error("There was an error preparing the catalog of interferograms.")]
     CatalogError,
     #[error("{0} (this was unexpected)")]
     UnexpectedError(String),
+    #[error("Preparing the I2S runs failed for {} date(s): {}", .0.len(), .0.iter().join(", "))]
+    FailedDates(Vec<chrono::NaiveDate>),
 }
 
 // ---------------------- //
 // Command line interface //
 // ---------------------- //
 
+/// The command line interface, with the patterns for the `daily` subcommand made optional
+/// so that they can come from the site configuration instead.
+fn cli_command() -> clap::Command {
+    const SITE_DEFAULT_ARGS: [&str; 4] = [
+        "igram_pattern",
+        "coord_file_pattern",
+        "met_file_pattern",
+        "run_dir_pattern",
+    ];
+
+    Cli::command().mut_subcommand("daily", |daily| {
+        SITE_DEFAULT_ARGS.into_iter().fold(daily, |cmd, id| {
+            cmd.mut_arg(id, |arg| {
+                arg.required(false)
+                    .default_value("")
+                    .hide_default_value(true)
+            })
+        })
+    })
+}
+
 #[derive(Debug, Parser)]
 struct Cli {
     #[command(subcommand)]
     command: PrepActions,
 
     #[command(flatten)]
-    verbose: Verbosity<WarnLevel>,
+    logging: LogArgs,
+
+    #[command(flatten)]
+    site_id_length: SiteIdLengthArgs,
 }
 
 #[derive(Debug, Subcommand)]
 enum PrepActions {
-    Daily(DailyCli),
-    DailyJson(DailyJsonCli),
+    Daily(Box<DailyCli>),
+    DailyJson(Box<DailyJsonCli>),
+}
+
+impl PrepActions {
+    fn site_id(&self) -> &str {
+        match self {
+            Self::Daily(args) => &args.site_id,
+            Self::DailyJson(args) => &args.site_id,
+        }
+    }
 }
 
+/// Prepare I2S runs for each day in a range of dates.
+///
+/// Any of the common arguments not given on the command line are taken from the site
+/// configuration, `$EGIPATH/sites/SITE_ID.toml`, if it exists. That file has the same
+/// contents as the TOML configuration for the daily-json subcommand.
 #[derive(Debug, Args)]
 struct DailyCli {
     #[command(flatten)]
     pub(crate) common: DailyCommonArgs,
 
-    /// The two-letter site ID to use in spectrum names.
+    /// The site ID to use in spectrum names (two characters, unless --site-id-length is given).
     pub(crate) site_id: String,
 
     /// The first date to process, in YYYY-MM-DD format.
@@ -76,20 +132,119 @@ struct DailyCli {
     /// The last date to process, in YYYY-MM-DD format.
     pub(crate) end_date: chrono::NaiveDate,
 
-    /// Where to write the file to drive the `parallel` utility to run I2S.
-    /// If not given, the default is to write to "multii2s.sh" in the current
-    /// directory.
+    #[command(flatten)]
+    pub(crate) exclude: ExcludeDatesArgs,
+
+    /// Where to write the file to drive the `parallel` utility (or the SLURM
+    /// array script, with `--scheduler slurm`) to run I2S. If not given, the
+    /// default is to write to "multii2s.in" in the current directory.
     #[clap(short = 'p', long, default_value = "multii2s.in")]
     pub(crate) parallel_file: PathBuf,
 
+    #[command(flatten)]
+    pub(crate) scheduler: SchedulerArgs,
+
     /// If a run directory already exists, it is deleted and recreated. Use with care!
     #[clap(long)]
     pub(crate) clear: bool,
 
-    /// If a date in the date range does not have an interferogram directory,
-    /// raise an error rather than continuing.
+    /// If a run directory already has its I2S input file (with a non-empty catalog)
+    /// and flimit file, leave it as-is and only add it to the parallel file. Useful
+    /// to resume a multi-day run that stopped partway through.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) skip_existing: bool,
+
+    /// If a run directory already has an I2S input file, keep the top of it (everything
+    /// before the catalog) and only rewrite the catalog. Use this to keep changes made to
+    /// the I2S parameters by hand when regenerating the catalog.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) no_overwrite_top: bool,
+
+    /// If a run directory already has a flimit.i2s file, do not replace it.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) no_overwrite_flimit: bool,
+
+    /// If any file in an interferogram directory cannot be checked against the glob
+    /// pattern (e.g. because of its permissions), fail that date instead of leaving the
+    /// file out and warning about it at the end.
+    #[clap(long)]
+    pub(crate) strict_glob: bool,
+
+    /// If a date in the date range does not have an interferogram directory, or its
+    /// directory has no interferograms matching the glob pattern, raise an error rather
+    /// than continuing.
     #[clap(short = 's', long)]
     pub(crate) no_skip_missing_dates: bool,
+
+    /// Also write "igram_paths.tsv" in each run directory, mapping each cataloged
+    /// interferogram's file name to its absolute path.
+    #[clap(long)]
+    pub(crate) write_path_map: bool,
+
+    /// Also write "met_flags.tsv" in each run directory, with flags indicating whether
+    /// each cataloged interferogram's surface met was interpolated, held from the nearest
+    /// met data point, or filled.
+    #[clap(long)]
+    pub(crate) write_met_flags: bool,
+
+    /// Only prepare dates after the last one successfully prepared by a previous run,
+    /// as recorded in the "i2s_prep_state.json" file in the root of the run directories.
+    /// Useful for near-real-time processing.
+    #[clap(long)]
+    pub(crate) incremental: bool,
+
+    /// Only prepare dates on or after this date, in YYYY-MM-DD format. This takes
+    /// precedence over the date recorded in the state file with `--incremental`.
+    #[clap(long)]
+    pub(crate) since: Option<chrono::NaiveDate>,
+
+    /// If preparing a date fails, log the error and continue with the remaining
+    /// dates rather than stopping. The dates that failed are listed at the end,
+    /// and the program exits with an error status. No date after the first failed
+    /// one is recorded in the state file, so an --incremental run retries from there.
+    #[clap(long)]
+    pub(crate) keep_going: bool,
+
+    /// Print which directories and files would be created, without
+    /// actually creating or modifying anything. Note that the catalog for
+    /// each date is still built to show what would be written, so the
+    /// interferogram headers and met files are still read and any met
+    /// scripts (ExtScriptV1 met sources) are still run.
+    #[clap(long)]
+    pub(crate) dry_run: bool,
+}
+
+impl DailyCli {
+    /// Fill in the common arguments not given on the command line from the site configuration,
+    /// then check that all the required patterns are set.
+    ///
+    /// `matches` must be the parsed arguments for the daily subcommand.
+    fn apply_site_defaults(&mut self, matches: &ArgMatches) -> error_stack::Result<(), CliError> {
+        let site_defaults = DailyCommonArgs::read_site_defaults(&self.site_id)
+            .change_context_lazy(|| {
+                CliError::BadInput(format!(
+                    "Error reading the site configuration for {}",
+                    self.site_id
+                ))
+            })?;
+        if let Some(site_defaults) = site_defaults {
+            self.common.merge_site_defaults(site_defaults, matches);
+        }
+
+        let missing = self.common.missing_patterns();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let site_file = site_config_path(&self.site_id)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| format!("$EGIPATH/sites/{}.toml", self.site_id));
+        Err(CliError::BadInput(format!(
+            "{} must be given on the command line or in the site configuration ({site_file})",
+            missing.join(", ")
+        ))
+        .into())
+    }
 }
 
 impl TryFrom<DailyJsonCli> for DailyCli {
@@ -98,7 +253,7 @@ impl TryFrom<DailyJsonCli> for DailyCli {
     fn try_from(value: DailyJsonCli) -> Result<Self, Self::Error> {
         let common =
             DailyCommonArgs::read_from_path(&value.json_file).change_context_lazy(|| {
-                CliError::BadInput("Error opening the configuration JSON file".to_string())
+                CliError::BadInput("Error opening the configuration file".to_string())
             })?;
 
         Ok(DailyCli {
@@ -106,18 +261,34 @@ impl TryFrom<DailyJsonCli> for DailyCli {
             site_id: value.site_id,
             start_date: value.start_date,
             end_date: value.end_date,
+            exclude: value.exclude,
             parallel_file: value.parallel_file,
+            scheduler: value.scheduler,
             clear: value.clear,
+            skip_existing: value.skip_existing,
+            no_overwrite_top: value.no_overwrite_top,
+            no_overwrite_flimit: value.no_overwrite_flimit,
+            strict_glob: value.strict_glob,
             no_skip_missing_dates: value.no_skip_missing_dates,
+            write_path_map: value.write_path_map,
+            write_met_flags: value.write_met_flags,
+            incremental: value.incremental,
+            since: value.since,
+            keep_going: value.keep_going,
+            dry_run: value.dry_run,
         })
     }
 }
 
+/// Prepare I2S runs for each day in a range of dates, with the common arguments read from a
+/// configuration file.
 #[derive(Debug, Args)]
 struct DailyJsonCli {
+    /// The configuration file with the common daily arguments, either
+    /// JSON or TOML (if the extension is ".toml").
     json_file: PathBuf,
 
-    /// The two-letter site ID to use in spectrum names.
+    /// The site ID to use in spectrum names (two characters, unless --site-id-length is given).
     pub(crate) site_id: String,
 
     /// The first date to process, in YYYY-MM-DD format.
@@ -126,18 +297,166 @@ struct DailyJsonCli {
     /// The last date to process, in YYYY-MM-DD format.
     pub(crate) end_date: chrono::NaiveDate,
 
-    /// Where to write the file to drive the `parallel` utility to run I2S.
-    /// If not given, the default is to write to "multii2s.sh" in the current
-    /// directory.
+    #[command(flatten)]
+    pub(crate) exclude: ExcludeDatesArgs,
+
+    /// Where to write the file to drive the `parallel` utility (or the SLURM
+    /// array script, with `--scheduler slurm`) to run I2S. If not given, the
+    /// default is to write to "multii2s.in" in the current directory.
     #[clap(short = 'p', long, default_value = "multii2s.in")]
     pub(crate) parallel_file: PathBuf,
 
+    #[command(flatten)]
+    pub(crate) scheduler: SchedulerArgs,
+
     /// If a run directory already exists, it is deleted and recreated. Use with care!
     #[clap(long)]
     pub(crate) clear: bool,
 
-    /// If a date in the date range does not have an interferogram directory,
-    /// raise an error rather than continuing.
+    /// If a run directory already has its I2S input file (with a non-empty catalog)
+    /// and flimit file, leave it as-is and only add it to the parallel file. Useful
+    /// to resume a multi-day run that stopped partway through.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) skip_existing: bool,
+
+    /// If a run directory already has an I2S input file, keep the top of it (everything
+    /// before the catalog) and only rewrite the catalog. Use this to keep changes made to
+    /// the I2S parameters by hand when regenerating the catalog.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) no_overwrite_top: bool,
+
+    /// If a run directory already has a flimit.i2s file, do not replace it.
+    #[clap(long, conflicts_with = "clear")]
+    pub(crate) no_overwrite_flimit: bool,
+
+    /// If any file in an interferogram directory cannot be checked against the glob
+    /// pattern (e.g. because of its permissions), fail that date instead of leaving the
+    /// file out and warning about it at the end.
+    #[clap(long)]
+    pub(crate) strict_glob: bool,
+
+    /// If a date in the date range does not have an interferogram directory, or its
+    /// directory has no interferograms matching the glob pattern, raise an error rather
+    /// than continuing.
     #[clap(short = 's', long)]
     pub(crate) no_skip_missing_dates: bool,
+
+    /// Also write "igram_paths.tsv" in each run directory, mapping each cataloged
+    /// interferogram's file name to its absolute path.
+    #[clap(long)]
+    pub(crate) write_path_map: bool,
+
+    /// Also write "met_flags.tsv" in each run directory, with flags indicating whether
+    /// each cataloged interferogram's surface met was interpolated, held from the nearest
+    /// met data point, or filled.
+    #[clap(long)]
+    pub(crate) write_met_flags: bool,
+
+    /// Only prepare dates after the last one successfully prepared by a previous run,
+    /// as recorded in the "i2s_prep_state.json" file in the root of the run directories.
+    /// Useful for near-real-time processing.
+    #[clap(long)]
+    pub(crate) incremental: bool,
+
+    /// Only prepare dates on or after this date, in YYYY-MM-DD format. This takes
+    /// precedence over the date recorded in the state file with `--incremental`.
+    #[clap(long)]
+    pub(crate) since: Option<chrono::NaiveDate>,
+
+    /// If preparing a date fails, log the error and continue with the remaining
+    /// dates rather than stopping. The dates that failed are listed at the end,
+    /// and the program exits with an error status. No date after the first failed
+    /// one is recorded in the state file, so an --incremental run retries from there.
+    #[clap(long)]
+    pub(crate) keep_going: bool,
+
+    /// Print which directories and files would be created, without
+    /// actually creating or modifying anything. Note that the catalog for
+    /// each date is still built to show what would be written, so the
+    /// interferogram headers and met files are still read and any met
+    /// scripts (ExtScriptV1 met sources) are still run.
+    #[clap(long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct SchedulerArgs {
+    /// Which job runner the file given by `--parallel-file` should be written for.
+    #[clap(long, value_enum, default_value_t = Scheduler::Parallel)]
+    pub(crate) scheduler: Scheduler,
+
+    /// SLURM partition to request, only used with `--scheduler slurm`.
+    #[clap(long)]
+    pub(crate) partition: Option<String>,
+
+    /// SLURM time limit for each array task (e.g. "02:00:00"), only used with `--scheduler slurm`.
+    #[clap(long)]
+    pub(crate) time: Option<String>,
+
+    /// SLURM memory request for each array task (e.g. "4G"), only used with `--scheduler slurm`.
+    #[clap(long)]
+    pub(crate) mem: Option<String>,
+
+    /// The most I2S runs to do at once. With `--scheduler slurm`, this limits how many
+    /// array tasks run at once; with `--scheduler parallel`, it is used in the suggested
+    /// `parallel` command. If not given, `default_jobs` from $GGGPATH/egi/egi_config.toml
+    /// is used, if set.
+    #[clap(short = 'j', long)]
+    pub(crate) jobs: Option<usize>,
+
+    /// The I2S executable to run. The default is `$GGGPATH/bin/i2s`; give this to use
+    /// e.g. a wrapper script that loads the modules I2S needs.
+    #[clap(long)]
+    pub(crate) i2s_exe: Option<PathBuf>,
+
+    /// The name of the file in each run directory to write the I2S output to. This may
+    /// include the same placeholders as the run directory pattern, e.g. "i2s_{DATE:%Y%m%d}.log".
+    #[clap(long, default_value = "i2s.log")]
+    pub(crate) log_name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Scheduler {
+    /// Write one command per line for GNU `parallel`
+    Parallel,
+    /// Write an `sbatch` array script with one task per run directory
+    Slurm,
+}
+
+impl std::fmt::Display for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scheduler::Parallel => write!(f, "GNU parallel"),
+            Scheduler::Slurm => write!(f, "SLURM array script"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_patterns_optional() {
+        let matches = cli_command()
+            .try_get_matches_from([
+                "em27-i2s-prep",
+                "daily",
+                "-i",
+                "/data/igms",
+                "xx",
+                "2024-04-01",
+                "2024-04-02",
+            ])
+            .unwrap();
+        let clargs = Cli::from_arg_matches(&matches).unwrap();
+        let PrepActions::Daily(args) = clargs.command else {
+            panic!("expected the daily subcommand");
+        };
+        assert_eq!(args.common.inputs.igram_pattern, "/data/igms");
+        assert_eq!(
+            args.common.missing_patterns(),
+            ["COORD_FILE_PATTERN", "MET_FILE_PATTERN", "RUN_DIR_PATTERN"]
+        );
+    }
 }
@@ -1,19 +1,31 @@
 use std::{
-    io::{BufReader, Read, Write},
+    io::{BufRead, BufReader, Write},
     path::{Path, PathBuf},
 };
 
 use egi_rs::{
-    config::DetectorSet,
-    default_files,
-    i2s_catalog::{self, make_catalog_entries},
-    utils::{ensure_trailing_path_sep, pattern_replacement::render_daily_pattern},
+    config::{
+        configured_jobs, glob_igrams, DailyCommonArgs, DailyInputPatterns, DetectorInference,
+        DetectorSet,
+    },
+    i2s_catalog::{
+        self, make_catalog_entries, max_run_number_for_digits, CatalogEntries, CatalogOptions,
+        IgramSkipReason,
+    },
+    i2s_top::{read_existing_top, write_input_top, write_provenance_comments, CatalogProvenance},
+    utils::{
+        ensure_trailing_path_sep, pattern_replacement::render_daily_pattern,
+        progress::ProgressReporter, relative_path, LineEndingWriter,
+    },
 };
 use error_stack::ResultExt;
-use ggg_rs::i2s::{self, I2SInputModifcations, I2SLineIter, I2SVersion};
-use log::{debug, info, warn};
+use ggg_rs::i2s;
+use log::{debug, error, info, warn};
 
-use crate::{CliError, DailyCli, DailyJsonCli};
+use crate::{
+    state::{state_file_path, PrepState},
+    CliError, DailyCli, DailyJsonCli, Scheduler, SchedulerArgs,
+};
 
 pub(crate) fn prep_daily_i2s_json(args: DailyJsonCli) -> error_stack::Result<(), CliError> {
     let args: DailyCli = args.try_into()?;
@@ -21,102 +33,322 @@ pub(crate) fn prep_daily_i2s_json(args: DailyJsonCli) -> error_stack::Result<(),
 }
 
 pub(crate) fn prep_daily_i2s(args: DailyCli) -> error_stack::Result<(), CliError> {
+    if let Some(flimit_file) = &args.common.flimit_file {
+        if !flimit_file.is_file() {
+            return Err(CliError::BadInput(format!(
+                "The flimit file {} does not exist or is not a file",
+                flimit_file.display()
+            ))
+            .into());
+        }
+    }
+
+    args.common
+        .spectrum_name_param(&args.site_id)
+        .change_context_lazy(|| {
+            CliError::BadInput("Invalid --spectrum-name-template".to_string())
+        })?;
+
     let mut glob_error_counts = vec![];
+    let mut date_summaries = vec![];
+    let mut failed_dates = vec![];
     let mut input_files = vec![];
 
-    let mut curr_date = args.start_date;
-    if args.end_date < curr_date {
+    let state_path = state_file_path(&args.common.run_dir_pattern, &args.site_id)?;
+    let mut state = PrepState::read_or_default(&state_path)?;
+
+    let excluded = args
+        .exclude
+        .load()
+        .change_context_lazy(|| CliError::BadInput("Invalid --exclude-dates".to_string()))?;
+
+    let mut curr_date = first_date_to_prep(&args, &state);
+    if args.end_date < args.start_date {
         warn!("Warning: end date is before start date, no days will be prepared.");
+    } else if args.end_date < curr_date {
+        info!("All dates through {} already prepared", args.end_date);
     }
 
     while curr_date <= args.end_date {
-        info!("Preparing I2S run for {curr_date}");
-
-        // Set up the run directory with a spectrum output directory and the correct flimit file
-        let res = setup_dirs(
-            &args.common.igram_pattern,
-            &args.common.run_dir_pattern,
-            &args.site_id,
-            curr_date,
-            args.clear,
-        );
+        if excluded.skip(curr_date) {
+            curr_date += chrono::Duration::days(1);
+            continue;
+        }
+        info!(date:% = curr_date, site = args.site_id.as_str(); "Preparing I2S run for {curr_date}");
 
-        // A bit messy, but this unpacks the directories if everything worked, otherwise it checks
-        // if the reason it failed is because there is no input data for that day and we are allowed
-        // to just skip in that case, advance the loop.
-        let (run_dir_path, igram_dir, spec_dir) = match res {
-            Ok(dirs) => dirs,
-            Err(e) => match (e.current_context(), args.no_skip_missing_dates) {
-                (CliError::MissingIgramDir(_), false) => {
-                    info!("Interferogram directory for {curr_date} missing, assuming no data");
-                    curr_date += chrono::Duration::days(1);
-                    continue;
+        let outcome = match prep_one_date(&args, curr_date) {
+            Ok(outcome) => outcome,
+            Err(e) if args.keep_going => {
+                error!(date:% = curr_date, site = args.site_id.as_str(); "Failed to prepare I2S run for {curr_date}, continuing with the next date: {e:?}");
+                if failed_dates.is_empty() && !args.dry_run {
+                    warn!("The state file will not record any date after {curr_date} as prepared, so that an --incremental run retries it");
                 }
-                _ => {
-                    return Err(e.change_context(CliError::IoError(format!(
-                        "Error setting up I2S run directory for date {curr_date}"
-                    ))))
-                }
-            },
+                state.mark_failed(&args.site_id, curr_date);
+                failed_dates.push((curr_date, e));
+                curr_date += chrono::Duration::days(1);
+                continue;
+            }
+            Err(e) => return Err(e),
         };
 
-        // Get the paths to the interferograms, as we'll need them if a UTC offset and/or detector set wasn't specified.
-        let igram_glob =
-            render_daily_pattern(&args.common.igram_glob_pattern, curr_date, &args.site_id)
-                .change_context_lazy(|| {
-                    CliError::BadInput("IGRAM_GLOB_PATTERN is not valid".to_string())
-                })?;
-        let (interferograms, n_glob_errs) = glob_igrams(&igram_dir, &igram_glob)?;
+        let (input_file, n_glob_errs) = match outcome {
+            DatePrepOutcome::NoData => {
+                curr_date += chrono::Duration::days(1);
+                continue;
+            }
+            DatePrepOutcome::AlreadyPrepared {
+                input_file,
+                n_glob_errs,
+            } => (input_file, n_glob_errs),
+            DatePrepOutcome::Prepared {
+                input_file,
+                summary,
+            } => {
+                let n_glob_errs = summary.n_glob_errors;
+                date_summaries.push(summary);
+                (input_file, n_glob_errs)
+            }
+        };
 
         if n_glob_errs > 0 {
             glob_error_counts.push((curr_date, n_glob_errs));
         }
 
-        let (mut i2s_input_file, i2s_input_path) = create_i2s_top(
+        input_files.push((curr_date, input_file));
+        if !args.dry_run {
+            state.mark_prepared(&args.site_id, curr_date);
+            state.write(&state_path)?;
+        }
+
+        curr_date += chrono::Duration::days(1);
+    }
+
+    write_summary_file(&date_summaries, &args.parallel_file, args.dry_run)?;
+    write_run_file(
+        &input_files,
+        args.parallel_file,
+        &args.scheduler,
+        &args.site_id,
+        args.dry_run,
+    )?;
+
+    for (date, n) in glob_error_counts {
+        warn!("Warning: there were {n} files on {date} that could not be checked against the glob pattern, double check the catalog for {date}");
+    }
+
+    if args.dry_run {
+        println!(
+            "Dry run complete: {} I2S run directories would be prepared, nothing was written.",
+            input_files.len()
+        );
+    } else {
+        info!("Prepared {} I2S run directories", input_files.len());
+    }
+
+    if failed_dates.is_empty() {
+        Ok(())
+    } else {
+        for (date, e) in failed_dates.iter() {
+            error!("Preparing the I2S run for {date} failed: {e}");
+        }
+        Err(CliError::FailedDates(failed_dates.into_iter().map(|(d, _)| d).collect()).into())
+    }
+}
+
+/// The result of preparing the I2S run for one date.
+enum DatePrepOutcome {
+    /// There were no interferograms for this date, and missing dates are allowed.
+    NoData,
+    /// The run directory was already complete, and `--skip-existing` was given.
+    AlreadyPrepared {
+        input_file: PathBuf,
+        n_glob_errs: u64,
+    },
+    /// The run directory was set up.
+    Prepared {
+        input_file: PathBuf,
+        summary: DateSummary,
+    },
+}
+
+/// Set up the run directory for one date, with the I2S input file (including the catalog) and flimit file.
+///
+/// # Errors
+/// - if the interferogram directory does not exist, or has no interferograms matching the glob pattern,
+///   and `args.no_skip_missing_dates` is `true`,
+/// - if any of the patterns are invalid for this date,
+/// - if the detectors cannot be determined, or
+/// - if creating the I2S input file, its catalog, or the flimit file fails.
+fn prep_one_date(
+    args: &DailyCli,
+    curr_date: chrono::NaiveDate,
+) -> error_stack::Result<DatePrepOutcome, CliError> {
+    // Find the interferograms first, so that we do not create (or clear) a run directory for a
+    // date without data. If there is no data for this date, check if we are allowed to just skip it.
+    let (igram_dir, interferograms, n_glob_errs) = match find_igrams(
+        &args.common.inputs,
+        &args.site_id,
+        curr_date,
+        args.strict_glob,
+    ) {
+        Ok(found) => found,
+        Err(e) => match (e.current_context(), args.no_skip_missing_dates) {
+            (CliError::MissingIgramDir(_), false) => {
+                info!("Interferogram directory for {curr_date} missing, assuming no data");
+                return Ok(DatePrepOutcome::NoData);
+            }
+            (CliError::NoMatchingIgrams(dir, glob), false) => {
+                warn!(
+                    "Interferogram directory {} for {curr_date} exists, but no files in it match {glob}; skipping this date",
+                    dir.display()
+                );
+                return Ok(DatePrepOutcome::NoData);
+            }
+            _ => {
+                return Err(e.change_context(CliError::BadInput(format!(
+                    "Error finding the interferograms for date {curr_date}"
+                ))))
+            }
+        },
+    };
+
+    // Set up the run directory with a spectrum output directory
+    let (run_dir_path, spec_dir) = setup_dirs(
+        &args.common.run_dir_pattern,
+        &args.common.spectra_subdir,
+        &args.site_id,
+        curr_date,
+        args.clear,
+        args.dry_run,
+    )
+    .change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Error setting up I2S run directory for date {curr_date}"
+        ))
+    })?;
+
+    if args.skip_existing && run_dir_is_complete(&run_dir_path, &interferograms) {
+        info!(
+            "Run directory {} already prepared for {curr_date}, skipping",
+            run_dir_path.display()
+        );
+        return Ok(DatePrepOutcome::AlreadyPrepared {
+            input_file: run_dir_path.join("opus-i2s.in"),
+            n_glob_errs,
+        });
+    }
+
+    let detectors = resolve_detectors(
+        args.common
+            .detector_schedule
+            .for_date(curr_date)
+            .or(args.common.detectors),
+        args.common.detector_inference,
+        &interferograms,
+        curr_date,
+    )?;
+
+    let existing_top = if args.no_overwrite_top {
+        read_existing_i2s_top(&run_dir_path)?
+    } else {
+        None
+    };
+    let (mut i2s_input_file, i2s_input_path) = if let Some(top) = existing_top {
+        rewrite_existing_i2s_top(&run_dir_path, &top, &args.common, args.dry_run)?
+    } else {
+        create_i2s_top(
             &igram_dir,
             &run_dir_path,
             &spec_dir,
             &interferograms,
-            args.common.detectors,
+            detectors,
             &args.site_id,
-            args.common.utc_offset.as_deref(),
-            args.common.top_file.as_deref(),
+            &args.common,
             curr_date,
+            args.dry_run,
+        )?
+    };
+    debug!("I2S input top written to {}", i2s_input_path.display());
+
+    let flimit_path = run_dir_path.join("flimit.i2s");
+    if args.no_overwrite_flimit && flimit_path.exists() {
+        info!("Keeping existing flimit file {}", flimit_path.display());
+    } else {
+        write_flimit_file(
+            &run_dir_path,
+            detectors,
+            args.common.flimit_file.as_deref(),
+            args.dry_run,
         )?;
-        debug!("I2S input top written to {}", i2s_input_path.display());
+    }
 
-        let n_entries = add_catalog_to_top(
-            &mut i2s_input_file,
-            &interferograms,
-            &args.site_id,
-            &args.common.coord_file_pattern,
-            &args.common.met_file_pattern,
-            curr_date,
-        )
-        .change_context_lazy(|| {
-            CliError::IoError(format!(
-                "Error occurred while adding catalog to {}",
-                i2s_input_path.display()
-            ))
-        })?;
+    let catalog = add_catalog_to_top(
+        &mut i2s_input_file,
+        &interferograms,
+        &args.site_id,
+        detectors,
+        &args.common,
+        curr_date,
+    )
+    .change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Error occurred while adding catalog to {}",
+            i2s_input_path.display()
+        ))
+    })?;
+    let n_entries = catalog.entries.len();
+    if args.dry_run {
+        println!(
+            "[dry run] Would write {} interferograms to the catalog in {}",
+            n_entries,
+            i2s_input_path.display()
+        );
+    } else {
         debug!(
             "{} interferograms written to the catalog in {}",
             n_entries,
             i2s_input_path.display()
         );
-
-        input_files.push(i2s_input_path);
-
-        curr_date += chrono::Duration::days(1);
     }
 
-    write_parallel_file(&input_files, args.parallel_file)?;
+    if args.write_path_map {
+        let path_map = run_dir_path.join("igram_paths.tsv");
+        if args.dry_run {
+            println!(
+                "[dry run] Would write interferogram path map {}",
+                path_map.display()
+            );
+        } else {
+            catalog
+                .write_path_map(&path_map)
+                .change_context_lazy(|| CliError::CatalogError)?;
+        }
+    }
 
-    for (date, n) in glob_error_counts {
-        warn!("Warning: there were {n} files on {date} that could not be checked against the glob pattern, double check the catalog for {date}");
+    if args.write_met_flags {
+        let met_flags = run_dir_path.join("met_flags.tsv");
+        if args.dry_run {
+            println!(
+                "[dry run] Would write met flags file {}",
+                met_flags.display()
+            );
+        } else {
+            catalog
+                .write_met_flags(&met_flags)
+                .change_context_lazy(|| CliError::CatalogError)?;
+        }
     }
 
-    Ok(())
+    Ok(DatePrepOutcome::Prepared {
+        input_file: i2s_input_path,
+        summary: DateSummary {
+            date: curr_date,
+            n_interferograms: catalog.n_input,
+            n_catalogued: n_entries,
+            n_skipped_missing_met: catalog.n_skipped_for(IgramSkipReason::MetUnavailable),
+            n_glob_errors: n_glob_errs,
+        },
+    })
 }
 
 // ---------------------------------------------------------- //
@@ -125,43 +357,142 @@ pub(crate) fn prep_daily_i2s(args: DailyCli) -> error_stack::Result<(), CliError
 //           task of setting up an I2S run directory          //
 // ---------------------------------------------------------- //
 
+/// Get the first date to prepare.
+///
+/// This is `args.start_date`, unless `args.since` is given or `args.incremental` is set
+/// and `state` has a date prepared for this site, and that (or the day after the last
+/// prepared date, respectively) is later.
+fn first_date_to_prep(args: &DailyCli, state: &PrepState) -> chrono::NaiveDate {
+    let since = if let Some(since) = args.since {
+        Some(since)
+    } else if args.incremental {
+        let last = state.last_prepared(&args.site_id);
+        match last {
+            Some(d) => info!("Last prepared date for {} was {d}", args.site_id),
+            None => info!(
+                "No previously prepared date for {}, starting at {}",
+                args.site_id, args.start_date
+            ),
+        }
+        last.map(|d| d + chrono::Duration::days(1))
+    } else {
+        None
+    };
+
+    since.map_or(args.start_date, |d| d.max(args.start_date))
+}
+
+/// Find the interferograms for `curr_date` from the daily input patterns.
+///
+/// # Returns
+/// - the directory containing the interferograms for this day,
+/// - the paths to the interferograms, and
+/// - the number of files that could not be checked against the glob pattern.
+///
+/// # Errors
+/// - [`CliError::MissingIgramDir`] if the interferogram directory does not exist,
+/// - [`CliError::NoMatchingIgrams`] if it exists but no files in it match the glob pattern,
+/// - if `strict_glob` is `true` and a file could not be checked against the glob pattern, or
+/// - if the interferogram or glob patterns are invalid.
+fn find_igrams(
+    inputs: &DailyInputPatterns,
+    site_id: &str,
+    curr_date: chrono::NaiveDate,
+    strict_glob: bool,
+) -> error_stack::Result<(PathBuf, Vec<PathBuf>, u64), CliError> {
+    let igram_dir = inputs
+        .igram_dir(curr_date, site_id)
+        .change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "IGRAM_PATTERN is not valid (while processing {curr_date})"
+            ))
+        })?;
+    if !igram_dir.is_dir() {
+        return Err(CliError::MissingIgramDir(igram_dir).into());
+    }
+
+    let igram_glob = inputs
+        .igram_glob(curr_date, site_id)
+        .change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "IGRAM_GLOB_PATTERN is not valid (while processing {curr_date})"
+            ))
+        })?;
+    let (interferograms, n_glob_errs) = glob_igrams(&igram_dir, &igram_glob, strict_glob)
+        .change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "Could not list the interferograms in {} matching the IGRAM_GLOB_PATTERN",
+                igram_dir.display()
+            ))
+        })?;
+
+    if interferograms.is_empty() {
+        return Err(CliError::NoMatchingIgrams(igram_dir, igram_glob).into());
+    }
+    Ok((igram_dir, interferograms, n_glob_errs))
+}
+
 /// Setup the run directory and the necessary modifications for the I2S head parameters
 ///
 /// # Inputs
-/// - igram_pattern: template for paths where the interferograms are stored
 /// - run_dir_pattern: template for paths where I2S should set up to run
+/// - spectra_subdir: name of the subdirectory of the run directory to write the spectra to
 /// - detectors: which set of detector(s) the EM27 has for this date
 /// - curr_date: which date is being processed
+/// - clear_existing: whether to delete the run directory first if it already exists
+/// - dry_run: if `true`, only print which directories would be deleted or created
 ///
 /// # Returns
-/// Three [`PathBuf`] instances
-/// - path to the run directory,
-/// - path to the directory containing the interferograms for this day, and
+/// Two [`PathBuf`] instances
+/// - path to the run directory, and
 /// - path within the run directory where the spectra will be written.
 ///
 /// # Errors
-/// - if `igram_pattern` or `run_dir_pattern` are invalid (e.g. have an unknown substitution key), or
+/// - if `run_dir_pattern` is invalid (e.g. has an unknown substitution key), or
 /// - if there is an I/O error creating the needed output directories or flimit file
 fn setup_dirs(
-    igram_pattern: &str,
     run_dir_pattern: &str,
+    spectra_subdir: &str,
     site_id: &str,
     curr_date: chrono::NaiveDate,
     clear_existing: bool,
-) -> error_stack::Result<(PathBuf, PathBuf, PathBuf), CliError> {
+    dry_run: bool,
+) -> error_stack::Result<(PathBuf, PathBuf), CliError> {
     // Set up and create paths
-    let igram_dir = render_daily_pattern(igram_pattern, curr_date, site_id)
-        .change_context_lazy(|| CliError::BadInput("IGRAM_PATTERN is not valid".to_string()))?;
-    let igram_path = PathBuf::from(&igram_dir);
+    let run_dir =
+        render_daily_pattern(run_dir_pattern, curr_date, site_id).change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "RUN_DIR_PATTERN is not valid (while processing {curr_date})"
+            ))
+        })?;
 
-    if !igram_path.is_dir() {
-        return Err(CliError::MissingIgramDir(igram_path).into());
+    let run_dir_path = PathBuf::from(&run_dir);
+    let spec_dir_path = run_dir_path.join(spectra_subdir);
+    if dry_run {
+        if clear_existing && run_dir_path.exists() {
+            println!(
+                "[dry run] Would delete existing run directory {}",
+                run_dir_path.display()
+            );
+            println!("[dry run] Would create run directory {run_dir}");
+            println!(
+                "[dry run] Would create spectrum output directory {}",
+                spec_dir_path.display()
+            );
+        } else {
+            if !run_dir_path.exists() {
+                println!("[dry run] Would create run directory {run_dir}");
+            }
+            if !spec_dir_path.exists() {
+                println!(
+                    "[dry run] Would create spectrum output directory {}",
+                    spec_dir_path.display()
+                );
+            }
+        }
+        return Ok((run_dir_path, spec_dir_path));
     }
 
-    let run_dir = render_daily_pattern(run_dir_pattern, curr_date, site_id)
-        .change_context_lazy(|| CliError::BadInput("RUN_DIR_PATTERN is not valid".to_string()))?;
-
-    let run_dir_path = PathBuf::from(&run_dir);
     if clear_existing && run_dir_path.exists() {
         std::fs::remove_dir_all(&run_dir_path)
             .map(|_| info!("Deleted existing run directory {}", run_dir_path.display()))
@@ -179,7 +510,6 @@ fn setup_dirs(
         })?;
     }
 
-    let spec_dir_path = run_dir_path.join("spectra");
     if !spec_dir_path.exists() {
         std::fs::create_dir(&spec_dir_path).change_context_lazy(|| {
             CliError::IoError(format!(
@@ -189,7 +519,32 @@ fn setup_dirs(
         })?;
     }
 
-    Ok((run_dir_path, igram_path, spec_dir_path))
+    Ok((run_dir_path, spec_dir_path))
+}
+
+/// Determine what detector(s) this instrument has on `curr_date`.
+///
+/// If `detectors` is `None` (i.e. it wasn't included in the config), this will try to infer
+/// the detector set from the interferogram headers, using the method given by `inference`.
+///
+/// # Errors
+/// - If the detector set must be inferred and the interferograms have different detectors (or, for
+///   majority inference, are evenly split between detector sets) or their headers cannot be read.
+fn resolve_detectors(
+    detectors: Option<DetectorSet>,
+    inference: DetectorInference,
+    interferograms: &[PathBuf],
+    curr_date: chrono::NaiveDate,
+) -> error_stack::Result<DetectorSet, CliError> {
+    if let Some(det) = detectors {
+        return Ok(det);
+    }
+
+    let dtmp = inference.infer(interferograms).change_context_lazy(|| {
+        CliError::BadInput(format!("Unable to infer detector set for {curr_date}"))
+    })?;
+    log::info!("Interferograms on {curr_date} appear to use {dtmp} detector(s)");
+    Ok(dtmp)
 }
 
 /// Writes the first part of the I2S input files: the top containing I2S settings and the flimit file
@@ -198,90 +553,241 @@ fn setup_dirs(
 /// - `igram_dir`: path to where the interferograms can be found
 /// - `run_dir`: path to where I2S will be run
 /// - `interferograms`: a slice of paths to all the interferograms to be processed on this date
-/// - `detectors`: which detector set the instrument has; if `None`, this function will try to infer that
-///   from the interferogram headers.
+/// - `detectors`: which detector set the instrument has (see [`resolve_detectors`]).
 /// - `site_id`: the two-character site ID to use for this instrument
-/// - `user_utc_offset`: the UTC offset value to enter into the I2S top file to convert interferogram timestamps
-///   to UTC. If `None`, this function will try to infer that from the interferogram headers.
-/// - `top_file_template`: a path to an I2S input top template to base the input on. If not given, the default
-///   one bundled with EGI will be used. Note that parameters 1 (interferogram path), 2 (spectrum path), 7 (channel
-///   to process), 8 (flimit file path), 9 (spectrum name patter), 11 (interferogram detector characters),
-///   12 (spectrum detector characters) and 19 (UTC offset) will be overridden.
+/// - `common`: the common daily options. Of these, `utc_offset` is the UTC offset value to enter into the I2S
+///   top file to convert interferogram timestamps to UTC (if `None`, this function will try to infer that from
+///   the interferogram headers, handling different offsets as `mixed_timezones` says), `top_file` is a path to
+///   an I2S input top template to base the input on (if not given, the default one bundled with EGI will be
+///   used), and `line_endings` is the line terminator to write the input file with. The interferogram and
+///   spectrum paths are written as `absolute_paths` and `relative_igram` say (see [`i2s_dir_params`]). Note that
+///   parameters 1 (interferogram path), 2 (spectrum path), 7 (channel to process), 8 (flimit file path), 9
+///   (spectrum name pattern, from `spectrum_name_template`), 11 (interferogram detector characters), 12
+///   (spectrum detector characters) and 19 (UTC offset) of the top will be overridden.
 /// - `curr_date`: the data date for which this input file is being created.
+/// - `dry_run`: if `true`, do not create the input file, only print what would be written.
 ///
 /// # Returns
-/// - [`std::fs::File`]: a writable file handle to the I2S input file
+/// - [`Write`] implementor: a writable handle to the I2S input file (which discards everything written
+///   to it if `dry_run` is `true`)
 /// - [`PathBuf`]: the path to the input file
 ///
 /// # Errors
-/// - If the detector set must be inferred and the interferogram have different detectors or their
-///   headers cannot be read.
 /// - If the UTC offset must be inferred ard the inteferograms have different UTC offsets or their
 ///   headers cannot be read.
 /// - If the interferogram or spectrum directory paths cannot be encoded as UTF-8.
-/// - If writing the I2S input top or flimit file fails.
+/// - If writing the I2S input top fails.
 fn create_i2s_top(
     igram_dir: &Path,
     run_dir: &Path,
     spec_dir: &Path,
     interferograms: &[PathBuf],
-    detectors: Option<DetectorSet>,
+    detectors: DetectorSet,
     site_id: &str,
-    user_utc_offset: Option<&str>,
-    top_file_template: Option<&Path>,
+    common: &DailyCommonArgs,
     curr_date: chrono::NaiveDate,
-) -> error_stack::Result<(std::fs::File, PathBuf), CliError> {
-    // Determine what detector(s) this instrument has if that wasn't included in the config.
-    let detectors = if let Some(det) = detectors {
-        det
-    } else {
-        let dtmp =
-            DetectorSet::infer_from_multi_headers(&interferograms).change_context_lazy(|| {
-                CliError::BadInput(format!("Unable to infer detector set for {curr_date}"))
-            })?;
-        log::info!("Interferograms on {curr_date} appear to use {dtmp} detector(s)");
-        dtmp
-    };
-
-    let utc_offset = get_utc_offset(user_utc_offset, interferograms).change_context_lazy(|| {
-        CliError::BadInput(format!(
-            "Could not determine a consistent timezone for interferograms on date {curr_date}"
-        ))
-    })?;
+    dry_run: bool,
+) -> error_stack::Result<(Box<dyn Write>, PathBuf), CliError> {
+    let user_utc_offset = common.utc_offset.as_deref();
+    let top_file_template = common.top_file.as_deref();
+    let utc_offset = get_utc_offset(user_utc_offset, interferograms, common.mixed_timezones)
+        .change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "Could not determine a consistent timezone for interferograms on date {curr_date}"
+            ))
+        })?;
 
-    let igm_dir_param = ensure_trailing_path_sep(igram_dir).ok_or_else(|| {
-        CliError::BadInput(format!("Could not encode {} as UTF-8", igram_dir.display()))
-    })?;
-    // Since our multii2s file ensures we CD into the run directory, it's better to make this relative
-    // so that if we move this directory later, the path still works.
-    let rel_spec_dir = spec_dir
-        .strip_prefix(run_dir)
-        .expect("spec_dir should be a subdirectory of run_dir");
-    let spec_dir_param = ensure_trailing_path_sep(rel_spec_dir).ok_or_else(|| {
-        CliError::BadInput(format!("Could not encode {} as UTF-8", spec_dir.display()))
-    })?;
+    let (igm_dir_param, spec_dir_param) = i2s_dir_params(igram_dir, run_dir, spec_dir, common)?;
     let mut i2s_changes = detectors.get_changes();
     i2s_changes.set_parameter_change(1, igm_dir_param);
     i2s_changes.set_parameter_change(2, spec_dir_param);
     i2s_changes.set_parameter_change(8, "./flimit.i2s".to_string());
-    i2s_changes.set_parameter_change(9, format!("{}YYYYMMDDS0e00C.RRRR", site_id));
+    let spectrum_name_param = common
+        .spectrum_name_param(site_id)
+        .change_context_lazy(|| {
+            CliError::BadInput("Invalid --spectrum-name-template".to_string())
+        })?;
+    i2s_changes.set_parameter_change(9, spectrum_name_param);
     i2s_changes.set_parameter_change(19, utc_offset);
 
     debug!("Interferograms will be read from {}", igram_dir.display());
     debug!("Run directory will be {}", run_dir.display());
 
-    // Create the input files in two parts. First we write the top of the I2S input file (with all of the options) plus
-    // the flimit file. Then we add the catalog of interferograms to the input file.
+    // Create the input file in two parts. First we write the top of the I2S input file (with all of the options).
+    // Then we add the catalog of interferograms to the input file.
+    let i2s_input_path = run_dir.join("opus-i2s.in");
+    let mut i2s_input_file: Box<dyn Write> = if dry_run {
+        println!(
+            "[dry run] Would write I2S input file {} for {detectors} detector(s)",
+            i2s_input_path.display()
+        );
+        Box::new(std::io::sink())
+    } else {
+        let f = std::fs::File::create(&i2s_input_path).change_context_lazy(|| {
+            CliError::IoError(format!(
+                "Could not create the I2S input file at {}",
+                i2s_input_path.display()
+            ))
+        })?;
+        Box::new(LineEndingWriter::new(f, common.line_endings))
+    };
+    write_input_top(&mut i2s_input_file, &i2s_changes, top_file_template).change_context_lazy(
+        || {
+            CliError::IoError(format!(
+                "Error writing the top of the I2S input file at {}",
+                i2s_input_path.display()
+            ))
+        },
+    )?;
+
+    Ok((i2s_input_file, i2s_input_path))
+}
+
+/// Get the interferogram and spectrum directory parameters (1 and 2) for the I2S input file.
+///
+/// By default, the interferogram directory is written as given and the spectrum directory relative
+/// to `run_dir`. `common.absolute_paths` makes both absolute, and `common.relative_igram` makes the
+/// interferogram directory relative to `run_dir` too.
+///
+/// # Errors
+/// - if both `absolute_paths` and `relative_igram` are set,
+/// - if a path cannot be made absolute or relative, or
+/// - if either path is not valid UTF-8.
+fn i2s_dir_params(
+    igram_dir: &Path,
+    run_dir: &Path,
+    spec_dir: &Path,
+    common: &DailyCommonArgs,
+) -> error_stack::Result<(String, String), CliError> {
+    if common.absolute_paths && common.relative_igram {
+        return Err(CliError::BadInput(
+            "absolute_paths and relative_igram cannot both be set".to_string(),
+        )
+        .into());
+    }
+
+    let igm_dir = if common.absolute_paths {
+        std::path::absolute(igram_dir).change_context_lazy(|| {
+            CliError::IoError(format!(
+                "Could not make the interferogram directory {} absolute",
+                igram_dir.display()
+            ))
+        })?
+    } else if common.relative_igram {
+        relative_path(run_dir, igram_dir).change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "Could not make the interferogram directory {} relative to the run directory {}",
+                igram_dir.display(),
+                run_dir.display()
+            ))
+        })?
+    } else {
+        igram_dir.to_path_buf()
+    };
+    let igm_dir_param = ensure_trailing_path_sep(&igm_dir).change_context_lazy(|| {
+        CliError::BadInput("The interferogram directory must be valid UTF-8".to_string())
+    })?;
+
+    // Since our multii2s file ensures we CD into the run directory, it's better to make this relative
+    // so that if we move this directory later, the path still works.
+    let spec_dir_path = if common.absolute_paths {
+        std::path::absolute(spec_dir).change_context_lazy(|| {
+            CliError::IoError(format!(
+                "Could not make the spectrum directory {} absolute",
+                spec_dir.display()
+            ))
+        })?
+    } else {
+        spec_dir
+            .strip_prefix(run_dir)
+            .map_err(|_| {
+                CliError::BadInput(format!(
+                    "The spectrum directory {} is not inside the run directory {}",
+                    spec_dir.display(),
+                    run_dir.display()
+                ))
+            })?
+            .to_path_buf()
+    };
+    let spec_dir_param = ensure_trailing_path_sep(&spec_dir_path).change_context_lazy(|| {
+        CliError::BadInput(format!(
+            "The spectrum directory {} must be valid UTF-8",
+            spec_dir.display()
+        ))
+    })?;
+
+    Ok((igm_dir_param, spec_dir_param))
+}
+
+/// Read the top (everything before the catalog) of the existing I2S input file in `run_dir`.
+///
+/// Returns `None` if there is no input file, or if it has no catalog to tell where the top ends;
+/// in the latter case a warning is logged, since the top will be regenerated.
+///
+/// # Errors
+/// - if the input file exists but cannot be read.
+fn read_existing_i2s_top(run_dir: &Path) -> error_stack::Result<Option<String>, CliError> {
     let i2s_input_path = run_dir.join("opus-i2s.in");
-    let mut i2s_input_file = std::fs::File::create(&i2s_input_path).change_context_lazy(|| {
+    if !i2s_input_path.exists() {
+        return Ok(None);
+    }
+
+    let err = || {
+        CliError::IoError(format!(
+            "Could not read the existing I2S input file at {}",
+            i2s_input_path.display()
+        ))
+    };
+    let f = std::fs::File::open(&i2s_input_path).change_context_lazy(err)?;
+    let top = read_existing_top(std::io::BufReader::new(f)).change_context_lazy(err)?;
+    if top.is_none() {
+        warn!(
+            "Existing I2S input file {} has no catalog, so cannot tell where its top ends; the top will be regenerated",
+            i2s_input_path.display()
+        );
+    }
+    Ok(top)
+}
+
+/// Recreate the I2S input file in `run_dir` with `top` (from [`read_existing_i2s_top`]) as its top.
+///
+/// Returns the same values as [`create_i2s_top`], and like that function, does not create the file
+/// if `dry_run` is `true`.
+fn rewrite_existing_i2s_top(
+    run_dir: &Path,
+    top: &str,
+    common: &DailyCommonArgs,
+    dry_run: bool,
+) -> error_stack::Result<(Box<dyn Write>, PathBuf), CliError> {
+    let i2s_input_path = run_dir.join("opus-i2s.in");
+    if dry_run {
+        println!(
+            "[dry run] Would keep the top of the existing I2S input file {} and rewrite its catalog",
+            i2s_input_path.display()
+        );
+        return Ok((Box::new(std::io::sink()), i2s_input_path));
+    }
+
+    info!(
+        "Keeping the top of the existing I2S input file {}",
+        i2s_input_path.display()
+    );
+    let f = std::fs::File::create(&i2s_input_path).change_context_lazy(|| {
         CliError::IoError(format!(
             "Could not create the I2S input file at {}",
             i2s_input_path.display()
         ))
     })?;
-    write_input_top(&mut i2s_input_file, &i2s_changes, top_file_template)?;
-    write_flimit_file(run_dir, detectors)?;
-
+    let mut i2s_input_file: Box<dyn Write> =
+        Box::new(LineEndingWriter::new(f, common.line_endings));
+    i2s_input_file
+        .write_all(top.as_bytes())
+        .change_context_lazy(|| {
+            CliError::IoError(format!(
+                "Error writing the top of the I2S input file at {}",
+                i2s_input_path.display()
+            ))
+        })?;
     Ok((i2s_input_file, i2s_input_path))
 }
 
@@ -292,101 +798,192 @@ fn create_i2s_top(
 ///   already written and be ready to write the catalog header as the next line.
 /// - `interferograms`: a slice of paths to all the interferograms to be processed on this date
 /// - `site_id`: the two-character site ID to use for this instrument
-/// - `coord_file_pattern`: a string, optionally with substitutions (e.g. date and site ID), that
-///   can be rendered to produce the path to the coordinate input file for this date.
-/// - `met_file_pattern`: like `coord_file_pattern`, except for the input file specifying the met
-///   type and necessary options to access the met information.
+/// - `detectors`: which detector set the instrument has; passed on to met sources that need it.
+/// - `common`: the common daily options. Of these, `inputs` gives the patterns that can be rendered
+///   to produce the paths to the coordinate input file and the input file specifying the met type and
+///   necessary options to access the met information for this date, `run_step` how much to advance the
+///   run number between catalog entries, `instrument_temperature_params` which header parameters
+///   to read the instrument temperature from, and `provenance_comments` whether to write comment lines
+///   recording the EGI-RS version and these input files before the catalog.
 /// - `curr_date`: the data date for which this input file is being created.
 ///
 /// # Returns
-/// - [`usize`] - the number of catalog entries added
+/// - [`CatalogEntries`] - the catalog entries added, and which interferograms were skipped
 ///
 /// # Errors
 /// - If the coordinate or met file pattern is not valid.
 /// - If assembling the catalog entries fails (see [`make_catalog_entries`] for why this might happen).
 /// - If writing to the input file fails.
-fn add_catalog_to_top(
-    i2s_input_file: &mut std::fs::File,
+fn add_catalog_to_top<W: Write>(
+    i2s_input_file: &mut W,
     interferograms: &[PathBuf],
     site_id: &str,
-    coord_file_pattern: &str,
-    met_file_pattern: &str,
+    detectors: DetectorSet,
+    common: &DailyCommonArgs,
     curr_date: chrono::NaiveDate,
-) -> error_stack::Result<usize, CliError> {
-    let coordinate_file = render_daily_pattern(coord_file_pattern, curr_date, site_id)
-        .map(PathBuf::from)
+) -> error_stack::Result<CatalogEntries, CliError> {
+    let inputs = &common.inputs;
+    let coordinate_file = inputs
+        .coord_file(curr_date, site_id)
         .change_context_lazy(|| {
-            CliError::BadInput("COORD_FILE_PATTERN is not valid".to_string())
+            CliError::BadInput(format!(
+                "COORD_FILE_PATTERN is not valid (while processing {curr_date})"
+            ))
+        })?;
+    let met_source_file = inputs
+        .met_file(curr_date, site_id)
+        .change_context_lazy(|| {
+            CliError::BadInput(format!(
+                "MET_FILE_PATTERN is not valid (while processing {curr_date})"
+            ))
         })?;
-    let met_source_file = render_daily_pattern(met_file_pattern, curr_date, site_id)
-        .map(PathBuf::from)
-        .change_context_lazy(|| CliError::BadInput("MET_FILE_PATTERN is not valid".to_string()))?;
 
-    let catalog_entries =
-        make_catalog_entries(&coordinate_file, &met_source_file, &interferograms, false)
-            .change_context_lazy(|| CliError::CatalogError)?;
+    let mut progress = ProgressReporter::new(&format!("Cataloging {curr_date}"));
+    let met_source_files = [met_source_file];
+    let catalog_entries = make_catalog_entries(
+        &coordinate_file,
+        &met_source_files,
+        &interferograms,
+        &CatalogOptions {
+            site_id: Some(site_id),
+            detectors: Some(detectors),
+            run_step: common.run_step,
+            max_run_number: max_run_number_for_digits(common.run_number_digits()),
+            instrument_temperature_params: &common.instrument_temperature_params,
+            ..Default::default()
+        },
+        Some(&mut |done, total| progress.update(done, total)),
+    )
+    .change_context_lazy(|| CliError::CatalogError)?;
+    progress.finish();
+    info!(date:% = curr_date, site = site_id; "{curr_date}: {}", catalog_entries.summary());
+    for (igram, reason) in catalog_entries.skipped.iter() {
+        debug!("{curr_date}: skipped {} because {reason}", igram.display());
+    }
 
     // Write the catalog
-    i2s::write_opus_catalogue_table(i2s_input_file, &catalog_entries, false)
+    if common.provenance_comments {
+        let provenance = CatalogProvenance {
+            coordinate_file: &coordinate_file,
+            met_files: &met_source_files,
+        };
+        write_provenance_comments(i2s_input_file, &provenance).change_context_lazy(|| {
+            CliError::IoError("Error writing the catalog comments".to_string())
+        })?;
+    }
+    i2s::write_opus_catalogue_table(i2s_input_file, &catalog_entries.entries, false)
         .map_err(|e| CliError::IoError(e.to_string()))?;
-    Ok(catalog_entries.len())
+    Ok(catalog_entries)
 }
 
-/// Get the list of interferograms matching a glob pattern
-fn glob_igrams(
-    igram_path: &Path,
-    igram_glob: &str,
-) -> error_stack::Result<(Vec<PathBuf>, u64), CliError> {
-    let mut igrams = vec![];
-    let mut n_glob_err = 0;
-
-    let full_igram_pattern = igram_path.join(igram_glob);
-    let full_igram_pattern = full_igram_pattern.to_str().ok_or_else(|| {
-        CliError::BadInput(format!(
-            "Could not convert the interferogram pattern '{}' into a valid UTF-8 string",
-            full_igram_pattern.display()
-        ))
-    })?;
+// ------------------------------------------------- //
+//               ADDITIONAL HELPER FUNCTIONS         //
+//    The functions in this section perform smaller, //
+//                   individual tasks.               //
+// ------------------------------------------------- //
 
-    let glob_iter = glob::glob(full_igram_pattern).change_context_lazy(|| {
-        CliError::BadInput("The IGRAM_GLOB_PATTERN produced an invalid glob pattern".to_string())
-    })?;
+/// Check whether a run directory was already fully prepared by a previous run.
+///
+/// A run directory is considered complete if it contains both `opus-i2s.in` and `flimit.i2s`
+/// and the input file's catalog lists at least one of the given interferograms. Any error
+/// reading the input file is treated as the directory being incomplete.
+fn run_dir_is_complete(run_dir: &Path, interferograms: &[PathBuf]) -> bool {
+    let input_path = run_dir.join("opus-i2s.in");
+    if !input_path.exists() || !run_dir.join("flimit.i2s").exists() {
+        return false;
+    }
 
-    for entry in glob_iter {
-        match entry {
-            Ok(p) => igrams.push(p),
-            Err(_) => n_glob_err += 1,
+    match std::fs::File::open(&input_path) {
+        Ok(f) => catalog_has_entries(BufReader::new(f), interferograms).unwrap_or_else(|e| {
+            warn!(
+                "Could not read existing I2S input file {}: {e}",
+                input_path.display()
+            );
+            false
+        }),
+        Err(e) => {
+            warn!(
+                "Could not open existing I2S input file {}: {e}",
+                input_path.display()
+            );
+            false
         }
     }
-
-    Ok((igrams, n_glob_err))
 }
 
-// ------------------------------------------------- //
-//               ADDITIONAL HELPER FUNCTIONS         //
-//    The functions in this section perform smaller, //
-//                   individual tasks.               //
-// ------------------------------------------------- //
+/// Return `true` if any line of an I2S input file starts with the base name of one of the
+/// interferograms, i.e. the catalog has at least one entry for them.
+fn catalog_has_entries<R: BufRead>(input: R, interferograms: &[PathBuf]) -> std::io::Result<bool> {
+    let igram_names: Vec<&str> = interferograms
+        .iter()
+        .filter_map(|p| p.file_name().and_then(|n| n.to_str()))
+        .collect();
+
+    for line in input.lines() {
+        let line = line?;
+        if let Some(first) = line.split_whitespace().next() {
+            if igram_names.contains(&first) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
 
-/// Get the UTC offset string for a set of interferograms
+/// Get the UTC offset string for a set of interferograms; `mixed` says what to do if they
+/// have different offsets.
 fn get_utc_offset(
     user_utc_offset: Option<&str>,
     igram_paths: &[PathBuf],
+    mixed: i2s_catalog::MixedTimezones,
 ) -> error_stack::Result<String, i2s_catalog::IgramTimezoneError> {
     if let Some(offset) = user_utc_offset {
         return Ok(offset.to_string());
     }
 
-    let igram_tz = i2s_catalog::get_common_igram_timezone(igram_paths)?;
+    let igram_tz = i2s_catalog::get_igram_timezone(igram_paths, mixed)?;
     let offset_hour = -igram_tz.local_minus_utc() as f32 / 3600.0;
     Ok(format!("{offset_hour:.2}"))
 }
 
+/// Write the flimit file to `run_dir_path`, which I2S parameter 8 points to.
+///
+/// If `user_flimit` is given, that file is copied; otherwise the flimit bundled with EGI
+/// for `detectors` is written.
 fn write_flimit_file(
     run_dir_path: &Path,
     detectors: DetectorSet,
+    user_flimit: Option<&Path>,
+    dry_run: bool,
 ) -> error_stack::Result<(), CliError> {
     let flimit_path = run_dir_path.join("flimit.i2s");
+    if dry_run {
+        if let Some(user_flimit) = user_flimit {
+            println!(
+                "[dry run] Would copy flimit file {} to {}",
+                user_flimit.display(),
+                flimit_path.display()
+            );
+        } else {
+            println!(
+                "[dry run] Would write flimit file {}",
+                flimit_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(user_flimit) = user_flimit {
+        std::fs::copy(user_flimit, &flimit_path).change_context_lazy(|| {
+            CliError::IoError(format!(
+                "Error copying flimit file {} to {}",
+                user_flimit.display(),
+                flimit_path.display()
+            ))
+        })?;
+        return Ok(());
+    }
+
     let flimit_contents = detectors.get_flimit();
     let mut f = std::fs::File::create(&flimit_path).change_context_lazy(|| {
         CliError::IoError(format!(
@@ -405,144 +1002,348 @@ fn write_flimit_file(
     Ok(())
 }
 
-/// Write the top part of the I2S input file
+/// Write the file that will run I2S in each of the prepared run directories.
 ///
-/// # Inputs
-/// - `input_file` - handle to write the top to
-/// - `top_edits` - collection of parameters that should be set
-/// - `source_top_path` - path pointing to an existing I2S top file to use as a template,
-///   if `None`, the default EM27 template is used.
+/// Depending on `scheduler.scheduler`, this will either be a list of commands for
+/// GNU `parallel` or an `sbatch` array script for SLURM. If `dry_run` is `true`,
+/// only print what would be written.
 ///
-/// # Errors
-/// - if cannot open/read the source top file (if given), or
-/// - if cannot write the output file successfully
-fn write_input_top(
-    input_file: &mut std::fs::File,
-    top_edits: &I2SInputModifcations,
-    source_top_path: Option<&Path>,
+/// Each command runs `scheduler.i2s_exe` (or `$GGGPATH/bin/i2s` if that is not given)
+/// on one of `input_files`, writing its output to `scheduler.log_name` rendered for
+/// that input file's date.
+fn write_run_file(
+    input_files: &[(chrono::NaiveDate, PathBuf)],
+    run_file: PathBuf,
+    scheduler: &SchedulerArgs,
+    site_id: &str,
+    dry_run: bool,
 ) -> error_stack::Result<(), CliError> {
-    let top_contents = if let Some(p) = source_top_path {
-        let mut f = std::fs::File::open(p).change_context_lazy(|| {
-            CliError::IoError(format!(
-                "Error opening source I2S top file at {}",
-                p.display()
+    let i2s_exe = if let Some(exe) = &scheduler.i2s_exe {
+        exe.clone()
+    } else {
+        let gggpath = ggg_rs::utils::get_ggg_path().change_context_lazy(|| {
+            CliError::BadInput(
+                "Could not get GGGPATH, ensure the environmental variable is set".to_string(),
+            )
+        })?;
+        gggpath.join("bin").join("i2s")
+    };
+    let i2s_exe = i2s_exe.to_str().ok_or_else(|| {
+        CliError::IoError(format!(
+            "Could not convert the I2S executable path {} to valid UTF-8",
+            i2s_exe.display()
+        ))
+    })?;
+
+    let commands = input_files
+        .iter()
+        .map(|(date, f)| {
+            let log_name = render_daily_pattern(&scheduler.log_name, *date, site_id)
+                .change_context_lazy(|| {
+                    CliError::BadInput(format!("LOG_NAME is not valid (while processing {date})"))
+                })?;
+            i2s_run_command(i2s_exe, f, &log_name)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if dry_run {
+        println!(
+            "[dry run] Would write {} I2S commands to {} ({})",
+            commands.len(),
+            run_file.display(),
+            scheduler.scheduler
+        );
+        return Ok(());
+    }
+
+    let mut writer = std::fs::File::create(&run_file).change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Could not create I2S run file at {}",
+            run_file.display()
+        ))
+    })?;
+
+    let jobs = configured_jobs(scheduler.jobs);
+    match scheduler.scheduler {
+        Scheduler::Parallel => write_parallel_file(&mut writer, &commands),
+        Scheduler::Slurm => write_slurm_file(&mut writer, &commands, scheduler, jobs),
+    }
+    .change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Error occurred writing I2S commands to {}",
+            run_file.display()
+        ))
+    })?;
+
+    if scheduler.scheduler == Scheduler::Parallel {
+        info!(
+            "Run I2S with: parallel -t --delay=1 -j{} < {}",
+            jobs.unwrap_or(1),
+            run_file.display()
+        );
+    }
+    Ok(())
+}
+
+/// The number of interferograms found and cataloged for one date, for the summary file.
+#[derive(Debug, serde::Serialize)]
+struct DateSummary {
+    date: chrono::NaiveDate,
+    n_interferograms: usize,
+    n_catalogued: usize,
+    n_skipped_missing_met: usize,
+    n_glob_errors: u64,
+}
+
+/// Write the per-date interferogram counts to "i2s_prep_summary.csv" in the same directory
+/// as `run_file`, overwriting any existing summary. Dates skipped because they had no
+/// interferogram directory or were already prepared (with `--skip-existing`) are not included.
+fn write_summary_file(
+    summaries: &[DateSummary],
+    run_file: &Path,
+    dry_run: bool,
+) -> error_stack::Result<(), CliError> {
+    let summary_file = run_file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("i2s_prep_summary.csv");
+
+    if dry_run {
+        println!(
+            "[dry run] Would write catalog counts for {} dates to {}",
+            summaries.len(),
+            summary_file.display()
+        );
+        return Ok(());
+    }
+
+    let writer = std::fs::File::create(&summary_file).change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Could not create summary file at {}",
+            summary_file.display()
+        ))
+    })?;
+    write_summary(writer, summaries).change_context_lazy(|| {
+        CliError::IoError(format!(
+            "Error occurred writing the summary to {}",
+            summary_file.display()
+        ))
+    })?;
+    info!("Wrote catalog counts to {}", summary_file.display());
+    Ok(())
+}
+
+fn write_summary<W: Write>(writer: W, summaries: &[DateSummary]) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    for summary in summaries {
+        wtr.serialize(summary)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Build the shell command that runs I2S for one input file, e.g.
+/// `cd /data/run && $GGGPATH/bin/i2s opus-i2s.in > i2s.log`.
+fn i2s_run_command(
+    i2s_exe: &str,
+    input_file: &Path,
+    log_name: &str,
+) -> error_stack::Result<String, CliError> {
+    let run_dir = input_file
+        .parent()
+        .ok_or_else(|| {
+            CliError::UnexpectedError(format!(
+                "Could not get parent of input file {}",
+                input_file.display()
             ))
+        })?
+        .to_str()
+        .ok_or_else(|| {
+            CliError::IoError("Could not convert run directory path to valid UTF-8".to_string())
         })?;
 
-        let mut buf = String::new();
-        f.read_to_string(&mut buf).change_context_lazy(|| {
-            CliError::IoError(format!(
-                "Error reading source I2S top file at {}",
-                p.display()
+    let input_name = input_file
+        .file_name()
+        .ok_or_else(|| {
+            CliError::UnexpectedError(format!(
+                "Could not get base name of input file {}",
+                input_file.display()
             ))
+        })?
+        .to_str()
+        .ok_or_else(|| {
+            CliError::IoError(
+                "Could not convert base name of input file to valid UTF-8".to_string(),
+            )
         })?;
 
-        buf
-    } else {
-        default_files::I2S_TOP.to_string()
-    };
+    Ok(format!(
+        "cd {run_dir} && {i2s_exe} {input_name} > {log_name}"
+    ))
+}
 
-    let reader = BufReader::new(top_contents.as_bytes());
-    modify_i2s_head(reader, top_edits, input_file)?;
+fn write_parallel_file<W: Write>(writer: &mut W, commands: &[String]) -> std::io::Result<()> {
+    for cmd in commands {
+        writeln!(writer, "{cmd}")?;
+    }
     Ok(())
 }
 
-/// Write a version of the I2S header with specific changes made
-///
-/// # Inputs
-/// - `top`: the template for the I2S header to modify. Can be anything that implements
-///   the [`Read`] trait, typically a [`std::fs::File`] instance or a `&[u8]`.
-/// - `edits`: collection of parameters in the I2S header to set.
-/// - `writer`: handle to write the changes to, e.g. a mutable [`std::fs::File`] instance.
-///
-/// # Errors
-/// - if reading a line from `top` fails, or
-/// - if writing a line to `writer` fails
-fn modify_i2s_head<R: Read, W: Write>(
-    top: R,
-    edits: &I2SInputModifcations,
-    mut writer: W,
-) -> error_stack::Result<(), CliError> {
-    // TODO: this should go into ggg_rs::i2s once error types in ggg_rs are cleaned up
-    let rdr = BufReader::new(top);
-    let iterator = I2SLineIter::new(rdr, I2SVersion::I2S2020);
-    for head_line in iterator {
-        let (line_type, head_line) = head_line
-            .change_context_lazy(|| CliError::IoError("Error reading I2S top file".to_string()))?;
-
-        if let Some(new_line) = edits.change_line_opt(line_type) {
-            writeln!(writer, "{}", new_line).change_context_lazy(|| {
-                CliError::IoError("Error writing new line to I2S input file".to_string())
-            })?;
+/// Write an `sbatch` array script in which task `i` runs the `i`th command.
+fn write_slurm_file<W: Write>(
+    writer: &mut W,
+    commands: &[String],
+    scheduler: &SchedulerArgs,
+    max_jobs: Option<usize>,
+) -> std::io::Result<()> {
+    writeln!(writer, "#!/bin/bash")?;
+    writeln!(writer, "#SBATCH --job-name=em27-i2s")?;
+    if !commands.is_empty() {
+        if let Some(n) = max_jobs {
+            writeln!(writer, "#SBATCH --array=0-{}%{n}", commands.len() - 1)?;
         } else {
-            write!(writer, "{}", head_line).change_context_lazy(|| {
-                CliError::IoError("Error writing existing line to I2S input file".to_string())
-            })?;
+            writeln!(writer, "#SBATCH --array=0-{}", commands.len() - 1)?;
         }
     }
+    if let Some(partition) = &scheduler.partition {
+        writeln!(writer, "#SBATCH --partition={partition}")?;
+    }
+    if let Some(time) = &scheduler.time {
+        writeln!(writer, "#SBATCH --time={time}")?;
+    }
+    if let Some(mem) = &scheduler.mem {
+        writeln!(writer, "#SBATCH --mem={mem}")?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "case $SLURM_ARRAY_TASK_ID in")?;
+    for (i, cmd) in commands.iter().enumerate() {
+        writeln!(writer, "    {i}) {cmd} ;;")?;
+    }
+    writeln!(
+        writer,
+        "    *) echo \"Unexpected array task ID $SLURM_ARRAY_TASK_ID\" >&2; exit 1 ;;"
+    )?;
+    writeln!(writer, "esac")?;
     Ok(())
 }
 
-fn write_parallel_file(
-    input_files: &[PathBuf],
-    parallel_file: PathBuf,
-) -> error_stack::Result<(), CliError> {
-    let gggpath = ggg_rs::utils::get_ggg_path().change_context_lazy(|| {
-        CliError::BadInput(
-            "Could not get GGGPATH, ensure the environmental variable is set".to_string(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i2s_run_command() {
+        let cmd = i2s_run_command(
+            "/ggg/bin/i2s",
+            Path::new("/data/xx/20240101/opus-i2s.in"),
+            "i2s.log",
         )
-    })?;
-    let gggpath = gggpath.to_str().ok_or_else(|| {
-        CliError::IoError("Could not convert GGGPATH value to valid UTF-8".to_string())
-    })?;
+        .unwrap();
+        assert_eq!(
+            cmd,
+            "cd /data/xx/20240101 && /ggg/bin/i2s opus-i2s.in > i2s.log"
+        );
 
-    let mut writer = std::fs::File::create(&parallel_file).change_context_lazy(|| {
-        CliError::IoError(format!(
-            "Could not create parallel input file at {}",
-            parallel_file.display()
-        ))
-    })?;
+        let cmd = i2s_run_command(
+            "i2s-wrapper",
+            Path::new("/data/xx/20240101/opus-i2s.in"),
+            "i2s_20240101.log",
+        )
+        .unwrap();
+        assert_eq!(
+            cmd,
+            "cd /data/xx/20240101 && i2s-wrapper opus-i2s.in > i2s_20240101.log"
+        );
+    }
 
-    for file in input_files {
-        let run_dir = file
-            .parent()
-            .ok_or_else(|| {
-                CliError::UnexpectedError(format!(
-                    "Could not get parent of input file {}",
-                    file.display()
-                ))
-            })?
-            .to_str()
-            .ok_or_else(|| {
-                CliError::IoError("Could not convert run directory path to valid UTF-8".to_string())
-            })?;
-
-        let input_file = file
-            .file_name()
-            .ok_or_else(|| {
-                CliError::UnexpectedError(format!(
-                    "Could not get base name of input file {}",
-                    file.display()
-                ))
-            })?
-            .to_str()
-            .ok_or_else(|| {
-                CliError::IoError(
-                    "Could not convert base name of input file to valid UTF-8".to_string(),
-                )
-            })?;
+    #[test]
+    fn test_catalog_has_entries() {
+        let igrams = vec![
+            PathBuf::from("/data/xx20240101.0001"),
+            PathBuf::from("/data/xx20240101.0002"),
+        ];
+        let input = "/data/\n./spectra/\n\nxx20240101.0002  2024  1  1  12.5  34.1\n";
+        assert!(catalog_has_entries(input.as_bytes(), &igrams).unwrap());
 
-        writeln!(
-            &mut writer,
-            "cd {run_dir} && {gggpath}/bin/i2s {input_file} > i2s.log"
-        )
-        .change_context_lazy(|| {
-            CliError::IoError(format!(
-                "Error occurred writing line for run directory {run_dir} to {}",
-                parallel_file.display()
-            ))
-        })?;
+        let top_only = "/data/\n./spectra/\n\n";
+        assert!(!catalog_has_entries(top_only.as_bytes(), &igrams).unwrap());
     }
 
-    Ok(())
+    #[test]
+    fn test_find_igrams() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("20240401")).unwrap();
+        std::fs::create_dir_all(root.join("20240402")).unwrap();
+        std::fs::write(root.join("20240401").join("xx20240401s0e00a.0001"), "").unwrap();
+
+        let inputs = DailyInputPatterns {
+            igram_pattern: format!("{}/{{DATE:%Y%m%d}}", root.display()),
+            coord_file_pattern: "coords.json".to_string(),
+            met_file_pattern: "met.json".to_string(),
+            igram_glob_pattern: "*.0001".to_string(),
+        };
+        let d = |day| chrono::NaiveDate::from_ymd_opt(2024, 4, day).unwrap();
+        let with_data = find_igrams(&inputs, "xx", d(1), true);
+        let empty = find_igrams(&inputs, "xx", d(2), false);
+        let missing = find_igrams(&inputs, "xx", d(3), false);
+
+        let (_, igrams, _) = with_data.unwrap();
+        assert_eq!(igrams.len(), 1);
+        assert!(matches!(
+            empty.unwrap_err().current_context(),
+            CliError::NoMatchingIgrams(_, _)
+        ));
+        assert!(matches!(
+            missing.unwrap_err().current_context(),
+            CliError::MissingIgramDir(_)
+        ));
+    }
+
+    #[test]
+    fn test_write_summary() {
+        let summaries = vec![DateSummary {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(),
+            n_interferograms: 10,
+            n_catalogued: 8,
+            n_skipped_missing_met: 2,
+            n_glob_errors: 0,
+        }];
+        let mut buf = vec![];
+        write_summary(&mut buf, &summaries).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            csv,
+            "date,n_interferograms,n_catalogued,n_skipped_missing_met,n_glob_errors\n2024-04-01,10,8,2,0\n"
+        );
+    }
+
+    #[test]
+    fn test_slurm_file() {
+        let commands = vec!["cmd a".to_string(), "cmd b".to_string()];
+        let scheduler = SchedulerArgs {
+            scheduler: Scheduler::Slurm,
+            partition: Some("short".to_string()),
+            time: None,
+            mem: Some("4G".to_string()),
+            jobs: None,
+            i2s_exe: None,
+            log_name: "i2s.log".to_string(),
+        };
+        let mut buf = vec![];
+        write_slurm_file(&mut buf, &commands, &scheduler, None).unwrap();
+        let script = String::from_utf8(buf).unwrap();
+        assert!(script.starts_with("#!/bin/bash\n"));
+        assert!(script.contains("#SBATCH --array=0-1\n"));
+        assert!(script.contains("#SBATCH --partition=short\n"));
+        assert!(script.contains("#SBATCH --mem=4G\n"));
+        assert!(!script.contains("--time"));
+        assert!(script.contains("    0) cmd a ;;\n"));
+        assert!(script.contains("    1) cmd b ;;\n"));
+
+        let mut buf = vec![];
+        write_slurm_file(&mut buf, &commands, &scheduler, Some(4)).unwrap();
+        let script = String::from_utf8(buf).unwrap();
+        assert!(script.contains("#SBATCH --array=0-1%4\n"));
+    }
 }
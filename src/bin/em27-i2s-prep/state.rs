@@ -0,0 +1,173 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use egi_rs::utils::pattern_replacement::render_daily_pattern;
+use error_stack::ResultExt;
+use serde::{Deserialize, Serialize};
+
+use crate::CliError;
+
+/// The name of the file in the root of the run directories that records which dates were prepared.
+pub(crate) const STATE_FILE_NAME: &str = "i2s_prep_state.json";
+
+/// Placeholders that make a run directory pattern depend on the date.
+const DATE_KEYS: [&str; 4] = ["{DATE", "{YEAR", "{MONTH", "{DOY"];
+
+/// The record of the last date successfully prepared for each site, used for incremental runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PrepState {
+    last_prepared: BTreeMap<String, chrono::NaiveDate>,
+    /// The first date that failed for each site in this run (with `--keep-going`). This is not
+    /// saved; it only stops [`PrepState::mark_prepared`] from moving the state past that date.
+    #[serde(skip)]
+    first_failed: BTreeMap<String, chrono::NaiveDate>,
+}
+
+impl PrepState {
+    /// Read the state from `path`, or return an empty state if `path` does not exist.
+    pub(crate) fn read_or_default(path: &Path) -> error_stack::Result<Self, CliError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let f = std::fs::File::open(path).change_context_lazy(|| {
+            CliError::IoError(format!("could not open state file {}", path.display()))
+        })?;
+        serde_json::from_reader(std::io::BufReader::new(f)).change_context_lazy(|| {
+            CliError::BadInput(format!("state file {} is malformed", path.display()))
+        })
+    }
+
+    /// Write the state to `path`, overwriting any existing file.
+    pub(crate) fn write(&self, path: &Path) -> error_stack::Result<(), CliError> {
+        let f = std::fs::File::create(path).change_context_lazy(|| {
+            CliError::IoError(format!("could not create state file {}", path.display()))
+        })?;
+        serde_json::to_writer_pretty(f, self).change_context_lazy(|| {
+            CliError::IoError(format!("could not write state file {}", path.display()))
+        })
+    }
+
+    /// The last date prepared for `site_id`, if any.
+    pub(crate) fn last_prepared(&self, site_id: &str) -> Option<chrono::NaiveDate> {
+        self.last_prepared.get(site_id).copied()
+    }
+
+    /// Record `date` as prepared for `site_id`. An earlier date than the one already
+    /// recorded is ignored, so that re-running old dates does not move the state back.
+    /// So is a date after one marked as failed with [`PrepState::mark_failed`], so that
+    /// the next incremental run starts from the failed date.
+    pub(crate) fn mark_prepared(&mut self, site_id: &str, date: chrono::NaiveDate) {
+        if self.first_failed.get(site_id).is_some_and(|f| date > *f) {
+            return;
+        }
+        let last = self
+            .last_prepared
+            .entry(site_id.to_string())
+            .or_insert(date);
+        if date > *last {
+            *last = date;
+        }
+    }
+
+    /// Record that preparing `date` failed for `site_id`, so that the state does not move past it.
+    pub(crate) fn mark_failed(&mut self, site_id: &str, date: chrono::NaiveDate) {
+        let first = self.first_failed.entry(site_id.to_string()).or_insert(date);
+        if date < *first {
+            *first = date;
+        }
+    }
+}
+
+/// Get the path of the state file for the run directories given by `run_dir_pattern`.
+///
+/// The state file goes in the deepest directory of the pattern that does not depend on the
+/// date, e.g. `/data/xx/spectra` for the pattern `/data/{SITE_ID}/spectra/{DATE:%Y%m%d}`.
+///
+/// # Errors
+/// - if the date-independent part of `run_dir_pattern` is not a valid pattern.
+pub(crate) fn state_file_path(
+    run_dir_pattern: &str,
+    site_id: &str,
+) -> error_stack::Result<PathBuf, CliError> {
+    let date_start = DATE_KEYS
+        .iter()
+        .filter_map(|k| run_dir_pattern.find(k))
+        .min()
+        .unwrap_or(run_dir_pattern.len());
+    let prefix = &run_dir_pattern[..date_start];
+
+    // The date is arbitrary, since there are no date placeholders left in the prefix.
+    let prefix = render_daily_pattern(prefix, chrono::NaiveDate::MIN, site_id)
+        .change_context_lazy(|| CliError::BadInput("RUN_DIR_PATTERN is not valid".to_string()))?;
+
+    let root = if prefix.ends_with(std::path::is_separator) || date_start == run_dir_pattern.len() {
+        PathBuf::from(prefix)
+    } else {
+        PathBuf::from(prefix)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default()
+    };
+
+    if root.as_os_str().is_empty() {
+        Ok(PathBuf::from(".").join(STATE_FILE_NAME))
+    } else {
+        Ok(root.join(STATE_FILE_NAME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file_path() {
+        let p = state_file_path("/data/{SITE_ID}/spectra/{DATE:%Y%m%d}", "xx").unwrap();
+        assert_eq!(p, PathBuf::from("/data/xx/spectra/i2s_prep_state.json"));
+
+        let p = state_file_path("/data/{SITE_ID}/spectra/{SITE_ID}{YEAR}{DOY}", "xx").unwrap();
+        assert_eq!(p, PathBuf::from("/data/xx/spectra/i2s_prep_state.json"));
+
+        let p = state_file_path("{DATE}", "xx").unwrap();
+        assert_eq!(p, PathBuf::from("./i2s_prep_state.json"));
+    }
+
+    #[test]
+    fn test_mark_prepared() {
+        let d1 = chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let d2 = chrono::NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        let mut state = PrepState::default();
+        assert_eq!(state.last_prepared("xx"), None);
+
+        state.mark_prepared("xx", d2);
+        state.mark_prepared("xx", d1);
+        assert_eq!(state.last_prepared("xx"), Some(d2));
+        assert_eq!(state.last_prepared("yy"), None);
+    }
+
+    #[test]
+    fn test_mark_failed() {
+        // As with --keep-going and --incremental: the 2nd fails, the 3rd succeeds
+        let d1 = chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let d2 = chrono::NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        let d3 = chrono::NaiveDate::from_ymd_opt(2024, 4, 3).unwrap();
+        let mut state = PrepState::default();
+        state.mark_prepared("xx", d1);
+        state.mark_failed("xx", d2);
+        state.mark_prepared("xx", d3);
+        state.mark_prepared("yy", d3);
+        assert_eq!(state.last_prepared("xx"), Some(d1));
+        assert_eq!(state.last_prepared("yy"), Some(d3));
+
+        // The failure is not saved, so the next run can advance once d2 succeeds
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: PrepState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.last_prepared("xx"), Some(d1));
+        state.mark_prepared("xx", d2);
+        state.mark_prepared("xx", d3);
+        assert_eq!(state.last_prepared("xx"), Some(d3));
+    }
+}
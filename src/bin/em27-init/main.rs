@@ -2,27 +2,39 @@
 //! into GGG, e.g. adding new windows or correction files, creating
 //! a directory for EGI user data, ensuring the sqlite3 database
 //! is up to date (using the [sqlx migrate
-//! macro](https://docs.rs/sqlx/latest/sqlx/macro.migrate.html), and so on.
+//! macro](https://docs.rs/sqlx/latest/sqlx/macro.migrate.html)), and so on.
 //!
 //! Each step should be designed so that if this program is run multiple times,
 //! the step will only be done once (unless it somehow gets reverted in a way
 //! that the program can't detect).
+//!
+//! The `revert` subcommand undoes the integration, running the steps in reverse
+//! order. Files are only removed if their content still matches what EGI wrote.
+//!
+//! The `check` subcommand verifies an existing integration without changing anything:
+//! that the core configuration file is valid, the files EGI provides are unmodified,
+//! and the extra programs are available.
 use clap::Parser;
-use clap_verbosity_flag::{Verbosity, WarnLevel};
 use colored::{ColoredString, Colorize};
 use egi_rs::{
+    config::CoreConfig,
     default_files::{
         default_core_config_toml, EM27_ADCFS, EM27_AICFS, EM27_EXTRA_FILTERS, EM27_QC, EM27_WINDOWS,
     },
-    utils,
+    utils::{self, logging::LogArgs},
 };
 use ggg_rs::utils::{get_ggg_path, GggError};
 use inquire::{prompt_confirmation, InquireError};
 use itertools::Itertools;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    sqlite::SqliteConnectOptions,
+    ConnectOptions,
+};
 use std::{
     borrow::Cow,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::ExitCode,
 };
 
@@ -32,14 +44,29 @@ static INSTALL_GGG_RS: &'static str =
 fn main() -> ExitCode {
     let clargs = Cli::parse();
 
-    env_logger::Builder::new()
-        .filter_level(clargs.verbose.log_level_filter())
-        .init();
-
-    let res = driver(clargs.yes);
+    clargs.logging.init_logger();
+
+    let mode = match clargs.command {
+        None => DriverMode::Install,
+        Some(InitAction::Revert) => DriverMode::Revert,
+        Some(InitAction::Check) => DriverMode::Check,
+    };
+    let prompt = PromptOptions {
+        no_diff: clargs.no_diff,
+        non_interactive: clargs.non_interactive,
+    };
+    let res = driver(clargs.yes, prompt, mode);
     match res {
         Ok(true) => ExitCode::SUCCESS,
         Ok(false) => ExitCode::from(2),
+        Err(e) if mode == DriverMode::Check => {
+            eprintln!("Error checking EGI integration:\n{e}");
+            ExitCode::FAILURE
+        }
+        Err(e) if mode == DriverMode::Revert => {
+            eprintln!("Error reverting EGI integration:\n{e}\nCorrect the underlying cause and rerun this program to complete the revert.");
+            ExitCode::FAILURE
+        }
         Err(e) => {
             eprintln!("Error initializing EGI:\n{e}\nCorrect the underlying cause and rerun this program to complete initialization.");
             ExitCode::FAILURE
@@ -51,101 +78,217 @@ fn main() -> ExitCode {
 #[derive(Debug, clap::Parser)]
 struct Cli {
     #[command(flatten)]
-    verbose: Verbosity<WarnLevel>,
+    logging: LogArgs,
 
     /// Automatically answer "yes" to any prompts.
     #[clap(short = 'y', long)]
     yes: bool,
+
+    /// Do not print how an existing file differs from the one EGI would write
+    /// before asking whether to overwrite it. The full difference is still
+    /// written to a file in the temporary directory.
+    #[clap(long)]
+    no_diff: bool,
+
+    /// What to do if a file needs to be overwritten but this program is not running
+    /// in a terminal, so it cannot ask: "skip" the file (leaving the integration
+    /// incomplete), "fail" with an error, or "overwrite" the file.
+    #[clap(long, value_enum, default_value_t = NonInteractive::Skip)]
+    non_interactive: NonInteractive,
+
+    #[command(subcommand)]
+    command: Option<InitAction>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum InitAction {
+    /// Undo the EGI integration, removing the files and menu entries that
+    /// EGI added. Files that were modified after EGI created them are left
+    /// in place.
+    Revert,
+    /// Check that the EGI integration is complete and the configuration file
+    /// is valid, without changing anything.
+    Check,
+}
+
+/// What to do when the user must be asked something, but the program is not running interactively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum NonInteractive {
+    Skip,
+    Fail,
+    Overwrite,
 }
 
-fn driver(always_yes: bool) -> Result<bool, SetupError> {
+/// Options for how steps that might overwrite files ask the user for permission.
+#[derive(Debug, Clone, Copy)]
+struct PromptOptions {
+    /// Do not print the diff between the current and new file.
+    no_diff: bool,
+    /// What to do if the user cannot be asked.
+    non_interactive: NonInteractive,
+}
+
+/// Whether the driver is setting up, reverting, or checking the EGI integration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DriverMode {
+    Install,
+    Revert,
+    Check,
+}
+
+fn driver(always_yes: bool, prompt: PromptOptions, mode: DriverMode) -> Result<bool, SetupError> {
     let ggg_path = get_ggg_path()?;
+    let revert = mode == DriverMode::Revert;
+    let config_file = ggg_path.join("egi").join("egi_config.toml");
 
     let steps = [
-        MakeDirStep::new_boxed(ggg_path.join("egi"), false),
-        CreateFileStep::new_owned_boxed(
-            default_core_config_toml(),
-            ggg_path.join("egi").join("egi_config.toml"),
-        ),
+        MakeDirStep::new_boxed(ggg_path.join("egi"), false, &ggg_path),
+        MigrateDbStep::new_boxed(ggg_path.join("egi").join("egi.sqlite3")),
+        // The user is expected to edit the configuration file, so when checking, validate
+        // its contents rather than compare it against the default.
+        if mode == DriverMode::Check {
+            CheckConfigStep::new_boxed(config_file)
+        } else {
+            CreateFileStep::new_owned_boxed(default_core_config_toml(), config_file, prompt)
+        },
         CreateFileStep::new_boxed(
             EM27_WINDOWS,
-            ggg_path.join("windows").join("gnd").join("em27.gnd"),
+            ggg_path
+                .join("windows")
+                .join("gnd")
+                .join(utils::EM27_WINDOW_FILE),
+            prompt,
+        ),
+        CreateFileStep::new_boxed(
+            EM27_QC,
+            ggg_path.join("tccon").join("EXAMPLE_EM27_qc.dat"),
+            prompt,
         ),
-        CreateFileStep::new_boxed(EM27_QC, ggg_path.join("tccon").join("EXAMPLE_EM27_qc.dat")),
         CreateFileStep::new_boxed(
             EM27_EXTRA_FILTERS,
             ggg_path
                 .join("tccon")
                 .join("EXAMPLE_EM27_extra_filters.toml"),
+            prompt,
         ),
         CreateFileStep::new_boxed(
             EM27_ADCFS,
             ggg_path
                 .join("tccon")
                 .join("corrections_airmass_postavg.em27.dat"),
+            prompt,
         ),
         CreateFileStep::new_boxed(
             EM27_AICFS,
             ggg_path
                 .join("tccon")
                 .join("corrections_insitu_postavg.em27.dat"),
+            prompt,
         ),
         AddMenuEntryStep::new_boxed(
-            ggg_path.join("windows").join("gnd").join("windows.men"),
-            "em27.gnd",
+            utils::ground_windows_menu(&ggg_path),
+            utils::EM27_WINDOW_FILE,
             Some("Subset of standard windows for an EM27 with an extended InGaAs detector"),
         ),
         CheckExtraProgramStep::new_boxed(
             "collate_tccon_results",
-            PgrmLoc::GGGPATH,
+            PgrmLoc::GGGPATH(ggg_path.clone()),
             Some(INSTALL_GGG_RS),
         ),
         CheckExtraProgramStep::new_boxed(
             "apply_tccon_airmass_correction",
-            PgrmLoc::GGGPATH,
+            PgrmLoc::GGGPATH(ggg_path.clone()),
             Some(INSTALL_GGG_RS),
         ),
         CheckExtraProgramStep::new_boxed(
             "apply_tccon_insitu_correction",
-            PgrmLoc::GGGPATH,
+            PgrmLoc::GGGPATH(ggg_path.clone()),
+            Some(INSTALL_GGG_RS),
+        ),
+        CheckExtraProgramStep::new_boxed(
+            "add_nc_flags",
+            PgrmLoc::GGGPATH(ggg_path.clone()),
             Some(INSTALL_GGG_RS),
         ),
-        CheckExtraProgramStep::new_boxed("add_nc_flags", PgrmLoc::GGGPATH, Some(INSTALL_GGG_RS)),
     ];
 
+    // When reverting, undo the steps in the opposite order they were done
+    // so that e.g. files are removed before the directory containing them.
+    let ordered_steps: Box<dyn Iterator<Item = &Box<dyn SetupStep>>> = if revert {
+        Box::new(steps.iter().rev())
+    } else {
+        Box::new(steps.iter())
+    };
+
     let mut n_skipped = 0;
     let mut n_failed = 0;
     let mut outcomes = vec![];
-    for step in steps.iter() {
-        step.describe();
-        let outcome = step.execute(always_yes)?;
+    for step in ordered_steps {
+        let outcome = match mode {
+            DriverMode::Install => {
+                step.describe();
+                step.execute(always_yes)?
+            }
+            DriverMode::Revert => {
+                step.describe_revert();
+                step.revert(always_yes)?
+            }
+            DriverMode::Check => {
+                if !step.has_check() {
+                    continue;
+                }
+                // Describe the check first, since some checks print details as they go
+                step.describe_check();
+                let Some(res) = step.check() else {
+                    continue;
+                };
+                res?
+            }
+        };
+        let step_name = match mode {
+            DriverMode::Install => step.name(),
+            DriverMode::Revert => format!("Revert: {}", step.name()).into(),
+            DriverMode::Check => format!("Check: {}", step.name()).into(),
+        };
         match outcome {
+            SetupOutcome::Executed | SetupOutcome::NotNeeded if mode == DriverMode::Check => {
+                println!("  ↪Check passed.");
+                outcomes.push((SetupDisplayOutcome::Ok, step_name, None));
+            }
             SetupOutcome::Executed => {
                 print!("  ↪");
-                step.tell_completion();
-                outcomes.push((SetupDisplayOutcome::Ok, step.name(), None));
+                if revert {
+                    step.tell_reverted();
+                } else {
+                    step.tell_completion();
+                }
+                outcomes.push((SetupDisplayOutcome::Ok, step_name, None));
             }
             SetupOutcome::NotNeeded => {
                 print!("  ↪");
-                step.tell_not_needed();
-                outcomes.push((SetupDisplayOutcome::Ok, step.name(), None));
+                if revert {
+                    step.tell_revert_not_needed();
+                } else {
+                    step.tell_not_needed();
+                }
+                outcomes.push((SetupDisplayOutcome::Ok, step_name, None));
             }
             SetupOutcome::UserSkipped => {
                 println!("  ↪Skipped as requested");
                 n_skipped += 1;
-                outcomes.push((SetupDisplayOutcome::Skipped, step.name(), None));
+                outcomes.push((SetupDisplayOutcome::Skipped, step_name, None));
             }
             SetupOutcome::OtherSkip(reason) => {
                 println!("  ↪Step skipped: {reason}");
                 n_skipped += 1;
-                outcomes.push((SetupDisplayOutcome::Skipped, step.name(), None));
+                outcomes.push((SetupDisplayOutcome::Skipped, step_name, None));
             }
             SetupOutcome::Failed => {
                 println!("  ↪Step failed");
                 n_failed += 1;
                 outcomes.push((
                     SetupDisplayOutcome::Failed,
-                    step.name(),
+                    step_name,
                     step.suggested_action(),
                 ));
             }
@@ -160,9 +303,35 @@ fn driver(always_yes: bool) -> Result<bool, SetupError> {
         }
     }
 
-    if n_skipped == 0 && n_failed == 0 {
+    if n_skipped == 0 && n_failed == 0 && mode == DriverMode::Check {
+        println!("\nEGI integration and configuration are OK.");
+        Ok(true)
+    } else if n_skipped == 0 && n_failed == 0 && revert {
+        println!("\nEGI integration reverted.");
+        Ok(true)
+    } else if n_skipped == 0 && n_failed == 0 {
         println!("\nEGI initialization complete.");
         Ok(true)
+    } else if mode == DriverMode::Check {
+        println!();
+        if n_skipped > 0 {
+            print!("{n_skipped} checks were skipped, ");
+        }
+        if n_failed > 0 {
+            print!("{n_failed} checks failed, ");
+        }
+        println!("review the checks skipped/failed. Nothing was changed; rerun this program without `check` to repair the integration.");
+        Ok(false)
+    } else if revert {
+        println!();
+        if n_skipped > 0 {
+            print!("{n_skipped} steps were skipped, ");
+        }
+        if n_failed > 0 {
+            print!("{n_failed} steps/checks failed, ");
+        }
+        println!("some parts of the EGI integration may remain. Review the steps skipped/failed and remove those files manually if needed.");
+        Ok(false)
     } else {
         print!("\n");
         if n_skipped > 0 {
@@ -222,6 +391,8 @@ enum SetupError {
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     GggError(#[from] GggError),
+    #[error("{} needs to be overwritten, but the program is not running interactively", .0.display())]
+    NonInteractive(PathBuf),
     #[error("{0}")]
     Other(String),
 }
@@ -235,6 +406,43 @@ trait SetupStep {
     fn suggested_action(&self) -> Option<&str> {
         None
     }
+
+    /// Print what undoing this step will do.
+    fn describe_revert(&self) {
+        println!("Reverting step '{}'", self.name());
+    }
+
+    fn tell_reverted(&self) {
+        println!("Reverted.");
+    }
+
+    fn tell_revert_not_needed(&self) {
+        println!("Nothing to revert.");
+    }
+
+    /// Undo this step. The default is for steps that do not change anything
+    /// (e.g. checks), so there is nothing to undo.
+    fn revert(&self, _always_yes: bool) -> SetupResult {
+        Ok(SetupOutcome::NotNeeded)
+    }
+
+    /// Print what checking this step will do.
+    fn describe_check(&self) {
+        println!("Checking step '{}'", self.name());
+    }
+
+    /// Whether `em27-init check` checks this step, i.e. [`SetupStep::check`] returns `Some`.
+    /// Steps that implement `check` must also override this to return `true`.
+    fn has_check(&self) -> bool {
+        false
+    }
+
+    /// Check whether this step is complete without changing anything, returning
+    /// `NotNeeded` if so and `Failed` if not. The default is `None`, for steps
+    /// that are not checked by `em27-init check`.
+    fn check(&self) -> Option<SetupResult> {
+        None
+    }
 }
 
 /// Initialization step to create a file.
@@ -244,8 +452,13 @@ trait SetupStep {
 struct CreateFileStep {
     source: Cow<'static, str>,
     dest: PathBuf,
+    prompt: PromptOptions,
 }
 
+/// The most lines of a diff to print before asking to overwrite a file,
+/// the full diff is written to a file if it is longer than this.
+const MAX_DIFF_LINES: usize = 40;
+
 /// Used to indicate whether a file to create exists, needs created,
 /// or needs overwritten.
 #[derive(Debug, Clone)]
@@ -262,15 +475,23 @@ enum FileStatus {
 }
 
 impl CreateFileStep {
-    fn new_boxed(source: &'static str, dest: PathBuf) -> Box<dyn SetupStep> {
+    fn new_boxed(source: &'static str, dest: PathBuf, prompt: PromptOptions) -> Box<dyn SetupStep> {
         let source = Cow::Borrowed(source);
-        let me = Self { source, dest };
+        let me = Self {
+            source,
+            dest,
+            prompt,
+        };
         Box::new(me)
     }
 
-    fn new_owned_boxed(source: String, dest: PathBuf) -> Box<dyn SetupStep> {
+    fn new_owned_boxed(source: String, dest: PathBuf, prompt: PromptOptions) -> Box<dyn SetupStep> {
         let source = Cow::Owned(source);
-        let me = Self { source, dest };
+        let me = Self {
+            source,
+            dest,
+            prompt,
+        };
         Box::new(me)
     }
 
@@ -315,12 +536,48 @@ impl CreateFileStep {
             3,
         );
 
-        for line in diff {
+        // Print at most MAX_DIFF_LINES of the diff (or none with --no-diff) so that large
+        // files don't flood the terminal, and put the full diff in a file if any was left out.
+        let n_print = if self.prompt.no_diff {
+            0
+        } else {
+            diff.len().min(MAX_DIFF_LINES)
+        };
+        for line in &diff[..n_print] {
             println!("{line}");
         }
+        if n_print < diff.len() {
+            if n_print > 0 {
+                println!("...{} more lines...", diff.len() - n_print);
+            }
+            self.write_full_diff(&diff);
+        }
 
         prompt_confirmation("Okay to overwrite?")
     }
+
+    /// Write the full diff to a file in the temporary directory and tell the user where it is.
+    fn write_full_diff(&self, diff: &[String]) {
+        let file_name = self
+            .dest
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let diff_file = std::env::temp_dir().join(format!("egi-init-{file_name}.diff"));
+        let res = std::fs::File::create(&diff_file).and_then(|mut f| {
+            for line in diff {
+                writeln!(f, "{line}")?;
+            }
+            Ok(())
+        });
+        match res {
+            Ok(_) => println!("The full differences are in {}", diff_file.display()),
+            Err(e) => log::warn!(
+                "Could not write the full differences to {}: {e}",
+                diff_file.display()
+            ),
+        }
+    }
 }
 
 impl SetupStep for CreateFileStep {
@@ -355,11 +612,18 @@ impl SetupStep for CreateFileStep {
                     Err(InquireError::OperationCanceled) => return Err(SetupError::UserAbort),
                     Err(InquireError::OperationInterrupted) => panic!("Ctrl+C received, aborting"),
                     Err(InquireError::IO(e)) => return Err(SetupError::IoError(e)),
-                    Err(InquireError::NotTTY) => {
-                        return Ok(SetupOutcome::OtherSkip(
-                            "input required but program is not running interactively".to_string(),
-                        ))
-                    }
+                    Err(InquireError::NotTTY) => match self.prompt.non_interactive {
+                        NonInteractive::Skip => {
+                            return Ok(SetupOutcome::OtherSkip(
+                                "input required but program is not running interactively"
+                                    .to_string(),
+                            ))
+                        }
+                        NonInteractive::Fail => {
+                            return Err(SetupError::NonInteractive(self.dest.clone()))
+                        }
+                        NonInteractive::Overwrite => (),
+                    },
                     Err(InquireError::InvalidConfiguration(e)) => return Err(SetupError::Other(e)),
                     Err(InquireError::Custom(e)) => return Err(SetupError::Other(e.to_string())),
                 }
@@ -371,19 +635,82 @@ impl SetupStep for CreateFileStep {
         f.write_all(self.source.as_bytes())?;
         Ok(SetupOutcome::Executed)
     }
+
+    fn describe_revert(&self) {
+        println!("Removing file {}", self.dest.display());
+    }
+
+    fn tell_reverted(&self) {
+        println!("File removed.");
+    }
+
+    fn tell_revert_not_needed(&self) {
+        println!("File does not exist, nothing to remove.");
+    }
+
+    fn describe_check(&self) {
+        println!(
+            "Checking that {} matches the file EGI provides",
+            self.dest.display()
+        );
+    }
+
+    fn has_check(&self) -> bool {
+        true
+    }
+
+    fn check(&self) -> Option<SetupResult> {
+        let outcome = match self.file_status() {
+            Ok(FileStatus::Extant) => SetupOutcome::NotNeeded,
+            Ok(FileStatus::Missing) => {
+                println!("  {} does not exist", self.dest.display());
+                SetupOutcome::Failed
+            }
+            Ok(FileStatus::ContentDiffers(_)) => {
+                println!(
+                    "  {} differs from the file EGI provides",
+                    self.dest.display()
+                );
+                SetupOutcome::Failed
+            }
+            Err(e) => return Some(Err(e.into())),
+        };
+        Some(Ok(outcome))
+    }
+
+    fn revert(&self, _always_yes: bool) -> SetupResult {
+        match self.file_status()? {
+            FileStatus::Missing => Ok(SetupOutcome::NotNeeded),
+            FileStatus::ContentDiffers(_) => {
+                log::warn!(
+                    "{} has been modified since EGI created it, it will not be removed",
+                    self.dest.display()
+                );
+                Ok(SetupOutcome::OtherSkip(
+                    "file content differs from what EGI wrote, not removing it".to_string(),
+                ))
+            }
+            FileStatus::Extant => {
+                std::fs::remove_file(&self.dest)?;
+                Ok(SetupOutcome::Executed)
+            }
+        }
+    }
 }
 
 /// Initialization step to create a new directory.
 struct MakeDirStep {
     target_dir: PathBuf,
     create_parents: bool,
+    ggg_path: PathBuf,
 }
 
 impl MakeDirStep {
-    fn new_boxed(target_dir: PathBuf, create_parents: bool) -> Box<dyn SetupStep> {
+    fn new_boxed(target_dir: PathBuf, create_parents: bool, ggg_path: &Path) -> Box<dyn SetupStep> {
         let me = Self {
             target_dir,
             create_parents,
+            ggg_path: ggg_path.to_path_buf(),
         };
         Box::new(me)
     }
@@ -392,12 +719,8 @@ impl MakeDirStep {
 impl SetupStep for MakeDirStep {
     fn name(&self) -> Cow<'static, str> {
         // Abbreviate the path if it is inside GGGPATH
-        let dir_name = if let Ok(ggg_path) = get_ggg_path() {
-            if let Ok(subdir) = self.target_dir.strip_prefix(&ggg_path) {
-                format!("$GGGPATH/{}", subdir.display())
-            } else {
-                format!("{}", self.target_dir.display())
-            }
+        let dir_name = if let Ok(subdir) = self.target_dir.strip_prefix(&self.ggg_path) {
+            format!("$GGGPATH/{}", subdir.display())
         } else {
             format!("{}", self.target_dir.display())
         };
@@ -446,6 +769,40 @@ impl SetupStep for MakeDirStep {
             Ok(SetupOutcome::Executed)
         }
     }
+
+    fn describe_revert(&self) {
+        println!("Removing directory {}", self.target_dir.display());
+    }
+
+    fn tell_reverted(&self) {
+        println!("Directory removed.");
+    }
+
+    fn tell_revert_not_needed(&self) {
+        println!("Directory does not exist, nothing to remove.");
+    }
+
+    fn revert(&self, _always_yes: bool) -> SetupResult {
+        if !self.target_dir.exists() {
+            return Ok(SetupOutcome::NotNeeded);
+        } else if !self.target_dir.is_dir() {
+            return Ok(SetupOutcome::OtherSkip(
+                "Target directory exists as a file, which is not expected".to_string(),
+            ));
+        }
+
+        // Only remove the directory if everything EGI put in it was already removed,
+        // anything left is either user data or a file modified by the user.
+        let is_empty = std::fs::read_dir(&self.target_dir)?.next().is_none();
+        if !is_empty {
+            return Ok(SetupOutcome::OtherSkip(
+                "Directory is not empty, not removing it".to_string(),
+            ));
+        }
+
+        std::fs::remove_dir(&self.target_dir)?;
+        Ok(SetupOutcome::Executed)
+    }
 }
 
 /// Initialization step to add an entry to a GGG `.men` (i.e., menu) file.
@@ -497,23 +854,152 @@ impl SetupStep for AddMenuEntryStep {
     }
 
     fn execute(&self, _always_yes: bool) -> SetupResult {
-        let current_entries = utils::read_menu_file(&self.menu_file)?;
-        for entry in current_entries {
-            if entry.value == self.value {
-                return Ok(SetupOutcome::NotNeeded);
-            }
+        if utils::find_menu_entry(&self.menu_file, self.value)?.is_some() {
+            return Ok(SetupOutcome::NotNeeded);
         }
 
         utils::add_menu_entry(&self.menu_file, self.value, self.description)?;
         Ok(SetupOutcome::Executed)
     }
+
+    fn describe_revert(&self) {
+        println!(
+            "Removing entry '{}' from menu {}",
+            self.value,
+            self.menu_file.display()
+        );
+    }
+
+    fn tell_reverted(&self) {
+        println!("Removed entry.");
+    }
+
+    fn tell_revert_not_needed(&self) {
+        println!("Did not remove entry (not present).");
+    }
+
+    fn revert(&self, _always_yes: bool) -> SetupResult {
+        if !self.menu_file.exists() {
+            return Ok(SetupOutcome::NotNeeded);
+        }
+
+        if utils::remove_menu_entry(&self.menu_file, self.value)? {
+            Ok(SetupOutcome::Executed)
+        } else {
+            Ok(SetupOutcome::NotNeeded)
+        }
+    }
+}
+
+/// Initialization step to create the EGI sqlite3 database (if needed) and
+/// apply any migrations from the `migrations` directory not yet applied to it.
+struct MigrateDbStep {
+    db_file: PathBuf,
+}
+
+impl MigrateDbStep {
+    fn new_boxed(db_file: PathBuf) -> Box<dyn SetupStep> {
+        Box::new(Self { db_file })
+    }
+
+    async fn migrate(&self) -> Result<SetupOutcome, sqlx::Error> {
+        static MIGRATOR: Migrator = sqlx::migrate!();
+
+        let mut conn = SqliteConnectOptions::new()
+            .filename(&self.db_file)
+            .create_if_missing(true)
+            .connect()
+            .await?;
+
+        conn.ensure_migrations_table().await?;
+        let applied = conn
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect_vec();
+        let n_pending = MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+            .count();
+
+        if n_pending == 0 {
+            return Ok(SetupOutcome::NotNeeded);
+        }
+
+        log::info!(
+            "Applying {n_pending} migration(s) to {}",
+            self.db_file.display()
+        );
+        MIGRATOR.run(&mut conn).await?;
+        Ok(SetupOutcome::Executed)
+    }
+}
+
+impl SetupStep for MigrateDbStep {
+    fn name(&self) -> Cow<'static, str> {
+        "Update EGI database".into()
+    }
+
+    fn describe(&self) {
+        println!("Updating database {}", self.db_file.display());
+    }
+
+    fn tell_completion(&self) {
+        println!("Database migrations applied.");
+    }
+
+    fn tell_not_needed(&self) {
+        println!("Database already up to date.");
+    }
+
+    fn execute(&self, _always_yes: bool) -> SetupResult {
+        if !self.db_file.parent().is_some_and(|p| p.is_dir()) {
+            return Ok(SetupOutcome::OtherSkip(
+                "Could not create database; parent directory does not exist.".to_string(),
+            ));
+        }
+
+        // sqlx's connection timeouts need the tokio time driver.
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(SetupError::IoError)?;
+        rt.block_on(self.migrate())
+            .map_err(|e| SetupError::Other(format!("Error updating the EGI database: {e}")))
+    }
+
+    fn describe_revert(&self) {
+        println!(
+            "The EGI database {} is never removed when reverting, since it may contain user data",
+            self.db_file.display()
+        );
+    }
+
+    fn tell_revert_not_needed(&self) {
+        println!("Database does not exist, nothing to leave in place.");
+    }
+
+    /// This step cannot be reverted: the database may hold information the user added,
+    /// so they must delete it themselves if they want it gone.
+    fn revert(&self, _always_yes: bool) -> SetupResult {
+        if self.db_file.exists() {
+            Ok(SetupOutcome::OtherSkip(format!(
+                "not revertible, delete {} manually to remove the database",
+                self.db_file.display()
+            )))
+        } else {
+            Ok(SetupOutcome::NotNeeded)
+        }
+    }
 }
 
 /// Used to indicate where to look for extra programs
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum PgrmLoc {
-    /// Program expected to exist under $GGGPATH/bin.
-    GGGPATH,
+    /// Program expected to exist under $GGGPATH/bin, with the contained
+    /// [`PathBuf`] being the GGGPATH.
+    GGGPATH(PathBuf),
     /// Program expected to exist on the user's shell's PATH.
     #[allow(unused)]
     PATH,
@@ -550,7 +1036,7 @@ impl SetupStep for CheckExtraProgramStep {
 
     fn describe(&self) {
         match self.location {
-            PgrmLoc::GGGPATH => println!(
+            PgrmLoc::GGGPATH(_) => println!(
                 "Checking that program {} is available in $GGGPATH/bin",
                 self.program
             ),
@@ -570,12 +1056,18 @@ impl SetupStep for CheckExtraProgramStep {
         self.correction.as_deref()
     }
 
+    fn has_check(&self) -> bool {
+        true
+    }
+
+    fn check(&self) -> Option<SetupResult> {
+        // Looking for the program does not change anything, so checking is the same as executing.
+        Some(self.execute(false))
+    }
+
     fn execute(&self, _always_yes: bool) -> SetupResult {
-        let found = match self.location {
-            PgrmLoc::GGGPATH => {
-                let ggg_path = get_ggg_path()?;
-                ggg_path.join("bin").join(self.program).is_file()
-            }
+        let found = match &self.location {
+            PgrmLoc::GGGPATH(ggg_path) => ggg_path.join("bin").join(self.program).is_file(),
             PgrmLoc::PATH => which::which(self.program).is_ok(),
         };
 
@@ -586,3 +1078,70 @@ impl SetupStep for CheckExtraProgramStep {
         }
     }
 }
+
+/// Check that the core configuration file can be read and has valid email addresses.
+/// This is only used by `em27-init check`; the file is created by a [`CreateFileStep`].
+struct CheckConfigStep {
+    config_file: PathBuf,
+}
+
+impl CheckConfigStep {
+    fn new_boxed(config_file: PathBuf) -> Box<dyn SetupStep> {
+        Box::new(Self { config_file })
+    }
+}
+
+impl SetupStep for CheckConfigStep {
+    fn name(&self) -> Cow<'static, str> {
+        "Validate 'egi_config.toml'".into()
+    }
+
+    fn describe(&self) {
+        println!("Validating {}", self.config_file.display());
+    }
+
+    fn tell_completion(&self) {
+        println!("Configuration is valid.");
+    }
+
+    fn tell_not_needed(&self) {
+        println!("Configuration is valid.");
+    }
+
+    fn suggested_action(&self) -> Option<&str> {
+        Some("Edit $GGGPATH/egi/egi_config.toml to fix the problems listed above")
+    }
+
+    fn execute(&self, _always_yes: bool) -> SetupResult {
+        let config = match CoreConfig::read_from_path(&self.config_file) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("  {e}");
+                return Ok(SetupOutcome::Failed);
+            }
+        };
+
+        let problems = config.email_problems();
+        for problem in problems.iter() {
+            println!("  {problem}");
+        }
+
+        if problems.is_empty() {
+            Ok(SetupOutcome::NotNeeded)
+        } else {
+            Ok(SetupOutcome::Failed)
+        }
+    }
+
+    fn describe_check(&self) {
+        self.describe();
+    }
+
+    fn has_check(&self) -> bool {
+        true
+    }
+
+    fn check(&self) -> Option<SetupResult> {
+        Some(self.execute(false))
+    }
+}
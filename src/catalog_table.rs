@@ -0,0 +1,335 @@
+//! Reading and writing OPUS catalog tables.
+//!
+//! [`crate::i2s_catalog`] builds catalog entries from interferograms; this module is for
+//! working with catalogs that already exist (e.g. ones written by `em27-catalogue` or
+//! older versions of EGI). [`read_opus_catalogue_table`] loads a catalog into [`CatalogRow`]s,
+//! which can be edited or filtered, then written back out with [`write_opus_catalogue_rows`]
+//! or converted to [`OpusCatalogueEntry`] instances with [`CatalogRow::to_entry`].
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use error_stack::ResultExt;
+use ggg_rs::i2s::OpusCatalogueEntry;
+
+/// The number of columns that every catalog row must have.
+pub(crate) const N_REQUIRED_COLUMNS: usize = 14;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CatalogTableError {
+    #[error("Could not open catalog file {}", .0.display())]
+    Open(PathBuf),
+    #[error("Error reading line {0} of the catalog")]
+    Read(usize),
+    #[error("Line {0} of the catalog has {1} columns, expected at least {N_REQUIRED_COLUMNS}")]
+    TooFewColumns(usize, usize),
+    #[error("Line {0} of the catalog has an invalid {1} value: {2}")]
+    InvalidValue(usize, &'static str, String),
+    #[error("Could not convert the catalog row for {0} to a catalog entry")]
+    Entry(String),
+}
+
+/// One row of an OPUS catalog table, i.e. one interferogram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogRow {
+    /// The interferogram file name.
+    pub spectrum_name: String,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    /// The run number, which I2S uses to construct the spectrum names.
+    pub run: u32,
+    /// Latitude in degrees north.
+    pub lat: f32,
+    /// Longitude in degrees east.
+    pub lon: f32,
+    /// Altitude in kilometers.
+    pub alt: f32,
+    /// Instrument temperature in degrees C.
+    pub tins: f32,
+    /// Instrument pressure in hPa.
+    pub pins: f32,
+    /// Instrument relative humidity in percent.
+    pub hins: f32,
+    /// Outside temperature in degrees C.
+    pub tout: f32,
+    /// Outside pressure in hPa.
+    pub pout: f32,
+    /// Outside relative humidity in percent.
+    pub hout: f32,
+    /// Any columns after the outside humidity (e.g. solar intensity or wind), kept as-is
+    /// so that they are written back out unchanged.
+    pub extra: Vec<String>,
+}
+
+impl CatalogRow {
+    /// Convert this row to an [`OpusCatalogueEntry`], e.g. to pass to [`ggg_rs::i2s::write_opus_catalogue_table`].
+    ///
+    /// `fill` is the fill value for any missing values, as in [`OpusCatalogueEntry`]'s builder. Note that
+    /// `extra` is not carried over.
+    ///
+    /// # Errors
+    /// - if the date or coordinates are invalid.
+    pub fn to_entry(
+        &self,
+        fill: f32,
+    ) -> error_stack::Result<OpusCatalogueEntry, CatalogTableError> {
+        let err = || CatalogTableError::Entry(self.spectrum_name.clone());
+        OpusCatalogueEntry::build(self.spectrum_name.clone())
+            .with_time(self.year, self.month, self.day, self.run)
+            .change_context_lazy(err)?
+            .with_coordinates(self.lat, self.lon, self.alt)
+            .change_context_lazy(err)?
+            .with_instrument(self.tins, self.pins, self.hins)
+            .with_outside_met(self.tout, self.pout, self.hout)
+            .finalize(fill)
+            .change_context_lazy(err)
+    }
+
+    pub(crate) fn from_line(
+        line: &str,
+        line_num: usize,
+    ) -> error_stack::Result<Self, CatalogTableError> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < N_REQUIRED_COLUMNS {
+            return Err(CatalogTableError::TooFewColumns(line_num, parts.len()).into());
+        }
+
+        Ok(Self {
+            spectrum_name: parts[0].to_string(),
+            year: parse_column(parts[1], line_num, "year")?,
+            month: parse_column(parts[2], line_num, "month")?,
+            day: parse_column(parts[3], line_num, "day")?,
+            run: parse_column(parts[4], line_num, "run")?,
+            lat: parse_column(parts[5], line_num, "latitude")?,
+            lon: parse_column(parts[6], line_num, "longitude")?,
+            alt: parse_column(parts[7], line_num, "altitude")?,
+            tins: parse_column(parts[8], line_num, "instrument temperature")?,
+            pins: parse_column(parts[9], line_num, "instrument pressure")?,
+            hins: parse_column(parts[10], line_num, "instrument humidity")?,
+            tout: parse_column(parts[11], line_num, "outside temperature")?,
+            pout: parse_column(parts[12], line_num, "outside pressure")?,
+            hout: parse_column(parts[13], line_num, "outside humidity")?,
+            extra: parts[N_REQUIRED_COLUMNS..]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        })
+    }
+}
+
+fn parse_column<T: FromStr>(
+    s: &str,
+    line_num: usize,
+    column: &'static str,
+) -> Result<T, CatalogTableError> {
+    s.parse()
+        .map_err(|_| CatalogTableError::InvalidValue(line_num, column, s.to_string()))
+}
+
+/// Read an OPUS catalog table from `reader`.
+///
+/// Blank lines and comment lines (starting with a colon) are skipped, as is the first other line
+/// if it is a header (i.e. its second column is not an integer year). Every other line must have at least the 14
+/// standard columns (spectrum name through outside humidity); additional columns are kept
+/// in [`CatalogRow::extra`].
+///
+/// # Errors
+/// - if reading a line fails, or
+/// - if a line has too few columns or a value that cannot be parsed. This includes a second
+///   header-like line, so that a row with a corrupted year is not silently dropped.
+pub fn read_opus_catalogue_table<R: BufRead>(
+    reader: R,
+) -> error_stack::Result<Vec<CatalogRow>, CatalogTableError> {
+    let mut rows = vec![];
+    let mut first_line = true;
+    for (line_num, line) in reader.lines().enumerate() {
+        let line_num = line_num + 1;
+        let line = line.change_context_lazy(|| CatalogTableError::Read(line_num))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(':') {
+            continue;
+        }
+        // Only the first line can be the header; anything after that must be a valid row
+        let is_header = first_line && is_header_line(trimmed);
+        first_line = false;
+        if is_header {
+            continue;
+        }
+        rows.push(CatalogRow::from_line(trimmed, line_num)?);
+    }
+    Ok(rows)
+}
+
+/// Read an OPUS catalog table from the file at `path`, see [`read_opus_catalogue_table`].
+pub fn read_opus_catalogue_file(
+    path: &Path,
+) -> error_stack::Result<Vec<CatalogRow>, CatalogTableError> {
+    let f = std::fs::File::open(path)
+        .change_context_lazy(|| CatalogTableError::Open(path.to_path_buf()))?;
+    read_opus_catalogue_table(BufReader::new(f))
+}
+
+fn is_header_line(line: &str) -> bool {
+    line.split_whitespace()
+        .nth(1)
+        .map(|s| s.parse::<i32>().is_err())
+        .unwrap_or(false)
+}
+
+/// Write `rows` as an OPUS catalog table to `writer`, with a header line unless `no_header` is `true`.
+///
+/// The output can be read back with [`read_opus_catalogue_table`]. Coordinates are written with four
+/// (latitude and longitude) or three (altitude) decimal places and the met values with two.
+pub fn write_opus_catalogue_rows<W: Write>(
+    writer: &mut W,
+    rows: &[CatalogRow],
+    no_header: bool,
+) -> std::io::Result<()> {
+    let name_width = rows
+        .iter()
+        .map(|r| r.spectrum_name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Spectrum_Name".len());
+
+    if !no_header {
+        writeln!(
+            writer,
+            "{:<name_width$}  {:>4} {:>3} {:>3} {:>5} {:>9} {:>10} {:>7} {:>7} {:>8} {:>7} {:>7} {:>8} {:>7}",
+            "Spectrum_Name", "Year", "Mon", "Day", "Run", "Lat", "Lon", "Alt", "Tins", "Pins", "Hins", "Tout", "Pout", "Hout"
+        )?;
+    }
+
+    for row in rows {
+        write!(
+            writer,
+            "{:<name_width$}  {:>4} {:>3} {:>3} {:>5} {:>9.4} {:>10.4} {:>7.3} {:>7.2} {:>8.2} {:>7.2} {:>7.2} {:>8.2} {:>7.2}",
+            row.spectrum_name,
+            row.year,
+            row.month,
+            row.day,
+            row.run,
+            row.lat,
+            row.lon,
+            row.alt,
+            row.tins,
+            row.pins,
+            row.hins,
+            row.tout,
+            row.pout,
+            row.hout
+        )?;
+        for value in row.extra.iter() {
+            write!(writer, " {value}")?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_round_trip() {
+        let rows = vec![
+            CatalogRow {
+                spectrum_name: "xx20240401s0e00a.0001".to_string(),
+                year: 2024,
+                month: 4,
+                day: 1,
+                run: 1,
+                lat: 34.136,
+                lon: -118.127,
+                alt: 0.237,
+                tins: 30.5,
+                pins: 985.25,
+                hins: 20.0,
+                tout: 25.75,
+                pout: 985.25,
+                hout: 40.5,
+                extra: vec![],
+            },
+            CatalogRow {
+                spectrum_name: "xx20240401s0e00a.0002".to_string(),
+                year: 2024,
+                month: 4,
+                day: 1,
+                run: 3,
+                lat: 34.136,
+                lon: -118.127,
+                alt: 0.237,
+                tins: 30.75,
+                pins: 985.0,
+                hins: -99.0,
+                tout: 26.0,
+                pout: 985.0,
+                hout: 41.0,
+                extra: vec!["0.5".to_string()],
+            },
+        ];
+
+        let mut buf = vec![];
+        write_opus_catalogue_rows(&mut buf, &rows, false).unwrap();
+        let read_back = read_opus_catalogue_table(buf.as_slice()).unwrap();
+        assert_eq!(read_back, rows);
+
+        let mut buf = vec![];
+        write_opus_catalogue_rows(&mut buf, &rows, true).unwrap();
+        let read_back = read_opus_catalogue_table(buf.as_slice()).unwrap();
+        assert_eq!(read_back, rows);
+    }
+
+    #[test]
+    fn test_read_ggg_rs_catalog() {
+        let catalog = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_inputs")
+            .join("catalog")
+            .join("xx_catalog.txt");
+        let rows = read_opus_catalogue_file(&catalog).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let row = &rows[0];
+        assert_eq!(row.spectrum_name, "xx20240401s0e00a.0001");
+        assert_eq!((row.year, row.month, row.day, row.run), (2024, 4, 1, 1));
+        assert_eq!((row.lat, row.lon, row.alt), (34.136, -118.127, 0.237));
+        assert_eq!((row.tins, row.pins, row.hins), (30.5, 984.5, 45.0));
+        assert_eq!((row.tout, row.pout, row.hout), (21.0, 984.5, 45.0));
+        assert!(row.extra.is_empty());
+
+        assert_eq!(rows[1].run, 3);
+        assert_eq!(rows[1].hins, crate::CATALOG_FILL_FLOAT_F32);
+        for row in rows.iter() {
+            row.to_entry(crate::CATALOG_FILL_FLOAT_F32).unwrap();
+        }
+
+        // Our own writer should give back the same rows, so catalogs can be edited without losing anything
+        let mut buf = vec![];
+        write_opus_catalogue_rows(&mut buf, &rows, false).unwrap();
+        assert_eq!(read_opus_catalogue_table(buf.as_slice()).unwrap(), rows);
+    }
+
+    #[test]
+    fn test_read_catalog_errors() {
+        let short = "xx20240401s0e00a.0001 2024 4 1 1 34.1 -118.1\n";
+        assert!(read_opus_catalogue_table(short.as_bytes()).is_err());
+
+        let bad_value = "xx20240401s0e00a.0001 2024 4 1 1 34.1 -118.1 0.2 30 985 x 25 985 40\n";
+        assert!(read_opus_catalogue_table(bad_value.as_bytes()).is_err());
+
+        // A bad year after the header must be an error, not taken as another header
+        let bad_year = "Spectrum_Name Year Mon Day Run Lat Lon Alt Tins Pins Hins Tout Pout Hout
+xx20240401s0e00a.0001 2024 4 1 1 34.1 -118.1 0.2 30 985 40 25 985 40
+xx20240401s0e00a.0002 2O24 4 1 3 34.1 -118.1 0.2 30 985 40 25 985 40
+";
+        let err = read_opus_catalogue_table(bad_year.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            CatalogTableError::InvalidValue(3, "year", _)
+        ));
+    }
+}
@@ -1,18 +1,38 @@
 use std::{
+    collections::BTreeSet,
     fmt::Display,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use clap::Args;
+use clap::{Args, ValueEnum};
+use itertools::Itertools;
 use serde::{de, Deserialize, Serialize};
 
-use crate::default_files;
+use crate::{
+    default_files, get_egi_path,
+    i2s_catalog::MixedTimezones,
+    utils::{
+        igram::{read_igram_header, read_opus_directory},
+        pattern_replacement::{render_daily_pattern, PatternError},
+        LineEnding,
+    },
+    EgiPathError,
+};
 use ggg_rs::{
     i2s::{I2SHeaderEdit, I2SInputModifcations},
-    opus::{self, constants::bruker::BrukerParValue},
+    opus::constants::bruker::{BrukerBlockType, BrukerParValue},
 };
 
+/// The status blocks of the interferogram channels that [`DetectorSet::infer_from_header`] checks
+/// for data, with the name used for each channel in messages. These are the only channels that
+/// can be identified by name; any other interferogram data blocks are counted from the file's
+/// block directory and reported as unidentified.
+const IGRAM_CHANNEL_STATUS_BLOCKS: [(&str, BrukerBlockType); 2] = [
+    ("primary", BrukerBlockType::IgramPrimaryStatus),
+    ("secondary", BrukerBlockType::IgramSecondaryStatus),
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CoreConfig {
     /// The email address used to access the Caltech FTP server
@@ -22,6 +42,105 @@ pub struct CoreConfig {
     /// from the priors automation system. It does not need to
     /// be the same as the FTP email.
     pub priors_request_email: String,
+
+    /// The number of I2S or GFIT runs to do at once when the
+    /// prep programs are not given a number of jobs.
+    #[serde(default)]
+    pub default_jobs: Option<usize>,
+}
+
+impl CoreConfig {
+    /// Read the core configuration from a TOML file, usually `$GGGPATH/egi/egi_config.toml`.
+    pub fn read_from_path<P: AsRef<Path>>(p: P) -> Result<Self, CommonConfigError> {
+        let p = p.as_ref();
+        let contents = std::fs::read_to_string(p).map_err(|e| {
+            CommonConfigError::IoError(format!("could not open {}: {e}", p.display()))
+        })?;
+        toml::from_str(&contents)
+            .map_err(|e| CommonConfigError::IoError(format!("{} is not correct: {e}", p.display())))
+    }
+
+    /// Read the core configuration from `egi_config.toml` in the EGI directory, i.e.
+    /// `$GGGPATH/egi/egi_config.toml` unless `EGIPATH` is set.
+    ///
+    /// This uses the EGI directory found by the first lookup, so it stays consistent for the whole run.
+    pub fn read_from_ggg_path() -> Result<Self, CommonConfigError> {
+        let egi_path = crate::get_egi_path().map_err(|e| {
+            CommonConfigError::IoError(format!("could not get the EGI directory: {e}"))
+        })?;
+        Self::read_from_path(egi_path.join("egi_config.toml"))
+    }
+
+    /// Check that the email addresses look like email addresses.
+    ///
+    /// Returns a description of each problem found, so an empty vector means the
+    /// configuration is valid. The placeholder address written by `em27-init` counts
+    /// as a problem, since it means the configuration was never filled in.
+    pub fn email_problems(&self) -> Vec<String> {
+        [
+            ("ftp_email", &self.ftp_email),
+            ("priors_request_email", &self.priors_request_email),
+        ]
+        .into_iter()
+        .filter_map(|(key, email)| {
+            if email == PLACEHOLDER_EMAIL {
+                Some(format!(
+                    "{key} is still the placeholder {PLACEHOLDER_EMAIL}"
+                ))
+            } else if !looks_like_email(email) {
+                Some(format!(
+                    "{key} = '{email}' does not look like an email address"
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+    }
+}
+
+/// Get the number of I2S or GFIT runs to do at once.
+///
+/// This is `jobs` if given, otherwise `default_jobs` from the core configuration
+/// (see [`CoreConfig::read_from_ggg_path`]) if that is set, otherwise 1.
+pub fn resolve_jobs(jobs: Option<usize>) -> usize {
+    configured_jobs(jobs).unwrap_or(1)
+}
+
+/// Like [`resolve_jobs`], but returns `None` if neither `jobs` nor `default_jobs` are set.
+pub fn configured_jobs(jobs: Option<usize>) -> Option<usize> {
+    if jobs.is_some() {
+        return jobs;
+    }
+
+    match CoreConfig::read_from_ggg_path() {
+        Ok(config) => config.default_jobs,
+        Err(e) => {
+            log::debug!(
+                "Could not read the core configuration for the default number of jobs: {e}"
+            );
+            None
+        }
+    }
+}
+
+/// The email address put in the core configuration file when it is first created.
+pub const PLACEHOLDER_EMAIL: &str = "you@example.com";
+
+/// A loose check that `s` is an email address: something, an @, then a domain with a dot in it,
+/// and no whitespace.
+fn looks_like_email(s: &str) -> bool {
+    if s.contains(char::is_whitespace) {
+        return false;
+    }
+    let Some((user, domain)) = s.split_once('@') else {
+        return false;
+    };
+    !user.is_empty()
+        && !domain.contains('@')
+        && domain.split('.').filter(|part| !part.is_empty()).count() >= 2
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -34,8 +153,202 @@ pub enum CommonConfigError {
     UserInputReq(String),
 }
 
-#[derive(Debug, Args, Deserialize)]
+#[derive(Debug, PartialEq, Args, Deserialize)]
 pub struct DailyCommonArgs {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub inputs: DailyInputPatterns,
+
+    /// A path with a date placeholder where I2S should be set up to run (required).
+    ///
+    /// These paths can substitute in value using the same sort of patterns
+    /// as IGRAM_PATTERN.
+    #[clap(short = 'o', long)]
+    pub run_dir_pattern: String,
+
+    /// Which detector configuration the EM27 data used (optional)
+    ///
+    /// Options are "single" (for a standard InGaAs detector only),
+    /// "dual" (for a standard InGaAs plus an extended InGaAs
+    /// to cover the CO band; "extended" and "co" are also accepted),
+    /// and "midir" (for the mid-IR configuration). If omitted, the
+    /// detectors are inferred from the interferogram headers.
+    #[clap(short = 'd', long)]
+    #[serde(default, deserialize_with = "deserialize_detector_set_opt")]
+    pub detectors: Option<DetectorSet>,
+
+    /// Detector sets to use for specific date ranges (configuration file only).
+    ///
+    /// For dates that fall in one of these ranges, the detector set given for
+    /// that range takes precedence over DETECTORS. Dates not covered by any
+    /// range use DETECTORS, or infer the detectors from the interferogram
+    /// headers if that is not given either.
+    #[clap(skip)]
+    #[serde(default)]
+    pub detector_schedule: DetectorSchedule,
+
+    /// How to infer the detector set from the interferogram headers for dates
+    /// where neither DETECTORS nor the detector schedule give it. "strict" (the
+    /// default) requires all interferograms to agree, "majority" uses the detector
+    /// set of most of the interferograms, warning about any that disagree.
+    #[clap(long, value_enum, default_value_t = DetectorInference::Strict)]
+    #[serde(default)]
+    pub detector_inference: DetectorInference,
+
+    /// A file containing the top part of an I2S input file (i.e.
+    /// the header parameters) to use as a template (optional). Note that
+    /// some parameters will always be overwritten to handle the file
+    /// structure and detectors. If omitted, the recommended top will
+    /// be used.
+    #[clap(short = 't', long)]
+    pub top_file: Option<PathBuf>,
+
+    /// A flimit file to copy into each run directory instead of the one
+    /// bundled with EGI for the detector set (optional). Use this if you
+    /// need a custom spectral region. It is always copied to "flimit.i2s"
+    /// in the run directory, which the I2S input file points to.
+    #[clap(short = 'l', long = "flimit")]
+    #[serde(default)]
+    pub flimit_file: Option<PathBuf>,
+
+    /// If given, the UTC offset to insert in the I2S input file header (optional).
+    /// The default is "0.0", which assumes your interferograms were
+    /// collected by a computer with the time set to UTC. Negative values
+    /// are permitted.
+    #[clap(short = 'u', long, allow_negative_numbers = true)]
+    pub utc_offset: Option<String>,
+
+    /// What to do when the UTC offset is read from the interferograms and they do not
+    /// all have the same offset (e.g. on the day of a daylight saving time change):
+    /// "error" (the default) fails that date, "majority" uses the offset most of the
+    /// interferograms have, and "earliest" uses the offset of the earliest interferogram.
+    /// The interferograms with a different offset are listed in a warning.
+    #[clap(long, value_enum, default_value_t = MixedTimezones::Error)]
+    #[serde(default)]
+    pub mixed_timezones: MixedTimezones,
+
+    /// The template I2S uses to name the spectra (parameter 9 of the I2S input file).
+    /// {SITE_ID} is replaced with the site ID; the rest is passed to I2S, which
+    /// replaces YYYY, MM, and DD with the date, C with the detector character, and
+    /// R's with the run number (as many digits as there are R's). The template must
+    /// include YYYY, MM, DD, C, and RRRR. The default is "{SITE_ID}YYYYMMDDS0e00C.RRRR".
+    #[clap(long, default_value = DEFAULT_SPECTRUM_NAME_TEMPLATE)]
+    #[serde(default = "default_spectrum_name_template")]
+    pub spectrum_name_template: String,
+
+    /// The name of the subdirectory of each run directory that I2S writes the spectra
+    /// to. The default is "spectra". This must be a single directory name, not a path.
+    /// `em27-gfit-prep` must be given the same name to find the spectra.
+    #[clap(long, default_value = DEFAULT_SPECTRA_SUBDIR, value_parser = parse_spectra_subdir)]
+    #[serde(
+        default = "default_spectra_subdir",
+        deserialize_with = "deserialize_spectra_subdir"
+    )]
+    pub spectra_subdir: String,
+
+    /// Write the interferogram and spectrum directories (parameters 1 and 2) in the I2S
+    /// input files as absolute paths. By default, the interferogram directory is written
+    /// as given by IGRAM_PATTERN and the spectrum directory relative to the run directory,
+    /// so that the run directory can be moved. Cannot be combined with --relative-igram.
+    #[clap(long, conflicts_with = "relative_igram")]
+    #[serde(default)]
+    pub absolute_paths: bool,
+
+    /// Write the interferogram directory (parameter 1) in the I2S input files relative
+    /// to the run directory, rather than as given by IGRAM_PATTERN. Cannot be combined
+    /// with --absolute-paths.
+    #[clap(long)]
+    #[serde(default)]
+    pub relative_igram: bool,
+
+    /// Write comment lines before the catalog in each I2S input file recording
+    /// the EGI-RS version, when the catalog was made, and the coordinate and met
+    /// files used. I2S ignores these lines.
+    #[clap(long)]
+    #[serde(default)]
+    pub provenance_comments: bool,
+
+    /// How much to advance the run number between interferograms in the catalog.
+    /// The default of 2 assumes each interferogram has a forward and a reverse
+    /// scan; use 1 for an EM27 configured for forward-only acquisition.
+    #[clap(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    #[serde(default = "default_run_step")]
+    pub run_step: u32,
+
+    /// The parameter in the instrument status block of the interferogram headers
+    /// to read the instrument temperature from. Give a comma-separated list (or
+    /// this option multiple times) to try several parameters in order, for
+    /// interferograms from different OPUS versions. The default is "TSC".
+    #[clap(
+        long = "instrument-temperature-param",
+        default_value = "TSC",
+        value_delimiter = ','
+    )]
+    #[serde(default = "default_instrument_temperature_params")]
+    pub instrument_temperature_params: Vec<String>,
+
+    /// Which line endings to write the I2S input files with, "lf" (the default)
+    /// or "crlf". Use "crlf" if your I2S build requires Windows-style line endings.
+    #[clap(long, value_enum, default_value_t = LineEnding::Lf)]
+    #[serde(default)]
+    pub line_endings: LineEnding,
+}
+
+/// The default for [`DailyCommonArgs::spectrum_name_template`], the usual TCCON spectrum name.
+pub const DEFAULT_SPECTRUM_NAME_TEMPLATE: &str = "{SITE_ID}YYYYMMDDS0e00C.RRRR";
+
+/// The fields that a spectrum name template must include, so that each spectrum gets a unique name.
+/// The detector character "C" is needed whenever there is more than one detector, since I2S writes
+/// a spectrum for each one from the same interferogram; it is always required since the detector
+/// set can change between dates.
+const REQUIRED_SPECTRUM_NAME_FIELDS: [&str; 5] = ["YYYY", "MM", "DD", "C", "RRRR"];
+
+fn default_spectrum_name_template() -> String {
+    DEFAULT_SPECTRUM_NAME_TEMPLATE.to_string()
+}
+
+/// The default for [`DailyCommonArgs::spectra_subdir`], the subdirectory of the run directories with the spectra.
+pub const DEFAULT_SPECTRA_SUBDIR: &str = "spectra";
+
+fn default_spectra_subdir() -> String {
+    DEFAULT_SPECTRA_SUBDIR.to_string()
+}
+
+/// Check that `subdir` can be used as [`DailyCommonArgs::spectra_subdir`], i.e. that it is a single
+/// relative path component, so that the spectra stay inside the run directory.
+///
+/// # Errors
+/// - if `subdir` is empty, absolute, `.` or `..`, or has more than one component.
+pub fn parse_spectra_subdir(subdir: &str) -> Result<String, CommonConfigError> {
+    let mut components = Path::new(subdir).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) => Ok(subdir.to_string()),
+        _ => Err(CommonConfigError::CannotConvert(format!(
+            "the spectra subdirectory '{subdir}' must be a single directory name within the run directory, not a path"
+        ))),
+    }
+}
+
+fn deserialize_spectra_subdir<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let subdir = String::deserialize(deserializer)?;
+    parse_spectra_subdir(&subdir).map_err(de::Error::custom)
+}
+
+fn default_run_step() -> u32 {
+    2
+}
+
+fn default_instrument_temperature_params() -> Vec<String> {
+    vec!["TSC".to_string()]
+}
+
+/// Patterns giving where to find the interferograms, coordinates, and met for each
+/// date when processing a range of dates.
+#[derive(Debug, PartialEq, Args, Deserialize)]
+pub struct DailyInputPatterns {
     /// A path with a date placeholder where interferograms are stored.
     ///
     /// This uses curly braces to indicate a placeholder. The current date
@@ -43,7 +356,10 @@ pub struct DailyCommonArgs {
     /// and {SITE_ID}, respectively. A format can also be given after a colon
     /// for DATE, e.g. {DATE:%Y%j} would be replaced with the four
     /// digit year and three digit day of year. If no format is given,
-    /// as in {DATE}, it defaults to YYYY-MM-DD format.
+    /// as in {DATE}, it defaults to YYYY-MM-DD format. As shorthand,
+    /// {YEAR}, {MONTH}, and {DOY} insert the four digit year, two digit
+    /// month, and three digit day of year. Environmental variables can be
+    /// inserted with {ENV:NAME}, e.g. {ENV:EM27_DATA}.
     ///
     /// Two examples, assuming that we are processing 1 Apr 2024 with site ID "xx",
     /// "/data/{DATE}/igms" would resolve to "/data/2024-04-01/igms",
@@ -52,13 +368,6 @@ pub struct DailyCommonArgs {
     #[clap(short = 'i', long)]
     pub igram_pattern: String,
 
-    /// A path with a date placeholder where I2S should be set up to run (required).
-    ///
-    /// These paths can substitute in value using the same sort of patterns
-    /// as IGRAM_PATTERN.
-    #[clap(short = 'o', long)]
-    pub run_dir_pattern: String,
-
     /// A path with an optional date placeholder pointing to the coordinates JSON file (required).
     ///
     /// These paths can substitute in values using the same sort of patterns
@@ -79,53 +388,507 @@ pub struct DailyCommonArgs {
     /// starting with "ifg_20240401" for 1 Apr 2024.
     #[clap(short='g', long, default_value_t = String::from("*"))]
     pub igram_glob_pattern: String,
+}
 
-    /// Which detector configuration the EM27 data used (required)
-    ///
-    /// Options are "single" (for a standard InGaAs detector only)
-    /// and "dual" (for a standard InGaAs plus an extended InGaAs
-    /// to cover the CO band).
-    #[clap(short = 'd', long)]
-    #[serde(default, deserialize_with = "deserialize_detector_set_opt")]
-    pub detectors: Option<DetectorSet>,
+impl DailyInputPatterns {
+    /// Get the directory containing the interferograms for `date`.
+    pub fn igram_dir(
+        &self,
+        date: chrono::NaiveDate,
+        site_id: &str,
+    ) -> Result<PathBuf, PatternError> {
+        render_daily_pattern(&self.igram_pattern, date, site_id).map(PathBuf::from)
+    }
 
-    /// A file containing the top part of an I2S input file (i.e.
-    /// the header parameters) to use as a template (optional). Note that
-    /// some parameters will always be overwritten to handle the file
-    /// structure and detectors. If omitted, the recommended top will
-    /// be used.
-    #[clap(short = 't', long)]
-    pub top_file: Option<PathBuf>,
+    /// Get the glob pattern that matches the interferograms for `date` in [`DailyInputPatterns::igram_dir`].
+    pub fn igram_glob(
+        &self,
+        date: chrono::NaiveDate,
+        site_id: &str,
+    ) -> Result<String, PatternError> {
+        render_daily_pattern(&self.igram_glob_pattern, date, site_id)
+    }
 
-    /// If given, the UTC offset to insert in the I2S input file header (optional).
-    /// The default is "0.0", which assumes your interferograms were
-    /// collected by a computer with the time set to UTC. Negative values
-    /// are permitted.
-    #[clap(short = 'u', long, allow_negative_numbers = true)]
-    pub utc_offset: Option<String>,
+    /// Get the path to the coordinates JSON file for `date`.
+    pub fn coord_file(
+        &self,
+        date: chrono::NaiveDate,
+        site_id: &str,
+    ) -> Result<PathBuf, PatternError> {
+        render_daily_pattern(&self.coord_file_pattern, date, site_id).map(PathBuf::from)
+    }
+
+    /// Get the path to the meteorology JSON file for `date`.
+    pub fn met_file(
+        &self,
+        date: chrono::NaiveDate,
+        site_id: &str,
+    ) -> Result<PathBuf, PatternError> {
+        render_daily_pattern(&self.met_file_pattern, date, site_id).map(PathBuf::from)
+    }
+}
+
+/// Get the list of files in `igram_dir` matching the glob pattern `igram_glob`.
+///
+/// Also returns the number of files that could not be checked against the pattern
+/// (e.g. because of permissions), so that the caller can warn about them. If `strict`
+/// is `true`, the first such file is an error instead.
+pub fn glob_igrams(
+    igram_dir: &Path,
+    igram_glob: &str,
+    strict: bool,
+) -> Result<(Vec<PathBuf>, u64), CommonConfigError> {
+    let mut igrams = vec![];
+    let mut n_glob_err = 0;
+
+    let full_igram_pattern = igram_dir.join(igram_glob);
+    let full_igram_pattern = full_igram_pattern.to_str().ok_or_else(|| {
+        CommonConfigError::CannotConvert(format!(
+            "Could not convert the interferogram pattern '{}' into a valid UTF-8 string",
+            full_igram_pattern.display()
+        ))
+    })?;
+
+    let glob_iter = glob::glob(full_igram_pattern).map_err(|e| {
+        CommonConfigError::CannotConvert(format!(
+            "The interferogram glob pattern '{full_igram_pattern}' is invalid: {e}"
+        ))
+    })?;
+
+    for entry in glob_iter {
+        match entry {
+            Ok(p) => igrams.push(p),
+            Err(e) if strict => {
+                return Err(CommonConfigError::IoError(format!(
+                    "could not check {} against the interferogram glob pattern: {}",
+                    e.path().display(),
+                    e.error()
+                )))
+            }
+            Err(_) => n_glob_err += 1,
+        }
+    }
+
+    Ok((igrams, n_glob_err))
+}
+
+/// Read a list of interferogram paths from `list_file`, one per line.
+///
+/// Leading and trailing whitespace is removed from each line, and blank lines and lines
+/// starting with "#" are skipped. Relative paths are returned as-is, i.e. they will be
+/// relative to the current directory, not the list file.
+pub fn read_igram_list(list_file: &Path) -> Result<Vec<PathBuf>, CommonConfigError> {
+    let contents = std::fs::read_to_string(list_file).map_err(|e| {
+        CommonConfigError::IoError(format!(
+            "could not read interferogram list {}: {e}",
+            list_file.display()
+        ))
+    })?;
+
+    let igrams = contents
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(PathBuf::from)
+        .collect();
+    Ok(igrams)
 }
 
 impl DailyCommonArgs {
+    /// Read the arguments from a configuration file.
+    ///
+    /// Files with a `.toml` extension are parsed as TOML, all others as JSON.
+    /// Note that dates in the `detector_schedule` must be quoted strings in TOML.
     pub fn read_from_path<P: AsRef<Path>>(p: P) -> Result<Self, CommonConfigError> {
-        let rdr = std::fs::File::open(p.as_ref()).map_err(|e| {
-            CommonConfigError::IoError(format!(
-                "could not open JSON file {}: {e}",
-                p.as_ref().display()
-            ))
+        let p = p.as_ref();
+        let is_toml = p
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let fmt = if is_toml { "TOML" } else { "JSON" };
+
+        let contents = std::fs::read_to_string(p).map_err(|e| {
+            CommonConfigError::IoError(format!("could not open {fmt} file {}: {e}", p.display()))
         })?;
 
-        let value: DailyCommonArgs = serde_json::from_reader(rdr).map_err(|e| {
+        let value: Result<DailyCommonArgs, String> = if is_toml {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        };
+
+        value.map_err(|e| {
             CommonConfigError::IoError(format!(
-                "the JSON file {} is not correct: {e}",
-                p.as_ref().display()
+                "the {fmt} file {} is not correct: {e}",
+                p.display()
             ))
-        })?;
+        })
+    }
 
-        Ok(value)
+    /// Read the default arguments for `site_id` from its site configuration (see [`site_config_path`]).
+    ///
+    /// Returns `Ok(None)` if the EGI directory cannot be found or has no configuration for this site.
+    ///
+    /// # Errors
+    /// - if the site configuration exists but cannot be read or is not valid.
+    pub fn read_site_defaults(site_id: &str) -> Result<Option<Self>, CommonConfigError> {
+        let path = match site_config_path(site_id) {
+            Ok(p) => p,
+            Err(e) => {
+                log::debug!("Not looking for a site configuration for {site_id}: {e}");
+                return Ok(None);
+            }
+        };
+
+        if !path.exists() {
+            log::debug!("No site configuration at {}", path.display());
+            return Ok(None);
+        }
+
+        log::info!("Using defaults for {site_id} from {}", path.display());
+        Self::read_from_path(&path).map(Some)
+    }
+
+    /// Replace any arguments that were not given on the command line with the values from `site`.
+    ///
+    /// `matches` must be the parsed command line arguments that `self` was created from; an argument
+    /// counts as given if its value came from anywhere other than its default. The `detector_schedule`
+    /// cannot be given on the command line, so it always comes from `site`.
+    pub fn merge_site_defaults(&mut self, site: Self, matches: &clap::ArgMatches) {
+        let given = |id: &str| {
+            matches
+                .value_source(id)
+                .is_some_and(|src| src != clap::parser::ValueSource::DefaultValue)
+        };
+
+        let DailyCommonArgs {
+            inputs:
+                DailyInputPatterns {
+                    igram_pattern,
+                    coord_file_pattern,
+                    met_file_pattern,
+                    igram_glob_pattern,
+                },
+            run_dir_pattern,
+            detectors,
+            detector_schedule,
+            detector_inference,
+            top_file,
+            flimit_file,
+            utc_offset,
+            mixed_timezones,
+            spectrum_name_template,
+            spectra_subdir,
+            absolute_paths,
+            relative_igram,
+            provenance_comments,
+            run_step,
+            instrument_temperature_params,
+            line_endings,
+        } = site;
+
+        if !given("igram_pattern") {
+            self.inputs.igram_pattern = igram_pattern;
+        }
+        if !given("coord_file_pattern") {
+            self.inputs.coord_file_pattern = coord_file_pattern;
+        }
+        if !given("met_file_pattern") {
+            self.inputs.met_file_pattern = met_file_pattern;
+        }
+        if !given("igram_glob_pattern") {
+            self.inputs.igram_glob_pattern = igram_glob_pattern;
+        }
+        if !given("run_dir_pattern") {
+            self.run_dir_pattern = run_dir_pattern;
+        }
+        if !given("detectors") {
+            self.detectors = detectors;
+        }
+        self.detector_schedule = detector_schedule;
+        if !given("detector_inference") {
+            self.detector_inference = detector_inference;
+        }
+        if !given("top_file") {
+            self.top_file = top_file;
+        }
+        if !given("flimit_file") {
+            self.flimit_file = flimit_file;
+        }
+        if !given("utc_offset") {
+            self.utc_offset = utc_offset;
+        }
+        if !given("mixed_timezones") {
+            self.mixed_timezones = mixed_timezones;
+        }
+        if !given("spectrum_name_template") {
+            self.spectrum_name_template = spectrum_name_template;
+        }
+        if !given("spectra_subdir") {
+            self.spectra_subdir = spectra_subdir;
+        }
+        if !given("absolute_paths") {
+            self.absolute_paths = absolute_paths;
+        }
+        if !given("relative_igram") {
+            self.relative_igram = relative_igram;
+        }
+        if !given("provenance_comments") {
+            self.provenance_comments = provenance_comments;
+        }
+        if !given("run_step") {
+            self.run_step = run_step;
+        }
+        if !given("instrument_temperature_params") {
+            self.instrument_temperature_params = instrument_temperature_params;
+        }
+        if !given("line_endings") {
+            self.line_endings = line_endings;
+        }
+    }
+
+    /// The spectrum name template for I2S parameter 9, with `site_id` inserted.
+    ///
+    /// # Errors
+    /// - if [`DailyCommonArgs::spectrum_name_template`] is missing a required field (YYYY, MM, DD, C, or RRRR)
+    ///   or has a placeholder other than {SITE_ID}.
+    pub fn spectrum_name_param(&self, site_id: &str) -> Result<String, CommonConfigError> {
+        let template = self.spectrum_name_template.replace("{SITE_ID}", site_id);
+        if template.contains(['{', '}']) {
+            return Err(CommonConfigError::CannotConvert(format!(
+                "the spectrum name template '{}' may only contain the {{SITE_ID}} placeholder",
+                self.spectrum_name_template
+            )));
+        }
+
+        // Check the template without the site ID, so that e.g. a "C" in the site ID does not count
+        let fixed_part = self.spectrum_name_template.replace("{SITE_ID}", "");
+        let missing = REQUIRED_SPECTRUM_NAME_FIELDS
+            .iter()
+            .filter(|field| !fixed_part.contains(*field))
+            .join(", ");
+        if !missing.is_empty() {
+            return Err(CommonConfigError::CannotConvert(format!(
+                "the spectrum name template '{}' is missing {missing}",
+                self.spectrum_name_template
+            )));
+        }
+        Ok(template)
+    }
+
+    /// How many digits I2S has for the run number in the spectrum names, i.e. the length of the
+    /// longest run of R's in [`DailyCommonArgs::spectrum_name_template`].
+    pub fn run_number_digits(&self) -> u32 {
+        let fixed_part = self.spectrum_name_template.replace("{SITE_ID}", "");
+        fixed_part
+            .split(|c| c != 'R')
+            .map(|rs| rs.len() as u32)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The names of the required patterns that are empty, e.g. because they were left off
+    /// the command line and there was no site configuration to fill them in.
+    pub fn missing_patterns(&self) -> Vec<&'static str> {
+        [
+            ("IGRAM_PATTERN", &self.inputs.igram_pattern),
+            ("COORD_FILE_PATTERN", &self.inputs.coord_file_pattern),
+            ("MET_FILE_PATTERN", &self.inputs.met_file_pattern),
+            ("RUN_DIR_PATTERN", &self.run_dir_pattern),
+        ]
+        .into_iter()
+        .filter_map(|(name, pattern)| pattern.is_empty().then_some(name))
+        .collect()
+    }
+}
+
+/// Get the path to the default configuration for a site, `$EGIPATH/sites/<site_id>.toml`.
+///
+/// This file has the same contents as the TOML configuration for `em27-i2s-prep daily-json`,
+/// and `em27-i2s-prep daily` uses it for any arguments not given on the command line.
+pub fn site_config_path(site_id: &str) -> Result<PathBuf, EgiPathError> {
+    Ok(get_egi_path()?
+        .join("sites")
+        .join(format!("{site_id}.toml")))
+}
+
+/// A list of date ranges with the detector set used in each.
+///
+/// In a configuration file, this is a list of tables/objects with the first and last
+/// dates (inclusive) and the detector set, e.g. in JSON:
+///
+/// ```json
+/// [
+///   {"start": "2023-01-01", "end": "2023-06-14", "detectors": "single"},
+///   {"start": "2023-06-15", "end": "2099-12-31", "detectors": "dual"}
+/// ]
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct DetectorSchedule(Vec<DetectorScheduleEntry>);
+
+impl DetectorSchedule {
+    /// Return the detector set for the first range that includes `date`, or `None`
+    /// if no range includes it.
+    pub fn for_date(&self, date: chrono::NaiveDate) -> Option<DetectorSet> {
+        self.0
+            .iter()
+            .find(|entry| entry.start <= date && date <= entry.end)
+            .map(|entry| entry.detectors)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DetectorScheduleEntry {
+    /// First date (inclusive) that uses this detector set
+    pub start: chrono::NaiveDate,
+    /// Last date (inclusive) that uses this detector set
+    pub end: chrono::NaiveDate,
+    /// The detector set to use for dates in this range
+    #[serde(deserialize_with = "deserialize_detector_set")]
+    pub detectors: DetectorSet,
+}
+
+/// Command line arguments to skip specific dates within a date range.
+#[derive(Debug, Default, Clone, PartialEq, Args)]
+pub struct ExcludeDatesArgs {
+    /// Dates to skip (e.g. days with instrument maintenance), either as a comma-separated
+    /// list of YYYY-MM-DD dates or the path to a file with one YYYY-MM-DD date per line.
+    /// Blank lines and lines starting with "#" in the file are ignored.
+    #[clap(long)]
+    pub exclude_dates: Option<String>,
+}
+
+impl ExcludeDatesArgs {
+    /// Parse the dates given by `--exclude-dates`.
+    ///
+    /// If the value is the path to an existing file, the dates are read from that file,
+    /// otherwise it is parsed as a comma-separated list of dates.
+    ///
+    /// # Errors
+    /// - if the file cannot be read, or
+    /// - if any of the dates is not in YYYY-MM-DD format.
+    pub fn load(&self) -> Result<ExcludedDates, CommonConfigError> {
+        let value = if let Some(v) = &self.exclude_dates {
+            v
+        } else {
+            return Ok(ExcludedDates::default());
+        };
+
+        let p = Path::new(value);
+        if p.is_file() {
+            let contents = std::fs::read_to_string(p).map_err(|e| {
+                CommonConfigError::IoError(format!(
+                    "could not read excluded dates file {}: {e}",
+                    p.display()
+                ))
+            })?;
+            let dates = contents
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty() && !l.starts_with('#'));
+            ExcludedDates::parse(dates)
+        } else {
+            ExcludedDates::parse(value.split(',').map(|s| s.trim()))
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The number of characters in a site ID, unless `--site-id-length` says otherwise.
+pub const DEFAULT_SITE_ID_LENGTH: usize = 2;
+
+/// Command line argument giving how many characters site IDs have.
+///
+/// This is a global argument, so the programs can flatten it into their top level interface
+/// and check the site ID given to any subcommand with [`SiteIdLengthArgs::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Args)]
+pub struct SiteIdLengthArgs {
+    /// How many characters site IDs have. The default is 2, as for TCCON sites. Site IDs
+    /// given on the command line must be exactly this many letters or digits.
+    #[clap(
+        long,
+        global = true,
+        default_value_t = DEFAULT_SITE_ID_LENGTH,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub site_id_length: usize,
+}
+
+impl SiteIdLengthArgs {
+    /// Check that `site_id` is [`SiteIdLengthArgs::site_id_length`] ASCII letters or digits.
+    ///
+    /// # Errors
+    /// - if `site_id` is the wrong length or has any other characters.
+    pub fn validate(&self, site_id: &str) -> Result<(), CommonConfigError> {
+        let n = self.site_id_length;
+        if site_id.chars().count() != n {
+            return Err(CommonConfigError::CannotConvert(format!(
+                "site ID '{site_id}' must be {n} character(s) long (use --site-id-length if your site IDs are not {n} characters)"
+            )));
+        }
+        if !site_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(CommonConfigError::CannotConvert(format!(
+                "site ID '{site_id}' must contain only ASCII letters and digits"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A set of dates to skip when iterating over a date range, see [`ExcludeDatesArgs`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExcludedDates(BTreeSet<chrono::NaiveDate>);
+
+impl ExcludedDates {
+    fn parse<'a, I: Iterator<Item = &'a str>>(dates: I) -> Result<Self, CommonConfigError> {
+        let dates = dates
+            .map(|s| {
+                chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| {
+                    CommonConfigError::CannotConvert(format!(
+                        "excluded date '{s}' is not a YYYY-MM-DD date (or the path to an existing file)"
+                    ))
+                })
+            })
+            .try_collect()?;
+        Ok(Self(dates))
+    }
+
+    /// Return `true` if `date` should be skipped, logging that it is.
+    pub fn skip(&self, date: chrono::NaiveDate) -> bool {
+        let skip = self.0.contains(&date);
+        if skip {
+            log::info!("Skipping excluded date {date}");
+        }
+        skip
+    }
+}
+
+/// How to infer one detector set for a group of interferograms from their headers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorInference {
+    /// All interferograms must have the same detector set, see [`DetectorSet::infer_from_multi_headers`].
+    #[default]
+    Strict,
+    /// Use the most common detector set, see [`DetectorSet::infer_from_multi_headers_majority`].
+    Majority,
+}
+
+impl DetectorInference {
+    /// Infer the detector set for `interferograms` using this method.
+    pub fn infer<P: AsRef<Path>>(
+        &self,
+        interferograms: &[P],
+    ) -> Result<DetectorSet, CommonConfigError> {
+        match self {
+            DetectorInference::Strict => DetectorSet::infer_from_multi_headers(interferograms),
+            DetectorInference::Majority => {
+                DetectorSet::infer_from_multi_headers_majority(interferograms)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DetectorSet {
     Single,
     Dual,
@@ -166,16 +929,64 @@ impl DetectorSet {
         Ok(detectors)
     }
 
+    /// Infer the detector set to use for a collection of interferograms by majority vote.
+    ///
+    /// Unlike [`DetectorSet::infer_from_multi_headers`], this tolerates a few interferograms
+    /// with unreadable or unusual headers: it returns the detector set indicated by the most
+    /// interferograms, logging a warning for each interferogram that could not be read or
+    /// disagreed with the majority. It returns an error if none of the headers could be read,
+    /// or if two or more detector sets are tied for the most interferograms.
+    pub fn infer_from_multi_headers_majority<P: AsRef<Path>>(
+        interferograms: &[P],
+    ) -> Result<DetectorSet, CommonConfigError> {
+        let mut igram_detectors = vec![];
+        for igm in interferograms {
+            let igm = igm.as_ref();
+            match DetectorSet::infer_from_header(igm) {
+                Ok(det) => igram_detectors.push((igm, det)),
+                Err(e) => log::warn!(
+                    "Could not determine the detector set for {}, ignoring it: {e}",
+                    igm.display()
+                ),
+            }
+        }
+
+        let detectors = majority_detector_set(&igram_detectors)?;
+        for (igm, det) in igram_detectors.iter() {
+            if *det != detectors {
+                log::warn!(
+                    "{} appears to have {det} detector(s), but most interferograms have {detectors}",
+                    igm.display()
+                );
+            }
+        }
+        Ok(detectors)
+    }
+
+    /// The short name for this detector set, as used in configuration files
+    /// (i.e. "single", "dual", or "midir").
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            DetectorSet::Single => "single",
+            DetectorSet::Dual => "dual",
+            DetectorSet::MidIR => "midir",
+        }
+    }
+
     /// Infer the detector set to use for a single interferogram
     ///
     /// This will check the header of the given interferogram and determine detector
-    /// set to use for it.
+    /// set to use for it from the instrument name and which channels have data. If that
+    /// combination is not a known detector set, or the file has more interferogram data
+    /// blocks than the primary and secondary channels account for, this returns an error
+    /// listing what was found. Note that this includes a mid-IR instrument whose primary
+    /// channel has no readable NPT.
     ///
     /// # See also
     /// [`DetectorSet::infer_from_multi_headers`] to determine a single detector set to use for
     /// many interferograms, and verify that they all contain the same detectors.
     pub fn infer_from_header(interferogram: &Path) -> Result<DetectorSet, CommonConfigError> {
-        let header = opus::IgramHeader::read_full_igram_header(interferogram).map_err(|e| {
+        let header = read_igram_header(interferogram).map_err(|e| {
             CommonConfigError::IoError(format!(
                 "Error reading interferogram {}: {e}",
                 interferogram.display()
@@ -183,10 +994,7 @@ impl DetectorSet {
         })?;
 
         let instrument = header
-            .get_value(
-                opus::constants::bruker::BrukerBlockType::InstrumentStatus,
-                "INS",
-            )
+            .get_value(BrukerBlockType::InstrumentStatus, "INS")
             .map_err(|e| {
                 CommonConfigError::IoError(format!(
                     "Could not find instrument name in header of {}: {e}",
@@ -214,56 +1022,95 @@ impl DetectorSet {
             ""
         };
 
-        if instrument == "EM27/SUN MIR" {
-            // Jacob noted in the original EGI that this configuration is the rarest,
-            // so we just assume that such an instrument will match this instrument string
-            return Ok(Self::MidIR);
-        }
-
         // Most instruments probably just set the instrument value to "EM27/SUN", so we can't
         // distinguish ones with and without the dual detector from the instrument name.
-        // Instead, check the number of data points in the second channel; if this is present and
-        // not 0, then we *should* have an extended InGaAs detector
+        // Instead, check which channels have data, i.e. have a number of data points that is
+        // present and not 0.
         // TODO: test on some of the early Caltech data with only one detector (/oco2-data/tccon/data/caltech_em27)
         // to ensure this is reading the right NPT parameter.
-        let npt2_res = header.get_value(
-            opus::constants::bruker::BrukerBlockType::IgramSecondaryStatus,
-            "NPT",
-        );
-        let npt2 = match npt2_res {
-            Ok(BrukerParValue::Integer(v)) => {
-                log::debug!(
-                    "NPT2 parameter value in {} = {v}",
-                    interferogram
-                        .file_name()
-                        .map(|s| s.to_string_lossy())
-                        .unwrap_or_default()
-                );
-                *v
+        let file_name = interferogram
+            .file_name()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        let mut channels = vec![];
+        for (channel, block) in IGRAM_CHANNEL_STATUS_BLOCKS {
+            match header.get_value(block, "NPT") {
+                Ok(BrukerParValue::Integer(v)) => {
+                    log::debug!("NPT value for the {channel} channel in {file_name} = {v}");
+                    if *v != 0 {
+                        channels.push(channel);
+                    }
+                }
+                Err(_) => {
+                    log::debug!("NPT parameter for the {channel} channel was not present in {file_name}, assuming it has no data");
+                }
+                Ok(value) => {
+                    return Err(CommonConfigError::IoError(format!(
+                        "Unexpected type for the {channel} channel's NPT parameter in {}, expected integer, got {}",
+                        interferogram.display(),
+                        value.opus_type()
+                    )))
+                }
             }
-            Err(_) => {
-                log::debug!(
-                    "NPT2 parameter was not present in {}, using 0 to determine detectors",
-                    interferogram
-                        .file_name()
-                        .map(|s| s.to_string_lossy())
-                        .unwrap_or_default()
-                );
-                0
-            }
-            Ok(value) => {
-                return Err(CommonConfigError::IoError(format!(
-                    "Unexpected type for NPT2 parameter in {}, expected integer, got {}",
-                    interferogram.display(),
-                    value.opus_type()
-                )))
+        }
+
+        // The status blocks can only tell us about the primary and secondary channels, so count
+        // the interferogram data blocks in the file to catch instruments with more channels.
+        let igram_blocks = match read_opus_directory(interferogram) {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|e| e.is_igram_data())
+                .map(|e| e.block_type)
+                .collect_vec(),
+            Err(e) => {
+                log::warn!("Could not list the data blocks in {file_name}, only the primary and secondary channels will be checked: {e}");
+                vec![]
             }
         };
+        let n_unidentified = igram_blocks.len().saturating_sub(channels.len());
+        log::debug!(
+            "{file_name} has {} interferogram data block(s) (block types: {})",
+            igram_blocks.len(),
+            igram_blocks.iter().join(", ")
+        );
 
-        if npt2 == 0 {
-            Ok(Self::Single)
+        let detectors = if n_unidentified > 0 {
+            None
         } else {
-            Ok(Self::Dual)
+            Self::from_instrument_channels(instrument, &channels)
+        };
+        detectors.ok_or_else(|| {
+            let mut found = channels.iter().map(|c| c.to_string()).collect_vec();
+            if n_unidentified > 0 {
+                found.push(format!(
+                    "{n_unidentified} unidentified (EGI can only identify the primary and secondary channels; interferogram data block types: {})",
+                    igram_blocks.iter().join(", ")
+                ));
+            }
+            let found = if found.is_empty() {
+                "none".to_string()
+            } else {
+                found.join(", ")
+            };
+            CommonConfigError::UserInputReq(format!(
+                "unrecognized detector configuration in {}: instrument '{instrument}' with data in channel(s): {found}. Specify the detector set to use instead of inferring it.",
+                interferogram.display()
+            ))
+        })
+    }
+
+    /// Map an instrument name (the "INS" header parameter) and the channels with data (named as in
+    /// [`IGRAM_CHANNEL_STATUS_BLOCKS`], in that order) to a detector set, or `None` if the combination
+    /// is not a known configuration.
+    fn from_instrument_channels(instrument: &str, channels: &[&str]) -> Option<Self> {
+        // Jacob noted in the original EGI that the mid-IR configuration is the rarest,
+        // so we just assume that such an instrument will match this instrument string
+        let is_mid_ir = instrument.trim() == "EM27/SUN MIR";
+        match (is_mid_ir, channels) {
+            (true, ["primary"] | ["primary", "secondary"]) => Some(Self::MidIR),
+            (false, ["primary"]) => Some(Self::Single),
+            (false, ["primary", "secondary"]) => Some(Self::Dual),
+            _ => None,
         }
     }
 
@@ -341,6 +1188,32 @@ impl DetectorSet {
     }
 }
 
+/// Get the most common detector set in `igram_detectors`, see [`DetectorSet::infer_from_multi_headers_majority`].
+fn majority_detector_set(
+    igram_detectors: &[(&Path, DetectorSet)],
+) -> Result<DetectorSet, CommonConfigError> {
+    let counts = igram_detectors.iter().map(|(_, det)| *det).counts();
+    let Some(max_count) = counts.values().max().copied() else {
+        return Err(CommonConfigError::IoError(
+            "No readable interferogram headers, so no detectors to infer".to_string(),
+        ));
+    };
+
+    let most_common = counts
+        .into_iter()
+        .filter(|(_, n)| *n == max_count)
+        .map(|(det, _)| det)
+        .sorted_by_key(|det| det.config_name())
+        .collect_vec();
+    if most_common.len() > 1 {
+        return Err(CommonConfigError::UserInputReq(format!(
+            "the interferograms are evenly split between detector sets ({}, {max_count} interferograms each). Either specify the correct detector set to use, or ensure each interferogram group has the same detector set.",
+            most_common.iter().join(", ")
+        )));
+    }
+    Ok(most_common[0])
+}
+
 impl Display for DetectorSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -357,7 +1230,7 @@ impl FromStr for DetectorSet {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_ascii_lowercase().as_str() {
             "s" | "single" => Ok(Self::Single),
-            "d" | "dual" => Ok(Self::Dual),
+            "d" | "dual" | "extended" | "co" => Ok(Self::Dual),
             "m" | "midir" => Ok(Self::MidIR),
             _ => Err(CommonConfigError::CannotConvert(format!(
                 "'{s}' is not a valid detector set"
@@ -384,3 +1257,442 @@ where
     let det_set = deserialize_detector_set(deserializer)?;
     Ok(Some(det_set))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_detectors_from_channels() {
+        let infer = DetectorSet::from_instrument_channels;
+        assert_eq!(infer("EM27/SUN", &["primary"]), Some(DetectorSet::Single));
+        assert_eq!(
+            infer("EM27/SUN", &["primary", "secondary"]),
+            Some(DetectorSet::Dual)
+        );
+        assert_eq!(
+            infer("EM27/SUN MIR", &["primary"]),
+            Some(DetectorSet::MidIR)
+        );
+        assert_eq!(
+            infer("EM27/SUN MIR ", &["primary", "secondary"]),
+            Some(DetectorSet::MidIR)
+        );
+        assert_eq!(infer("EM27/SUN", &[]), None);
+        assert_eq!(infer("EM27/SUN", &["secondary"]), None);
+        // A mid-IR instrument still needs data in its primary channel
+        assert_eq!(infer("EM27/SUN MIR", &[]), None);
+    }
+
+    #[test]
+    fn test_majority_detector_set() {
+        let a = Path::new("a");
+        let b = Path::new("b");
+        let c = Path::new("c");
+
+        let dets = [
+            (a, DetectorSet::Dual),
+            (b, DetectorSet::Single),
+            (c, DetectorSet::Dual),
+        ];
+        assert_eq!(majority_detector_set(&dets).unwrap(), DetectorSet::Dual);
+
+        let tied = [(a, DetectorSet::Dual), (b, DetectorSet::Single)];
+        assert!(majority_detector_set(&tied).is_err());
+        assert!(majority_detector_set(&[]).is_err());
+    }
+
+    #[test]
+    fn test_detector_set_spellings() {
+        #[derive(Debug, clap::Parser)]
+        struct TestCli {
+            #[command(flatten)]
+            common: DailyCommonArgs,
+        }
+
+        let spellings = [
+            ("s", DetectorSet::Single),
+            ("single", DetectorSet::Single),
+            ("Single", DetectorSet::Single),
+            ("d", DetectorSet::Dual),
+            ("dual", DetectorSet::Dual),
+            ("extended", DetectorSet::Dual),
+            ("co", DetectorSet::Dual),
+            ("CO", DetectorSet::Dual),
+            ("m", DetectorSet::MidIR),
+            ("midir", DetectorSet::MidIR),
+        ];
+
+        for (spelling, expected) in spellings {
+            let entry: DetectorScheduleEntry = serde_json::from_value(serde_json::json!({
+                "start": "2024-01-01", "end": "2024-12-31", "detectors": spelling
+            }))
+            .unwrap();
+            assert_eq!(entry.detectors, expected, "serde, spelling = {spelling}");
+
+            let cli = <TestCli as clap::Parser>::try_parse_from([
+                "test", "-i", "igms", "-c", "coords", "-m", "met", "-o", "run", "-d", spelling,
+            ])
+            .unwrap();
+            assert_eq!(
+                cli.common.detectors,
+                Some(expected),
+                "clap, spelling = {spelling}"
+            );
+        }
+
+        assert!(DetectorSet::from_str("triple").is_err());
+    }
+
+    #[test]
+    fn test_core_config_emails() {
+        let cfg = CoreConfig {
+            ftp_email: "user@caltech.edu".to_string(),
+            priors_request_email: "user@example.org".to_string(),
+            default_jobs: None,
+        };
+        assert!(cfg.email_problems().is_empty());
+
+        let cfg = CoreConfig {
+            ftp_email: PLACEHOLDER_EMAIL.to_string(),
+            priors_request_email: "user at example.org".to_string(),
+            default_jobs: None,
+        };
+        assert_eq!(cfg.email_problems().len(), 2);
+
+        assert!(!looks_like_email("user@localhost"));
+        assert!(!looks_like_email("@example.org"));
+        assert!(!looks_like_email("user@example."));
+        assert!(!looks_like_email("user@@example.org"));
+    }
+
+    #[test]
+    fn test_core_config_default_jobs() {
+        let old: CoreConfig = toml::from_str(
+            r#"
+            ftp_email = "user@caltech.edu"
+            priors_request_email = "user@example.org"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(old.default_jobs, None);
+
+        let default: CoreConfig =
+            toml::from_str(&crate::default_files::default_core_config_toml()).unwrap();
+        assert_eq!(default.default_jobs, Some(1));
+
+        assert_eq!(resolve_jobs(Some(4)), 4);
+    }
+
+    #[test]
+    fn test_parse_spectra_subdir() {
+        assert_eq!(parse_spectra_subdir("spectra").unwrap(), "spectra");
+        assert!(parse_spectra_subdir("spectra/").is_ok());
+        assert!(parse_spectra_subdir("/scratch/spectra").is_err());
+        assert!(parse_spectra_subdir("../x").is_err());
+        assert!(parse_spectra_subdir("a/b").is_err());
+        assert!(parse_spectra_subdir("..").is_err());
+        assert!(parse_spectra_subdir(".").is_err());
+        assert!(parse_spectra_subdir("").is_err());
+
+        let config = |subdir: &str| {
+            toml::from_str::<DailyCommonArgs>(&format!(
+                r#"
+                igram_pattern = "/site/igms"
+                igram_glob_pattern = "*"
+                coord_file_pattern = "/site/coords.json"
+                met_file_pattern = "/site/met.json"
+                run_dir_pattern = "/site/spectra/{{DATE}}"
+                spectra_subdir = "{subdir}"
+                "#
+            ))
+        };
+        assert_eq!(config("spec").unwrap().spectra_subdir, "spec");
+        assert!(config("/scratch/spectra").is_err());
+    }
+
+    #[test]
+    fn test_validate_site_id() {
+        let two = SiteIdLengthArgs {
+            site_id_length: DEFAULT_SITE_ID_LENGTH,
+        };
+        assert!(two.validate("xx").is_ok());
+        assert!(two.validate("x").is_err());
+        assert!(two.validate("xxx").is_err());
+        assert!(two.validate("x/").is_err());
+
+        let one = SiteIdLengthArgs { site_id_length: 1 };
+        assert!(one.validate("x").is_ok());
+        assert!(one.validate("xx").is_err());
+    }
+
+    #[test]
+    fn test_exclude_dates() {
+        let d = |day| NaiveDate::from_ymd_opt(2024, 4, day).unwrap();
+
+        let none = ExcludeDatesArgs::default().load().unwrap();
+        assert!(!none.skip(d(1)));
+
+        let list = ExcludeDatesArgs {
+            exclude_dates: Some("2024-04-02, 2024-04-05".to_string()),
+        }
+        .load()
+        .unwrap();
+        assert!(list.skip(d(2)));
+        assert!(list.skip(d(5)));
+        assert!(!list.skip(d(3)));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("exclude.txt");
+        std::fs::write(&file, "# maintenance\n2024-04-03\n\n2024-04-04\n").unwrap();
+        let from_file = ExcludeDatesArgs {
+            exclude_dates: Some(file.to_string_lossy().to_string()),
+        }
+        .load();
+        let from_file = from_file.unwrap();
+        assert!(from_file.skip(d(3)));
+        assert!(from_file.skip(d(4)));
+        assert!(!from_file.skip(d(2)));
+
+        let bad = ExcludeDatesArgs {
+            exclude_dates: Some("2024-04-02,20240403".to_string()),
+        };
+        assert!(bad.load().is_err());
+    }
+
+    #[test]
+    fn test_flimit_per_detector_set() {
+        let single = DetectorSet::Single.get_flimit();
+        let dual = DetectorSet::Dual.get_flimit();
+        let midir = DetectorSet::MidIR.get_flimit();
+        assert_ne!(single, dual);
+        assert_ne!(single, midir);
+        assert_ne!(dual, midir);
+    }
+
+    #[test]
+    fn test_detector_schedule() {
+        let schedule: DetectorSchedule = serde_json::from_str(
+            r#"[
+                {"start": "2023-01-01", "end": "2023-06-14", "detectors": "single"},
+                {"start": "2023-06-15", "end": "2023-12-31", "detectors": "dual"}
+            ]"#,
+        )
+        .unwrap();
+
+        let d = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(schedule.for_date(d(2022, 12, 31)), None);
+        assert_eq!(schedule.for_date(d(2023, 1, 1)), Some(DetectorSet::Single));
+        assert_eq!(schedule.for_date(d(2023, 6, 14)), Some(DetectorSet::Single));
+        assert_eq!(schedule.for_date(d(2023, 6, 15)), Some(DetectorSet::Dual));
+        assert_eq!(schedule.for_date(d(2024, 1, 1)), None);
+    }
+
+    #[test]
+    fn test_read_igram_list() {
+        let tmp = tempfile::tempdir().unwrap();
+        let list_file = tmp.path().join("igrams.txt");
+        std::fs::write(
+            &list_file,
+            "# igrams for 1 Apr\n/data/xx20240401s0e00a.0001\n\n  xx20240401s0e00a.0002  \n",
+        )
+        .unwrap();
+        let igrams = read_igram_list(&list_file);
+
+        assert_eq!(
+            igrams.unwrap(),
+            [
+                PathBuf::from("/data/xx20240401s0e00a.0001"),
+                PathBuf::from("xx20240401s0e00a.0002")
+            ]
+        );
+        assert!(read_igram_list(&tmp.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn test_merge_site_defaults() {
+        use clap::{CommandFactory, FromArgMatches};
+
+        #[derive(Debug, clap::Parser)]
+        struct TestCli {
+            #[command(flatten)]
+            common: DailyCommonArgs,
+        }
+
+        let matches = TestCli::command()
+            .mut_arg("met_file_pattern", |arg| {
+                arg.required(false).default_value("")
+            })
+            .mut_arg("run_dir_pattern", |arg| {
+                arg.required(false).default_value("")
+            })
+            .try_get_matches_from([
+                "test",
+                "-i",
+                "/cli/igms",
+                "-c",
+                "/cli/coords.json",
+                "-d",
+                "single",
+            ])
+            .unwrap();
+        let mut args = TestCli::from_arg_matches(&matches).unwrap().common;
+        assert_eq!(
+            args.missing_patterns(),
+            ["MET_FILE_PATTERN", "RUN_DIR_PATTERN"]
+        );
+
+        let site: DailyCommonArgs = toml::from_str(
+            r#"
+            igram_pattern = "/site/igms"
+            igram_glob_pattern = "*.0001"
+            coord_file_pattern = "/site/coords.json"
+            met_file_pattern = "/site/met.json"
+            run_dir_pattern = "/site/spectra/{DATE}"
+            detectors = "dual"
+            run_step = 1
+            "#,
+        )
+        .unwrap();
+        args.merge_site_defaults(site, &matches);
+
+        assert!(args.missing_patterns().is_empty());
+        assert_eq!(args.inputs.igram_pattern, "/cli/igms");
+        assert_eq!(args.inputs.coord_file_pattern, "/cli/coords.json");
+        assert_eq!(args.inputs.met_file_pattern, "/site/met.json");
+        assert_eq!(args.inputs.igram_glob_pattern, "*.0001");
+        assert_eq!(args.run_dir_pattern, "/site/spectra/{DATE}");
+        assert_eq!(args.detectors, Some(DetectorSet::Single));
+        assert_eq!(args.run_step, 1);
+        assert_eq!(args.spectrum_name_template, DEFAULT_SPECTRUM_NAME_TEMPLATE);
+        assert_eq!(args.spectra_subdir, DEFAULT_SPECTRA_SUBDIR);
+    }
+
+    #[test]
+    fn test_spectrum_name_param() {
+        let mut args: DailyCommonArgs = toml::from_str(
+            r#"
+            igram_pattern = "/site/igms"
+            igram_glob_pattern = "*"
+            coord_file_pattern = "/site/coords.json"
+            met_file_pattern = "/site/met.json"
+            run_dir_pattern = "/site/spectra/{DATE}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            args.spectrum_name_param("xx").unwrap(),
+            "xxYYYYMMDDS0e00C.RRRR"
+        );
+
+        args.spectrum_name_template = "{SITE_ID}_YYYYMMDD_C.RRRRR".to_string();
+        assert_eq!(
+            args.spectrum_name_param("xx").unwrap(),
+            "xx_YYYYMMDD_C.RRRRR"
+        );
+
+        args.spectrum_name_template = "{SITE_ID}YYYYMMDDS0e00C.RR".to_string();
+        assert!(args.spectrum_name_param("xx").is_err());
+        args.spectrum_name_template = "{SITE}YYYYMMDDS0e00C.RRRR".to_string();
+        assert!(args.spectrum_name_param("xx").is_err());
+
+        // Without the detector character, the spectra from each detector would overwrite each other
+        args.spectrum_name_template = "{SITE_ID}YYYYMMDDS0e00.RRRR".to_string();
+        let err = args.spectrum_name_param("xx").unwrap_err();
+        assert!(err.to_string().contains("missing C"), "{err}");
+        // and a C in the site ID doesn't count
+        assert!(args.spectrum_name_param("Cx").is_err());
+
+        args.spectrum_name_template = DEFAULT_SPECTRUM_NAME_TEMPLATE.to_string();
+        assert_eq!(args.run_number_digits(), 4);
+        args.spectrum_name_template = "{SITE_ID}_YYYYMMDD_C.RRRRR".to_string();
+        assert_eq!(args.run_number_digits(), 5);
+    }
+
+    #[test]
+    fn test_daily_args_json_toml_equivalent() {
+        let from_json: DailyCommonArgs = serde_json::from_str(
+            r#"{
+                "igram_pattern": "/data/{SITE_ID}/{DATE:%Y%m%d}/interferograms/",
+                "igram_glob_pattern": "*{DATE:%Y%m%d}*",
+                "coord_file_pattern": "/data/{SITE_ID}/coords.json",
+                "met_file_pattern": "/data/{SITE_ID}/{DATE:%Y%m%d}/met_source.json",
+                "run_dir_pattern": "/data/{SITE_ID}/spectra/{DATE:%Y%m%d}",
+                "detectors": "dual",
+                "detector_schedule": [
+                    {"start": "2023-01-01", "end": "2023-06-14", "detectors": "single"}
+                ],
+                "utc_offset": "-7.0"
+            }"#,
+        )
+        .unwrap();
+
+        let from_toml: DailyCommonArgs = toml::from_str(
+            r#"
+            igram_pattern = "/data/{SITE_ID}/{DATE:%Y%m%d}/interferograms/"
+            igram_glob_pattern = "*{DATE:%Y%m%d}*"
+            coord_file_pattern = "/data/{SITE_ID}/coords.json"
+            met_file_pattern = "/data/{SITE_ID}/{DATE:%Y%m%d}/met_source.json"
+            run_dir_pattern = "/data/{SITE_ID}/spectra/{DATE:%Y%m%d}"
+            detectors = "dual"
+            utc_offset = "-7.0"
+
+            [[detector_schedule]]
+            start = "2023-01-01"
+            end = "2023-06-14"
+            detectors = "single"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(from_json, from_toml);
+        assert_eq!(from_json.detectors, Some(DetectorSet::Dual));
+        assert_eq!(from_toml.top_file, None);
+        assert_eq!(from_toml.flimit_file, None);
+        assert_eq!(from_toml.instrument_temperature_params, ["TSC"]);
+        assert_eq!(from_toml.detector_inference, DetectorInference::Strict);
+    }
+
+    #[test]
+    fn test_daily_input_patterns() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let igram_dir = dir.join("xx").join("20240401");
+        std::fs::create_dir_all(&igram_dir).unwrap();
+        for name in ["xx20240401.0001", "xx20240401.0002", "notes.txt"] {
+            std::fs::write(igram_dir.join(name), "").unwrap();
+        }
+
+        let patterns = DailyInputPatterns {
+            igram_pattern: format!("{}/{{SITE_ID}}/{{DATE:%Y%m%d}}", dir.display()),
+            coord_file_pattern: "/data/{SITE_ID}/coords.json".to_string(),
+            met_file_pattern: "/data/{SITE_ID}/{DATE}/met.json".to_string(),
+            igram_glob_pattern: "{SITE_ID}{DATE:%Y%m%d}.*".to_string(),
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let found_dir = patterns.igram_dir(date, "xx").unwrap();
+        let igram_glob = patterns.igram_glob(date, "xx").unwrap();
+        let (mut igrams, n_err) = glob_igrams(&found_dir, &igram_glob, true).unwrap();
+
+        igrams.sort();
+        assert_eq!(found_dir, igram_dir);
+        assert_eq!(
+            igrams,
+            [
+                igram_dir.join("xx20240401.0001"),
+                igram_dir.join("xx20240401.0002")
+            ]
+        );
+        assert_eq!(n_err, 0);
+        assert_eq!(
+            patterns.coord_file(date, "xx").unwrap(),
+            PathBuf::from("/data/xx/coords.json")
+        );
+        assert_eq!(
+            patterns.met_file(date, "xx").unwrap(),
+            PathBuf::from("/data/xx/2024-04-01/met.json")
+        );
+    }
+}
@@ -5,7 +5,12 @@ use std::{
 
 use chrono::{DateTime, FixedOffset};
 
-use crate::get_egi_path;
+use crate::{get_egi_path, path_relative_to_config, ConfigPathError, EgiPathError};
+
+pub use track::{Track, TrackInterpolation};
+#[cfg(feature = "dem")]
+mod dem;
+mod track;
 
 #[derive(Debug, thiserror::Error)]
 pub enum CoordinateError {
@@ -17,19 +22,46 @@ pub enum CoordinateError {
     UnknownExtension(PathBuf),
     #[error("Received a coordinate file with invalid UTF-8 in its extension: {0}")]
     InvalidExtension(PathBuf),
+    #[error("Coordinate file {0} has an out-of-range value: {1}")]
+    OutOfRange(PathBuf, String),
+    #[error("Error reading GPS track file {0}: {1}")]
+    TrackReadError(PathBuf, csv::Error),
+    #[error(
+        "Fixed coordinates must include either an altitude or a DEM to look up the altitude from"
+    )]
+    MissingAltitude,
+    #[error("Cannot get altitude from DEM {0}: {1}")]
+    DemError(PathBuf, String),
+    #[error(
+        "Cannot get altitude from DEM {0}: EGI was built without DEM support (the \"dem\" feature)"
+    )]
+    DemUnsupported(PathBuf),
+    #[error("{0}")]
+    ConfigPathError(#[from] ConfigPathError),
+    #[error("Cannot find the EGI directory for coordinate files: {0}")]
+    EgiPath(#[from] EgiPathError),
+    #[error("Coordinate file {0} does not exist")]
+    MissingFile(PathBuf),
+    #[error("{0} coordinate sources are not supported yet")]
+    Unsupported(&'static str),
 }
 
 /// An enum representing a source for geographic coordinates where the EM27 was located.
 /// For all variants, longitude and latitude must be given in degrees with west and south,
 /// respectively, input as negative values. Altitude must be given in meters.
+///
+/// In the JSON file, the variant is specified by the "type" key. For backwards compatibility,
+/// files without a "type" key are still accepted for the `Fixed` and `Coordfile` variants, see
+/// [`LegacyCoordinateConfig`].
 #[derive(Debug, serde::Deserialize)]
-#[serde(untagged)]
+#[serde(tag = "type")]
 enum CoordinateConfig {
     /// This indicates the EM27 was at a fixed location for all of the associated measurements.
     /// It will return the same coordinates no matter what time is queried. This corresponds to
     /// a JSON file such as:
     /// ```text
     /// {
+    ///   "type": "Fixed",
     ///   "longitude": -118.17,
     ///   "latitude": 34.20,
     ///   "altitude": 338.0,
@@ -38,24 +70,141 @@ enum CoordinateConfig {
     /// You may include additional keys with more information. A key "__comment__" with a description
     /// of what these coordinates represent is strongly recommended.
     /// ```
+    ///
+    /// If the altitude is not known, "altitude" can be replaced with "dem", the path to a GeoTIFF
+    /// digital elevation model (absolute or relative to the JSON file). The altitude is then taken
+    /// from the DEM at the given latitude and longitude when the file is loaded. This requires EGI
+    /// to be built with the "dem" feature. If both are given, "altitude" is used.
     Fixed {
         latitude: f64,
         longitude: f64,
-        altitude: f64,
+        #[serde(default)]
+        altitude: Option<f64>,
+        #[serde(default)]
+        dem: Option<PathBuf>,
     },
 
+    /// This indicates that the coordinates should be read from an EGI v1 coordinate file,
+    /// `$EGIPATH/coordinates/{site_id}_dlla.dat` (`$GGGPATH/egi/coordinates/{site_id}_dlla.dat`
+    /// if `EGIPATH` is not set). This corresponds to a JSON file such as:
+    /// ```text
+    /// {
+    ///   "type": "Coordfile",
+    ///   "site_id": "xx"
+    /// }
+    /// ```
+    Coordfile { site_id: String },
+
+    /// This indicates the EM27 was moving, and its location over time is given by a GPS track
+    /// in a CSV file (see [`Track::read_csv`] for the format). The coordinates for each
+    /// interferogram are interpolated to its ZPD time. This corresponds to a JSON file such as:
+    /// ```text
+    /// {
+    ///   "type": "Track",
+    ///   "file": "./gps_track.csv",
+    ///   "interpolation": "Linear"
+    /// }
+    /// ```
+    ///
+    /// "file" may be an absolute path or relative to the JSON file. "interpolation" is optional;
+    /// it may be "Linear" (the default) or "Nearest". Interferograms with ZPD times before the
+    /// first or after the last point in the track will not be included in the catalog.
+    ///
+    /// You can also pass the CSV file as the coordinate file directly, in which case linear
+    /// interpolation is used.
+    Track {
+        file: PathBuf,
+        #[serde(default)]
+        interpolation: TrackInterpolation,
+    },
+}
+
+/// The coordinate JSON formats from before the "type" key was required.
+///
+/// These are only used if the JSON file has no "type" key. Because the variant has to be
+/// inferred from which keys are present, errors in these files are less informative, so
+/// new files should include the "type" key.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum LegacyCoordinateConfig {
+    Fixed {
+        latitude: f64,
+        longitude: f64,
+        altitude: f64,
+    },
     Coordfile {
         site_id: String,
     },
 }
 
+impl From<LegacyCoordinateConfig> for CoordinateConfig {
+    fn from(value: LegacyCoordinateConfig) -> Self {
+        match value {
+            LegacyCoordinateConfig::Fixed {
+                latitude,
+                longitude,
+                altitude,
+            } => Self::Fixed {
+                latitude,
+                longitude,
+                altitude: Some(altitude),
+                dem: None,
+            },
+            LegacyCoordinateConfig::Coordfile { site_id } => Self::Coordfile { site_id },
+        }
+    }
+}
+
 impl CoordinateConfig {
     fn load_json(coord_json_file: &Path) -> Result<Self, CoordinateError> {
-        let reader = std::fs::File::open(coord_json_file)
+        let contents = std::fs::read_to_string(coord_json_file)
             .map_err(|e| CoordinateError::CannotReadFile(coord_json_file.to_path_buf(), e))?;
-        serde_json::from_reader(reader)
+        Self::from_json_str(&contents)
             .map_err(|e| CoordinateError::DeserializationError(coord_json_file.to_path_buf(), e))
     }
+
+    fn from_json_str(contents: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(contents)?;
+        if value.get("type").is_some() {
+            serde_json::from_value(value)
+        } else {
+            log::debug!(
+                "Coordinate JSON has no \"type\" key, inferring the coordinate type from its keys"
+            );
+            serde_json::from_value::<LegacyCoordinateConfig>(value).map(Self::from)
+        }
+    }
+
+    fn resolve_relative_paths(self, config_file: &Path) -> Result<Self, ConfigPathError> {
+        let resolved = match self {
+            CoordinateConfig::Track {
+                file,
+                interpolation,
+            } => {
+                let file = path_relative_to_config(config_file, file)?;
+                CoordinateConfig::Track {
+                    file,
+                    interpolation,
+                }
+            }
+            CoordinateConfig::Fixed {
+                latitude,
+                longitude,
+                altitude,
+                dem: Some(dem),
+            } => {
+                let dem = path_relative_to_config(config_file, dem)?;
+                CoordinateConfig::Fixed {
+                    latitude,
+                    longitude,
+                    altitude,
+                    dem: Some(dem),
+                }
+            }
+            CoordinateConfig::Fixed { dem: None, .. } | CoordinateConfig::Coordfile { .. } => self,
+        };
+        Ok(resolved)
+    }
 }
 
 pub enum CoordinateSource {
@@ -65,6 +214,7 @@ pub enum CoordinateSource {
         altitude: f64,
     },
     Coordfile,
+    Track(Track),
 }
 
 impl CoordinateSource {
@@ -74,63 +224,166 @@ impl CoordinateSource {
     ///
     /// Supported file formats:
     /// - `.json`
+    /// - `.csv` (a GPS track, see [`Track::read_csv`])
+    ///
+    /// The coordinates are checked when loaded, so this returns an error if the latitude
+    /// is outside [-90, 90], the longitude is outside [-180, 180], or the altitude is not
+    /// a finite number.
     pub fn load_file(coord_file: &Path) -> Result<Self, CoordinateError> {
-        let cfg = match CoordinateFileType::try_from(coord_file)? {
-            CoordinateFileType::Json => CoordinateConfig::load_json(coord_file),
-        }?;
-        Self::try_from(cfg)
+        let source = match CoordinateFileType::try_from(coord_file)? {
+            CoordinateFileType::Json => {
+                let cfg = CoordinateConfig::load_json(coord_file)?;
+                Self::try_from(cfg.resolve_relative_paths(coord_file)?)?
+            }
+            CoordinateFileType::Csv => {
+                Self::Track(Track::read_csv(coord_file, TrackInterpolation::Linear)?)
+            }
+        };
+        source
+            .validate()
+            .map_err(|reason| CoordinateError::OutOfRange(coord_file.to_path_buf(), reason))?;
+        Ok(source)
+    }
+
+    /// Check that the coordinates are physically reasonable, returning a description of
+    /// the first invalid value if not.
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            CoordinateSource::Fixed {
+                latitude,
+                longitude,
+                altitude,
+            } => check_coordinates(*latitude, *longitude, *altitude),
+            CoordinateSource::Coordfile => Ok(()),
+            CoordinateSource::Track(track) => track.validate(),
+        }
     }
 
     /// Return the coordinates where the EM27 was for a given datetime.
     /// The return values are latitude (south is negative), longitude (west is negative),
-    /// and altitude (in meters).
-    pub fn get_coords_for_datetime(&self, _datetime: DateTime<FixedOffset>) -> (f64, f64, f64) {
+    /// and altitude (in meters). Returns `None` if the coordinates are not known at that
+    /// time, i.e. if it is outside the time span of a GPS track.
+    pub fn get_coords_for_datetime(
+        &self,
+        datetime: DateTime<FixedOffset>,
+    ) -> Option<(f64, f64, f64)> {
         match self {
             CoordinateSource::Fixed {
                 latitude,
                 longitude,
                 altitude,
-            } => (*latitude, *longitude, *altitude),
+            } => Some((*latitude, *longitude, *altitude)),
             CoordinateSource::Coordfile => todo!(),
+            CoordinateSource::Track(track) => track.coords_at(datetime),
         }
     }
 }
 
+#[cfg(feature = "dem")]
+fn altitude_from_dem(dem: &Path, latitude: f64, longitude: f64) -> Result<f64, CoordinateError> {
+    let altitude = dem::sample_dem(dem, latitude, longitude)?;
+    log::info!(
+        "Using altitude {altitude} m from DEM {} for latitude {latitude}, longitude {longitude}",
+        dem.display()
+    );
+    Ok(altitude)
+}
+
+#[cfg(not(feature = "dem"))]
+fn altitude_from_dem(dem: &Path, _latitude: f64, _longitude: f64) -> Result<f64, CoordinateError> {
+    Err(CoordinateError::DemUnsupported(dem.to_path_buf()))
+}
+
+fn check_coordinates(latitude: f64, longitude: f64, altitude: f64) -> Result<(), String> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!(
+            "latitude {latitude} is outside the range [-90, 90]"
+        ));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!(
+            "longitude {longitude} is outside the range [-180, 180]"
+        ));
+    }
+    if !altitude.is_finite() {
+        return Err(format!("altitude {altitude} is not a finite number"));
+    }
+    Ok(())
+}
+
 impl TryFrom<CoordinateConfig> for CoordinateSource {
     type Error = CoordinateError;
 
     fn try_from(value: CoordinateConfig) -> Result<Self, Self::Error> {
         match value {
+            CoordinateConfig::Track {
+                file,
+                interpolation,
+            } => Ok(Self::Track(Track::read_csv(&file, interpolation)?)),
             CoordinateConfig::Fixed {
                 latitude,
                 longitude,
                 altitude,
-            } => Ok(Self::Fixed {
-                latitude,
-                longitude,
-                altitude,
-            }),
+                dem,
+            } => {
+                let altitude = match (altitude, dem) {
+                    (Some(alt), dem) => {
+                        if let Some(dem) = dem {
+                            log::warn!(
+                                "Fixed coordinates include both an altitude and a DEM ({}), using the altitude",
+                                dem.display()
+                            );
+                        }
+                        alt
+                    }
+                    (None, Some(dem)) => altitude_from_dem(&dem, latitude, longitude)?,
+                    (None, None) => return Err(CoordinateError::MissingAltitude),
+                };
+                Ok(Self::Fixed {
+                    latitude,
+                    longitude,
+                    altitude,
+                })
+            }
             CoordinateConfig::Coordfile { site_id } => {
-                let egipath = get_egi_path().unwrap();
-                let coord_file = egipath
-                    .join("coordinates")
-                    .join(format!("{site_id}_dlla.dat"));
-                if !coord_file.exists() {
-                    // TODO: error
-                }
-
-                // TODO: parse the coordinate file. Need to check how Jacob handles the case with no UTCTime column;
-                // for an instrument that moves locations in say the Pacific time zone, if we just assume that the location
-                // changes at midnight, that could confuse things.
-                todo!()
+                let coord_file = egi_coord_file(get_egi_path(), &site_id)?;
+                coordfile_source(&coord_file)
             }
         }
     }
 }
 
+/// Load coordinates from the EGI v1 coordinate file `coord_file`.
+///
+/// Reading these files is not implemented yet, so this always returns an error: a
+/// [`CoordinateError::MissingFile`] if the file does not exist, otherwise
+/// [`CoordinateError::Unsupported`].
+fn coordfile_source(coord_file: &Path) -> Result<CoordinateSource, CoordinateError> {
+    if !coord_file.exists() {
+        return Err(CoordinateError::MissingFile(coord_file.to_path_buf()));
+    }
+
+    // TODO: parse the coordinate file. Need to check how Jacob handles the case with no UTCTime column;
+    // for an instrument that moves locations in say the Pacific time zone, if we just assume that the location
+    // changes at midnight, that could confuse things.
+    Err(CoordinateError::Unsupported("Coordfile"))
+}
+
+/// The path to the EGI v1 coordinate file for `site_id` in the EGI directory `egi_path`.
+/// Returns an error (rather than panicking) if the EGI directory could not be found.
+fn egi_coord_file(
+    egi_path: Result<PathBuf, EgiPathError>,
+    site_id: &str,
+) -> Result<PathBuf, CoordinateError> {
+    Ok(egi_path?
+        .join("coordinates")
+        .join(format!("{site_id}_dlla.dat")))
+}
+
 #[derive(Debug, Clone)]
 enum CoordinateFileType {
     Json,
+    Csv,
 }
 
 impl TryFrom<&Path> for CoordinateFileType {
@@ -145,7 +398,151 @@ impl TryFrom<&Path> for CoordinateFileType {
 
         match extension {
             "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
             _ => Err(CoordinateError::UnknownExtension(value.to_path_buf())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_egi_coord_file() {
+        let path = egi_coord_file(Ok(PathBuf::from("/ggg/egi")), "xx").unwrap();
+        assert_eq!(path, Path::new("/ggg/egi/coordinates/xx_dlla.dat"));
+
+        let err = egi_coord_file(Err(EgiPathError::NotSet), "xx").unwrap_err();
+        assert!(matches!(
+            err,
+            CoordinateError::EgiPath(EgiPathError::NotSet)
+        ));
+    }
+
+    #[test]
+    fn test_coordfile_source_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let coord_file = dir.path().join("xx_dlla.dat");
+        assert!(matches!(
+            coordfile_source(&coord_file),
+            Err(CoordinateError::MissingFile(p)) if p == coord_file
+        ));
+
+        std::fs::write(&coord_file, "").unwrap();
+        assert!(matches!(
+            coordfile_source(&coord_file),
+            Err(CoordinateError::Unsupported("Coordfile"))
+        ));
+    }
+
+    #[test]
+    fn test_check_coordinates() {
+        assert!(check_coordinates(34.2, -118.17, 338.0).is_ok());
+        assert!(check_coordinates(-90.0, 180.0, -10.0).is_ok());
+        assert!(check_coordinates(340.0, -118.17, 338.0).is_err());
+        assert!(check_coordinates(34.2, -181.0, 338.0).is_err());
+        assert!(check_coordinates(34.2, -118.17, f64::NAN).is_err());
+        assert!(check_coordinates(f64::NAN, -118.17, 338.0).is_err());
+    }
+
+    #[test]
+    fn test_tagged_coordinate_config() {
+        let cfg = CoordinateConfig::from_json_str(
+            r#"{"type": "Fixed", "latitude": 34.2, "longitude": -118.17, "altitude": 338.0, "__comment__": "Caltech"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            cfg,
+            CoordinateConfig::Fixed { latitude, longitude, altitude: Some(altitude), dem: None }
+                if latitude == 34.2 && longitude == -118.17 && altitude == 338.0
+        ));
+
+        let cfg =
+            CoordinateConfig::from_json_str(r#"{"type": "Coordfile", "site_id": "xx"}"#).unwrap();
+        assert!(matches!(cfg, CoordinateConfig::Coordfile { site_id } if site_id == "xx"));
+
+        // With the tag, errors should point to the actual problem rather than failing to match any variant.
+        let e = CoordinateConfig::from_json_str(
+            r#"{"type": "Fixed", "latitude": 34.2, "longitud": -118.17, "altitude": 338.0}"#,
+        )
+        .unwrap_err();
+        assert!(e.to_string().contains("missing field `longitude`"), "{e}");
+        let e =
+            CoordinateConfig::from_json_str(r#"{"type": "Fxied", "site_id": "xx"}"#).unwrap_err();
+        assert!(e.to_string().contains("unknown variant `Fxied`"), "{e}");
+    }
+
+    #[test]
+    fn test_legacy_coordinate_config() {
+        let cfg = CoordinateConfig::from_json_str(
+            r#"{"latitude": 34.2, "longitude": -118.17, "altitude": 338.0}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            cfg,
+            CoordinateConfig::Fixed { latitude, longitude, altitude: Some(altitude), dem: None }
+                if latitude == 34.2 && longitude == -118.17 && altitude == 338.0
+        ));
+
+        let cfg = CoordinateConfig::from_json_str(r#"{"site_id": "xx"}"#).unwrap();
+        assert!(matches!(cfg, CoordinateConfig::Coordfile { site_id } if site_id == "xx"));
+    }
+
+    #[test]
+    fn test_fixed_altitude() {
+        let cfg = CoordinateConfig::from_json_str(
+            r#"{"type": "Fixed", "latitude": 34.2, "longitude": -118.17, "altitude": 338.0, "dem": "dem.tif"}"#,
+        )
+        .unwrap();
+        let source = CoordinateSource::try_from(cfg).unwrap();
+        assert!(matches!(source, CoordinateSource::Fixed { altitude, .. } if altitude == 338.0));
+
+        let cfg = CoordinateConfig::from_json_str(
+            r#"{"type": "Fixed", "latitude": 34.2, "longitude": -118.17}"#,
+        )
+        .unwrap();
+        let res = CoordinateSource::try_from(cfg);
+        assert!(matches!(res, Err(CoordinateError::MissingAltitude)));
+    }
+
+    #[test]
+    fn test_load_out_of_range_coords() {
+        let tmp = tempfile::tempdir().unwrap();
+        let coord_file = tmp.path().join("coords.json");
+        std::fs::write(
+            &coord_file,
+            r#"{"latitude": 340.0, "longitude": -118.17, "altitude": 338.0}"#,
+        )
+        .unwrap();
+
+        let res = CoordinateSource::load_file(&coord_file);
+        assert!(matches!(res, Err(CoordinateError::OutOfRange(p, _)) if p == coord_file));
+    }
+
+    #[test]
+    fn test_load_track_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let coord_file = tmp.path().join("coords.json");
+        std::fs::write(
+            &coord_file,
+            r#"{"type": "Track", "file": "track.csv", "interpolation": "Nearest"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmp.path().join("track.csv"),
+            "timestamp,lat,lon,alt\n2025-03-01T09:00:00-07:00,34.0,-118.0,300.0\n2025-03-01T09:10:00-07:00,34.2,-118.2,340.0\n",
+        )
+        .unwrap();
+
+        let res = CoordinateSource::load_file(&coord_file);
+        let coords = res.unwrap();
+        let t = DateTime::parse_from_rfc3339("2025-03-01T09:08:00-07:00").unwrap();
+        assert_eq!(
+            coords.get_coords_for_datetime(t),
+            Some((34.2, -118.2, 340.0))
+        );
+        let t = DateTime::parse_from_rfc3339("2025-03-01T09:11:00-07:00").unwrap();
+        assert_eq!(coords.get_coords_for_datetime(t), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use tiff::{
+    decoder::{Decoder, DecodingResult, Limits},
+    tags::Tag,
+    ColorType,
+};
+
+use super::CoordinateError;
+
+/// Get the altitude at a latitude/longitude from a GeoTIFF digital elevation model.
+///
+/// The DEM must be a single band GeoTIFF on a latitude/longitude grid (e.g. EPSG:4326, as
+/// SRTM or Copernicus DEM tiles are distributed) with the ModelPixelScale and ModelTiepoint
+/// tags defining the grid. The value of the pixel containing the given coordinates is returned,
+/// so the DEM values should be in meters above sea level.
+///
+/// # Errors
+/// - If the DEM cannot be read or is missing the georeferencing tags,
+/// - if the DEM has more than one band,
+/// - if the coordinates are outside the DEM, or
+/// - if the DEM has no data (as indicated by the GDAL no data tag) at the coordinates.
+pub(super) fn sample_dem(
+    dem_file: &Path,
+    latitude: f64,
+    longitude: f64,
+) -> Result<f64, CoordinateError> {
+    let dem_err = |msg: String| CoordinateError::DemError(dem_file.to_path_buf(), msg);
+
+    let f = std::fs::File::open(dem_file)
+        .map_err(|e| CoordinateError::CannotReadFile(dem_file.to_path_buf(), e))?;
+    let mut decoder = Decoder::new(std::io::BufReader::new(f))
+        .map_err(|e| dem_err(e.to_string()))?
+        .with_limits(Limits::unlimited());
+
+    let colortype = decoder.colortype().map_err(|e| dem_err(e.to_string()))?;
+    if !matches!(colortype, ColorType::Gray(_)) {
+        return Err(dem_err(format!(
+            "expected a single band DEM, got color type {colortype:?}"
+        )));
+    }
+
+    let (width, height) = decoder.dimensions().map_err(|e| dem_err(e.to_string()))?;
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .map_err(|e| dem_err(format!("could not read the pixel scale: {e}")))?;
+    let tiepoint = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .map_err(|e| dem_err(format!("could not read the tie point: {e}")))?;
+    if scale.len() < 2 || tiepoint.len() < 6 {
+        return Err(dem_err(
+            "the pixel scale or tie point tags have too few values".to_string(),
+        ));
+    }
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|s| s.trim_end_matches('\0').trim().parse::<f64>().ok());
+
+    let (col, row) = pixel_index(&scale, &tiepoint, latitude, longitude)
+        .filter(|&(col, row)| col < width as usize && row < height as usize)
+        .ok_or_else(|| {
+            dem_err(format!(
+                "latitude {latitude}, longitude {longitude} is outside the DEM"
+            ))
+        })?;
+
+    let image = decoder.read_image().map_err(|e| dem_err(e.to_string()))?;
+    let i = row * width as usize + col;
+    let value = match image {
+        DecodingResult::U8(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::U16(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::U32(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::U64(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::F32(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::F64(v) => v.get(i).copied(),
+        DecodingResult::I8(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::I16(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::I32(v) => v.get(i).map(|&x| x as f64),
+        DecodingResult::I64(v) => v.get(i).map(|&x| x as f64),
+    }
+    .ok_or_else(|| dem_err("the DEM has fewer values than its dimensions".to_string()))?;
+
+    if !value.is_finite() || nodata.is_some_and(|nd| value == nd) {
+        return Err(dem_err(format!(
+            "the DEM has no data at latitude {latitude}, longitude {longitude}"
+        )));
+    }
+    Ok(value)
+}
+
+/// Get the column and row of the pixel containing the given coordinates, or `None` if
+/// they are before the first row or column.
+///
+/// `scale` is the (x, y) pixel size and `tiepoint` the (i, j, k, x, y, z) tie point from
+/// the GeoTIFF tags, where x is longitude and y is latitude. Rows go from north to south.
+fn pixel_index(
+    scale: &[f64],
+    tiepoint: &[f64],
+    latitude: f64,
+    longitude: f64,
+) -> Option<(usize, usize)> {
+    let col = (longitude - tiepoint[3]) / scale[0] + tiepoint[0];
+    let row = (tiepoint[4] - latitude) / scale[1] + tiepoint[1];
+    if col < 0.0 || row < 0.0 {
+        return None;
+    }
+    Some((col.floor() as usize, row.floor() as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    use super::*;
+
+    /// Write a 3x2 DEM covering 34 to 36 N, 119 to 116 W with 1 degree pixels.
+    fn write_test_dem(path: &Path) {
+        let f = std::fs::File::create(path).unwrap();
+        let mut tiff = TiffEncoder::new(f).unwrap();
+        let mut image = tiff.new_image::<colortype::Gray32Float>(3, 2).unwrap();
+        image
+            .encoder()
+            .write_tag(Tag::ModelPixelScaleTag, &[1.0f64, 1.0, 0.0][..])
+            .unwrap();
+        image
+            .encoder()
+            .write_tag(
+                Tag::ModelTiepointTag,
+                &[0.0f64, 0.0, 0.0, -119.0, 36.0, 0.0][..],
+            )
+            .unwrap();
+        image.encoder().write_tag(Tag::GdalNodata, "-9999").unwrap();
+        image
+            .write_data(&[100.0, 200.0, 300.0, 400.0, 500.0, -9999.0])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sample_dem() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dem_file = tmp.path().join("dem.tif");
+        write_test_dem(&dem_file);
+
+        let top_left = sample_dem(&dem_file, 35.5, -118.5);
+        let bottom_middle = sample_dem(&dem_file, 34.2, -117.2);
+        let nodata = sample_dem(&dem_file, 34.2, -116.2);
+        let outside = sample_dem(&dem_file, 34.2, -115.5);
+
+        assert_eq!(top_left.unwrap(), 100.0);
+        assert_eq!(bottom_middle.unwrap(), 500.0);
+        assert!(nodata.is_err());
+        assert!(outside.is_err());
+    }
+}
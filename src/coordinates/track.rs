@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+
+use super::{check_coordinates, CoordinateError};
+
+/// How to compute coordinates from a GPS track at times between the track points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum TrackInterpolation {
+    /// Use the coordinates of the track point closest in time.
+    Nearest,
+    /// Linearly interpolate latitude, longitude, and altitude between the track points
+    /// before and after the time.
+    #[default]
+    Linear,
+}
+
+/// One point in a GPS track.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct TrackPoint {
+    #[serde(alias = "time", alias = "datetime")]
+    timestamp: DateTime<FixedOffset>,
+    #[serde(alias = "lat")]
+    latitude: f64,
+    #[serde(alias = "lon")]
+    longitude: f64,
+    #[serde(alias = "alt")]
+    altitude: f64,
+}
+
+/// A GPS track giving the location of a mobile EM27 over time.
+#[derive(Debug, Clone)]
+pub struct Track {
+    points: Vec<TrackPoint>,
+    interpolation: TrackInterpolation,
+}
+
+impl Track {
+    /// Read a GPS track from a CSV file.
+    ///
+    /// The CSV file must have a header line with the columns "timestamp", "latitude", "longitude",
+    /// and "altitude" (or "time"/"datetime", "lat", "lon", and "alt", respectively). Timestamps must be
+    /// ISO-8601 datetimes that include a UTC offset, e.g. "2025-03-01T09:01:02-07:00" or
+    /// "2025-03-01T16:01:02Z". Latitude and longitude must be in degrees, with south and west
+    /// negative, and altitude in meters. Lines starting with "#" are ignored. The rows do not
+    /// need to be in time order.
+    pub(super) fn read_csv(
+        track_file: &Path,
+        interpolation: TrackInterpolation,
+    ) -> Result<Self, CoordinateError> {
+        let f = std::fs::File::open(track_file)
+            .map_err(|e| CoordinateError::CannotReadFile(track_file.to_path_buf(), e))?;
+        Self::read_csv_inner(f, interpolation)
+            .map_err(|e| CoordinateError::TrackReadError(track_file.to_path_buf(), e))
+    }
+
+    fn read_csv_inner<R: std::io::Read>(
+        input: R,
+        interpolation: TrackInterpolation,
+    ) -> Result<Self, csv::Error> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(input);
+
+        let mut points: Vec<TrackPoint> = rdr.deserialize().collect::<Result<_, _>>()?;
+        points.sort_by_key(|p| p.timestamp);
+        Ok(Self {
+            points,
+            interpolation,
+        })
+    }
+
+    /// Check that the track has at least one point and that all the points have
+    /// physically reasonable coordinates, returning a description of the problem if not.
+    pub(super) fn validate(&self) -> Result<(), String> {
+        if self.points.is_empty() {
+            return Err("the GPS track has no points".to_string());
+        }
+
+        for point in self.points.iter() {
+            check_coordinates(point.latitude, point.longitude, point.altitude)
+                .map_err(|e| format!("{e} (at {})", point.timestamp))?;
+        }
+        Ok(())
+    }
+
+    /// Get the latitude, longitude, and altitude at `datetime`, or `None` if `datetime`
+    /// is before the first or after the last point of the track.
+    pub(super) fn coords_at(&self, datetime: DateTime<FixedOffset>) -> Option<(f64, f64, f64)> {
+        let first = self.points.first()?;
+        let last = self.points.last()?;
+        if datetime < first.timestamp || datetime > last.timestamp {
+            return None;
+        }
+
+        // Index of the first point after `datetime`; the bounds check above means the point
+        // before `datetime` exists unless `datetime` is exactly the first point's time.
+        let i = self.points.partition_point(|p| p.timestamp <= datetime);
+        if i == 0 {
+            return Some(first.coords());
+        }
+        let before = &self.points[i - 1];
+        let Some(after) = self.points.get(i) else {
+            return Some(before.coords());
+        };
+
+        let span = (after.timestamp - before.timestamp).num_milliseconds() as f64;
+        let weight = if span > 0.0 {
+            (datetime - before.timestamp).num_milliseconds() as f64 / span
+        } else {
+            0.0
+        };
+
+        match self.interpolation {
+            TrackInterpolation::Nearest if weight <= 0.5 => Some(before.coords()),
+            TrackInterpolation::Nearest => Some(after.coords()),
+            TrackInterpolation::Linear => {
+                let lerp = |a: f64, b: f64| a + weight * (b - a);
+                // Take the short way around if the track crosses the antimeridian.
+                let mut after_lon = after.longitude;
+                if after_lon - before.longitude > 180.0 {
+                    after_lon -= 360.0;
+                } else if before.longitude - after_lon > 180.0 {
+                    after_lon += 360.0;
+                }
+                let mut lon = lerp(before.longitude, after_lon);
+                if lon < -180.0 {
+                    lon += 360.0;
+                } else if lon > 180.0 {
+                    lon -= 360.0;
+                }
+                Some((
+                    lerp(before.latitude, after.latitude),
+                    lon,
+                    lerp(before.altitude, after.altitude),
+                ))
+            }
+        }
+    }
+}
+
+impl TrackPoint {
+    fn coords(&self) -> (f64, f64, f64) {
+        (self.latitude, self.longitude, self.altitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_TRACK: &str = "timestamp,lat,lon,alt
+# a comment line
+2025-03-01T09:10:00-07:00, 34.2, -118.2, 340.0
+2025-03-01T09:00:00-07:00, 34.0, -118.0, 300.0
+";
+
+    fn dt(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn assert_coords_close(a: (f64, f64, f64), b: (f64, f64, f64)) {
+        assert!((a.0 - b.0).abs() < 1e-9, "latitude {} != {}", a.0, b.0);
+        assert!((a.1 - b.1).abs() < 1e-9, "longitude {} != {}", a.1, b.1);
+        assert!((a.2 - b.2).abs() < 1e-9, "altitude {} != {}", a.2, b.2);
+    }
+
+    #[test]
+    fn test_linear_track() {
+        let track =
+            Track::read_csv_inner(TEST_TRACK.as_bytes(), TrackInterpolation::Linear).unwrap();
+        track.validate().unwrap();
+
+        let c = track.coords_at(dt("2025-03-01T16:05:00Z")).unwrap();
+        assert_coords_close(c, (34.1, -118.1, 320.0));
+        let c = track.coords_at(dt("2025-03-01T09:00:00-07:00")).unwrap();
+        assert_coords_close(c, (34.0, -118.0, 300.0));
+        let c = track.coords_at(dt("2025-03-01T09:10:00-07:00")).unwrap();
+        assert_coords_close(c, (34.2, -118.2, 340.0));
+
+        assert!(track.coords_at(dt("2025-03-01T08:59:59-07:00")).is_none());
+        assert!(track.coords_at(dt("2025-03-01T09:10:01-07:00")).is_none());
+    }
+
+    #[test]
+    fn test_nearest_track() {
+        let track =
+            Track::read_csv_inner(TEST_TRACK.as_bytes(), TrackInterpolation::Nearest).unwrap();
+        let c = track.coords_at(dt("2025-03-01T09:04:00-07:00")).unwrap();
+        assert_coords_close(c, (34.0, -118.0, 300.0));
+        let c = track.coords_at(dt("2025-03-01T09:06:00-07:00")).unwrap();
+        assert_coords_close(c, (34.2, -118.2, 340.0));
+    }
+
+    #[test]
+    fn test_track_antimeridian() {
+        let track_csv = "timestamp,latitude,longitude,altitude
+2025-03-01T00:00:00Z,0.0,179.0,0.0
+2025-03-01T01:00:00Z,0.0,-179.0,0.0
+";
+        let track =
+            Track::read_csv_inner(track_csv.as_bytes(), TrackInterpolation::Linear).unwrap();
+        let c = track.coords_at(dt("2025-03-01T00:15:00Z")).unwrap();
+        assert_coords_close(c, (0.0, 179.5, 0.0));
+        let c = track.coords_at(dt("2025-03-01T00:45:00Z")).unwrap();
+        assert_coords_close(c, (0.0, -179.5, 0.0));
+    }
+
+    #[test]
+    fn test_invalid_track() {
+        let track = Track::read_csv_inner("timestamp,lat,lon,alt\n".as_bytes(), Default::default())
+            .unwrap();
+        assert!(track.validate().is_err());
+
+        let track_csv = "timestamp,lat,lon,alt
+2025-03-01T00:00:00Z,95.0,0.0,0.0
+";
+        let track = Track::read_csv_inner(track_csv.as_bytes(), Default::default()).unwrap();
+        assert!(track.validate().is_err());
+    }
+}
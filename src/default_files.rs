@@ -1,10 +1,11 @@
-use crate::config::CoreConfig;
+use crate::config::{CoreConfig, PLACEHOLDER_EMAIL};
 pub use inner::*;
 
 pub fn default_core_config_toml() -> String {
     let default_cfg = CoreConfig {
-        ftp_email: "you@example.com".to_string(),
-        priors_request_email: "you@example.com".to_string(),
+        ftp_email: PLACEHOLDER_EMAIL.to_string(),
+        priors_request_email: PLACEHOLDER_EMAIL.to_string(),
+        default_jobs: Some(1),
     };
     let s = toml::to_string_pretty(&default_cfg)
         .expect("failed to serialize the default core configuration as TOML - this is a bug");
@@ -14,7 +15,7 @@ pub fn default_core_config_toml() -> String {
 #[cfg(unix)]
 mod inner {
     pub static I2S_TOP: &'static str = include_str!("etc/em27_i2s.top");
-    pub static FLIMIT_SINGLE: &'static str = include_str!("etc/flimit-dual.i2s");
+    pub static FLIMIT_SINGLE: &'static str = include_str!("etc/flimit-single.i2s");
     pub static FLIMIT_DUAL: &'static str = include_str!("etc/flimit-dual.i2s");
     pub static FLIMIT_MIDIR: &'static str = include_str!("etc/flimit-mid-ir.i2s");
     pub static EM27_WINDOWS: &'static str = include_str!("etc/em27_windows.gnd");
@@ -29,7 +30,7 @@ mod inner {
 #[cfg(windows)]
 mod inner {
     pub static I2S_TOP: &'static str = include_str!(r"etc\em27_i2s.top");
-    pub static FLIMIT_SINGLE: &'static str = include_str!(r"etc\flimit-dual.i2s");
+    pub static FLIMIT_SINGLE: &'static str = include_str!(r"etc\flimit-single.i2s");
     pub static FLIMIT_DUAL: &'static str = include_str!(r"etc\flimit-dual.i2s");
     pub static FLIMIT_MIDIR: &'static str = include_str!(r"etc\flimit-mid-ir.i2s");
     pub static EM27_WINDOWS: &'static str = include_str!(r"etc\em27_windows.gnd");
@@ -1,25 +1,30 @@
 use std::{
     collections::HashSet,
     fmt::Display,
+    io::Write,
     path::{Path, PathBuf},
 };
 
-use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeZone, Timelike};
 use error_stack::ResultExt;
 use itertools::Itertools;
-use log::trace;
+use log::{debug, info, trace, warn};
 use once_cell::sync::Lazy;
 use regex::Regex;
 
 use crate::{
+    config::DetectorSet,
     coordinates::CoordinateSource,
-    meteorology::{read_met_file, MetEntry, MetSource},
-    CATALOG_FILL_FLOAT_F32, CATALOG_FILL_FLOAT_F64,
+    meteorology::{get_igram_time_span, read_met_file, MetConfig, MetEntry},
+    utils::igram::{is_gzipped, read_igram_header},
 };
 use ggg_rs::{
     i2s::{self, OpusCatalogueEntry},
     interpolation::{ConstantValueInterp, InterpolationError, InterpolationMethod},
-    opus::{self, constants::bruker::BrukerBlockType, IgramHeader, MissingOpusParameterError},
+    opus::{
+        constants::bruker::{BrukerBlockType, BrukerParValue},
+        IgramHeader, MissingOpusParameterError,
+    },
 };
 
 type CatalogResult<T> = error_stack::Result<T, CatalogError>;
@@ -28,50 +33,166 @@ type CatalogResult<T> = error_stack::Result<T, CatalogError>;
 ///
 /// # Inputs
 /// - `coordinate_file`: path to the file specifying latitude/longitude/altitude coordinates; see [`CoordinateSource`] for formats this file may take.
-/// - `surface_met_source_file`: path to the file specifying how to access the surface meteorology data; see [`MetSource`] for formats this file may take.
+/// - `surface_met_source_files`: paths to the files specifying how to access the surface meteorology data; see [`MetSource`](crate::meteorology::MetSource) for formats these files
+///   may take. If more than one is given, they are tried in order for each interferogram and the first one with met data available at that
+///   interferogram's ZPD time is used.
 /// - `interferograms`: a slice of paths to the interferograms to include in the catalog.
-/// - `keep_if_missing_met`: if `false`, then interferograms for which surface meteorology could not be found are not included in the catalog.
-///   Setting this to `true` will keep them, with fill values for the met data. In most cases, this should be `false`.
+/// - `options`: additional settings for how to create the catalog, see [`CatalogOptions`].
+/// - `progress`: if given, this is called as `progress(done, total)` as the interferograms are cataloged,
+///   e.g. to update a progress bar (the EGI programs use `utils::progress::ProgressReporter`, which
+///   needs the "progress" feature). `total` excludes any duplicate interferograms that were removed.
 ///
 /// # Errors
 /// - If the coordinates or surface meteorology could not be loaded, due to incorrect format or an I/O failure.
+/// - If the ZPD time could not be calculated for any interferogram.
+/// - If the interferograms' ZPD times are on more than one date and `options.multi_date_check` is [`MultiDateCheck::Error`].
+/// - If some interferograms appear to be copies of the same scan and `options.duplicate_check` is [`DuplicateCheck::Error`].
 /// - If creating the catalog for any interferogram failed (see [`create_catalog_entry_for_igram`] for possible reasons)
 pub fn make_catalog_entries<P: AsRef<Path>>(
     coordinate_file: &Path,
-    surface_met_source_file: &Path,
+    surface_met_source_files: &[PathBuf],
+    interferograms: &[P],
+    options: &CatalogOptions,
+    progress: Option<&mut dyn FnMut(usize, usize)>,
+) -> error_stack::Result<CatalogEntries, MainCatalogError> {
+    let coords = load_coordinates(coordinate_file)?;
+    let zpd_times = read_zpd_times(interferograms, options)
+        .change_context_lazy(|| MainCatalogError::Catalog)?;
+    let met: Vec<SurfaceMet> = surface_met_source_files
+        .iter()
+        .map(|met_file| load_met_for_zpd_times(met_file, &zpd_times, options))
+        .try_collect()?;
+    build_entries_for_zpd_times(&coords, &met, interferograms, zpd_times, options, progress)
+}
+
+/// Load the coordinates for a catalog from a coordinate file, see [`CoordinateSource::load_file`].
+///
+/// This is the first step of [`make_catalog_entries`], exposed separately so that the coordinates
+/// can be inspected before making the catalog.
+pub fn load_coordinates(
+    coordinate_file: &Path,
+) -> error_stack::Result<CoordinateSource, MainCatalogError> {
+    CoordinateSource::load_file(coordinate_file)
+        .change_context_lazy(|| MainCatalogError::Coordinates)
+}
+
+/// Load the surface met data from one met source file for a set of interferograms.
+///
+/// This is the second step of [`make_catalog_entries`], which calls this for each of its met source files.
+/// The interferograms are needed because some met sources infer their time zone from the interferograms'
+/// ZPD times or only provide data around those times.
+///
+/// # Inputs
+/// - `met_source_file`: path to the file specifying how to access the surface meteorology data, see [`MetSource`](crate::meteorology::MetSource).
+/// - `interferograms`: a slice of paths to the interferograms that will be in the catalog.
+/// - `options`: the catalog options. Of these, `site_id`, `detectors`, `default_utc_offset`, and `utc_offset_override`
+///   are used; see [`CatalogOptions`] for their meanings.
+///
+/// # Errors
+/// - If the met source file or met data could not be read.
+/// - If the ZPD time could not be calculated for any interferogram.
+pub fn load_met_for_igrams<P: AsRef<Path>>(
+    met_source_file: &Path,
+    interferograms: &[P],
+    options: &CatalogOptions,
+) -> error_stack::Result<SurfaceMet, MainCatalogError> {
+    let zpd_times = read_zpd_times(interferograms, options)
+        .change_context_lazy(|| MainCatalogError::Catalog)?;
+    load_met_for_zpd_times(met_source_file, &zpd_times, options)
+}
+
+fn load_met_for_zpd_times(
+    met_source_file: &Path,
+    zpd_times: &[DateTime<FixedOffset>],
+    options: &CatalogOptions,
+) -> error_stack::Result<SurfaceMet, MainCatalogError> {
+    let surf_met_config = MetConfig::from_config_json(met_source_file)
+        .change_context_lazy(|| MainCatalogError::Met(met_source_file.to_path_buf()))?;
+    let entries = load_met(
+        zpd_times,
+        &surf_met_config,
+        options.site_id,
+        options.detectors,
+    )
+    .change_context_lazy(|| MainCatalogError::Met(met_source_file.to_path_buf()))?;
+    Ok(SurfaceMet {
+        source_file: met_source_file.to_path_buf(),
+        entries,
+        max_extrapolation: surf_met_config.max_extrapolation(),
+    })
+}
+
+/// Create the catalog entries for a set of interferograms from already loaded coordinates and met.
+///
+/// This is the last step of [`make_catalog_entries`]; see it for the meaning of the inputs and the possible
+/// errors (other than those from loading the coordinates and met). `met` should be loaded for the same
+/// interferograms with [`load_met_for_igrams`].
+pub fn build_entries<P: AsRef<Path>>(
+    coords: &CoordinateSource,
+    met: &[SurfaceMet],
+    interferograms: &[P],
+    options: &CatalogOptions,
+) -> error_stack::Result<CatalogEntries, MainCatalogError> {
+    let zpd_times = read_zpd_times(interferograms, options)
+        .change_context_lazy(|| MainCatalogError::Catalog)?;
+    build_entries_for_zpd_times(coords, met, interferograms, zpd_times, options, None)
+}
+
+fn build_entries_for_zpd_times<P: AsRef<Path>>(
+    coords: &CoordinateSource,
+    met: &[SurfaceMet],
     interferograms: &[P],
-    keep_if_missing_met: bool,
-) -> error_stack::Result<Vec<OpusCatalogueEntry>, MainCatalogError> {
-    let coords = CoordinateSource::load_file(coordinate_file)
-        .change_context_lazy(|| MainCatalogError::Coordinates)?;
-    let surf_met_source = MetSource::from_config_json(surface_met_source_file)
-        .change_context_lazy(|| MainCatalogError::Met(surface_met_source_file.to_path_buf()))?;
-    let met = load_met(interferograms, surf_met_source)
-        .change_context_lazy(|| MainCatalogError::Met(surface_met_source_file.to_path_buf()))?;
-
-    let mut run_num = 1;
-    let catalog_entries: Vec<i2s::OpusCatalogueEntry> = interferograms
+    zpd_times: Vec<DateTime<FixedOffset>>,
+    options: &CatalogOptions,
+    mut progress: Option<&mut dyn FnMut(usize, usize)>,
+) -> error_stack::Result<CatalogEntries, MainCatalogError> {
+    check_zpd_dates(&zpd_times, options.multi_date_check)?;
+    let n_input = interferograms.len();
+    let (interferograms, _, mut skipped) =
+        remove_duplicate_igrams(interferograms, zpd_times, options.duplicate_check)?;
+
+    let n_gzipped = interferograms.iter().filter(|igm| is_gzipped(igm)).count();
+    if n_gzipped > 0 {
+        log::warn!("{n_gzipped} interferograms are gzipped. The catalog will list their .gz names, which I2S cannot read, so they must be decompressed (and the catalog paths updated) before running I2S.");
+    }
+
+    let mut run_numbers = RunNumbers::new(options);
+    let n_to_catalog = interferograms.len();
+    let cataloged: Vec<(PathBuf, (i2s::OpusCatalogueEntry, MetFlags))> = interferograms
         .into_iter()
-        .filter_map(|igm| {
+        .enumerate()
+        .inspect(|(i, _)| {
+            if let Some(cb) = progress.as_mut() {
+                cb(*i, n_to_catalog);
+            }
+        })
+        .filter_map(|(_, igm)| {
             // Three cases. (1) Successfully made a catalog entry, add it to the list. (2) Should skip this entry,
-            // log that and do not add it to the list. (3) Other error, put it in the list so that try_collect() can
-            // return that error at the end.
-            match create_catalog_entry_for_igram(
-                igm.as_ref(),
-                run_num,
-                &coords,
-                &met,
-                keep_if_missing_met,
-            ) {
+            // log that, record why, and do not add it to the list. (3) Other error, put it in the list so that
+            // try_collect() can return that error at the end.
+            let run_num = match run_numbers.current() {
+                Ok(n) => n,
+                Err(e) => return Some(Err(e)),
+            };
+            match create_catalog_entry_for_igram(igm, run_num, coords, met, options) {
                 Ok(entry) => {
-                    // Only advance the run number if we successfully added the interferogram. We're assuming that there's
-                    // forward and reverse scans, so each interferogram should have two runs.
-                    run_num += 2;
-                    Some(Ok(entry))
+                    // Only advance the run number if we successfully added the interferogram. By default we assume that
+                    // there's forward and reverse scans, so each interferogram should have two runs.
+                    run_numbers.advance();
+                    Some(Ok((igm.to_path_buf(), entry)))
                 }
                 Err(e) => {
                     if let CatalogError::SkippingIgram(igm, reason) = e.current_context() {
-                        log::warn!("Skipping {} because {}", igm.display(), reason);
+                        if matches!(
+                            reason,
+                            IgramSkipReason::OutsideSolarWindow | IgramSkipReason::HighSza
+                        ) {
+                            // This is filtering the user asked for, so it does not need a warning
+                            debug!("Skipping {} because {}", igm.display(), reason);
+                        } else {
+                            log::warn!("Skipping {} because {}", igm.display(), reason);
+                        }
+                        skipped.push((igm.to_path_buf(), *reason));
                         None
                     } else {
                         Some(Err(e))
@@ -81,8 +202,624 @@ pub fn make_catalog_entries<P: AsRef<Path>>(
         })
         .try_collect()
         .change_context_lazy(|| MainCatalogError::Catalog)?;
+    if let Some(cb) = progress.as_mut() {
+        cb(n_to_catalog, n_to_catalog);
+    }
+    let n_outside_window = skipped
+        .iter()
+        .filter(|(_, r)| *r == IgramSkipReason::OutsideSolarWindow)
+        .count();
+    if let Some(window) = options.solar_window {
+        info!(
+            "Excluded {n_outside_window} interferograms more than {window} h from local solar noon"
+        );
+    }
+    if let Some(max_sza) = options.max_sza {
+        let n_high_sza = skipped
+            .iter()
+            .filter(|(_, r)| *r == IgramSkipReason::HighSza)
+            .count();
+        info!("Excluded {n_high_sza} interferograms with a solar zenith angle above {max_sza} deg");
+    }
+    let (igram_paths, entries_and_flags): (Vec<_>, Vec<_>) = cataloged.into_iter().unzip();
+    let (entries, met_flags) = entries_and_flags.into_iter().unzip();
+
+    Ok(CatalogEntries {
+        entries,
+        igram_paths,
+        met_flags,
+        n_input,
+        skipped,
+        next_run_number: run_numbers.next,
+    })
+}
+
+/// Assigns the run numbers of catalog entries, see [`CatalogOptions::first_run_number`],
+/// [`CatalogOptions::run_step`], and [`CatalogOptions::max_run_number`].
+struct RunNumbers {
+    next: u32,
+    step: u32,
+    max: u32,
+}
+
+impl RunNumbers {
+    fn new(options: &CatalogOptions) -> Self {
+        Self {
+            next: options.first_run_number,
+            step: options.run_step.max(1),
+            max: options.max_run_number,
+        }
+    }
+
+    /// The run number for the next catalog entry. Errors if it is larger than the spectrum
+    /// names have room for, since I2S would then give spectra the wrong (or the same) names.
+    fn current(&self) -> CatalogResult<u32> {
+        if self.next > self.max {
+            return Err(CatalogError::RunNumberTooLarge {
+                run: self.next,
+                max: self.max,
+            }
+            .into());
+        }
+        Ok(self.next)
+    }
+
+    /// Move on to the next run number, once an entry has used the current one.
+    fn advance(&mut self) {
+        self.next = self.next.saturating_add(self.step);
+    }
+}
+
+/// The result of [`make_catalog_entries`]: the catalog entries plus which interferograms were
+/// left out of the catalog and why.
+#[derive(Debug)]
+pub struct CatalogEntries {
+    /// The catalog entries, in the same order as the interferograms were given.
+    pub entries: Vec<OpusCatalogueEntry>,
+    /// The paths to the interferograms for each of `entries`, as they were given.
+    pub igram_paths: Vec<PathBuf>,
+    /// How the surface met values in each of `entries` were obtained.
+    pub met_flags: Vec<MetFlags>,
+    /// The number of interferograms given to [`make_catalog_entries`].
+    pub n_input: usize,
+    /// The interferograms that were not included in the catalog, with the reason for each.
+    pub skipped: Vec<(PathBuf, IgramSkipReason)>,
+    /// The run number that the next catalog entry would have, i.e. the
+    /// [`CatalogOptions::first_run_number`] for a catalog continuing this one.
+    pub next_run_number: u32,
+}
+
+impl CatalogEntries {
+    /// The number of interferograms skipped for the given reason.
+    pub fn n_skipped_for(&self, reason: IgramSkipReason) -> usize {
+        self.skipped.iter().filter(|(_, r)| *r == reason).count()
+    }
+
+    /// A one-line summary of how many interferograms were cataloged and skipped, e.g.
+    /// "10 interferograms in, 8 cataloged, 2 skipped (2 missing met)".
+    pub fn summary(&self) -> String {
+        let reasons = [
+            (IgramSkipReason::MetUnavailable, "missing met"),
+            (
+                IgramSkipReason::CoordinatesUnavailable,
+                "missing coordinates",
+            ),
+            (IgramSkipReason::Duplicate, "duplicates"),
+            (IgramSkipReason::OutsideSolarWindow, "outside solar window"),
+            (IgramSkipReason::TooFewPoints, "too few points"),
+            (IgramSkipReason::HighSza, "high SZA"),
+        ]
+        .into_iter()
+        .filter_map(|(reason, desc)| {
+            let n = self.n_skipped_for(reason);
+            (n > 0).then(|| format!("{n} {desc}"))
+        })
+        .join(", ");
+
+        let mut summary = format!(
+            "{} interferograms in, {} cataloged, {} skipped",
+            self.n_input,
+            self.entries.len(),
+            self.skipped.len()
+        );
+        if !reasons.is_empty() {
+            summary.push_str(&format!(" ({reasons})"));
+        }
+        summary
+    }
+
+    /// Write a file mapping the interferogram file name of each catalog entry to the absolute path of the
+    /// interferogram, to trace spectra back to the interferograms they came from.
+    ///
+    /// If `path` has a ".json" extension, this is written as a JSON object with the file names as keys.
+    /// Otherwise, it is written as a tab-separated table with "filename" and "path" columns.
+    pub fn write_path_map(&self, path: &Path) -> error_stack::Result<(), MainCatalogError> {
+        let output_err = || MainCatalogError::Output(path.to_path_buf());
+        let mut map = vec![];
+        for igm in self.igram_paths.iter() {
+            let name = igm
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let abs_path = std::path::absolute(igm).change_context_lazy(output_err)?;
+            map.push((name, abs_path.to_string_lossy().to_string()));
+        }
+
+        let mut f = std::fs::File::create(path).change_context_lazy(output_err)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            let map: serde_json::Map<String, serde_json::Value> = map
+                .into_iter()
+                .map(|(name, p)| (name, serde_json::Value::String(p)))
+                .collect();
+            serde_json::to_writer_pretty(&mut f, &map).change_context_lazy(output_err)?;
+        } else {
+            writeln!(f, "filename\tpath").change_context_lazy(output_err)?;
+            for (name, p) in map {
+                writeln!(f, "{name}\t{p}").change_context_lazy(output_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a file listing how the surface met in each catalog entry was obtained, so that entries
+    /// with extrapolated or missing met can be identified later.
+    ///
+    /// This is a tab-separated table with "filename" and "met_flags" columns. The flags are three
+    /// characters, for the pressure, temperature, and humidity in that order; see [`MetQuality`] for
+    /// the meaning of each character.
+    pub fn write_met_flags(&self, path: &Path) -> error_stack::Result<(), MainCatalogError> {
+        let output_err = || MainCatalogError::Output(path.to_path_buf());
+        let mut f = std::fs::File::create(path).change_context_lazy(output_err)?;
+        writeln!(f, "filename\tmet_flags").change_context_lazy(output_err)?;
+        for (igm, flags) in self.igram_paths.iter().zip(self.met_flags.iter()) {
+            let name = igm
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            writeln!(f, "{name}\t{flags}").change_context_lazy(output_err)?;
+        }
+        Ok(())
+    }
+}
+
+/// How one surface met value in a catalog entry was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetQuality {
+    /// Interpolated between met data points (flag character "I").
+    Interpolated,
+    /// Taken from the nearest met data point, because the ZPD time was outside the met data
+    /// but within its allowed extrapolation (flag character "H").
+    Held,
+    /// No met data was available, so the fill value was used (flag character "F").
+    Filled,
+}
+
+impl MetQuality {
+    /// The character representing this quality in a met flags file.
+    pub fn flag_char(&self) -> char {
+        match self {
+            MetQuality::Interpolated => 'I',
+            MetQuality::Held => 'H',
+            MetQuality::Filled => 'F',
+        }
+    }
+}
+
+/// How each of the surface met values in a catalog entry was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetFlags {
+    pub pressure: MetQuality,
+    pub temperature: MetQuality,
+    pub humidity: MetQuality,
+}
+
+impl MetFlags {
+    fn all(quality: MetQuality) -> Self {
+        Self {
+            pressure: quality,
+            temperature: quality,
+            humidity: quality,
+        }
+    }
+}
+
+impl Display for MetFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.pressure.flag_char(),
+            self.temperature.flag_char(),
+            self.humidity.flag_char()
+        )
+    }
+}
+
+/// Options for [`make_catalog_entries`]. The default is suitable for most cases.
+#[derive(Debug, Clone, Copy)]
+pub struct CatalogOptions<'a> {
+    /// If `false`, then interferograms for which surface meteorology could not be found are not included in the catalog.
+    /// Setting this to `true` will keep them, with fill values for the met data. In most cases, this should be `false`.
+    pub keep_if_missing_met: bool,
+    /// If `false`, then an interferogram whose header has none of the `instrument_temperature_params` causes an
+    /// error. Setting this to `true` will instead catalog it with the fill value for the instrument temperature
+    /// and log a warning. This is meant for archival data where a few interferograms lost their instrument status block.
+    pub keep_if_missing_tins: bool,
+    /// The value to use in the catalog for any missing values. [`crate::CATALOG_FILL_FLOAT_F32`] is the usual choice.
+    pub fill: f32,
+    /// The site ID of the EM27, if known. Only used by met sources that need it (e.g. [`MetSource::ExtScriptV1`](crate::meteorology::MetSource::ExtScriptV1)).
+    pub site_id: Option<&'a str>,
+    /// The detector set of the EM27, if known. Only used by met sources that need it.
+    pub detectors: Option<DetectorSet>,
+    /// What to do if the interferograms' ZPD times are on more than one date.
+    pub multi_date_check: MultiDateCheck,
+    /// What to do if some of the interferograms appear to be copies of the same scan.
+    pub duplicate_check: DuplicateCheck,
+    /// How much to advance the run number between catalog entries. The default of 2 is for
+    /// interferograms with forward and reverse scans; use 1 for forward-only acquisition.
+    pub run_step: u32,
+    /// The run number of the first catalog entry. The default is 1; use the
+    /// [`CatalogEntries::next_run_number`] of a previous catalog to continue its run numbers,
+    /// e.g. so that per-day catalogs can be concatenated without the run numbers colliding.
+    pub first_run_number: u32,
+    /// The largest run number the spectrum names have room for, i.e. all 9's with as many digits as there are
+    /// R's in the I2S spectrum name template. Cataloging fails if an entry would need a larger run number. The
+    /// default is [`DEFAULT_MAX_RUN_NUMBER`], for the usual "RRRR"; see [`max_run_number_for_digits`].
+    pub max_run_number: u32,
+    /// Which interferogram header parameters to read the instrument's interior pressure and humidity from.
+    pub instrument_met_params: InstrumentMetParams<'a>,
+    /// The parameters in the instrument status block of the interferogram headers to read the instrument
+    /// temperature from, tried in order. The default is just "TSC"; some OPUS versions use other names.
+    pub instrument_temperature_params: &'a [String],
+    /// The UTC offset to assume for interferograms whose header time does not include a GMT offset.
+    /// If `None` (the default), such interferograms cause an error, since guessing the offset wrong
+    /// would silently give the wrong ZPD times.
+    pub default_utc_offset: Option<FixedOffset>,
+    /// If given, the interferogram header times are interpreted as being in this UTC offset, ignoring the GMT
+    /// offset in the headers (and `default_utc_offset`). This is for interferograms whose headers have the
+    /// wrong offset, e.g. because the acquisition computer's time zone was set incorrectly.
+    pub utc_offset_override: Option<FixedOffset>,
+    /// If given, only interferograms with ZPD times within this many hours of local solar noon (as
+    /// calculated by [`hours_from_solar_noon`]) are cataloged. The default of `None` keeps all of them.
+    pub solar_window: Option<f64>,
+    /// If given, only interferograms with a solar zenith angle (as calculated by [`solar_zenith_angle`]
+    /// at their ZPD time and coordinates) of at most this many degrees are cataloged. The default of
+    /// `None` keeps all of them.
+    pub max_sza: Option<f64>,
+    /// Interferograms whose primary channel has fewer than this many data points (the "NPT" header
+    /// parameter) are skipped. The default of 1 only skips interferograms with no data points at all,
+    /// which a truncated acquisition can write.
+    pub min_points: u32,
+}
+
+/// The default for [`CatalogOptions::max_run_number`], for the four R's in the usual TCCON spectrum names.
+pub const DEFAULT_MAX_RUN_NUMBER: u32 = 9999;
+
+/// The largest run number that fits in a spectrum name with `digits` R's (e.g. 9999 for 4), see
+/// [`CatalogOptions::max_run_number`].
+pub fn max_run_number_for_digits(digits: u32) -> u32 {
+    10_u32
+        .checked_pow(digits)
+        .map(|n| n - 1)
+        .unwrap_or(u32::MAX)
+}
+
+/// The default header parameters to read the instrument temperature from, see [`CatalogOptions`].
+pub static DEFAULT_INSTRUMENT_TEMPERATURE_PARAMS: Lazy<Vec<String>> =
+    Lazy::new(|| vec!["TSC".to_string()]);
+
+impl<'a> Default for CatalogOptions<'a> {
+    fn default() -> Self {
+        Self {
+            keep_if_missing_met: false,
+            keep_if_missing_tins: false,
+            fill: crate::CATALOG_FILL_FLOAT_F32,
+            site_id: None,
+            detectors: None,
+            multi_date_check: MultiDateCheck::default(),
+            duplicate_check: DuplicateCheck::default(),
+            run_step: 2,
+            first_run_number: 1,
+            max_run_number: DEFAULT_MAX_RUN_NUMBER,
+            instrument_met_params: InstrumentMetParams::default(),
+            instrument_temperature_params: &DEFAULT_INSTRUMENT_TEMPERATURE_PARAMS,
+            default_utc_offset: None,
+            utc_offset_override: None,
+            solar_window: None,
+            max_sza: None,
+            min_points: 1,
+        }
+    }
+}
+
+/// Parameters in the instrument status block of the interferogram headers that hold
+/// the pressure and humidity inside the instrument.
+///
+/// Most EM27s only record their instrument temperature, but some newer units with an
+/// onboard pressure/humidity sensor also record the interior pressure (in hPa) and relative
+/// humidity (in percent). When the parameter named here is present in an interferogram's
+/// header, its value is used for the instrument pressure or humidity in the catalog.
+/// Otherwise, the surface pressure or humidity is used, as for EM27s without these sensors.
+/// Setting a parameter to `None` always uses the surface value.
+///
+/// By default, the "PRS" (pressure) and "HUM" (humidity) parameters are checked.
+#[derive(Debug, Clone, Copy)]
+pub struct InstrumentMetParams<'a> {
+    /// The parameter with the instrument pressure in hPa.
+    pub pressure: Option<&'a str>,
+    /// The parameter with the instrument relative humidity in percent.
+    pub humidity: Option<&'a str>,
+}
+
+impl<'a> InstrumentMetParams<'a> {
+    /// Do not read instrument pressure or humidity from the interferogram headers.
+    pub fn none() -> Self {
+        Self {
+            pressure: None,
+            humidity: None,
+        }
+    }
+}
 
-    Ok(catalog_entries)
+impl<'a> Default for InstrumentMetParams<'a> {
+    fn default() -> Self {
+        Self {
+            pressure: Some("PRS"),
+            humidity: Some("HUM"),
+        }
+    }
+}
+
+/// How to handle interferograms with ZPD times on more than one date when making a catalog.
+///
+/// Catalogs are normally made for one day of interferograms, and each catalog entry uses the
+/// date of its interferogram's ZPD time (in the time zone of the interferogram), so a catalog with
+/// more than one date usually means that interferograms from another day were included by mistake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MultiDateCheck {
+    /// Do not check the dates.
+    Ignore,
+    /// Log a warning listing the dates found.
+    #[default]
+    Warn,
+    /// Return an error listing the dates found.
+    Error,
+}
+
+/// Check that the ZPD times are all on one date, handling more than one date as `check` specifies.
+fn check_zpd_dates(
+    zpd_times: &[DateTime<FixedOffset>],
+    check: MultiDateCheck,
+) -> error_stack::Result<(), MainCatalogError> {
+    if check == MultiDateCheck::Ignore {
+        return Ok(());
+    }
+
+    let dates: std::collections::BTreeSet<NaiveDate> =
+        zpd_times.iter().map(|t| t.date_naive()).collect();
+    if dates.len() <= 1 {
+        return Ok(());
+    }
+
+    let dates = dates.iter().map(|d| d.to_string()).join(", ");
+    if check == MultiDateCheck::Error {
+        Err(MainCatalogError::MultipleDates(dates).into())
+    } else {
+        log::warn!("Interferograms have ZPD times on more than one date: {dates}");
+        Ok(())
+    }
+}
+
+/// How to handle interferograms that appear to be copies of the same scan when making a catalog.
+///
+/// This usually happens when a glob pattern matches both an interferogram and a renamed copy of it.
+/// Including both would produce duplicate spectra, so by default the copies are left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateCheck {
+    /// Do not check for duplicates; every interferogram gets a catalog entry.
+    Ignore,
+    /// Log a warning listing the duplicates and only keep the first of each set (in the order given).
+    #[default]
+    Warn,
+    /// Return an error listing the duplicates.
+    Error,
+}
+
+/// The maximum difference in ZPD time for two interferograms to be considered copies of the same scan.
+const DUPLICATE_ZPD_TOLERANCE_MS: i64 = 500;
+
+/// Interferograms and their ZPD times left after removing duplicates, plus the removed interferograms.
+type DedupedIgrams<'a> = (
+    Vec<&'a Path>,
+    Vec<DateTime<FixedOffset>>,
+    Vec<(PathBuf, IgramSkipReason)>,
+);
+
+/// Check for interferograms that are copies of the same scan, handling them as `check` specifies.
+///
+/// Returns the interferograms and ZPD times to make catalog entries for, in the original order,
+/// and the interferograms that were left out.
+fn remove_duplicate_igrams<P: AsRef<Path>>(
+    interferograms: &[P],
+    zpd_times: Vec<DateTime<FixedOffset>>,
+    check: DuplicateCheck,
+) -> error_stack::Result<DedupedIgrams<'_>, MainCatalogError> {
+    let igram_paths = interferograms.iter().map(|p| p.as_ref()).collect_vec();
+    if check == DuplicateCheck::Ignore {
+        return Ok((igram_paths, zpd_times, vec![]));
+    }
+
+    let file_sizes = igram_paths
+        .iter()
+        .map(|p| std::fs::metadata(p).ok().map(|m| m.len()))
+        .collect_vec();
+    let groups = find_duplicate_groups(&zpd_times, &file_sizes);
+    if groups.is_empty() {
+        return Ok((igram_paths, zpd_times, vec![]));
+    }
+
+    let description = groups
+        .iter()
+        .map(|g| {
+            g.iter()
+                .map(|&i| igram_paths[i].display().to_string())
+                .join(" = ")
+        })
+        .join("; ");
+    if check == DuplicateCheck::Error {
+        return Err(MainCatalogError::DuplicateInterferograms(description).into());
+    }
+    log::warn!(
+        "Some interferograms appear to be copies of the same scan, only the first of each will be cataloged: {description}"
+    );
+
+    let skip: HashSet<usize> = groups.iter().flat_map(|g| g[1..].iter().copied()).collect();
+    let skipped = skip
+        .iter()
+        .sorted()
+        .map(|&i| (igram_paths[i].to_path_buf(), IgramSkipReason::Duplicate))
+        .collect_vec();
+    let (igram_paths, zpd_times) = igram_paths
+        .into_iter()
+        .zip(zpd_times)
+        .enumerate()
+        .filter(|(i, _)| !skip.contains(i))
+        .map(|(_, pair)| pair)
+        .unzip();
+    Ok((igram_paths, zpd_times, skipped))
+}
+
+/// Find sets of interferograms that are copies of the same scan, i.e. that have ZPD times within
+/// [`DUPLICATE_ZPD_TOLERANCE_MS`] of each other and the same file size. (A renamed copy of a scan
+/// has the same size, while two different scans cannot have the same ZPD time.) Each set is given
+/// as indices into `zpd_times` in increasing order, and only sets with two or more interferograms
+/// are returned.
+fn find_duplicate_groups(
+    zpd_times: &[DateTime<FixedOffset>],
+    file_sizes: &[Option<u64>],
+) -> Vec<Vec<usize>> {
+    let tolerance = chrono::Duration::milliseconds(DUPLICATE_ZPD_TOLERANCE_MS);
+    let order = (0..zpd_times.len())
+        .sorted_by_key(|&i| zpd_times[i])
+        .collect_vec();
+
+    let mut assigned = vec![false; zpd_times.len()];
+    let mut groups = vec![];
+    for (k, &i) in order.iter().enumerate() {
+        if assigned[i] {
+            continue;
+        }
+        let mut group = vec![i];
+        for &j in order[k + 1..].iter() {
+            if zpd_times[j] - zpd_times[i] > tolerance {
+                break;
+            }
+            if !assigned[j] && file_sizes[i].is_some() && file_sizes[j] == file_sizes[i] {
+                assigned[j] = true;
+                group.push(j);
+            }
+        }
+        if group.len() > 1 {
+            group.sort();
+            groups.push(group);
+        }
+    }
+    groups.sort();
+    groups
+}
+
+/// A summary of how well the surface met sources cover a set of interferograms, see [`check_met_coverage`].
+#[derive(Debug, Clone)]
+pub struct CatalogCoverage {
+    /// The number of interferograms checked
+    pub n_igrams: usize,
+    /// The first and last interferogram ZPD times, `None` if no interferograms were given
+    pub igram_time_span: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    /// The number of interferograms with met available from at least one source
+    pub n_covered: usize,
+    /// The coverage for each met source, in the order the sources were given
+    pub met: Vec<MetCoverage>,
+}
+
+/// How well one surface met source covers a set of interferograms.
+#[derive(Debug, Clone)]
+pub struct MetCoverage {
+    /// The met source file
+    pub source_file: PathBuf,
+    /// The first and last times of the met data, `None` if the source had no data
+    pub met_time_span: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    /// The number of interferograms for which this source has met data (including allowed extrapolation)
+    pub n_inside: usize,
+    /// The number of interferograms for which this source does not have met data
+    pub n_outside: usize,
+}
+
+/// Check how many interferograms have surface met data available, without creating any catalog entries.
+///
+/// This loads the coordinates and met the same way as [`make_catalog_entries`], so any errors that would
+/// occur while loading them when making the catalog will occur here. An interferogram counts as covered
+/// by a met source if its ZPD time is within the time span of the met data, or no further than the source's
+/// allowed extrapolation from it.
+///
+/// # Inputs
+/// See [`make_catalog_entries`].
+///
+/// # Errors
+/// - If the coordinates or surface meteorology could not be loaded, due to incorrect format or an I/O failure.
+/// - If the ZPD time could not be calculated for any interferogram.
+pub fn check_met_coverage<P: AsRef<Path>>(
+    coordinate_file: &Path,
+    surface_met_source_files: &[PathBuf],
+    interferograms: &[P],
+    options: &CatalogOptions,
+) -> error_stack::Result<CatalogCoverage, MainCatalogError> {
+    load_coordinates(coordinate_file)?;
+    let zpd_times = read_zpd_times(interferograms, options)
+        .change_context_lazy(|| MainCatalogError::Catalog)?;
+
+    let mut covered = vec![false; zpd_times.len()];
+    let mut met_coverage = vec![];
+    for met_file in surface_met_source_files {
+        let met = load_met_for_zpd_times(met_file, &zpd_times, options)?;
+        let met_times = met.entries.iter().map(|m| m.datetime).collect_vec();
+        let max_extrapolation = met.max_extrapolation;
+
+        let mut n_inside = 0;
+        for (zpd_time, is_covered) in zpd_times.iter().zip(covered.iter_mut()) {
+            if met_covers_time(&met_times, *zpd_time, max_extrapolation) {
+                n_inside += 1;
+                *is_covered = true;
+            }
+        }
+
+        met_coverage.push(MetCoverage {
+            source_file: met_file.to_path_buf(),
+            met_time_span: get_igram_time_span(&met_times),
+            n_inside,
+            n_outside: zpd_times.len() - n_inside,
+        });
+    }
+
+    Ok(CatalogCoverage {
+        n_igrams: zpd_times.len(),
+        igram_time_span: get_igram_time_span(&zpd_times),
+        n_covered: covered.into_iter().filter(|c| *c).count(),
+        met: met_coverage,
+    })
+}
+
+/// Whether `zpd_time` is within the span of `met_times` or no more than `max_extrapolation` outside it.
+fn met_covers_time(
+    met_times: &[DateTime<FixedOffset>],
+    zpd_time: DateTime<FixedOffset>,
+    max_extrapolation: chrono::Duration,
+) -> bool {
+    let in_span = get_igram_time_span(met_times)
+        .is_some_and(|(first, last)| first <= zpd_time && zpd_time <= last);
+    in_span || find_nearest_met_within(met_times, zpd_time, max_extrapolation).is_some()
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -93,6 +830,14 @@ pub enum MainCatalogError {
     Met(PathBuf),
     #[error("Error creating an EM27 catalog entry or writing the catalog")]
     Catalog,
+    #[error("Error writing the catalog to {}", .0.display())]
+    Output(PathBuf),
+    #[error("Interferograms have ZPD times on more than one date: {0}")]
+    MultipleDates(String),
+    #[error("Problem locating the catalog inputs: {0}")]
+    Inputs(String),
+    #[error("Some interferograms appear to be copies of the same scan: {0}")]
+    DuplicateInterferograms(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -109,8 +854,12 @@ enum CatalogError {
     PathInvalidUnicode(PathBuf),
     #[error("{0}")]
     MissingHeaderParameter(#[from] MissingOpusParameterError),
+    #[error("None of the instrument temperature parameters ({0}) were found in the instrument status block")]
+    MissingInstrumentTemperature(String),
     #[error("Parameter {1} from block {0:?} had an unexpected type")]
     UnexpectedParameterType(BrukerBlockType, String),
+    #[error("Run number {run} is larger than the spectrum names have room for (at most {max}); use more R's in the spectrum name template or a smaller starting run number")]
+    RunNumberTooLarge { run: u32, max: u32 },
     #[error("Parameter {param} from block {block:?} had an unexpected format: {cause}")]
     UnexpectedParameterFormat {
         block: BrukerBlockType,
@@ -119,10 +868,40 @@ enum CatalogError {
     },
 }
 
-#[derive(Debug, thiserror::Error)]
-enum IgramSkipReason {
+/// Met data loaded from one source, along with the options from its configuration
+/// needed to match it up with interferograms. See [`load_met_for_igrams`].
+#[derive(Debug)]
+pub struct SurfaceMet {
+    /// The met source file the data was loaded from
+    pub source_file: PathBuf,
+    /// The met data, in the order the met source provided it
+    pub entries: Vec<MetEntry>,
+    /// The maximum time outside the span of the met data that an interferogram may use the nearest entry
+    pub max_extrapolation: chrono::Duration,
+}
+
+/// Why an interferogram was left out of a catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum IgramSkipReason {
+    /// None of the surface met sources had data at (or close enough to) the ZPD time.
     #[error("surface met data could not be interpolated to the ZPD time")]
     MetUnavailable,
+    /// The coordinates were not known at the ZPD time (e.g. it was outside a GPS track).
+    #[error("the EM27 coordinates are not known at the ZPD time")]
+    CoordinatesUnavailable,
+    /// The interferogram was a copy of another one in the catalog, see [`DuplicateCheck`].
+    #[error("it is a copy of another interferogram in the catalog")]
+    Duplicate,
+    /// The ZPD time was farther from local solar noon than [`CatalogOptions::solar_window`] allows.
+    #[error("its ZPD time is outside the window around local solar noon")]
+    OutsideSolarWindow,
+    /// The interferogram had fewer data points than [`CatalogOptions::min_points`], e.g. because
+    /// the acquisition was cut short.
+    #[error("it has too few data points")]
+    TooFewPoints,
+    /// The solar zenith angle at the ZPD time was greater than [`CatalogOptions::max_sza`].
+    #[error("the solar zenith angle at its ZPD time is too high")]
+    HighSza,
 }
 
 /// Create a catalog entry for one interferogram
@@ -132,48 +911,101 @@ enum IgramSkipReason {
 /// - `run`: an index for the interferogram, usually a 1-based index for the interferogram in the list
 ///   of interferograms for the whole catalog.
 /// - `coords`: a source of latitude, longitude, and altitude data for this day.
-/// - `met`: a slice of meteorology data entries for this day, to interpolate to the interferogram times.
-/// - `keep_if_missing_met`: if `false`, then interferograms for which surface meteorology could not be found return an error.
-///   Setting this to `true` return an entry with fill values for the met data. In most cases, this should be `false`.
+/// - `met`: a slice of meteorology data sources for this day, to interpolate to the interferogram times. These are tried
+///   in order, and the first one that has data at the ZPD time (including allowed extrapolation) is used.
+/// - `options`: the catalog options. Of these, `keep_if_missing_met`, `keep_if_missing_tins`, `fill`, `instrument_met_params`,
+///   `instrument_temperature_params`, `default_utc_offset`, `utc_offset_override`, `solar_window`, `max_sza`, and `min_points` are used; see
+///   [`CatalogOptions`] for their meanings.
 ///
 /// # Errors
 /// - If reading the interferogram header fails.
 /// - If calculating the ZPD time from the header fails, if e.g. the needed parameters in the header are missing, in an unexpected
 ///   format, or are not a valid value (such as a UTC offset that is too large).
-/// - If the instrument temperature could not be found in the header.
+/// - If none of the `instrument_temperature_params` are in the header and `keep_if_missing_tins` is `false`, or if the
+///   instrument temperature, pressure, or humidity are not numbers.
 /// - If a base filename cannot be determined from the `igram` path, or if it cannot be converted to valid unicode.
+/// - If the coordinates are not known at the ZPD time (e.g. it is outside the time span of a GPS track).
+/// - If the interferogram has fewer than `min_points` data points, or its number of points is not an integer.
 /// - If the met data cannot be interpolated to the interferogram ZPD time (i.e. the ZPD time is outside the time bounds of the
-///   available met data) and `keep_if_missing` is `false`.
+///   available met data by more than the allowed extrapolation for every source) and `keep_if_missing` is `false`.
 /// - If the date in the interferogram header is not a valid date.
 /// - If the latitude is outside -90 to 90 or the longitude is outside -180 to 180.
 fn create_catalog_entry_for_igram(
     igram: &Path,
     run: u32,
     coords: &CoordinateSource,
-    met: &[MetEntry],
-    keep_if_missing_met: bool,
-) -> CatalogResult<i2s::OpusCatalogueEntry> {
-    let igram_header = opus::IgramHeader::read_full_igram_header(igram)
+    met: &[SurfaceMet],
+    options: &CatalogOptions,
+) -> CatalogResult<(i2s::OpusCatalogueEntry, MetFlags)> {
+    let CatalogOptions {
+        keep_if_missing_met,
+        keep_if_missing_tins,
+        fill,
+        instrument_met_params,
+        instrument_temperature_params,
+        default_utc_offset,
+        utc_offset_override,
+        solar_window,
+        max_sza,
+        min_points,
+        ..
+    } = *options;
+
+    let igram_header = read_igram_header(igram)
         .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?;
-    let zpd_time = get_zpd_time(&igram_header)
+    check_min_points(igram, get_primary_npt(&igram_header)?, min_points)?;
+    let zpd_time = get_zpd_time(&igram_header, default_utc_offset, utc_offset_override)
         .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?;
 
-    let (lat, lon, alt) = coords.get_coords_for_datetime(zpd_time);
+    let (lat, lon, alt) = coords.get_coords_for_datetime(zpd_time).ok_or_else(|| {
+        CatalogError::SkippingIgram(igram.to_path_buf(), IgramSkipReason::CoordinatesUnavailable)
+    })?;
+
+    if let Some(window) = solar_window {
+        let from_noon = hours_from_solar_noon(zpd_time, lon);
+        if from_noon.abs() > window {
+            return Err(CatalogError::SkippingIgram(
+                igram.to_path_buf(),
+                IgramSkipReason::OutsideSolarWindow,
+            )
+            .into());
+        }
+    }
 
-    // EM27s only seem to record their instrument temperature, not humidity or pressure.
-    // The latter two must be assumed to match exterior conditions. This does mean that if
+    if let Some(max_sza) = max_sza {
+        let sza = solar_zenith_angle(zpd_time, lat, lon);
+        if sza > max_sza {
+            debug!(
+                "{} has a solar zenith angle of {sza:.1} deg",
+                igram.display()
+            );
+            return Err(
+                CatalogError::SkippingIgram(igram.to_path_buf(), IgramSkipReason::HighSza).into(),
+            );
+        }
+    }
+
+    // Most EM27s only record their instrument temperature, not humidity or pressure.
+    // For those, the latter two must be assumed to match exterior conditions. This does mean that if
     // a pressure correction is applied from the sunrun.dat file, it won't be applied to the
     // pins header value, so TODO: adjust pins if pcorr in sunrun.dat is not 0.
-    let tins: f64 = igram_header
-        .get_value(BrukerBlockType::InstrumentStatus, "TSC")
-        .map_err(|e| CatalogError::from(e))?
-        .as_float()
-        .map_err(|_| {
-            CatalogError::UnexpectedParameterType(
-                BrukerBlockType::InstrumentStatus,
-                "TSC".to_string(),
-            )
-        })?;
+    let tins = match get_instrument_temperature(&igram_header, instrument_temperature_params) {
+        Ok(tins) => tins,
+        Err(e)
+            if keep_if_missing_tins
+                && matches!(
+                    e.current_context(),
+                    CatalogError::MissingInstrumentTemperature(_)
+                ) =>
+        {
+            log::warn!(
+                "Instrument temperature missing from {}, using fill value",
+                igram.display()
+            );
+            fill as f64
+        }
+        Err(e) => return Err(e),
+    };
 
     let igram_name = igram
         .file_name()
@@ -182,32 +1014,247 @@ fn create_catalog_entry_for_igram(
         .ok_or_else(|| CatalogError::PathInvalidUnicode(igram.to_path_buf()))?
         .to_string();
 
-    // Interpolate met values to the interferograms
+    // Interpolate met values to the interferograms, taking the first source that covers this ZPD time.
+    let mut met_values = None;
+    for met_source in met {
+        met_values = interp_met_to_zpd(met_source, igram, zpd_time, fill as f64)?;
+        if met_values.is_some() {
+            debug!(
+                "Using met from {} for {}",
+                met_source.source_file.display(),
+                igram.display()
+            );
+            break;
+        }
+    }
+
+    let ((met_pres, met_temp, met_rh), met_flags) = if let Some(values) = met_values {
+        values
+    } else if keep_if_missing_met {
+        (
+            (fill as f64, fill as f64, fill as f64),
+            MetFlags::all(MetQuality::Filled),
+        )
+    } else {
+        return Err(CatalogError::SkippingIgram(
+            igram.to_path_buf(),
+            IgramSkipReason::MetUnavailable,
+        )
+        .into());
+    };
+
+    let pins = get_optional_instrument_value(&igram_header, instrument_met_params.pressure)?
+        .unwrap_or(met_pres);
+    let hins = get_optional_instrument_value(&igram_header, instrument_met_params.humidity)?
+        .unwrap_or(met_rh);
+
+    // Finalize just checks that the required year, month, day, run were present, so that shouldn't error.
+    // The other setters might though.
+    let entry = i2s::OpusCatalogueEntry::build(igram_name)
+        .with_time(zpd_time.year(), zpd_time.month(), zpd_time.day(), run)
+        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?
+        .with_coordinates(lat as f32, lon as f32, alt as f32)
+        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?
+        .with_instrument(tins as f32, pins as f32, hins as f32)
+        .with_outside_met(met_temp as f32, met_pres as f32, met_rh as f32)
+        .finalize(fill)
+        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?;
+
+    Ok((entry, met_flags))
+}
+
+/// Calculate the approximate number of hours between `time` and local solar noon at `longitude`
+/// (in degrees east).
+///
+/// This uses the apparent solar time, i.e. UTC shifted by the longitude and corrected with an
+/// approximate equation of time, which is accurate to within a minute or so. The result is between
+/// -12 and +12, negative before solar noon and positive after.
+pub fn hours_from_solar_noon(time: DateTime<FixedOffset>, longitude: f64) -> f64 {
+    let utc = time.naive_utc();
+    let utc_hours = utc.num_seconds_from_midnight() as f64 / 3600.0;
+
+    let b = 2.0 * std::f64::consts::PI * (utc.ordinal() as f64 - 81.0) / 364.0;
+    let eot_minutes = 9.87 * (2.0 * b).sin() - 7.53 * b.cos() - 1.5 * b.sin();
+
+    let solar_hours = utc_hours + longitude / 15.0 + eot_minutes / 60.0;
+    solar_hours.rem_euclid(24.0) - 12.0
+}
+
+/// Calculate the approximate solar zenith angle in degrees at `time`, `latitude` (degrees north),
+/// and `longitude` (degrees east).
+///
+/// The hour angle comes from [`hours_from_solar_noon`] and the solar declination from Cooper's
+/// approximation, so this is accurate to within about a degree, which is plenty for filtering
+/// out low-sun interferograms. It does not account for refraction.
+pub fn solar_zenith_angle(time: DateTime<FixedOffset>, latitude: f64, longitude: f64) -> f64 {
+    let day_of_year = time.naive_utc().ordinal() as f64;
+    let declination =
+        23.45_f64.to_radians() * (2.0 * std::f64::consts::PI * (284.0 + day_of_year) / 365.0).sin();
+    let hour_angle = (15.0 * hours_from_solar_noon(time, longitude)).to_radians();
+    let lat = latitude.to_radians();
+
+    let cos_sza = lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos();
+    cos_sza.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Get a numeric value from the instrument status block of an interferogram header.
+///
+/// Returns `None` if `param` is `None` or the parameter is not in the header.
+fn get_optional_instrument_value(
+    header: &IgramHeader,
+    param: Option<&str>,
+) -> CatalogResult<Option<f64>> {
+    let Some(param) = param else {
+        return Ok(None);
+    };
+    let Ok(value) = header.get_value(BrukerBlockType::InstrumentStatus, param) else {
+        return Ok(None);
+    };
+    let value = value.as_float().map_err(|_| {
+        CatalogError::UnexpectedParameterType(BrukerBlockType::InstrumentStatus, param.to_string())
+    })?;
+    Ok(Some(value))
+}
+
+/// Check that an interferogram with `npt` data points in its primary channel has at least `min_points`.
+///
+/// Returns a [`IgramSkipReason::TooFewPoints`] skip error if not. An interferogram without an NPT
+/// value cannot be checked, so it is allowed.
+fn check_min_points(igram: &Path, npt: Option<i64>, min_points: u32) -> CatalogResult<()> {
+    let Some(npt) = npt else {
+        debug!(
+            "{} has no NPT parameter for its primary channel, cannot check its number of points",
+            igram.display()
+        );
+        return Ok(());
+    };
+
+    if npt < i64::from(min_points) {
+        debug!(
+            "{} has {npt} data points, fewer than the minimum of {min_points}",
+            igram.display()
+        );
+        return Err(CatalogError::SkippingIgram(
+            igram.to_path_buf(),
+            IgramSkipReason::TooFewPoints,
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Read the number of data points in the primary channel from `header`, or `None` if it is not present.
+fn get_primary_npt(header: &IgramHeader) -> CatalogResult<Option<i64>> {
+    match header.get_value(BrukerBlockType::IgramPrimaryStatus, "NPT") {
+        Ok(BrukerParValue::Integer(v)) => Ok(Some(i64::from(*v))),
+        Ok(_) => Err(CatalogError::UnexpectedParameterType(
+            BrukerBlockType::IgramPrimaryStatus,
+            "NPT".to_string(),
+        )
+        .into()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Read the instrument temperature from the first of `params` present in the instrument status block of `header`.
+///
+/// Returns an error listing `params` if none of them are present.
+fn get_instrument_temperature(header: &IgramHeader, params: &[String]) -> CatalogResult<f64> {
+    for param in params {
+        if let Some(value) = get_optional_instrument_value(header, Some(param))? {
+            return Ok(value);
+        }
+        trace!("Instrument temperature parameter {param} not found in header");
+    }
+    Err(CatalogError::MissingInstrumentTemperature(params.join(", ")).into())
+}
+
+/// Surface pressure, temperature, and relative humidity.
+type MetValues = (f64, f64, f64);
+
+/// Interpolate one source of met data to an interferogram's ZPD time.
+///
+/// Returns the pressure, temperature, and relative humidity along with how each was obtained,
+/// or `None` if the ZPD time is outside the span of the met data by more than the source's
+/// allowed extrapolation. Temperature and humidity will be `fill` if the source did not include them.
+fn interp_met_to_zpd(
+    met: &SurfaceMet,
+    igram: &Path,
+    zpd_time: DateTime<FixedOffset>,
+    fill: f64,
+) -> CatalogResult<Option<(MetValues, MetFlags)>> {
     // TODO: these interpolation calls right now assume that an error is an out-of-bounds error, which should get a fill value.
     //  Really we should verify that is the case and log it; other errors should not result in fill values.
     let interpolator = ConstantValueInterp::new(false);
 
-    let met_times = met.iter().map(|m| m.datetime).collect_vec();
-    trace!("met_times[..10] = {:?}", &met_times[..10]);
+    let met_times = met.entries.iter().map(|m| m.datetime).collect_vec();
+    trace!(
+        "met_times[..10] = {:?}",
+        met_times.iter().take(10).collect_vec()
+    );
+
+    let met_pres = met.entries.iter().map(|m| m.pressure).collect_vec();
+    let met_temp = met
+        .entries
+        .iter()
+        .map(|m| m.temperature.unwrap_or(fill))
+        .collect_vec();
+    let met_rh = met
+        .entries
+        .iter()
+        .map(|m| m.humidity.unwrap_or(fill))
+        .collect_vec();
 
-    let met_pres = met.iter().map(|m| m.pressure).collect_vec();
     let met_pres_res =
         interpolator.interp1d_to_time(met_times.as_slice(), met_pres.as_slice(), zpd_time);
-    let met_pres = match met_pres_res {
-        Ok(v) => v,
+    let interp_optional = |values: &[f64], quality: MetQuality| {
+        if quality != MetQuality::Interpolated {
+            return (fill, MetQuality::Filled);
+        }
+        match interpolator.interp1d_to_time(met_times.as_slice(), values, zpd_time) {
+            Ok(v) => (v, MetQuality::Interpolated),
+            Err(_) => (fill, MetQuality::Filled),
+        }
+    };
+
+    let (met_pres, met_temp, met_rh, flags) = match met_pres_res {
+        Ok(p) => {
+            // Temperature and humidity are only present for this interferogram if the met entries
+            // either side of it have them; otherwise the interpolation would mix in fill values.
+            let bracket_flags = bracketing_met_indices(&met_times, zpd_time)
+                .map(|(i, j)| interpolated_met_flags(&met.entries[i], &met.entries[j]))
+                .unwrap_or(MetFlags {
+                    pressure: MetQuality::Interpolated,
+                    temperature: MetQuality::Filled,
+                    humidity: MetQuality::Filled,
+                });
+            let (t, t_flag) = interp_optional(&met_temp, bracket_flags.temperature);
+            let (h, h_flag) = interp_optional(&met_rh, bracket_flags.humidity);
+            let flags = MetFlags {
+                pressure: MetQuality::Interpolated,
+                temperature: t_flag,
+                humidity: h_flag,
+            };
+            (p, t, h, flags)
+        }
         Err(InterpolationError::OutOfDomain {
             left: _,
             right: _,
             out: _,
         }) => {
-            if keep_if_missing_met {
-                CATALOG_FILL_FLOAT_F64
+            if let Some((i, gap)) =
+                find_nearest_met_within(&met_times, zpd_time, met.max_extrapolation)
+            {
+                info!(
+                    "Extrapolating met data to {} by {} s (using met from {})",
+                    igram.display(),
+                    gap.num_seconds(),
+                    met_times[i]
+                );
+                let flags = held_met_flags(&met.entries[i]);
+                (met_pres[i], met_temp[i], met_rh[i], flags)
             } else {
-                return Err(CatalogError::SkippingIgram(
-                    igram.to_path_buf(),
-                    IgramSkipReason::MetUnavailable,
-                )
-                .into());
+                return Ok(None);
             }
         }
         Err(e) => {
@@ -216,54 +1263,99 @@ fn create_catalog_entry_for_igram(
         }
     };
     trace!("Interpolated pressure to ZPD time {zpd_time}: {met_pres}");
-
-    let met_temp = met
-        .iter()
-        .map(|m| m.temperature.unwrap_or(CATALOG_FILL_FLOAT_F64))
-        .collect_vec();
-    let met_temp = interpolator
-        .interp1d_to_time(met_times.as_slice(), met_temp.as_slice(), zpd_time)
-        .unwrap_or(CATALOG_FILL_FLOAT_F64);
     trace!("Interpolated temperature to ZPD time {zpd_time}: {met_temp}");
-
-    let met_rh = met
-        .iter()
-        .map(|m| m.humidity.unwrap_or(CATALOG_FILL_FLOAT_F64))
-        .collect_vec();
-    let met_rh = interpolator
-        .interp1d_to_time(met_times.as_slice(), met_rh.as_slice(), zpd_time)
-        .unwrap_or(CATALOG_FILL_FLOAT_F64);
     trace!("Interpolated RH to ZPD time {zpd_time}: {met_rh}");
 
-    // Finalize just checks that the required year, month, day, run were present, so that shouldn't error.
-    // The other setters might though.
-    let entry = i2s::OpusCatalogueEntry::build(igram_name)
-        .with_time(zpd_time.year(), zpd_time.month(), zpd_time.day(), run)
-        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?
-        .with_coordinates(lat as f32, lon as f32, alt as f32)
-        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?
-        .with_instrument(tins as f32, met_pres as f32, met_rh as f32)
-        .with_outside_met(met_temp as f32, met_pres as f32, met_rh as f32)
-        .finalize(CATALOG_FILL_FLOAT_F32)
-        .change_context_lazy(|| CatalogError::EntryCreationError(igram.to_path_buf()))?;
+    Ok(Some(((met_pres, met_temp, met_rh), flags)))
+}
 
-    Ok(entry)
+/// Find the met entries either side of `zpd_time`: the last one at or before it and the first one
+/// at or after it. The met times do not need to be sorted.
+///
+/// Returns `None` if `zpd_time` is outside the span of the met data.
+fn bracketing_met_indices(
+    met_times: &[DateTime<FixedOffset>],
+    zpd_time: DateTime<FixedOffset>,
+) -> Option<(usize, usize)> {
+    let (before, _) = met_times
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| **t <= zpd_time)
+        .max_by_key(|(_, t)| **t)?;
+    let (after, _) = met_times
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| **t >= zpd_time)
+        .min_by_key(|(_, t)| **t)?;
+    Some((before, after))
 }
 
-/// Load the meteorology from the given file.
-fn load_met<P: AsRef<Path>>(igrams: &[P], met_source: MetSource) -> CatalogResult<Vec<MetEntry>> {
-    // First check that all our interferograms have consistent timezones, since some met sources don't
-    // record the time zone for their timestamps.
-    let mut zpd_times = vec![];
-    for igm in igrams {
-        let header = IgramHeader::read_full_igram_header(igm.as_ref())
-            .map_err(|_| CatalogError::EntryCreationError(igm.as_ref().to_path_buf()))?;
-        let dt = get_zpd_time(&header)?;
-        zpd_times.push(dt);
+/// The flags for met interpolated between the entries `before` and `after`. Temperature and humidity
+/// are only interpolated if both entries have them, otherwise they will be fill values.
+fn interpolated_met_flags(before: &MetEntry, after: &MetEntry) -> MetFlags {
+    let interp_or_fill = |present: bool| {
+        if present {
+            MetQuality::Interpolated
+        } else {
+            MetQuality::Filled
+        }
+    };
+    MetFlags {
+        pressure: MetQuality::Interpolated,
+        temperature: interp_or_fill(before.temperature.is_some() && after.temperature.is_some()),
+        humidity: interp_or_fill(before.humidity.is_some() && after.humidity.is_some()),
+    }
+}
+
+/// The flags for met held from the nearest `entry`, for a ZPD time just outside the met data.
+fn held_met_flags(entry: &MetEntry) -> MetFlags {
+    let held_or_filled = |present: bool| {
+        if present {
+            MetQuality::Held
+        } else {
+            MetQuality::Filled
+        }
+    };
+    MetFlags {
+        pressure: MetQuality::Held,
+        temperature: held_or_filled(entry.temperature.is_some()),
+        humidity: held_or_filled(entry.humidity.is_some()),
     }
+}
+
+/// Find the met entry closest in time to `zpd_time`, if it is no more than `max_gap` away.
+///
+/// Returns the index of that entry and the (absolute) time between it and `zpd_time`.
+fn find_nearest_met_within(
+    met_times: &[DateTime<FixedOffset>],
+    zpd_time: DateTime<FixedOffset>,
+    max_gap: chrono::Duration,
+) -> Option<(usize, chrono::Duration)> {
+    met_times
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (i, (zpd_time - *t).abs()))
+        .min_by_key(|(_, gap)| *gap)
+        .filter(|(_, gap)| *gap <= max_gap)
+}
 
-    let met =
-        read_met_file(&met_source, &zpd_times).change_context_lazy(|| CatalogError::MetError)?;
+/// Load the meteorology described by the given met configuration.
+fn load_met(
+    zpd_times: &[DateTime<FixedOffset>],
+    met_config: &MetConfig,
+    site_id: Option<&str>,
+    detectors: Option<DetectorSet>,
+) -> CatalogResult<Vec<MetEntry>> {
+    // The ZPD times are passed in so that the met reader can check that all our interferograms have
+    // consistent timezones, since some met sources don't record the time zone for their timestamps.
+    let met = read_met_file(
+        &met_config.source,
+        zpd_times,
+        site_id,
+        detectors,
+        met_config.timezone,
+    )
+    .change_context_lazy(|| CatalogError::MetError)?;
 
     // For now, I'm using interpolators that don't care if the input is ordered. If they get slow, we can change this.
     // met.sort_by_key(|m| m.datetime);
@@ -271,7 +1363,34 @@ fn load_met<P: AsRef<Path>>(igrams: &[P], met_source: MetSource) -> CatalogResul
     Ok(met)
 }
 
-fn get_zpd_time(header: &IgramHeader) -> error_stack::Result<DateTime<FixedOffset>, CatalogError> {
+/// Get the ZPD times of the given interferograms from their headers.
+fn read_zpd_times<P: AsRef<Path>>(
+    igrams: &[P],
+    options: &CatalogOptions,
+) -> CatalogResult<Vec<DateTime<FixedOffset>>> {
+    let mut zpd_times = vec![];
+    for igm in igrams {
+        let header = read_igram_header(igm.as_ref())
+            .map_err(|_| CatalogError::EntryCreationError(igm.as_ref().to_path_buf()))?;
+        let dt = get_zpd_time(
+            &header,
+            options.default_utc_offset,
+            options.utc_offset_override,
+        )?;
+        zpd_times.push(dt);
+    }
+    Ok(zpd_times)
+}
+
+/// Get the ZPD time of an interferogram from its header, see [`parse_zpd_datetime`] for `default_offset`.
+///
+/// If `override_offset` is given, the header time is interpreted as being in that offset, regardless of
+/// the GMT offset in the header.
+fn get_zpd_time(
+    header: &IgramHeader,
+    default_offset: Option<FixedOffset>,
+    override_offset: Option<FixedOffset>,
+) -> error_stack::Result<DateTime<FixedOffset>, CatalogError> {
     // let header = opus::IgramHeader::read_full_igram_header(igram)
     //     .map_err(|e| ZpdTimeError::from(e))?;
 
@@ -297,55 +1416,122 @@ fn get_zpd_time(header: &IgramHeader) -> error_stack::Result<DateTime<FixedOffse
             )
         })?;
 
-    // The date string is easy to parse: it's dd/mm/yyyy. The time string is more a pain: "HH:MM:SS.fff (GMT+X)" or "-X" if the offset is negative.
+    let Some(offset) = override_offset else {
+        return parse_zpd_datetime(datestr, timestr, default_offset);
+    };
+
+    // Parsing with the override as the default means headers without an offset are already in the right
+    // offset; for those with one, keep the local time and replace the offset.
+    let header_time = parse_zpd_datetime(datestr, timestr, Some(offset))?;
+    replace_offset(header_time, offset)
+}
+
+/// Interpret the local (wall clock) time of `datetime` as being in `offset` instead of its own offset.
+fn replace_offset(
+    datetime: DateTime<FixedOffset>,
+    offset: FixedOffset,
+) -> error_stack::Result<DateTime<FixedOffset>, CatalogError> {
+    if datetime.offset() != &offset {
+        trace!("Replacing the GMT offset of {datetime} with {offset}");
+    }
+    let local = datetime.naive_local();
+    offset.from_local_datetime(&local).single().ok_or_else(|| {
+        CatalogError::UnexpectedParameterFormat {
+            block: BrukerBlockType::IgramPrimaryData,
+            param: "TIM".to_string(),
+            cause: format!("Date/time {local} is invalid or ambiguous for offset {offset}"),
+        }
+        .into()
+    })
+}
+
+/// Date formats for the DAT header parameter that we know of, in the order they are tried,
+/// as (chrono format, human readable format) pairs.
+const IGRAM_DATE_FORMATS: [(&str, &str); 2] =
+    [("%d/%m/%Y", "DD/MM/YYYY"), ("%Y/%m/%d", "YYYY/MM/DD")];
+
+/// Time formats for the start of the TIM header parameter that we know of, in the order they are tried,
+/// as (chrono format, human readable format) pairs.
+const IGRAM_TIME_FORMATS: [(&str, &str); 2] =
+    [("%H:%M:%S%.3f", "HH:MM:SS.fff"), ("%H:%M:%S", "HH:MM:SS")];
+
+/// Parse a string with the first of `formats` that matches it, or return an error message listing the
+/// formats tried.
+fn parse_with_formats<T, F>(value: &str, formats: &[(&str, &str)], parse: F) -> Result<T, String>
+where
+    F: Fn(&str, &str) -> chrono::ParseResult<T>,
+{
+    formats
+        .iter()
+        .find_map(|(fmt, _)| parse(value, fmt).ok())
+        .ok_or_else(|| {
+            let tried = formats
+                .iter()
+                .map(|(_, desc)| format!("'{desc}'"))
+                .join(", ");
+            format!("'{value}' did not match any of the known formats ({tried})")
+        })
+}
+
+/// Construct the ZPD time from the DAT and TIM interferogram header parameters.
+///
+/// If the time string does not include a GMT offset, `default_offset` is used; if that is `None`,
+/// an error is returned.
+fn parse_zpd_datetime(
+    datestr: &str,
+    timestr: &str,
+    default_offset: Option<FixedOffset>,
+) -> error_stack::Result<DateTime<FixedOffset>, CatalogError> {
+    // The date string is usually dd/mm/yyyy, though some older files have yyyy/mm/dd. The time string is more a pain:
+    // "HH:MM:SS.fff (GMT+X)" or "-X" if the offset is negative, and older files may not have the fractional seconds.
     let mut timestr_split = timestr.split_ascii_whitespace();
     let hhmmss_str = timestr_split.next()
         .ok_or_else(|| CatalogError::UnexpectedParameterFormat {
             block: BrukerBlockType::IgramPrimaryData, param: "TIM".to_string(),
             cause: "Expected a time string with at least one group of ASCII whitespace, got no whitespace".to_string()
         })?;
-    let offset_str = timestr_split.next()
-        .ok_or_else(|| CatalogError::UnexpectedParameterFormat {
-            block: BrukerBlockType::IgramPrimaryData, param: "TIM".to_string(),
-            cause: "Expected a time string with at least one group of ASCII whitespace, got no whitespace".to_string()
-        })?;
+    let offset_str = timestr_split.next();
 
-    let date = NaiveDate::parse_from_str(datestr, "%d/%m/%Y").change_context_lazy(|| {
-        CatalogError::UnexpectedParameterFormat {
+    let date = parse_with_formats(datestr, &IGRAM_DATE_FORMATS, NaiveDate::parse_from_str)
+        .map_err(|cause| CatalogError::UnexpectedParameterFormat {
             block: BrukerBlockType::IgramPrimaryData,
             param: "DAT".to_string(),
-            cause: format!("Expected a date string in format DD/MM/YYYY, got '{datestr}'"),
-        }
-    })?;
-    let time = NaiveTime::parse_from_str(hhmmss_str, "%H:%M:%S.%3f").change_context_lazy(|| {
-        CatalogError::UnexpectedParameterFormat {
+            cause,
+        })?;
+    let time = parse_with_formats(hhmmss_str, &IGRAM_TIME_FORMATS, NaiveTime::parse_from_str)
+        .map_err(|cause| CatalogError::UnexpectedParameterFormat {
             block: BrukerBlockType::IgramPrimaryData,
             param: "TIM".to_string(),
-            cause: format!(
-                "Expected a time string starting with 'HH:MM:SS.fff', got '{hhmmss_str}' instead"
-            ),
-        }
-    })?;
+            cause,
+        })?;
 
-    // TODO: check how this works with non-integer hour timezones
-    static OFFSET_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\(GMT([+\-]\d+)\)").unwrap());
-    let offset_hours: i32 = OFFSET_RE.captures(offset_str)
-        .map(|c| c.get(1))
-        .flatten()
-        .ok_or_else(|| CatalogError::UnexpectedParameterFormat {
-            block: BrukerBlockType::IgramPrimaryData, param: "TIM".to_string(),
-            cause: format!("Expected a time string ending with '(GMT+X)' or '(GMT-X)', got '{offset_str}' instead")
-        })?.as_str()
-        .parse()
-        .unwrap(); // should be okay to unwrap, we've constructed our regex to find valid integers
+    let offset = if let Some(offset_str) = offset_str {
+        let offset_secs = parse_gmt_offset_secs(offset_str)
+            .ok_or_else(|| CatalogError::UnexpectedParameterFormat {
+                block: BrukerBlockType::IgramPrimaryData, param: "TIM".to_string(),
+                cause: format!("Expected a time string ending with '(GMT+X)', '(GMT+X:MM)', or '(GMT+X.X)' (or the same with '-'), got '{offset_str}' instead")
+            })?;
 
-    let offset = FixedOffset::east_opt(offset_hours * 3600).ok_or_else(|| {
-        CatalogError::UnexpectedParameterFormat {
+        FixedOffset::east_opt(offset_secs).ok_or_else(|| {
+            CatalogError::UnexpectedParameterFormat {
+                block: BrukerBlockType::IgramPrimaryData,
+                param: "TIM".to_string(),
+                cause: format!(
+                    "GMT offset ({:+.2} h) was out of bounds",
+                    offset_secs as f64 / 3600.0
+                ),
+            }
+        })?
+    } else if let Some(offset) = default_offset {
+        trace!("No GMT offset in time string '{timestr}', assuming {offset}");
+        offset
+    } else {
+        return Err(CatalogError::UnexpectedParameterFormat {
             block: BrukerBlockType::IgramPrimaryData,
             param: "TIM".to_string(),
-            cause: format!("GMT offset ({offset_hours}) was out of bounds"),
-        }
-    })?;
+            cause: format!("Expected a time string ending with a GMT offset, e.g. '(GMT+X)', got '{timestr}' instead. If these interferograms have no offset, specify the offset to assume for them.")
+        }.into());
+    };
 
     // Finally we can construct the darn time!
     Ok(offset
@@ -358,6 +1544,37 @@ fn get_zpd_time(header: &IgramHeader) -> error_stack::Result<DateTime<FixedOffse
         })?)
 }
 
+/// Parse the GMT offset from the end of a Bruker "TIM" parameter into seconds east of UTC.
+///
+/// The offset may be given as whole hours ("(GMT+5)"), hours and minutes ("(GMT+5:30)"), or
+/// fractional hours ("(GMT-9.5)"). Returns `None` if `offset_str` is not in one of these formats.
+fn parse_gmt_offset_secs(offset_str: &str) -> Option<i32> {
+    static OFFSET_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"\(GMT(?<sign>[+\-])(?<hours>\d+)(?::(?<minutes>\d{2})|(?<frac>\.\d+))?\)")
+            .unwrap()
+    });
+    let caps = OFFSET_RE.captures(offset_str)?;
+
+    let sign = if &caps["sign"] == "-" { -1 } else { 1 };
+    let hours: i32 = caps["hours"].parse().ok()?;
+    let secs = if let Some(minutes) = caps.name("minutes") {
+        let minutes: i32 = minutes.as_str().parse().ok()?;
+        if minutes >= 60 {
+            return None;
+        }
+        hours.checked_mul(3600)?.checked_add(minutes * 60)?
+    } else if let Some(frac) = caps.name("frac") {
+        let frac: f64 = frac.as_str().parse().ok()?;
+        hours
+            .checked_mul(3600)?
+            .checked_add((frac * 3600.0).round() as i32)?
+    } else {
+        hours.checked_mul(3600)?
+    };
+
+    Some(sign * secs)
+}
+
 /// An error type for possible failures when getting a common timezone for multiple interferograms.
 /// (e.g. with [`get_common_igram_timezone`]).
 #[derive(Debug, thiserror::Error)]
@@ -398,6 +1615,21 @@ impl Display for IgramTimezoneError {
     }
 }
 
+/// How to choose one timezone for interferograms that were recorded with different
+/// UTC offsets, e.g. because a day's measurements straddle a daylight saving time change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MixedTimezones {
+    /// Return an error, the default.
+    #[default]
+    Error,
+    /// Use the offset shared by the most interferograms. Ties go to the offset of
+    /// the earliest interferogram.
+    Majority,
+    /// Use the offset of the interferogram with the earliest ZPD time.
+    Earliest,
+}
+
 /// Given a list of paths to interferograms, identify the timezone shared by them.
 ///
 /// Errors if:
@@ -408,22 +1640,580 @@ impl Display for IgramTimezoneError {
 pub fn get_common_igram_timezone<P: AsRef<Path>>(
     igrams: &[P],
 ) -> error_stack::Result<FixedOffset, IgramTimezoneError> {
-    let mut timezones = HashSet::new();
+    get_igram_timezone(igrams, MixedTimezones::Error)
+}
+
+/// Given a list of paths to interferograms, identify the timezone to use for them.
+///
+/// This is [`get_common_igram_timezone`], except that if the interferograms have different
+/// timezones and `mixed` is not [`MixedTimezones::Error`], one of them is chosen as `mixed`
+/// specifies and the interferograms with a different timezone are listed in a warning.
+///
+/// Errors if:
+/// - the interferogram header cannot be read,
+/// - the interferogram's time could not be parsed from the header,
+/// - the list of interferograms is empty, or
+/// - different interferograms had different timezones and `mixed` is [`MixedTimezones::Error`].
+pub fn get_igram_timezone<P: AsRef<Path>>(
+    igrams: &[P],
+    mixed: MixedTimezones,
+) -> error_stack::Result<FixedOffset, IgramTimezoneError> {
+    let mut zpd_times = vec![];
     for igm in igrams {
-        let igram_header = opus::IgramHeader::read_full_igram_header(igm.as_ref())
+        let igram_header = read_igram_header(igm.as_ref())
             .change_context_lazy(|| IgramTimezoneError::Error(igm.as_ref().to_owned()))?;
-        let this_tz = get_zpd_time(&igram_header)
-            .map(|t| t.timezone())
+        let zpd_time = get_zpd_time(&igram_header, None, None)
             .change_context_lazy(|| IgramTimezoneError::Error(igm.as_ref().to_owned()))?;
-        timezones.insert(this_tz);
+        zpd_times.push((igm.as_ref(), zpd_time));
     }
+    choose_igram_timezone(&zpd_times, mixed)
+}
 
+/// Choose the timezone for interferograms with the given ZPD times, see [`get_igram_timezone`].
+fn choose_igram_timezone(
+    zpd_times: &[(&Path, DateTime<FixedOffset>)],
+    mixed: MixedTimezones,
+) -> error_stack::Result<FixedOffset, IgramTimezoneError> {
+    let timezones: HashSet<FixedOffset> = zpd_times.iter().map(|(_, t)| t.timezone()).collect();
     if timezones.is_empty() {
-        Err(IgramTimezoneError::NoIgrams.into())
-    } else if timezones.len() > 1 {
-        Err(IgramTimezoneError::Multiple(timezones).into())
-    } else {
-        let tz = timezones.into_iter().next().unwrap();
-        Ok(tz)
+        return Err(IgramTimezoneError::NoIgrams.into());
+    } else if timezones.len() == 1 {
+        return Ok(timezones.into_iter().next().unwrap());
+    }
+
+    let earliest = zpd_times
+        .iter()
+        .min_by_key(|(_, t)| *t)
+        .map(|(_, t)| t.timezone())
+        .expect("zpd_times should not be empty");
+    let tz = match mixed {
+        MixedTimezones::Error => return Err(IgramTimezoneError::Multiple(timezones).into()),
+        MixedTimezones::Earliest => earliest,
+        MixedTimezones::Majority => {
+            let counts = zpd_times.iter().counts_by(|(_, t)| t.timezone());
+            let max_count = counts.values().copied().max().unwrap_or(0);
+            // Ties go to the timezone seen first
+            zpd_times
+                .iter()
+                .sorted_by_key(|(_, t)| *t)
+                .map(|(_, t)| t.timezone())
+                .find(|tz| counts.get(tz) == Some(&max_count))
+                .expect("at least one timezone should have the maximum count")
+        }
+    };
+
+    let others = zpd_times
+        .iter()
+        .filter(|(_, t)| t.timezone() != tz)
+        .map(|(p, t)| format!("{} ({})", p.display(), t.timezone()))
+        .join(", ");
+    warn!("Interferograms have different timezones, using {tz}. These interferograms have a different timezone: {others}");
+    Ok(tz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_igram_timezone() {
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        let zpd_times = [
+            (Path::new("a.0001"), t("2025-03-09T00:30:00-08:00")),
+            (Path::new("a.0002"), t("2025-03-09T01:30:00-08:00")),
+            (Path::new("a.0003"), t("2025-03-09T03:30:00-07:00")),
+            (Path::new("a.0004"), t("2025-03-09T04:30:00-07:00")),
+            (Path::new("a.0005"), t("2025-03-09T05:30:00-07:00")),
+        ];
+        let pst = FixedOffset::west_opt(8 * 3600).unwrap();
+        let pdt = FixedOffset::west_opt(7 * 3600).unwrap();
+
+        let e = choose_igram_timezone(&zpd_times, MixedTimezones::Error).unwrap_err();
+        assert!(matches!(
+            e.current_context(),
+            IgramTimezoneError::Multiple(_)
+        ));
+        assert_eq!(
+            choose_igram_timezone(&zpd_times, MixedTimezones::Majority).unwrap(),
+            pdt
+        );
+        assert_eq!(
+            choose_igram_timezone(&zpd_times, MixedTimezones::Earliest).unwrap(),
+            pst
+        );
+        // A tie goes to the earliest interferogram's offset
+        assert_eq!(
+            choose_igram_timezone(&zpd_times[1..3], MixedTimezones::Majority).unwrap(),
+            pst
+        );
+        assert_eq!(
+            choose_igram_timezone(&zpd_times[2..], MixedTimezones::Error).unwrap(),
+            pdt
+        );
+        assert!(choose_igram_timezone(&[], MixedTimezones::Majority).is_err());
+    }
+
+    #[test]
+    fn test_check_zpd_dates() {
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        let one_day = [
+            t("2025-03-01T06:00:00-07:00"),
+            t("2025-03-01T23:00:00-07:00"),
+        ];
+        let two_days = [
+            t("2025-03-01T23:00:00-07:00"),
+            t("2025-03-02T06:00:00-07:00"),
+        ];
+
+        assert!(check_zpd_dates(&one_day, MultiDateCheck::Error).is_ok());
+        assert!(check_zpd_dates(&two_days, MultiDateCheck::Ignore).is_ok());
+        assert!(check_zpd_dates(&two_days, MultiDateCheck::Warn).is_ok());
+        let e = check_zpd_dates(&two_days, MultiDateCheck::Error).unwrap_err();
+        assert!(matches!(
+            e.current_context(),
+            MainCatalogError::MultipleDates(d) if d == "2025-03-01, 2025-03-02"
+        ));
+    }
+
+    #[test]
+    fn test_find_duplicate_groups() {
+        let t = |s: &str| DateTime::parse_from_rfc3339(s).unwrap();
+        let zpd_times = [
+            t("2025-03-01T09:00:00.000-07:00"),
+            t("2025-03-01T09:01:00.000-07:00"),
+            t("2025-03-01T16:00:00.200Z"),
+            t("2025-03-01T09:01:00.100-07:00"),
+            t("2025-03-01T09:02:00.000-07:00"),
+        ];
+        let sizes = [Some(100), Some(100), Some(100), Some(200), Some(100)];
+        let groups = find_duplicate_groups(&zpd_times, &sizes);
+        assert_eq!(groups, [vec![0, 2]]);
+
+        let sizes = [Some(100), Some(100), None, Some(100), None];
+        let groups = find_duplicate_groups(&zpd_times, &sizes);
+        assert_eq!(groups, [vec![1, 3]]);
+    }
+
+    #[test]
+    fn test_catalog_summary() {
+        let catalog = CatalogEntries {
+            entries: vec![],
+            igram_paths: vec![],
+            met_flags: vec![],
+            n_input: 3,
+            skipped: vec![
+                (PathBuf::from("a.0001"), IgramSkipReason::MetUnavailable),
+                (PathBuf::from("b.0001"), IgramSkipReason::Duplicate),
+                (PathBuf::from("c.0001"), IgramSkipReason::MetUnavailable),
+            ],
+            next_run_number: 1,
+        };
+        assert_eq!(catalog.n_skipped_for(IgramSkipReason::MetUnavailable), 2);
+        assert_eq!(
+            catalog.summary(),
+            "3 interferograms in, 0 cataloged, 3 skipped (2 missing met, 1 duplicates)"
+        );
+    }
+
+    #[test]
+    fn test_write_path_map() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let catalog = CatalogEntries {
+            entries: vec![],
+            igram_paths: vec![dir.join("a.0001"), dir.join("b.0001")],
+            met_flags: vec![],
+            n_input: 2,
+            skipped: vec![],
+            next_run_number: 1,
+        };
+        let tsv_file = dir.join("paths.tsv");
+        let json_file = dir.join("paths.json");
+        catalog.write_path_map(&tsv_file).unwrap();
+        catalog.write_path_map(&json_file).unwrap();
+        let tsv = std::fs::read_to_string(&tsv_file).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json_file).unwrap()).unwrap();
+
+        let a_path = dir.join("a.0001").to_string_lossy().to_string();
+        assert_eq!(tsv.lines().next(), Some("filename\tpath"));
+        assert_eq!(
+            tsv.lines().nth(1),
+            Some(format!("a.0001\t{a_path}").as_str())
+        );
+        assert_eq!(json["a.0001"], serde_json::Value::String(a_path));
+    }
+
+    #[test]
+    fn test_hours_from_solar_noon() {
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        // Near the equinox the equation of time is about -7 minutes, so solar noon at Greenwich is
+        // at about 12:07 UTC.
+        let h = hours_from_solar_noon(t("2024-03-21T12:07:00Z"), 0.0);
+        assert!(h.abs() < 0.05, "{h}");
+        // Pasadena (118 W) is about 7.9 h behind Greenwich, and the ZPD time's own offset should not matter.
+        let h = hours_from_solar_noon(t("2024-03-21T12:00:00-08:00"), -118.13);
+        assert!(h.abs() < 0.3, "{h}");
+        // Wrapping around midnight
+        let h = hours_from_solar_noon(t("2024-03-21T23:00:00Z"), 180.0);
+        assert!((h - -1.1).abs() < 0.1, "{h}");
+        let h = hours_from_solar_noon(t("2024-03-21T00:00:00Z"), 0.0);
+        assert!((h - 11.9).abs() < 0.1, "{h}");
+    }
+
+    #[test]
+    fn test_solar_zenith_angle() {
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        // At solar noon near the equinox, the SZA is about the latitude.
+        let sza = solar_zenith_angle(t("2024-03-21T12:07:00Z"), 45.0, 0.0);
+        assert!((sza - 45.0).abs() < 1.0, "{sza}");
+        // At the June solstice, the sun is overhead at noon on the Tropic of Cancer...
+        let sza = solar_zenith_angle(t("2024-06-21T12:02:00Z"), 23.44, 0.0);
+        assert!(sza < 1.0, "{sza}");
+        // ...and it is below the horizon at midnight in Pasadena, regardless of the time's offset
+        let sza = solar_zenith_angle(t("2024-06-21T00:00:00-07:00"), 34.14, -118.13);
+        assert!(sza > 90.0, "{sza}");
+        // Near sunset at the equinox at the equator
+        let sza = solar_zenith_angle(t("2024-03-21T17:37:00Z"), 0.0, 0.0);
+        assert!((sza - 82.5).abs() < 1.0, "{sza}");
+    }
+
+    #[test]
+    fn test_next_run_number() {
+        let mut runs = RunNumbers::new(&CatalogOptions::default());
+        assert_eq!(runs.current().unwrap(), 1);
+        runs.advance();
+        assert_eq!(runs.current().unwrap(), 3);
+        runs.advance();
+        assert_eq!(runs.next, 5);
+
+        let options = CatalogOptions {
+            run_step: 1,
+            first_run_number: 98,
+            max_run_number: max_run_number_for_digits(2),
+            ..Default::default()
+        };
+        let mut runs = RunNumbers::new(&options);
+        assert_eq!(runs.current().unwrap(), 98);
+        runs.advance();
+        assert_eq!(runs.current().unwrap(), 99);
+        runs.advance();
+        // The next catalog's first run is still reported, but cannot be used
+        assert_eq!(runs.next, 100);
+        assert!(matches!(
+            runs.current().unwrap_err().current_context(),
+            CatalogError::RunNumberTooLarge { run: 100, max: 99 }
+        ));
+    }
+
+    #[test]
+    fn test_max_run_number_for_digits() {
+        assert_eq!(max_run_number_for_digits(4), DEFAULT_MAX_RUN_NUMBER);
+        assert_eq!(max_run_number_for_digits(1), 9);
+        assert_eq!(max_run_number_for_digits(12), u32::MAX);
+    }
+
+    #[test]
+    fn test_met_flags_display() {
+        let flags = MetFlags {
+            pressure: MetQuality::Interpolated,
+            temperature: MetQuality::Held,
+            humidity: MetQuality::Filled,
+        };
+        assert_eq!(flags.to_string(), "IHF");
+        assert_eq!(MetFlags::all(MetQuality::Filled).to_string(), "FFF");
+    }
+
+    fn met_entry(datetime: &str, temperature: Option<f64>, humidity: Option<f64>) -> MetEntry {
+        MetEntry {
+            datetime: DateTime::parse_from_rfc3339(datetime).unwrap(),
+            temperature,
+            pressure: 1000.0,
+            humidity,
+        }
+    }
+
+    #[test]
+    fn test_bracketing_met_indices() {
+        // Deliberately out of order, since the met sources are not required to be sorted
+        let times = [
+            "2024-04-01T18:10:00Z",
+            "2024-04-01T18:00:00Z",
+            "2024-04-01T18:20:00Z",
+        ]
+        .map(|t| DateTime::parse_from_rfc3339(t).unwrap());
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        assert_eq!(
+            bracketing_met_indices(&times, t("2024-04-01T18:05:00Z")),
+            Some((1, 0))
+        );
+        assert_eq!(
+            bracketing_met_indices(&times, t("2024-04-01T18:15:00Z")),
+            Some((0, 2))
+        );
+        assert_eq!(
+            bracketing_met_indices(&times, t("2024-04-01T18:10:00Z")),
+            Some((0, 0))
+        );
+        assert_eq!(
+            bracketing_met_indices(&times, t("2024-04-01T17:59:00Z")),
+            None
+        );
+        assert_eq!(
+            bracketing_met_indices(&times, t("2024-04-01T18:21:00Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_interpolated_met_flags() {
+        let full = met_entry("2024-04-01T18:00:00Z", Some(20.0), Some(40.0));
+        let no_temp = met_entry("2024-04-01T18:10:00Z", None, Some(45.0));
+        let no_rh = met_entry("2024-04-01T18:20:00Z", Some(22.0), None);
+
+        assert_eq!(interpolated_met_flags(&full, &full).to_string(), "III");
+        // A value missing from either bracketing entry must be filled, even if
+        // other entries in the met source have it.
+        assert_eq!(interpolated_met_flags(&full, &no_temp).to_string(), "IFI");
+        assert_eq!(interpolated_met_flags(&no_rh, &full).to_string(), "IIF");
+        assert_eq!(interpolated_met_flags(&no_temp, &no_rh).to_string(), "IFF");
+    }
+
+    #[test]
+    fn test_held_met_flags() {
+        let full = met_entry("2024-04-01T18:00:00Z", Some(20.0), Some(40.0));
+        assert_eq!(held_met_flags(&full).to_string(), "HHH");
+        let no_temp = met_entry("2024-04-01T18:00:00Z", None, Some(40.0));
+        assert_eq!(held_met_flags(&no_temp).to_string(), "HFH");
+        let pres_only = met_entry("2024-04-01T18:00:00Z", None, None);
+        assert_eq!(held_met_flags(&pres_only).to_string(), "HFF");
+    }
+
+    fn catalog_test_inputs() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_inputs")
+            .join("catalog")
+    }
+
+    #[test]
+    fn test_check_min_points() {
+        let igram = Path::new("xx20240401s0e00a.0001");
+        assert!(check_min_points(igram, Some(16), 16).is_ok());
+        assert!(check_min_points(igram, None, 16).is_ok());
+        let err = check_min_points(igram, Some(0), 1).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            CatalogError::SkippingIgram(_, IgramSkipReason::TooFewPoints)
+        ));
+    }
+
+    #[test]
+    fn test_skip_too_few_points_sample() {
+        // Like test_make_catalog_entries_sample, this reads the interferogram header, so it needs
+        // the full GGG-RS header reader.
+        let inputs = catalog_test_inputs();
+        let coords = load_coordinates(&inputs.join("xx_coords.json")).unwrap();
+        let igram = inputs.join("xx20240401s0e00a.0001");
+        // The sample interferogram has 16 points; the check comes before the met is needed.
+        let options = CatalogOptions {
+            min_points: 17,
+            ..Default::default()
+        };
+        let err = create_catalog_entry_for_igram(&igram, 1, &coords, &[], &options).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            CatalogError::SkippingIgram(_, IgramSkipReason::TooFewPoints)
+        ));
+    }
+
+    #[test]
+    fn test_load_sample_coords_and_met() {
+        let inputs = catalog_test_inputs();
+        let zpd_time = DateTime::parse_from_rfc3339("2024-04-01T18:05:00Z").unwrap();
+
+        let coords = load_coordinates(&inputs.join("xx_coords.json")).unwrap();
+        let (lat, lon, alt) = coords.get_coords_for_datetime(zpd_time).unwrap();
+        assert_eq!((lat, lon, alt), (34.136, -118.127, 237.0));
+
+        let met = load_met_for_zpd_times(
+            &inputs.join("xx_met.json"),
+            &[zpd_time],
+            &CatalogOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(met.entries.len(), 3);
+        assert_eq!(met.max_extrapolation, chrono::Duration::minutes(30));
+        assert_eq!(
+            met.entries[0].datetime,
+            DateTime::parse_from_rfc3339("2024-04-01T18:00:00Z").unwrap()
+        );
+        assert_eq!(met.entries[1].pressure, 984.0);
+        assert_eq!(met.entries[1].temperature, Some(22.0));
+        assert_eq!(met.entries[1].humidity, Some(50.0));
+    }
+
+    #[test]
+    fn test_make_catalog_entries_sample() {
+        let inputs = catalog_test_inputs();
+        let igram = inputs.join("xx20240401s0e00a.0001");
+        let catalog = make_catalog_entries(
+            &inputs.join("xx_coords.json"),
+            &[inputs.join("xx_met.json")],
+            &[&igram],
+            &CatalogOptions::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(catalog.n_input, 1);
+        assert!(catalog.skipped.is_empty());
+        assert_eq!(catalog.next_run_number, 3);
+
+        // Round trip through the catalog table to check the values that will go to I2S.
+        let mut buf = vec![];
+        i2s::write_opus_catalogue_table(&mut buf, &catalog.entries, false).unwrap();
+        let rows = crate::catalog_table::read_opus_catalogue_table(buf.as_slice()).unwrap();
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row.spectrum_name, "xx20240401s0e00a.0001");
+        assert_eq!((row.year, row.month, row.day, row.run), (2024, 4, 1, 1));
+        assert!((row.lat - 34.136).abs() < 1e-3);
+        assert!((row.lon - -118.127).abs() < 1e-3);
+        // The ZPD time is 18:05, halfway between the first two met records. The sample has no
+        // instrument pressure, so it should be the surface pressure.
+        assert!((row.pout - 984.5).abs() < 1e-3);
+        assert!((row.tout - 21.0).abs() < 1e-3);
+        assert!((row.hout - 45.0).abs() < 1e-3);
+        assert_eq!(row.pins, row.pout);
+        assert!((row.tins - 30.5).abs() < 1e-3);
+        assert_eq!(catalog.met_flags[0].to_string(), "III");
+    }
+
+    #[test]
+    fn test_met_covers_time() {
+        let t = |s| DateTime::parse_from_rfc3339(s).unwrap();
+        let met_times = [t("2025-03-01T16:00:00Z"), t("2025-03-01T18:00:00Z")];
+        let max_extrap = chrono::Duration::minutes(10);
+
+        assert!(met_covers_time(
+            &met_times,
+            t("2025-03-01T17:00:00Z"),
+            max_extrap
+        ));
+        assert!(met_covers_time(
+            &met_times,
+            t("2025-03-01T09:00:00-07:00"),
+            max_extrap
+        ));
+        assert!(met_covers_time(
+            &met_times,
+            t("2025-03-01T18:10:00Z"),
+            max_extrap
+        ));
+        assert!(!met_covers_time(
+            &met_times,
+            t("2025-03-01T18:10:01Z"),
+            max_extrap
+        ));
+        assert!(!met_covers_time(
+            &met_times,
+            t("2025-03-01T15:00:00Z"),
+            max_extrap
+        ));
+        assert!(!met_covers_time(&[], t("2025-03-01T17:00:00Z"), max_extrap));
+    }
+
+    #[test]
+    fn test_parse_zpd_datetime() {
+        let expected = DateTime::parse_from_rfc3339("2024-03-01T16:14:22.123-08:00").unwrap();
+        let dt = parse_zpd_datetime("01/03/2024", "16:14:22.123 (GMT-8)", None).unwrap();
+        assert_eq!(dt, expected);
+        let dt = parse_zpd_datetime("2024/03/01", "16:14:22.123 (GMT-8)", None).unwrap();
+        assert_eq!(dt, expected);
+
+        let expected = DateTime::parse_from_rfc3339("2024-03-01T16:14:22-08:00").unwrap();
+        let dt = parse_zpd_datetime("01/03/2024", "16:14:22 (GMT-8)", None).unwrap();
+        assert_eq!(dt, expected);
+        let dt = parse_zpd_datetime("2024/03/01", "16:14:22 (GMT-8)", None).unwrap();
+        assert_eq!(dt, expected);
+    }
+
+    #[test]
+    fn test_parse_zpd_datetime_bad_formats() {
+        let e = parse_zpd_datetime("2024-03-01", "16:14:22.123 (GMT-8)", None).unwrap_err();
+        let msg = e.current_context().to_string();
+        assert!(msg.contains("'DD/MM/YYYY', 'YYYY/MM/DD'"), "{msg}");
+
+        let e = parse_zpd_datetime("01/03/2024", "16h14m22s (GMT-8)", None).unwrap_err();
+        let msg = e.current_context().to_string();
+        assert!(msg.contains("'HH:MM:SS.fff', 'HH:MM:SS'"), "{msg}");
+    }
+
+    #[test]
+    fn test_replace_offset() {
+        let dt = parse_zpd_datetime("01/03/2024", "16:14:22.123 (GMT+0)", None).unwrap();
+        let pdt = FixedOffset::west_opt(7 * 3600).unwrap();
+        let dt = replace_offset(dt, pdt).unwrap();
+        assert_eq!(
+            dt,
+            DateTime::parse_from_rfc3339("2024-03-01T16:14:22.123-07:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_zpd_datetime_default_offset() {
+        assert!(parse_zpd_datetime("01/03/2024", "16:14:22.123", None).is_err());
+
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let dt = parse_zpd_datetime("01/03/2024", "16:14:22.123", Some(utc)).unwrap();
+        assert_eq!(
+            dt,
+            DateTime::parse_from_rfc3339("2024-03-01T16:14:22.123Z").unwrap()
+        );
+
+        // An offset in the header takes precedence over the default
+        let dt = parse_zpd_datetime("01/03/2024", "16:14:22.123 (GMT-8)", Some(utc)).unwrap();
+        assert_eq!(
+            dt,
+            DateTime::parse_from_rfc3339("2024-03-01T16:14:22.123-08:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_whole_hour_gmt_offset() {
+        assert_eq!(parse_gmt_offset_secs("(GMT+0)"), Some(0));
+        assert_eq!(parse_gmt_offset_secs("(GMT+2)"), Some(7200));
+        assert_eq!(parse_gmt_offset_secs("(GMT-7)"), Some(-7 * 3600));
+        assert_eq!(parse_gmt_offset_secs("(GMT-10)"), Some(-10 * 3600));
+    }
+
+    #[test]
+    fn test_half_hour_gmt_offset() {
+        assert_eq!(parse_gmt_offset_secs("(GMT+5:30)"), Some(5 * 3600 + 1800));
+        assert_eq!(parse_gmt_offset_secs("(GMT+5.5)"), Some(5 * 3600 + 1800));
+        assert_eq!(
+            parse_gmt_offset_secs("(GMT-9:30)"),
+            Some(-(9 * 3600 + 1800))
+        );
+        assert_eq!(parse_gmt_offset_secs("(GMT-9.5)"), Some(-(9 * 3600 + 1800)));
+    }
+
+    #[test]
+    fn test_45_minute_gmt_offset() {
+        assert_eq!(parse_gmt_offset_secs("(GMT+5:45)"), Some(5 * 3600 + 2700));
+        assert_eq!(parse_gmt_offset_secs("(GMT+12.75)"), Some(12 * 3600 + 2700));
+        assert_eq!(parse_gmt_offset_secs("(GMT+8:45)"), Some(8 * 3600 + 2700));
+    }
+
+    #[test]
+    fn test_bad_gmt_offset() {
+        assert_eq!(parse_gmt_offset_secs("(GMT)"), None);
+        assert_eq!(parse_gmt_offset_secs("(UTC+1)"), None);
+        assert_eq!(parse_gmt_offset_secs("(GMT+5:75)"), None);
+        assert_eq!(parse_gmt_offset_secs("(GMT+99999999999)"), None);
+        // Fits in an i32 as whole hours, but not once the fraction is added
+        assert_eq!(parse_gmt_offset_secs("(GMT+596523.5)"), None);
+        // Parses, but should be rejected as out of range when converted to a FixedOffset
+        let secs = parse_gmt_offset_secs("(GMT+30)").unwrap();
+        assert!(FixedOffset::east_opt(secs).is_none());
     }
 }
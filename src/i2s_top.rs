@@ -0,0 +1,274 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use error_stack::ResultExt;
+use ggg_rs::i2s::{I2SInputModifcations, I2SLineIter, I2SVersion};
+
+use crate::{
+    catalog_table::{CatalogRow, N_REQUIRED_COLUMNS},
+    default_files,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum I2STopError {
+    #[error("Error opening source I2S top file at {}", .0.display())]
+    OpenTemplate(PathBuf),
+    #[error("Error reading source I2S top file at {}", .0.display())]
+    ReadTemplate(PathBuf),
+    #[error("Error reading I2S top file")]
+    ReadTop,
+    #[error("Error writing {0} line to I2S input file")]
+    WriteLine(&'static str),
+    #[error("Error reading line {0} of the existing I2S input file")]
+    ReadExisting(usize),
+    #[error("Catalog comment line {0:?} would not be read by I2S as a comment")]
+    InvalidComment(String),
+}
+
+/// The start of the comment lines written by [`write_provenance_comments`].
+pub const PROVENANCE_COMMENT_PREFIX: &str = ": egi-rs ";
+
+/// The inputs used to make a catalog, to record in comment lines before it
+/// (see [`write_provenance_comments`]).
+pub struct CatalogProvenance<'a> {
+    /// The coordinate file given to [`crate::i2s_catalog::make_catalog_entries`].
+    pub coordinate_file: &'a Path,
+    /// The met source files given to [`crate::i2s_catalog::make_catalog_entries`].
+    pub met_files: &'a [PathBuf],
+}
+
+impl CatalogProvenance<'_> {
+    /// The comment lines (without line endings) describing this catalog, generated at `timestamp`.
+    fn comment_lines(&self, timestamp: chrono::DateTime<chrono::Utc>) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{PROVENANCE_COMMENT_PREFIX}version: {}",
+                env!("CARGO_PKG_VERSION")
+            ),
+            format!(
+                "{PROVENANCE_COMMENT_PREFIX}generated: {}",
+                timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            ),
+            format!(
+                "{PROVENANCE_COMMENT_PREFIX}coordinates: {}",
+                self.coordinate_file.display()
+            ),
+        ];
+        for met_file in self.met_files {
+            lines.push(format!(
+                "{PROVENANCE_COMMENT_PREFIX}met: {}",
+                met_file.display()
+            ));
+        }
+        lines
+    }
+}
+
+/// Write comment lines recording the EGI-RS version, the current time, and the inputs used to
+/// make a catalog, to go right before the catalog (either in a standalone catalog file or after
+/// the top of an I2S input file).
+///
+/// Each line starts with [`PROVENANCE_COMMENT_PREFIX`], so that I2S and
+/// [`crate::catalog_table::read_opus_catalogue_table`] skip it as a comment and
+/// [`read_existing_top`] leaves it out of the top.
+///
+/// # Errors
+/// - if a line would not be a single comment line, e.g. because a path contains a line break,
+///   or the I2S input file parser cannot read it, or
+/// - if writing to `writer` fails.
+pub fn write_provenance_comments<W: Write>(
+    writer: &mut W,
+    provenance: &CatalogProvenance,
+) -> error_stack::Result<(), I2STopError> {
+    let lines = provenance.comment_lines(chrono::Utc::now());
+    for line in lines.iter() {
+        if !line.starts_with(':') || line.contains(['\n', '\r']) {
+            return Err(I2STopError::InvalidComment(line.clone()).into());
+        }
+    }
+
+    let text: String = lines.iter().map(|l| format!("{l}\n")).collect();
+    for parsed in I2SLineIter::new(text.as_bytes(), I2SVersion::I2S2020) {
+        parsed.change_context_lazy(|| I2STopError::InvalidComment(text.clone()))?;
+    }
+
+    writer
+        .write_all(text.as_bytes())
+        .change_context_lazy(|| I2STopError::WriteLine("comment"))
+}
+
+/// Write the top part of the I2S input file
+///
+/// # Inputs
+/// - `input_file` - handle to write the top to
+/// - `top_edits` - collection of parameters that should be set
+/// - `source_top_path` - path pointing to an existing I2S top file to use as a template,
+///   if `None`, the default EM27 template is used.
+///
+/// # Errors
+/// - if cannot open/read the source top file (if given), or
+/// - if cannot write the output file successfully
+pub fn write_input_top<W: Write>(
+    input_file: &mut W,
+    top_edits: &I2SInputModifcations,
+    source_top_path: Option<&Path>,
+) -> error_stack::Result<(), I2STopError> {
+    let top_contents = if let Some(p) = source_top_path {
+        let mut f = std::fs::File::open(p)
+            .change_context_lazy(|| I2STopError::OpenTemplate(p.to_path_buf()))?;
+
+        let mut buf = String::new();
+        f.read_to_string(&mut buf)
+            .change_context_lazy(|| I2STopError::ReadTemplate(p.to_path_buf()))?;
+
+        buf
+    } else {
+        default_files::I2S_TOP.to_string()
+    };
+
+    modify_i2s_head(top_contents.as_bytes(), top_edits, input_file)
+}
+
+/// Write a version of the I2S header with specific changes made
+///
+/// # Inputs
+/// - `top`: the template for the I2S header to modify. Can be anything that implements
+///   the [`Read`] trait, typically a [`std::fs::File`] instance or a `&[u8]`.
+/// - `edits`: collection of parameters in the I2S header to set.
+/// - `writer`: handle to write the changes to, e.g. a mutable [`std::fs::File`] instance.
+///
+/// # Errors
+/// - if reading a line from `top` fails, or
+/// - if writing a line to `writer` fails
+pub fn modify_i2s_head<R: Read, W: Write>(
+    top: R,
+    edits: &I2SInputModifcations,
+    mut writer: W,
+) -> error_stack::Result<(), I2STopError> {
+    // TODO: this should go into ggg_rs::i2s once error types in ggg_rs are cleaned up
+    let rdr = BufReader::new(top);
+    let iterator = I2SLineIter::new(rdr, I2SVersion::I2S2020);
+    for head_line in iterator {
+        let (line_type, head_line) = head_line.change_context_lazy(|| I2STopError::ReadTop)?;
+
+        if let Some(new_line) = edits.change_line_opt(line_type) {
+            writeln!(writer, "{}", new_line)
+                .change_context_lazy(|| I2STopError::WriteLine("new"))?;
+        } else {
+            write!(writer, "{}", head_line)
+                .change_context_lazy(|| I2STopError::WriteLine("existing"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the top part of an existing I2S input file, i.e. everything before its catalog.
+///
+/// This is for regenerating the catalog of an input file without losing any changes made
+/// to its header parameters by hand. The catalog starts at the first line that can be read
+/// as a catalog row (see [`crate::catalog_table`]); that line and the catalog's column header
+/// just before it are not included in the returned top.
+///
+/// Returns `None` if the file has no catalog entries, since then where the top ends cannot be
+/// told reliably.
+///
+/// # Errors
+/// - if reading a line from `input` fails.
+pub fn read_existing_top<R: BufRead>(input: R) -> error_stack::Result<Option<String>, I2STopError> {
+    let mut top_lines: Vec<String> = vec![];
+    for (iline, line) in input.lines().enumerate() {
+        let mut line = line.change_context_lazy(|| I2STopError::ReadExisting(iline + 1))?;
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        if CatalogRow::from_line(line.trim(), iline + 1).is_ok() {
+            if top_lines.last().is_some_and(|l| is_catalog_header(l)) {
+                top_lines.pop();
+            }
+            // The provenance comments describe the old catalog, so they will be rewritten with the new one.
+            while top_lines
+                .last()
+                .is_some_and(|l| l.starts_with(PROVENANCE_COMMENT_PREFIX))
+            {
+                top_lines.pop();
+            }
+            let mut top = top_lines.join("\n");
+            top.push('\n');
+            return Ok(Some(top));
+        }
+        top_lines.push(line);
+    }
+    Ok(None)
+}
+
+/// Whether `line` looks like the column header of a catalog: at least as many columns as a
+/// catalog row, with a second column that is not a year.
+fn is_catalog_header(line: &str) -> bool {
+    let columns: Vec<&str> = line.split_whitespace().collect();
+    columns.len() >= N_REQUIRED_COLUMNS && columns[1].parse::<i32>().is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_existing_top() {
+        let input = "/data/xx/20240401/\n./spectra/\n: a comment the analyst added\n-7.00\n\n\
+Spectrum_Name Year Mon Day Run Lat Lon Alt Tins Pins Hins Tout Pout Hout\n\
+xx20240401s0e00a.0001 2024 4 1 1 34.1 -118.1 0.237 30.5 985.2 20.0 25.7 985.2 40.5\n";
+        let top = read_existing_top(input.as_bytes()).unwrap().unwrap();
+        assert_eq!(
+            top,
+            "/data/xx/20240401/\n./spectra/\n: a comment the analyst added\n-7.00\n\n"
+        );
+
+        let no_catalog = "/data/xx/20240401/\n./spectra/\n";
+        assert_eq!(read_existing_top(no_catalog.as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_provenance_comments() {
+        let met_files = [PathBuf::from("/data/xx/met.toml")];
+        let provenance = CatalogProvenance {
+            coordinate_file: Path::new("/data/xx/coords.toml"),
+            met_files: &met_files,
+        };
+        let timestamp = chrono::DateTime::parse_from_rfc3339("2024-04-02T08:00:00Z")
+            .unwrap()
+            .to_utc();
+        let lines = provenance.comment_lines(timestamp);
+        assert_eq!(
+            lines[1..],
+            [
+                ": egi-rs generated: 2024-04-02T08:00:00Z",
+                ": egi-rs coordinates: /data/xx/coords.toml",
+                ": egi-rs met: /data/xx/met.toml",
+            ]
+        );
+
+        // The comments must be skipped when reading the catalog or the top back in
+        let catalog = b"Spectrum_Name Year Mon Day Run Lat Lon Alt Tins Pins Hins Tout Pout Hout\n\
+xx20240401s0e00a.0001 2024 4 1 1 34.1 -118.1 0.237 30.5 985.2 20.0 25.7 985.2 40.5\n";
+        let mut catalog_file = vec![];
+        write_provenance_comments(&mut catalog_file, &provenance).unwrap();
+        catalog_file.extend_from_slice(catalog);
+        let rows =
+            crate::catalog_table::read_opus_catalogue_table(catalog_file.as_slice()).unwrap();
+        assert_eq!(rows.len(), 1);
+
+        let mut input_file = b"/data/xx/20240401/\n./spectra/\n\n".to_vec();
+        input_file.extend_from_slice(&catalog_file);
+        let top = read_existing_top(input_file.as_slice()).unwrap().unwrap();
+        assert_eq!(top, "/data/xx/20240401/\n./spectra/\n\n");
+
+        let bad_met = [PathBuf::from("/data/xx/met\n.toml")];
+        let bad = CatalogProvenance {
+            coordinate_file: Path::new("/data/xx/coords.toml"),
+            met_files: &bad_met,
+        };
+        assert!(write_provenance_comments(&mut vec![], &bad).is_err());
+    }
+}
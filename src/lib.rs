@@ -1,38 +1,58 @@
 use std::{
     env,
+    ffi::OsString,
     path::{Path, PathBuf},
 };
 
+use once_cell::sync::OnceCell;
+
+pub mod catalog_table;
 pub mod config;
 pub mod coordinates;
 pub mod default_files;
 pub mod i2s_catalog;
+pub mod i2s_top;
 pub mod meteorology;
 pub mod utils;
 
 pub const CATALOG_FILL_FLOAT_F32: f32 = -99.0;
 pub const CATALOG_FILL_FLOAT_F64: f64 = -99.0;
 
-/// If `p` is already an absolute path, return it unchanged. Otherwise, make it relative to
-/// the parent directory of `config_file`.
+/// Error returned when relative paths in a configuration file cannot be resolved.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigPathError {
+    /// Indicates that the directory containing the configuration file could not be determined,
+    /// e.g. because a root directory or empty path was given instead of a file. The contained
+    /// [`PathBuf`] will be the configuration path given.
+    #[error("Could not get the directory containing the configuration file '{}', relative paths in it cannot be resolved", .0.display())]
+    NoParent(PathBuf),
+}
+
+/// Expand a leading `~` in `p` (see [`utils::expand_user`]), then, if `p` is already an absolute
+/// path, return it. Otherwise, make it relative to the parent directory of `config_file`.
 ///
-/// # Panics
-/// Panics if it cannot get the parent directory of `config_file`, which should only happen
-/// if a root directory was given instead of a file, so this is considered an internal mistake.
-pub(crate) fn path_relative_to_config(config_file: &Path, p: PathBuf) -> PathBuf {
+/// # Errors
+/// Returns an error if it cannot get the parent directory of `config_file` and `p` is relative,
+/// which happens if a root directory or empty path was given instead of a file.
+pub(crate) fn path_relative_to_config(
+    config_file: &Path,
+    p: PathBuf,
+) -> Result<PathBuf, ConfigPathError> {
+    let p = utils::expand_user(&p);
     if p.is_absolute() {
-        p
+        Ok(p)
     } else if let Some(parent_dir) = config_file.parent() {
-        parent_dir.join(p)
+        Ok(parent_dir.join(p))
     } else {
-        panic!("Could not get parent from path {}", config_file.display());
+        Err(ConfigPathError::NoParent(config_file.to_path_buf()))
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub(crate) enum EgiPathError {
-    /// Indicates that no GGGPATH environmental variable was set in the current environment.
-    #[error("Neither EGIPATH nor egipath environmental variable set")]
+/// Error returned when the EGI directory (`$EGIPATH`, or `$GGGPATH/egi` if that is not set) cannot be found.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum EgiPathError {
+    /// Indicates that neither an EGIPATH nor a GGGPATH environmental variable was set in the current environment.
+    #[error("Neither EGIPATH nor egipath environmental variable set (and no GGGPATH or gggpath to use $GGGPATH/egi instead)")]
     NotSet,
     /// Indicates that the path taken from the environment points to a directory that
     /// doesn't exist at all. The contained [`PathBuf`] will be the path it expected.
@@ -44,11 +64,33 @@ pub(crate) enum EgiPathError {
     IsNotDir(PathBuf),
 }
 
+/// Get the path to the EGI directory, which holds EGI's configuration and shared files.
+///
+/// This is the `EGIPATH` (or `egipath`) environmental variable if set, otherwise
+/// the `egi` subdirectory of `GGGPATH` (or `gggpath`). The path is looked up and
+/// checked the first time this is called; later calls return the same result, so
+/// the path stays consistent for the whole run.
 pub(crate) fn get_egi_path() -> Result<PathBuf, EgiPathError> {
-    let env_path = env::var_os("GGGPATH")
-        .or_else(|| env::var_os("gggpath"))
-        .ok_or_else(|| EgiPathError::NotSet)
-        .and_then(|p| Ok(PathBuf::from(p)))?;
+    static EGI_PATH: OnceCell<Result<PathBuf, EgiPathError>> = OnceCell::new();
+    EGI_PATH
+        .get_or_init(|| {
+            egi_path_from_vars(
+                env::var_os("EGIPATH").or_else(|| env::var_os("egipath")),
+                env::var_os("GGGPATH").or_else(|| env::var_os("gggpath")),
+            )
+        })
+        .clone()
+}
+
+fn egi_path_from_vars(
+    egi_path: Option<OsString>,
+    ggg_path: Option<OsString>,
+) -> Result<PathBuf, EgiPathError> {
+    let env_path = match (egi_path, ggg_path) {
+        (Some(p), _) => PathBuf::from(p),
+        (None, Some(p)) => PathBuf::from(p).join("egi"),
+        (None, None) => return Err(EgiPathError::NotSet),
+    };
 
     if !env_path.exists() {
         return Err(EgiPathError::DoesNotExist(env_path));
@@ -60,3 +102,41 @@ pub(crate) fn get_egi_path() -> Result<PathBuf, EgiPathError> {
 
     Ok(env_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_relative_to_config() {
+        let p = path_relative_to_config(Path::new("/data/met.json"), PathBuf::from("met.txt"));
+        assert_eq!(p.unwrap(), PathBuf::from("/data/met.txt"));
+        let p = path_relative_to_config(Path::new("met.json"), PathBuf::from("met.txt"));
+        assert_eq!(p.unwrap(), PathBuf::from("met.txt"));
+        let p = path_relative_to_config(Path::new("/"), PathBuf::from("/data/met.txt"));
+        assert_eq!(p.unwrap(), PathBuf::from("/data/met.txt"));
+        let p = path_relative_to_config(Path::new("/"), PathBuf::from("met.txt"));
+        assert!(matches!(p, Err(ConfigPathError::NoParent(_))));
+    }
+
+    #[test]
+    fn test_egi_path_from_vars() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let egi_dir = root.join("egi");
+        std::fs::create_dir_all(&egi_dir).unwrap();
+
+        let from_ggg = egi_path_from_vars(None, Some(root.as_os_str().to_owned()));
+        let from_egi = egi_path_from_vars(
+            Some(egi_dir.clone().into_os_string()),
+            Some("/does/not/exist".into()),
+        );
+        let missing = egi_path_from_vars(Some(root.join("nope").into_os_string()), None);
+        let not_set = egi_path_from_vars(None, None);
+
+        assert_eq!(from_ggg.unwrap(), egi_dir);
+        assert_eq!(from_egi.unwrap(), egi_dir);
+        assert!(matches!(missing, Err(EgiPathError::DoesNotExist(_))));
+        assert!(matches!(not_set, Err(EgiPathError::NotSet)));
+    }
+}
@@ -1,22 +1,20 @@
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use chrono::Timelike;
-
+use error_stack::ResultExt;
 use ggg_rs::error::DateTimeError;
 use ggg_rs::utils::{is_usa_dst, read_unknown_encoding_file};
 use itertools::Itertools;
 
 use super::MetEntry;
 
-use crate::CATALOG_FILL_FLOAT_F64;
-
 #[derive(Debug, thiserror::Error)]
 pub(super) enum CitMetError {
-    #[error("Could not open file: {0}")]
-    IoError(#[from] std::io::Error),
+    #[error("Could not read {}", .0.display())]
+    ReadError(PathBuf),
     #[error("Unknown TCCON site: {0}")]
     UnknownSite(String),
     #[error("CIT .csv file {} missing header line", .0.display())]
@@ -46,22 +44,37 @@ pub(super) enum CitMetError {
     TimezoneError(#[from] DateTimeError),
 }
 
-enum TcconMetSite {
-    ParkFalls,
-    Lamont,
-    Caltech,
+/// The TCCON sites with met data available in the CIT .csv format, as
+/// (site ID, UTC offset in standard time, UTC offset in daylight savings time).
+/// All of these are in the US, so use US daylight savings rules.
+static TCCON_MET_SITES: [(&str, i32, i32); 4] = [
+    // Caltech (Pasadena, CA)
+    ("ci", -8, -7),
+    // Armstrong/Dryden (Edwards, CA)
+    ("df", -8, -7),
+    // Lamont, OK
+    ("oc", -6, -5),
+    // Park Falls, WI
+    ("pa", -6, -5),
+];
+
+struct TcconMetSite {
+    std_utc_offset: i32,
+    dst_utc_offset: i32,
 }
 
 impl FromStr for TcconMetSite {
     type Err = CitMetError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "ci" => Ok(Self::Caltech),
-            "oc" => Ok(Self::Lamont),
-            "pa" => Ok(Self::ParkFalls),
-            _ => Err(CitMetError::UnknownSite(s.to_string())),
-        }
+        TCCON_MET_SITES
+            .iter()
+            .find(|(site_id, _, _)| *site_id == s)
+            .map(|&(_, std_utc_offset, dst_utc_offset)| Self {
+                std_utc_offset,
+                dst_utc_offset,
+            })
+            .ok_or_else(|| CitMetError::UnknownSite(s.to_string()))
     }
 }
 
@@ -71,11 +84,10 @@ impl TcconMetSite {
         datetime: chrono::NaiveDateTime,
     ) -> Result<chrono::DateTime<chrono::FixedOffset>, DateTimeError> {
         let is_dst = is_usa_dst(datetime)?;
-        let utc_offset = match (self, is_dst) {
-            (Self::ParkFalls | Self::Lamont, false) => -6,
-            (Self::ParkFalls | Self::Lamont, true) => -5,
-            (Self::Caltech, false) => -8,
-            (Self::Caltech, true) => -7,
+        let utc_offset = if is_dst {
+            self.dst_utc_offset
+        } else {
+            self.std_utc_offset
         };
 
         let tz = chrono::FixedOffset::east_opt(utc_offset * 3600).unwrap();
@@ -96,29 +108,38 @@ pub(super) fn read_cit_csv_met(
     site: &str,
     temp_file: Option<&Path>,
     humid_file: Option<&Path>,
-) -> Result<Vec<MetEntry>, CitMetError> {
+    strict_times: bool,
+) -> error_stack::Result<Vec<MetEntry>, CitMetError> {
     let site = TcconMetSite::from_str(site)?;
 
     let (times, pressure) = read_cit_csv(pres_file, "Pressure (mb)")?;
 
     let temperature = if let Some(file) = temp_file {
         let (ttime, temp) = read_cit_csv(file, "Temperature")?;
-        check_times(&times, &ttime, pres_file, file)?;
-        temp
+        Some(match_times(
+            &times,
+            &ttime,
+            temp,
+            pres_file,
+            file,
+            strict_times,
+        )?)
     } else {
-        std::iter::repeat(CATALOG_FILL_FLOAT_F64)
-            .take(pressure.len())
-            .collect_vec()
+        None
     };
 
     let humidity = if let Some(file) = humid_file {
         let (htime, humid) = read_cit_csv(file, "Relative Humidity (%)")?;
-        check_times(&times, &htime, pres_file, file)?;
-        humid
+        Some(match_times(
+            &times,
+            &htime,
+            humid,
+            pres_file,
+            file,
+            strict_times,
+        )?)
     } else {
-        std::iter::repeat(CATALOG_FILL_FLOAT_F64)
-            .take(pressure.len())
-            .collect_vec()
+        None
     };
 
     let mut met_entries = vec![];
@@ -133,35 +154,63 @@ pub(super) fn read_cit_csv_met(
                 }
             })?;
 
-        // Skip times between midnight and 3a local. We never take data during those times anyway,
-        // and daylight savings time makes them a mess.
-        if datetime.hour() < 3 {
+        // Local times that do not exist or happen twice because of a daylight savings transition
+        // can't be converted to UTC unambiguously, so skip them.
+        if let Some(problem) = dst_transition_problem(datetime).map_err(CitMetError::from)? {
+            log::debug!(
+                "Skipping {datetime} (line {} of {}): {problem}",
+                i + 2,
+                pres_file.display()
+            );
             continue;
         }
 
-        let datetime = site.add_timezone(datetime)?;
+        let datetime = site.add_timezone(datetime).map_err(CitMetError::from)?;
 
         let p = pressure[i];
-        let t = temperature[i];
-        let h = humidity[i];
+        let t = temperature.as_ref().and_then(|temp| temp[i]);
+        let h = humidity.as_ref().and_then(|humid| humid[i]);
 
         met_entries.push(MetEntry {
             datetime,
-            temperature: Some(t),
+            temperature: t,
             pressure: p,
-            humidity: Some(h),
+            humidity: h,
         })
     }
 
     Ok(met_entries)
 }
 
+/// Check whether a local time falls in a US daylight savings transition.
+///
+/// Returns `Some` with a description if `datetime` is skipped by the spring-forward transition
+/// (e.g. 02:30 does not exist on that day) or repeated by the fall-back transition (e.g. 01:30
+/// happens once in daylight time and once in standard time), and `None` otherwise.
+fn dst_transition_problem(
+    datetime: chrono::NaiveDateTime,
+) -> Result<Option<&'static str>, DateTimeError> {
+    let one_hour = chrono::Duration::hours(1);
+    let is_dst = is_usa_dst(datetime)?;
+    if is_dst && !is_usa_dst(datetime - one_hour)? {
+        Ok(Some(
+            "time does not exist due to the start of daylight savings time",
+        ))
+    } else if is_dst && !is_usa_dst(datetime + one_hour)? {
+        Ok(Some(
+            "time is ambiguous due to the end of daylight savings time",
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
 fn read_cit_csv(
     csv_file: &Path,
     second_colname: &str,
-) -> Result<(Vec<String>, Vec<f64>), CitMetError> {
+) -> error_stack::Result<(Vec<String>, Vec<f64>), CitMetError> {
     let contents = read_unknown_encoding_file(csv_file)
-        .map_err(|e| CitMetError::IoError(std::io::Error::other(e)))?;
+        .change_context_lazy(|| CitMetError::ReadError(csv_file.to_path_buf()))?;
 
     let mut lines = contents.as_str().lines();
 
@@ -177,7 +226,8 @@ fn read_cit_csv(
                 file: csv_file.to_path_buf(),
                 col_index: 1,
                 expected: "Time".to_string(),
-            });
+            }
+            .into());
         }
 
         if !col2.contains(second_colname) {
@@ -185,7 +235,8 @@ fn read_cit_csv(
                 file: csv_file.to_path_buf(),
                 col_index: 2,
                 expected: second_colname.to_string(),
-            });
+            }
+            .into());
         }
     };
 
@@ -195,7 +246,7 @@ fn read_cit_csv(
     for (iline, line) in lines.enumerate() {
         let values = line.split(',').collect_vec();
         if values.len() < 2 {
-            return Err(CitMetError::LineTooShort(csv_file.to_path_buf(), iline + 2));
+            return Err(CitMetError::LineTooShort(csv_file.to_path_buf(), iline + 2).into());
         }
         let v = values[1]
             .parse::<f64>()
@@ -212,6 +263,46 @@ fn read_cit_csv(
     Ok((times, met_values))
 }
 
+/// Match the values from a secondary file to the pressure times.
+///
+/// If `strict` is `true`, the times in both files must be identical (see [`check_times`]).
+/// Otherwise, the values are matched to the pressure times by their timestamps; any pressure
+/// time without a value in the secondary file gets `None` (so that the fill value is used) and
+/// any secondary times not in the pressure file are ignored.
+fn match_times(
+    main_times: &[String],
+    new_times: &[String],
+    new_values: Vec<f64>,
+    main_file: &Path,
+    new_file: &Path,
+    strict: bool,
+) -> Result<Vec<Option<f64>>, CitMetError> {
+    if strict {
+        check_times(main_times, new_times, main_file, new_file)?;
+        return Ok(new_values.into_iter().map(Some).collect());
+    }
+
+    let lookup: HashMap<&str, f64> = new_times
+        .iter()
+        .map(|t| t.as_str())
+        .zip(new_values)
+        .collect();
+    let matched = main_times
+        .iter()
+        .map(|t| lookup.get(t.as_str()).copied())
+        .collect_vec();
+
+    let n_missing = matched.iter().filter(|v| v.is_none()).count();
+    if n_missing > 0 {
+        log::debug!(
+            "{n_missing} times in {} were not present in {}, these will use fill values",
+            main_file.display(),
+            new_file.display()
+        );
+    }
+    Ok(matched)
+}
+
 fn check_times(
     main_times: &[String],
     new_times: &[String],
@@ -241,3 +332,96 @@ fn check_times(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_offsets_around_dst() {
+        // In 2024, US DST started 10 Mar and ended 3 Nov
+        let before_dst = chrono::NaiveDate::from_ymd_opt(2024, 3, 9)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let during_dst = chrono::NaiveDate::from_ymd_opt(2024, 3, 11)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let after_dst = chrono::NaiveDate::from_ymd_opt(2024, 11, 4)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+
+        for (site_id, std_hours, dst_hours) in [
+            ("ci", -8, -7),
+            ("df", -8, -7),
+            ("oc", -6, -5),
+            ("pa", -6, -5),
+        ] {
+            let site = TcconMetSite::from_str(site_id).unwrap();
+            for (dt, expected) in [
+                (before_dst, std_hours),
+                (during_dst, dst_hours),
+                (after_dst, std_hours),
+            ] {
+                let offset = site.add_timezone(dt).unwrap().offset().local_minus_utc();
+                assert_eq!(
+                    offset,
+                    expected * 3600,
+                    "wrong UTC offset for site {site_id} at {dt}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dst_transition_problem() {
+        let dt = |m, d, h, mi| {
+            chrono::NaiveDate::from_ymd_opt(2024, m, d)
+                .unwrap()
+                .and_hms_opt(h, mi, 0)
+                .unwrap()
+        };
+
+        // Early morning times on normal days are fine in both standard and daylight time
+        assert_eq!(dst_transition_problem(dt(1, 15, 0, 30)).unwrap(), None);
+        assert_eq!(dst_transition_problem(dt(1, 15, 2, 30)).unwrap(), None);
+        assert_eq!(dst_transition_problem(dt(7, 15, 1, 30)).unwrap(), None);
+
+        // DST started at 2a on 10 Mar 2024, so 2:00 to 2:59 did not exist
+        assert_eq!(dst_transition_problem(dt(3, 10, 1, 59)).unwrap(), None);
+        assert!(dst_transition_problem(dt(3, 10, 2, 0)).unwrap().is_some());
+        assert!(dst_transition_problem(dt(3, 10, 2, 59)).unwrap().is_some());
+        assert_eq!(dst_transition_problem(dt(3, 10, 3, 0)).unwrap(), None);
+
+        // DST ended at 2a on 3 Nov 2024, so 1:00 to 1:59 happened twice
+        assert_eq!(dst_transition_problem(dt(11, 3, 0, 59)).unwrap(), None);
+        assert!(dst_transition_problem(dt(11, 3, 1, 0)).unwrap().is_some());
+        assert!(dst_transition_problem(dt(11, 3, 1, 59)).unwrap().is_some());
+        assert_eq!(dst_transition_problem(dt(11, 3, 2, 0)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_match_times() {
+        let main_times = ["a", "b", "c", "d"].map(String::from);
+        let new_times = ["a", "c", "d", "e"].map(String::from);
+        let new_values = vec![1.0, 3.0, 4.0, 5.0];
+        let p = Path::new("test");
+
+        let matched =
+            match_times(&main_times, &new_times, new_values.clone(), p, p, false).unwrap();
+        assert_eq!(matched, [Some(1.0), None, Some(3.0), Some(4.0)]);
+
+        let strict = match_times(&main_times, &new_times, new_values, p, p, true);
+        assert!(matches!(strict, Err(CitMetError::TimeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_unknown_site() {
+        assert!(matches!(
+            TcconMetSite::from_str("xx"),
+            Err(CitMetError::UnknownSite(_))
+        ));
+    }
+}
@@ -1,10 +1,18 @@
-use std::{path::Path, process::Command};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
 
 use itertools::Itertools;
-use log::{info, trace};
+use log::{debug, error, info, trace, warn};
 
-use super::MetEntry;
-use crate::utils::pattern_replacement::{render_met_script_arg_pattern, PatternError};
+use super::{get_igram_time_span, MetEntry};
+use crate::{
+    config::DetectorSet,
+    utils::pattern_replacement::{render_met_script_arg_pattern, PatternError},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub(super) enum ScriptMetError {
@@ -15,11 +23,24 @@ pub(super) enum ScriptMetError {
         script: String,
         error: std::io::Error,
     },
-    #[error("Error while getting met data: calling {script} with arguments {args} returned non-zero exit code {exit_code}")]
+    #[error("Error while getting met data: {script} {reason}. The script must be executable: either add a shebang (e.g. #!/usr/bin/env python3) as its first line and make it executable with chmod +x, or use the interpreter (e.g. python3) as the script and pass the script file as the first argument")]
+    ScriptNotExecutableError { script: String, reason: String },
+    #[error("Error while getting met data: calling {script} with arguments {args} returned non-zero exit code {exit_code}{}", format_stderr_tail(.stderr))]
     ScriptFailedError {
         script: String,
         args: String,
         exit_code: i32,
+        stderr: String,
+    },
+    #[error("Error while getting met data: {script} did not finish within {timeout_seconds} seconds and was stopped")]
+    ScriptTimeoutError {
+        script: String,
+        timeout_seconds: f64,
+    },
+    #[error("Error while getting met data: could not read output from {script}: {error}")]
+    ScriptOutputError {
+        script: String,
+        error: std::io::Error,
     },
     #[error("Error while getting met data: could not parse entry {entry_num}, error was: {error}. (Entry value was: '{entry_str}')")]
     EntryParseError {
@@ -30,6 +51,18 @@ pub(super) enum ScriptMetError {
 }
 
 impl ScriptMetError {
+    /// Whether running the script again might succeed, i.e. this is a failure of the
+    /// script's process (it could not be started, exited with an error, or timed out)
+    /// rather than a problem with the configuration or the script's output.
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::ScriptRunError { .. }
+                | Self::ScriptFailedError { .. }
+                | Self::ScriptTimeoutError { .. }
+        )
+    }
+
     fn script_run_error<S: ToString>(script: S, error: std::io::Error) -> Self {
         Self::ScriptRunError {
             script: script.to_string(),
@@ -37,10 +70,18 @@ impl ScriptMetError {
         }
     }
 
+    fn script_not_executable_error<S: ToString, R: ToString>(script: S, reason: R) -> Self {
+        Self::ScriptNotExecutableError {
+            script: script.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
     fn script_failed_error<S: ToString>(
         script: S,
         args: &[String],
         exit_code: Option<i32>,
+        stderr: String,
     ) -> Self {
         let args = args.join(" ");
         // If terminated by a signal, the exit code will apparently be none.
@@ -50,6 +91,7 @@ impl ScriptMetError {
             script: script.to_string(),
             args,
             exit_code,
+            stderr,
         }
     }
 
@@ -63,6 +105,50 @@ impl ScriptMetError {
     }
 }
 
+/// How many lines from the end of a met script's stderr to include in the error if it fails.
+const SCRIPT_STDERR_TAIL_LINES: usize = 20;
+
+/// Format the end of a failed script's stderr to append to its error message.
+fn format_stderr_tail(stderr: &str) -> String {
+    let lines = stderr.lines().collect_vec();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let tail = &lines[lines.len().saturating_sub(SCRIPT_STDERR_TAIL_LINES)..];
+    let skipped = if tail.len() < lines.len() {
+        format!(" (last {} lines)", tail.len())
+    } else {
+        String::new()
+    };
+    format!(". Its stderr was{skipped}:\n{}", tail.join("\n"))
+}
+
+/// The longest that the wait between met script retries can grow to by doubling.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The wait before the retry after one that waited `delay`: double the delay, but no more than
+/// [`MAX_RETRY_DELAY`] unless `first_delay` was already longer than that.
+fn next_retry_delay(delay: Duration, first_delay: Duration) -> Duration {
+    delay
+        .saturating_mul(2)
+        .min(MAX_RETRY_DELAY.max(first_delay))
+}
+
+/// Settings controlling how an external met script is run
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct ScriptRunOptions {
+    /// If given, the script is stopped and an error returned if it runs longer than this.
+    pub(super) timeout: Option<Duration>,
+    /// If `true`, the ZPD times of all the interferograms are written to the script's stdin,
+    /// one ISO-8601 datetime per line.
+    pub(super) pass_igram_times: bool,
+    /// How many more times to run the script if it fails (see [`ScriptMetError::is_retryable`]).
+    pub(super) retries: u32,
+    /// How long to wait before the first retry; this doubles for each later retry, up to
+    /// [`MAX_RETRY_DELAY`] (or this delay, if it is longer).
+    pub(super) retry_delay: Duration,
+}
+
 /// Get meteorology for an I2S catalog by calling an external script or program
 ///
 /// # Arguments
@@ -71,55 +157,282 @@ impl ScriptMetError {
 /// - `args`: a list of arguments to pass to the program. Any paths must be absolute or
 ///   relative to the working directory.
 /// - `working_dir`: path (preferably absolute) in which to execute this script.
-
+/// - `em27_zpd_times`: the ZPD times of the interferograms that need met data; the first and
+///   last are used for the `{FIRST_IGRAM_TIME}` and `{LAST_IGRAM_TIME}` argument placeholders.
+/// - `site_id`: the site ID to use for the `{SITE_ID}` argument placeholder, if known.
+/// - `detectors`: the detector set to use for the `{DETECTORS}` argument placeholder, if known.
+/// - `options`: additional settings for running the script, see [`ScriptRunOptions`].
+///
+/// If the script cannot be started, exits with a non-zero code, or times out, it is run again
+/// up to `options.retries` times. Errors in its output are not retried, since running it again
+/// would most likely give the same output.
 pub(super) fn read_met_with_script<S: AsRef<str>>(
     script: &str,
     args: &[S],
     working_dir: &Path,
-    first_igram_time: chrono::DateTime<chrono::FixedOffset>,
-    last_igram_time: chrono::DateTime<chrono::FixedOffset>,
-) -> Result<Vec<MetEntry>, ScriptMetError> {
+    em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
+    site_id: Option<&str>,
+    detectors: Option<DetectorSet>,
+    options: ScriptRunOptions,
+) -> error_stack::Result<Vec<MetEntry>, ScriptMetError> {
+    let (first_igram_time, last_igram_time) =
+        get_igram_time_span(em27_zpd_times).unwrap_or_else(|| {
+            (
+                chrono::DateTime::from_timestamp_nanos(0).into(),
+                chrono::DateTime::from_timestamp_nanos(0).into(),
+            )
+        });
     let args: Vec<String> = args
         .iter()
-        .map(|a| render_met_script_arg_pattern(a.as_ref(), first_igram_time, last_igram_time))
-        .try_collect()?;
+        .map(|a| {
+            render_met_script_arg_pattern(
+                a.as_ref(),
+                first_igram_time,
+                last_igram_time,
+                site_id,
+                detectors,
+            )
+        })
+        .try_collect()
+        .map_err(ScriptMetError::from)?;
+
+    check_script_executable(script, working_dir)?;
+
+    let mut attempt = 0;
+    let mut delay = options.retry_delay;
+    loop {
+        attempt += 1;
+        let err = match run_met_script_once(script, &args, working_dir, em27_zpd_times, options) {
+            Ok(entries) => return Ok(entries),
+            Err(e) if !e.current_context().is_retryable() => return Err(e),
+            Err(e) => e,
+        };
 
+        if attempt > options.retries {
+            if options.retries > 0 {
+                error!(
+                    "Met script '{script}' failed on all {attempt} attempts, giving up: {}",
+                    err.current_context()
+                );
+            }
+            return Err(err);
+        }
+
+        warn!(
+            "Met script '{script}' failed (attempt {attempt} of {}), retrying in {:.1} s: {}",
+            options.retries + 1,
+            delay.as_secs_f64(),
+            err.current_context()
+        );
+        std::thread::sleep(delay);
+        delay = next_retry_delay(delay, options.retry_delay);
+    }
+}
+
+/// Run the met script once, see [`read_met_with_script`]. `args` must already have their placeholders filled in.
+fn run_met_script_once(
+    script: &str,
+    args: &[String],
+    working_dir: &Path,
+    em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
+    options: ScriptRunOptions,
+) -> error_stack::Result<Vec<MetEntry>, ScriptMetError> {
     info!(
         "Calling script '{script}' in directory '{}' to get met entries",
         working_dir.display()
     );
-    let output = Command::new(script)
-        .args(&args)
+    let mut cmd = Command::new(script);
+    cmd.args(args)
         .current_dir(working_dir)
-        .output()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if options.pass_igram_times {
+        cmd.stdin(Stdio::piped());
+    }
+    let mut child = cmd
+        .spawn()
         .map_err(|e| ScriptMetError::script_run_error(script, e))?;
 
-    if !output.status.success() {
-        return Err(ScriptMetError::script_failed_error(
+    if options.pass_igram_times {
+        let mut stdin = child.stdin.take().ok_or_else(|| {
+            ScriptMetError::script_run_error(
+                script,
+                std::io::Error::other("could not connect to the script's stdin"),
+            )
+        })?;
+        let times_input = format_igram_times(em27_zpd_times);
+        let script_name = script.to_string();
+        // Write from a separate thread, so that a script that writes output before it has
+        // read all of its input can't deadlock with us. Dropping stdin at the end of the
+        // thread closes it, so the script will see the end of the input.
+        std::thread::spawn(move || {
+            if let Err(e) = stdin.write_all(times_input.as_bytes()) {
+                warn!("Could not write all interferogram times to the stdin of {script_name}: {e}");
+            }
+        });
+    }
+
+    // Parse the output in a separate thread as it is produced, so that we can
+    // enforce the timeout here and don't need to hold the full output in memory.
+    let stdout = child.stdout.take().ok_or_else(|| {
+        ScriptMetError::script_run_error(
+            script,
+            std::io::Error::other("could not connect to the script's stdout"),
+        )
+    })?;
+    let script_name = script.to_string();
+    let reader = std::thread::spawn(move || parse_met_entries(stdout, &script_name));
+
+    // Likewise collect stderr as it is written, so that a script that writes a lot to it can't
+    // block, and so that we can include it in the error if the script fails.
+    let mut stderr = child.stderr.take().ok_or_else(|| {
+        ScriptMetError::script_run_error(
+            script,
+            std::io::Error::other("could not connect to the script's stderr"),
+        )
+    })?;
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buf = vec![];
+        let _ = stderr.read_to_end(&mut buf);
+        String::from_utf8_lossy(&buf).into_owned()
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        let status = child
+            .try_wait()
+            .map_err(|e| ScriptMetError::script_run_error(script, e))?;
+        if let Some(status) = status {
+            break status;
+        }
+
+        if let Some(timeout) = options.timeout {
+            if start.elapsed() > timeout {
+                // Ignore errors here, since the process may have exited on its own in the meantime.
+                let _ = child.kill();
+                let _ = child.wait();
+                // If the output could not be parsed, report that rather than the timeout, so that
+                // it is not retried: running the script again would give the same output.
+                if reader.is_finished() {
+                    reader
+                        .join()
+                        .expect("Thread parsing the met script output panicked")?;
+                }
+                return Err(ScriptMetError::ScriptTimeoutError {
+                    script: script.to_string(),
+                    timeout_seconds: timeout.as_secs_f64(),
+                }
+                .into());
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    // Wait for the output to be parsed before checking the exit status: if the script wrote
+    // something we couldn't parse, the reader stops reading, and the script may then be killed
+    // by SIGPIPE. The parse error is the real problem in that case, so it takes precedence.
+    let parsed = reader
+        .join()
+        .expect("Thread parsing the met script output panicked");
+    let stderr = stderr_reader
+        .join()
+        .expect("Thread reading the met script stderr panicked");
+    let entries = parsed?;
+
+    if !status.success() {
+        return Err(
+            ScriptMetError::script_failed_error(script, args, status.code(), stderr).into(),
+        );
+    }
+
+    if !stderr.trim().is_empty() {
+        debug!("stderr from met script '{script}':\n{stderr}");
+    }
+    Ok(entries)
+}
+
+/// Check that `script` exists and can be executed, so that we can give a more helpful error
+/// than the OS's "permission denied" if not.
+///
+/// A `script` without a path separator (e.g. "python3") is searched for on the `PATH`, anything
+/// else is taken as relative to `working_dir`. The check for execute permission is only done
+/// on Unix systems.
+fn check_script_executable(script: &str, working_dir: &Path) -> Result<(), ScriptMetError> {
+    if !script.contains(std::path::is_separator) {
+        return match which::which(script) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ScriptMetError::script_not_executable_error(
+                script,
+                "could not be found on the PATH",
+            )),
+        };
+    }
+
+    let script_path = working_dir.join(script);
+    let metadata = std::fs::metadata(&script_path).map_err(|_| {
+        ScriptMetError::script_not_executable_error(
+            script,
+            format!("does not exist (looked for {})", script_path.display()),
+        )
+    })?;
+
+    if !metadata.is_file() {
+        return Err(ScriptMetError::script_not_executable_error(
             script,
-            &args,
-            output.status.code(),
+            "is not a file",
         ));
     }
 
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(ScriptMetError::script_not_executable_error(
+                script,
+                "does not have execute permission",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Format the interferogram ZPD times as the input for a met script, one ISO-8601 (RFC 3339)
+/// datetime per line.
+fn format_igram_times(em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>]) -> String {
+    let mut s = String::new();
+    for t in em27_zpd_times {
+        s.push_str(&t.to_rfc3339());
+        s.push('\n');
+    }
+    s
+}
+
+/// Parse the stdout of a met script, which should have one JSON [`MetEntry`] per line.
+fn parse_met_entries<R: Read>(stdout: R, script: &str) -> Result<Vec<MetEntry>, ScriptMetError> {
     let mut met_entries = vec![];
 
     // In principle, this should handle OSes that LF, CR+LF, or CR only newlines.
-    // By skipping empty lines, if we get a CR+LF, the LF on its own created by
-    // splitting on the CR should be skipped.
-
+    // By skipping empty lines, if we get a CR+LF, the empty line between the CR
+    // and LF will be skipped.
     let mut ientry = 0;
-    for line in output.stdout.split(|b| *b == b'\n' || *b == b'\r') {
-        let line = line.trim_ascii();
-        if !line.is_empty() {
-            ientry += 1;
-            let entry: MetEntry = serde_json::from_slice(&line)
-                .map_err(|e| ScriptMetError::entry_parse_error(ientry, e, line))?;
-            trace!(
-                "Deserialized line ({}) from met script as {entry:?}",
-                String::from_utf8_lossy(&line)
-            );
-            met_entries.push(entry);
+    for chunk in BufReader::new(stdout).split(b'\n') {
+        let chunk = chunk.map_err(|error| ScriptMetError::ScriptOutputError {
+            script: script.to_string(),
+            error,
+        })?;
+        for line in chunk.split(|b| *b == b'\r') {
+            let line = line.trim_ascii();
+            if !line.is_empty() {
+                ientry += 1;
+                let entry: MetEntry = serde_json::from_slice(line)
+                    .map_err(|e| ScriptMetError::entry_parse_error(ientry, e, line))?;
+                trace!(
+                    "Deserialized line ({}) from met script as {entry:?}",
+                    String::from_utf8_lossy(line)
+                );
+                met_entries.push(entry);
+            }
         }
     }
 
@@ -137,7 +450,16 @@ mod tests {
         let t1 = chrono::DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
         let t2 = chrono::DateTime::parse_from_rfc3339("2025-03-02T00:00:00Z").unwrap();
         let wd = crate_root.join("test_inputs");
-        let entries = read_met_with_script::<String>("./dummy_met.py", &[], &wd, t1, t2).unwrap();
+        let entries = read_met_with_script::<String>(
+            "./dummy_met.py",
+            &[],
+            &wd,
+            &[t1, t2],
+            None,
+            None,
+            ScriptRunOptions::default(),
+        )
+        .unwrap();
         let expected = vec![
             MetEntry {
                 datetime: chrono::DateTime::parse_from_rfc3339("2025-03-01T12:00:00Z").unwrap(),
@@ -169,4 +491,249 @@ mod tests {
             assert_eq!(a, b);
         }
     }
+
+    #[test]
+    fn test_ext_met_script_timeout() {
+        let t = chrono::DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
+        let start = Instant::now();
+        let res = read_met_with_script(
+            "sleep",
+            &["10"],
+            Path::new("."),
+            &[t],
+            None,
+            None,
+            ScriptRunOptions {
+                timeout: Some(Duration::from_millis(200)),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            res.unwrap_err().current_context(),
+            ScriptMetError::ScriptTimeoutError { .. }
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ext_met_script_retries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let t = chrono::DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
+        let options = |retries| ScriptRunOptions {
+            retries,
+            retry_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        std::fs::write(
+            dir.join("entries.json"),
+            r#"{"datetime": "2025-03-01T06:00:00Z", "pressure": 1013.25}"#,
+        )
+        .unwrap();
+        // Fails the first time it is run in `dir`, then succeeds. (Braces would be taken as
+        // argument placeholders, so the output comes from a file.)
+        let flaky = [
+            "-c",
+            "echo run >> runs; if [ -e ran ]; then cat entries.json; else touch ran; exit 1; fi",
+        ];
+        let count_runs = || {
+            let n = std::fs::read_to_string(dir.join("runs"))
+                .unwrap()
+                .lines()
+                .count();
+            std::fs::remove_file(dir.join("runs")).unwrap();
+            let _ = std::fs::remove_file(dir.join("ran"));
+            n
+        };
+
+        let res = read_met_with_script("sh", &flaky, dir, &[t], None, None, options(0));
+        assert!(matches!(
+            res.unwrap_err().current_context(),
+            ScriptMetError::ScriptFailedError { .. }
+        ));
+        assert_eq!(count_runs(), 1);
+
+        let entries =
+            read_met_with_script("sh", &flaky, dir, &[t], None, None, options(2)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(count_runs(), 2);
+
+        // Bad output is not retried
+        let bad_output = ["-c", "echo run >> runs; echo 'not json'"];
+        let res = read_met_with_script("sh", &bad_output, dir, &[t], None, None, options(2));
+        assert!(matches!(
+            res.unwrap_err().current_context(),
+            ScriptMetError::EntryParseError { .. }
+        ));
+        assert_eq!(count_runs(), 1);
+
+        // Nor is bad output from a script that then hangs until it times out
+        let bad_then_hang = ["-c", "echo run >> runs; echo 'not json'; exec sleep 10"];
+        let res = read_met_with_script(
+            "sh",
+            &bad_then_hang,
+            dir,
+            &[t],
+            None,
+            None,
+            ScriptRunOptions {
+                timeout: Some(Duration::from_millis(500)),
+                ..options(2)
+            },
+        );
+        assert!(matches!(
+            res.unwrap_err().current_context(),
+            ScriptMetError::EntryParseError { .. }
+        ));
+        assert_eq!(count_runs(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ext_met_script_failure_errors() {
+        let t = chrono::DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
+
+        // The script's stderr should be in the error message
+        let res = read_met_with_script(
+            "sh",
+            &["-c", "echo 'no met for this day' >&2; exit 3"],
+            Path::new("."),
+            &[t],
+            None,
+            None,
+            ScriptRunOptions::default(),
+        );
+        let err = res.unwrap_err();
+        let err = err.current_context();
+        assert!(matches!(
+            err,
+            ScriptMetError::ScriptFailedError { exit_code: 3, .. }
+        ));
+        assert!(err.to_string().contains("no met for this day"), "{err}");
+
+        // A script that keeps writing after bad output will get SIGPIPE once we stop reading,
+        // but the parse error is what should be reported.
+        let res = read_met_with_script(
+            "sh",
+            &[
+                "-c",
+                "echo 'not json'; sleep 0.2; while true; do echo line; done",
+            ],
+            Path::new("."),
+            &[t],
+            None,
+            None,
+            ScriptRunOptions::default(),
+        );
+        assert!(matches!(
+            res.unwrap_err().current_context(),
+            ScriptMetError::EntryParseError { entry_num: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_next_retry_delay() {
+        let first = Duration::from_secs(5);
+        let mut delay = first;
+        let mut delays = vec![];
+        for _ in 0..8 {
+            delays.push(delay.as_secs());
+            delay = next_retry_delay(delay, first);
+        }
+        assert_eq!(delays, [5, 10, 20, 40, 80, 160, 300, 300]);
+
+        // A first delay longer than the cap is kept
+        let long = Duration::from_secs(600);
+        assert_eq!(next_retry_delay(long, long), long);
+    }
+
+    #[test]
+    fn test_format_stderr_tail() {
+        assert_eq!(format_stderr_tail(""), "");
+        assert_eq!(format_stderr_tail("oops\n"), ". Its stderr was:\noops");
+        let long = (1..=25).map(|i| format!("line {i}\n")).collect::<String>();
+        let tail = format_stderr_tail(&long);
+        assert!(
+            tail.starts_with(". Its stderr was (last 20 lines):\nline 6\n"),
+            "{tail}"
+        );
+        assert!(tail.ends_with("line 25"), "{tail}");
+    }
+
+    #[test]
+    fn test_ext_met_script_igram_times() {
+        let crate_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let wd = crate_root.join("test_inputs");
+        let times = [
+            chrono::DateTime::parse_from_rfc3339("2025-03-01T09:01:02-07:00").unwrap(),
+            chrono::DateTime::parse_from_rfc3339("2025-03-01T09:02:30-07:00").unwrap(),
+            chrono::DateTime::parse_from_rfc3339("2025-03-01T09:04:01-07:00").unwrap(),
+        ];
+        let entries = read_met_with_script::<String>(
+            "./stdin_times_met.py",
+            &[],
+            &wd,
+            &times,
+            None,
+            None,
+            ScriptRunOptions {
+                pass_igram_times: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let met_times = entries.iter().map(|e| e.datetime).collect_vec();
+        assert_eq!(met_times, times);
+    }
+
+    #[test]
+    fn test_check_script_executable() {
+        let crate_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let wd = crate_root.join("test_inputs");
+        check_script_executable("./dummy_met.py", &wd).unwrap();
+        check_script_executable("sh", &wd).unwrap();
+
+        let e = check_script_executable("./no_such_met.py", &wd).unwrap_err();
+        assert!(matches!(e, ScriptMetError::ScriptNotExecutableError { .. }));
+        let e = check_script_executable("egi-no-such-program", &wd).unwrap_err();
+        assert!(matches!(e, ScriptMetError::ScriptNotExecutableError { .. }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_script_not_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("met.py"), "print('hi')\n").unwrap();
+
+        let e = check_script_executable("./met.py", dir.path()).unwrap_err();
+        assert!(e.to_string().contains("chmod +x"));
+    }
+
+    #[test]
+    fn test_format_igram_times() {
+        let times = [
+            chrono::DateTime::parse_from_rfc3339("2025-03-01T09:01:02-07:00").unwrap(),
+            chrono::DateTime::parse_from_rfc3339("2025-03-01T16:02:30Z").unwrap(),
+        ];
+        assert_eq!(
+            format_igram_times(&times),
+            "2025-03-01T09:01:02-07:00\n2025-03-01T16:02:30+00:00\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_met_entries_line_endings() {
+        let output = b"{\"datetime\": \"2025-03-01T12:00:00Z\", \"pressure\": 1000.0}\r\n\r{\"datetime\": \"2025-03-01T13:00:00Z\", \"pressure\": 1001.0}\r{\"datetime\": \"2025-03-01T14:00:00Z\", \"pressure\": 1002.0}";
+        let entries = parse_met_entries(&output[..], "test").unwrap();
+        let pressures = entries.iter().map(|e| e.pressure).collect_vec();
+        assert_eq!(pressures, [1000.0, 1001.0, 1002.0]);
+
+        let output = b"{\"datetime\": \"2025-03-01T12:00:00Z\", \"pressure\": 1000.0}\nnot json\n";
+        let err = parse_met_entries(&output[..], "test").unwrap_err();
+        assert!(matches!(
+            err,
+            ScriptMetError::EntryParseError { entry_num: 2, .. }
+        ));
+    }
 }
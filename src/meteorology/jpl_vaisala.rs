@@ -1,20 +1,22 @@
-use std::{fmt::Display, path::Path};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use error_stack::ResultExt;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-use ggg_rs::utils::{read_unknown_encoding_file, EncodingError};
+use ggg_rs::utils::read_unknown_encoding_file;
 
 use super::MetEntry;
 
 #[derive(Debug, thiserror::Error)]
 pub(super) enum JplMetError {
-    #[error("Could not open file: {0}")]
-    IoError(#[from] std::io::Error),
-    #[error("Could not decode contents of file: {0}")]
-    EncodingError(#[from] EncodingError),
+    #[error("Could not read {}", .0.display())]
+    ReadError(PathBuf),
     #[error("JPL Vaisala file missing header line")]
     HeaderLineMissing,
     #[error("JPL Vaisala file missing fields from the header: {}", .0.join(", "))]
@@ -30,8 +32,9 @@ pub(super) enum JplMetError {
 pub(super) fn read_jpl_vaisala_met(
     met_file: &Path,
     tz_offset: FixedOffset,
-) -> Result<Vec<MetEntry>, JplMetError> {
-    let contents = read_unknown_encoding_file(met_file)?;
+) -> error_stack::Result<Vec<MetEntry>, JplMetError> {
+    let contents = read_unknown_encoding_file(met_file)
+        .change_context_lazy(|| JplMetError::ReadError(met_file.to_path_buf()))?;
     let mut lines = contents.as_str().lines();
 
     // Identify the indices for the quantities we care about
@@ -50,16 +53,23 @@ pub(super) fn read_jpl_vaisala_met(
         }
         let parts = line.split(',').collect_vec();
 
-        let temperature = parse_line_numeric_part(&parts, Col::Temp, &column_inds)?;
-        let pressure = parse_line_numeric_part(&parts, Col::Pres, &column_inds)?;
-        let humidity = parse_line_numeric_part(&parts, Col::RH, &column_inds)?;
+        // Temperature and humidity are optional, since some deployments disable those sensors.
+        let temperature = column_inds
+            .temp
+            .map(|i| parse_line_numeric_part(&parts, Col::Temp, i))
+            .transpose()?;
+        let pressure = parse_line_numeric_part(&parts, Col::Pres, column_inds.pres)?;
+        let humidity = column_inds
+            .rh
+            .map(|i| parse_line_numeric_part(&parts, Col::RH, i))
+            .transpose()?;
         let datetime = parse_line_datetime(&parts, &column_inds, tz_offset)?;
 
         met_data.push(MetEntry {
             datetime,
-            temperature: Some(temperature),
+            temperature,
             pressure,
-            humidity: Some(humidity),
+            humidity,
         })
     }
 
@@ -84,12 +94,15 @@ fn parse_line_datetime(
             format!("expected YYYYMMDD, got {yyyymmdd_str}. Parsing error was {e}"),
         )
     })?;
-    let time = NaiveTime::parse_from_str(&hhmm_str, "%H:%M").map_err(|e| {
-        JplMetError::ParsingError(
-            Col::Time,
-            format!("expected HH:MM, got {hhmm_str}. Parsing error was {e}"),
-        )
-    })?;
+    // Newer versions of the logging script include seconds in the time, so accept either format.
+    let time = NaiveTime::parse_from_str(hhmm_str, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(hhmm_str, "%H:%M"))
+        .map_err(|e| {
+            JplMetError::ParsingError(
+                Col::Time,
+                format!("expected HH:MM:SS or HH:MM, got {hhmm_str}. Parsing error was {e}"),
+            )
+        })?;
 
     match offset.from_local_datetime(&date.and_time(time)) {
         chrono::LocalResult::Single(t) => Ok(t),
@@ -99,7 +112,7 @@ fn parse_line_datetime(
     }
 }
 
-fn parse_line_numeric_part(parts: &[&str], col: Col, inds: &ColInds) -> Result<f64, JplMetError> {
+fn parse_line_numeric_part(parts: &[&str], col: Col, i: usize) -> Result<f64, JplMetError> {
     static HUM_PAT: &str = r"Ua=(\d+\.\d+)P";
     static PRES_PAT: &str = r"Pa=(\d+\.\d+)H";
     static TEMP_PAT: &str = r"Ta=(\d+\.\d+)C";
@@ -107,10 +120,10 @@ fn parse_line_numeric_part(parts: &[&str], col: Col, inds: &ColInds) -> Result<f
     static RE_PRES: Lazy<Regex> = Lazy::new(|| Regex::new(PRES_PAT).unwrap());
     static RE_TEMP: Lazy<Regex> = Lazy::new(|| Regex::new(TEMP_PAT).unwrap());
 
-    let (i, re, pat) = match col {
-        Col::Pres => (inds.pres, &RE_PRES, PRES_PAT),
-        Col::Temp => (inds.temp, &RE_TEMP, TEMP_PAT),
-        Col::RH => (inds.rh, &RE_HUM, HUM_PAT),
+    let (re, pat) = match col {
+        Col::Pres => (&RE_PRES, PRES_PAT),
+        Col::Temp => (&RE_TEMP, TEMP_PAT),
+        Col::RH => (&RE_HUM, HUM_PAT),
         _ => panic!("Tried to call parse_line_numeric_part with col = {col}, a non-numeric column"),
     };
 
@@ -151,13 +164,15 @@ impl Display for Col {
     }
 }
 
+/// Column indices for the quantities in a JPL Vaisala file. Temperature and humidity
+/// are `None` if the file does not have those columns.
 #[derive(Debug, Default)]
 struct ColInds {
     date: usize,
     time: usize,
     pres: usize,
-    temp: usize,
-    rh: usize,
+    temp: Option<usize>,
+    rh: Option<usize>,
 }
 
 fn header_to_inds(header: &[&str]) -> Result<ColInds, JplMetError> {
@@ -170,22 +185,20 @@ fn header_to_inds(header: &[&str]) -> Result<ColInds, JplMetError> {
         missing.push("YYYYMMDD");
     }
 
-    if let Some(i) = header.iter().position(|&s| s == "HH:MM") {
+    if let Some(i) = header.iter().position(|&s| s == "HH:MM" || s == "HH:MM:SS") {
         inds.time = i;
     } else {
         missing.push("HH:MM");
     }
 
-    if let Some(i) = header.iter().position(|&s| s == "Temperature") {
-        inds.temp = i;
-    } else {
-        missing.push("Temperature");
+    inds.temp = header.iter().position(|&s| s == "Temperature");
+    if inds.temp.is_none() {
+        log::debug!("JPL Vaisala file has no Temperature column, temperature will be missing");
     }
 
-    if let Some(i) = header.iter().position(|&s| s == "Humidity") {
-        inds.rh = i;
-    } else {
-        missing.push("Humidity");
+    inds.rh = header.iter().position(|&s| s == "Humidity");
+    if inds.rh.is_none() {
+        log::debug!("JPL Vaisala file has no Humidity column, humidity will be missing");
     }
 
     if let Some(i) = header.iter().position(|&s| s == "Pressure") {
@@ -200,3 +213,60 @@ fn header_to_inds(header: &[&str]) -> Result<ColInds, JplMetError> {
         Err(JplMetError::HeaderMissingFields(missing))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_read_jpl_vaisala_seconds() {
+        let crate_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let met_file = crate_root
+            .join("test_inputs")
+            .join("jpl_vaisala_seconds.txt");
+        let tz = FixedOffset::west_opt(7 * 3600).unwrap();
+        let entries = read_jpl_vaisala_met(&met_file, tz).unwrap();
+
+        // The first data line is a junk line and should be skipped
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].datetime,
+            DateTime::parse_from_rfc3339("2023-08-26T16:14:35-07:00").unwrap()
+        );
+        assert_eq!(entries[0].pressure, 972.7);
+        assert_eq!(entries[0].temperature, Some(26.8));
+        assert_eq!(entries[0].humidity, Some(39.3));
+        // Lines without seconds are still accepted
+        assert_eq!(
+            entries[2].datetime,
+            DateTime::parse_from_rfc3339("2023-08-26T16:16:00-07:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_jpl_vaisala_no_humidity() {
+        let contents = "YYYYMMDD,HH:MM,Address,Temperature,Pressure
+20230826,16:14,0R2,Ta=26.8C,Pa=972.7H
+";
+        let header = contents.lines().next().unwrap().split(',').collect_vec();
+        let inds = header_to_inds(&header).unwrap();
+        assert!(inds.temp.is_some());
+        assert!(inds.rh.is_none());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let met_file = tmp.path().join("vaisala.txt");
+        std::fs::write(&met_file, contents).unwrap();
+        let entries = read_jpl_vaisala_met(&met_file, FixedOffset::east_opt(0).unwrap());
+
+        let entries = entries.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].pressure, 972.7);
+        assert_eq!(entries[0].temperature, Some(26.8));
+        assert_eq!(entries[0].humidity, None);
+
+        let header = ["YYYYMMDD", "HH:MM", "Temperature", "Humidity"];
+        assert!(header_to_inds(&header).is_err());
+    }
+}
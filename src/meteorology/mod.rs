@@ -5,40 +5,48 @@ use std::{
 
 use chrono::{DateTime, FixedOffset};
 use error_stack::{Context, ResultExt};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use ggg_rs::utils::EncodingError;
 
-use crate::path_relative_to_config;
+use crate::{config::DetectorSet, path_relative_to_config, ConfigPathError};
 mod cit_csv;
 mod external_script;
 mod jpl_vaisala;
 mod legacy;
+mod zeno;
 
 /// This struct indicates an error while reading input met data and interpolating it to
-/// the ZPD time of EM27 interferograms.
+/// the ZPD time of EM27 interferograms. The specific cause (e.g. a [`MetErrorType`] or an
+/// error from the reader for the source's file format) is the previous context in the
+/// error report.
 #[derive(Debug)]
 pub struct MetError {
     met_source_type: MetSource,
-    /// An enum describing the reason for the error.
-    pub reason: MetErrorType,
+}
+
+impl MetError {
+    fn new(met_source_type: &MetSource) -> Self {
+        Self {
+            met_source_type: met_source_type.to_owned(),
+        }
+    }
 }
 
 impl Display for MetError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Error reading met data from {}: {}",
+            "Error reading met data from {}",
             self.met_source_type.long_string(),
-            self.reason
         )
     }
 }
 
 impl Context for MetError {}
 
-/// An enum describing the reason interpolating met data to interferogram ZPDs failed
-/// TODO: migrate to using error_stack instead of having this internal error type.
+/// An enum describing problems with met configuration or with matching met data up to
+/// interferograms that are not specific to one met file format.
 #[derive(Debug, thiserror::Error)]
 pub enum MetErrorType {
     /// This represents a problem reading the contents of the met file. This might include
@@ -52,9 +60,10 @@ pub enum MetErrorType {
     #[error("Problem with the met config file: {0}")]
     ConfigError(String),
 
-    /// This represents a problem parsing the values stored in the met file
-    #[error("Error during parsing: {0}")]
-    ParsingError(String),
+    /// This indicates that the relative paths in the met config file could not be resolved because the
+    /// directory containing the config file could not be determined.
+    #[error("{0}")]
+    ConfigPathError(#[from] ConfigPathError),
 
     /// This error indicates that a met file cannot be matched up with a given batch of interferograms
     /// because the interferograms do not have a consistent time zone. This error arises when a met input
@@ -64,79 +73,6 @@ pub enum MetErrorType {
     /// interferograms have differing GMT offsets, this assumption is not straightforward.
     #[error("This met type requires that all interferograms being matched with it have the same time zone.")]
     BadTimezoneError,
-
-    /// Placeholder during migration to error_stack
-    #[error("see following error messages for cause")]
-    Stack,
-}
-
-impl From<jpl_vaisala::JplMetError> for MetErrorType {
-    fn from(value: jpl_vaisala::JplMetError) -> Self {
-        match value {
-            jpl_vaisala::JplMetError::IoError(e) => MetErrorType::IoError(e.into()),
-            jpl_vaisala::JplMetError::EncodingError(e) => MetErrorType::IoError(e),
-            jpl_vaisala::JplMetError::HeaderLineMissing => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            jpl_vaisala::JplMetError::HeaderMissingFields(_) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            jpl_vaisala::JplMetError::LineTooShort(_) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            jpl_vaisala::JplMetError::ParsingError(_, _) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            jpl_vaisala::JplMetError::InvalidTime(_, _, _) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-        }
-    }
-}
-
-impl From<cit_csv::CitMetError> for MetErrorType {
-    fn from(value: cit_csv::CitMetError) -> Self {
-        match value {
-            cit_csv::CitMetError::IoError(e) => MetErrorType::IoError(e.into()),
-            cit_csv::CitMetError::UnknownSite(_) => MetErrorType::ConfigError(value.to_string()),
-            cit_csv::CitMetError::HeaderLineMissing(_) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            cit_csv::CitMetError::UnexpectedColumn {
-                file: _,
-                col_index: _,
-                expected: _,
-            } => MetErrorType::ParsingError(value.to_string()),
-            cit_csv::CitMetError::LineTooShort(_, _) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            cit_csv::CitMetError::ParsingError {
-                file: _,
-                line: _,
-                col: _,
-                reason: _,
-            } => MetErrorType::ParsingError(value.to_string()),
-            cit_csv::CitMetError::TimeMismatch {
-                file1: _,
-                file2: _,
-                cause: _,
-            } => MetErrorType::ParsingError(value.to_string()),
-            cit_csv::CitMetError::TimezoneError(_) => MetErrorType::ParsingError(value.to_string()),
-        }
-    }
-}
-
-impl From<legacy::LegacyMetError> for MetErrorType {
-    fn from(value: legacy::LegacyMetError) -> Self {
-        match value {
-            legacy::LegacyMetError::InvalidTimeFormat(_) => {
-                MetErrorType::ParsingError(value.to_string())
-            }
-            legacy::LegacyMetError::InvalidTime(_) => MetErrorType::ParsingError(value.to_string()),
-            legacy::LegacyMetError::ReadError(_) => MetErrorType::ParsingError(value.to_string()),
-            legacy::LegacyMetError::CsvError(_) => MetErrorType::ParsingError(value.to_string()),
-        }
-    }
 }
 
 /// A structure represting a single set of meteorology measurements for one time
@@ -164,7 +100,7 @@ impl From<legacy::LegacyMetError> for MetErrorType {
 /// Note that the datetime values must include a UTC offset. The first specifies 7 hours
 /// behind UTC with the trailing "-07:00" while the second indicates UTC with the "Z" suffix.
 ///
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct MetEntry {
     /// The time & date (with time zone) of the met data, note that it is assumed that
     /// the measurements are instantaneous at this time.
@@ -298,6 +234,10 @@ pub enum MetSource {
     /// 20230826,16:16,0R2,Ta=26.8C,Ua=40.3P,Pa=972.7H
     /// ```
     ///
+    /// Only the YYYYMMDD, HH:MM, and Pressure columns are required; if the Temperature or
+    /// Humidity column is absent (e.g. because that sensor was disabled), those values will
+    /// be missing. Times may also include seconds (with an "HH:MM:SS" header).
+    ///
     /// If the path for "file" is relative, it is interpreted as relative to the location
     /// of the met source file. That is, the example above means that the file
     /// `20230826_vaisala.txt` must be in the same directory as the JSON file.
@@ -318,6 +258,39 @@ pub enum MetSource {
         utc_offset: Option<f32>,
     },
 
+    /// Met data recorded by a Zeno (Coastal Environmental Systems) data logger.
+    /// The minimum JSON file corresponding to this variant would look like:
+    /// ```json
+    /// {
+    ///   "type": "ZenoV1",
+    ///   "file": "./20230826_zeno.txt"
+    /// }
+    /// ```
+    ///
+    /// The value of "type" must be *exactly* "ZenoV1". The value of "file" must be a path
+    /// to the ASCII records from the logger. Each record must be a comma-separated line with
+    /// the date (YYYY/MM/DD), time (HH:MM:SS), pressure (hPa), temperature (degrees C), and
+    /// relative humidity (%), in that order:
+    ///
+    /// ```text
+    /// DATE,TIME,PRES,TEMP,RH,BATT
+    /// 2023/08/26,16:15:00,972.7,26.8,39.3,13.1
+    /// 2023/08/26,16:16:00,972.7,26.9,39.8,13.1
+    /// ```
+    ///
+    /// Additional columns after the humidity are ignored, as are lines that do not start with
+    /// a digit (e.g. headers and logger status messages). An empty temperature or humidity
+    /// field is treated as a missing value; the pressure is required. As with "file" for
+    /// `JplVaisalaV1`, a relative path is interpreted as relative to the location of the JSON file.
+    ///
+    /// By default, the times are assumed to be in the same time zone as the interferograms.
+    /// If not, use the "utc_offset" key to specify the offset from UTC in hours, e.g.
+    /// `"utc_offset": -8.0` for Pacific Standard Time.
+    ZenoV1 {
+        file: PathBuf,
+        utc_offset: Option<f32>,
+    },
+
     /// Met data download from a Caltech weather station through http://tccon-weather.caltech.edu/index.php.
     /// The JSON file corresponding to this variant would look like:
     /// ```json
@@ -346,11 +319,18 @@ pub enum MetSource {
     /// "temp_file" and "humid_file" are optional (but highly recommended) and would point
     /// to the files for temperature and humidity, respectively. If any of these paths are
     /// relative, they are interpreted as relative to the configuration JSON file.
+    ///
+    /// By default, the temperature and humidity files must have exactly the same times as the
+    /// pressure file. Setting "strict_times" to `false` instead matches their values to the
+    /// pressure times by timestamp, using fill values for any times missing from the temperature
+    /// or humidity files.
     CitCsvV1 {
         pres_file: PathBuf,
         site: String,
         temp_file: Option<PathBuf>,
         humid_file: Option<PathBuf>,
+        #[serde(default = "default_true")]
+        strict_times: bool,
     },
 
     /// This input allows you to define an external script to call to retrieve the met data to
@@ -402,6 +382,12 @@ pub enum MetSource {
     /// _strongly_ recommended that you include the time zone in the datetime format and parse it
     /// in your script, rather that relying on the times to be in a specific time zone.
     ///
+    /// The `{SITE_ID}` and `{DETECTORS}` placeholders insert the two-character site ID and
+    /// the detector set ("single", "dual", or "midir") of the EM27 the catalog is being made
+    /// for. These are always available when running I2S prep daily; when creating a catalog
+    /// directly, they are only available if given on the command line, and using them
+    /// otherwise is an error.
+    ///
     /// The script must print a JSON representations of [`MetEntry`]s one per line to stdout.
     /// See the documentation for [`MetEntry`] for examples of how to write it as a JSON value.
     /// Most scripting languages should have built in support for writing data as JSON.
@@ -427,16 +413,170 @@ pub enum MetSource {
     /// This should make it easier for the scripts to emit an arbitrary number of [`MetEntry`]
     /// values, since it will not have to worry about correctly closing a list or omitting the
     /// final comma.
+    ///
+    /// If the script might take a long time or hang (e.g. if it queries a remote database),
+    /// set "timeout_seconds" to the maximum number of seconds it should be allowed to run.
+    /// If it runs longer than that, it will be stopped and the catalog creation will fail.
+    /// By default, there is no time limit.
+    ///
+    /// If the script needs the exact times of every interferogram rather than just the first
+    /// and last, set "pass_igram_times" to `true`. The ZPD times of all the interferograms will
+    /// then be written to the script's stdin, one per line, as ISO-8601 datetimes with a UTC
+    /// offset (e.g. `2025-03-01T09:01:02-07:00`). The script can read these until it reaches
+    /// the end of its input, and should output one [`MetEntry`] per time. This is `false` by
+    /// default, in which case nothing is written to the script's stdin.
+    ///
+    /// If the script can fail transiently (e.g. because a database it queries is sometimes
+    /// unreachable), set "retries" to the number of times to run it again after it fails,
+    /// i.e. exits with a non-zero code, cannot be started, or times out. EGI waits
+    /// "retry_delay_seconds" (default 5) before the first retry and doubles that wait for
+    /// each later one, up to 5 minutes. The script is not run again if its output cannot be
+    /// parsed. By default, the script is not retried.
     ExtScriptV1 {
         script: String,
         #[serde(default)]
         args: Vec<String>,
         #[serde(default = "curr_dir")]
         working_dir: PathBuf,
+        #[serde(default)]
+        timeout_seconds: Option<f64>,
+        #[serde(default)]
+        pass_igram_times: bool,
+        #[serde(default)]
+        retries: u32,
+        #[serde(default = "default_retry_delay_seconds")]
+        retry_delay_seconds: f64,
     },
 }
 
+/// The full contents of a met source configuration file: the [`MetSource`] describing
+/// where to get the met data from, plus options that apply regardless of the source type.
+///
+/// All of the options are given as additional keys alongside "type" in the JSON file.
+/// For example, to allow interferograms up to 30 minutes before the first or after the
+/// last met record to use the values from that record, a JSON file would have:
+///
+/// ```json
+/// {
+///   "type": "JplVaisalaV1",
+///   "file": "./20230826_vaisala.txt",
+///   "max_extrapolation_minutes": 30
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetConfig {
+    /// Where to get the met data from.
+    #[serde(flatten)]
+    pub source: MetSource,
+
+    /// How far outside the time span of the met data (in minutes) an interferogram's ZPD
+    /// time can be and still use the values from the nearest met record. Interferograms
+    /// further than this from the met data are treated as missing met. The default is 0,
+    /// meaning no extrapolation is done.
+    #[serde(default)]
+    pub max_extrapolation_minutes: f64,
+
+    /// The time zone of the timestamps in the met data, overriding the source's usual
+    /// assumption. This may be "utc", "igram" (the time zone of the interferograms'
+    /// ZPD times), or a number of hours east of UTC, e.g. -7. If not given, each source
+    /// type uses its default (see [`MetSource`]). See [`read_met_file`] for how this
+    /// applies to each source type.
+    #[serde(default)]
+    pub timezone: Option<MetTimezone>,
+}
+
+/// The time zone that the timestamps in a met source are in, see [`MetConfig::timezone`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetTimezone {
+    /// The timestamps are in UTC.
+    Utc,
+    /// The timestamps are in the same time zone as the interferograms' ZPD times, which
+    /// must all have the same UTC offset.
+    Igram,
+    /// The timestamps are in this fixed UTC offset.
+    Offset(FixedOffset),
+}
+
+impl MetTimezone {
+    fn resolve(
+        &self,
+        em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
+        met_type: &MetSource,
+    ) -> error_stack::Result<FixedOffset, MetError> {
+        match self {
+            MetTimezone::Utc => Ok(FixedOffset::east_opt(0).unwrap()),
+            MetTimezone::Igram => {
+                let em27_tz_offset =
+                    Timezones::check_consistent_timezones(em27_zpd_times.iter().copied());
+                get_em27_tz(em27_tz_offset, met_type)
+            }
+            MetTimezone::Offset(offset) => Ok(*offset),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MetTimezone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawTimezone {
+            Name(String),
+            Hours(f64),
+        }
+
+        match RawTimezone::deserialize(deserializer)? {
+            RawTimezone::Name(name) if name.eq_ignore_ascii_case("utc") => Ok(Self::Utc),
+            RawTimezone::Name(name) if name.eq_ignore_ascii_case("igram") => Ok(Self::Igram),
+            RawTimezone::Name(name) => Err(serde::de::Error::custom(format!(
+                "unknown timezone '{name}', expected \"utc\", \"igram\", or a number of hours"
+            ))),
+            RawTimezone::Hours(hours) => FixedOffset::east_opt((hours * 3600.0).round() as i32)
+                .map(Self::Offset)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!(
+                        "UTC offset {hours:+.2} is out of the allowed range (-24 to +24)"
+                    ))
+                }),
+        }
+    }
+}
+
+impl MetConfig {
+    /// Create a `MetConfig` instance from a JSON file.
+    ///
+    /// As with [`MetSource::from_config_json`], relative paths in the configuration are
+    /// converted to absolute paths, considering them relative to the directory containing
+    /// the configuration file.
+    pub fn from_config_json(config_file: &Path) -> Result<Self, MetErrorType> {
+        let reader = std::fs::File::open(config_file).map_err(|e| EncodingError::IoError(e))?;
+        let mut this: Self = serde_json::from_reader(reader)?;
+        this.source = this.source.resolve_relative_paths(config_file)?;
+        Ok(this)
+    }
+
+    /// The maximum time between an interferogram's ZPD time and the nearest met record
+    /// for which the interferogram may use that record's values when outside the time
+    /// span of the met data.
+    pub fn max_extrapolation(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds((self.max_extrapolation_minutes * 60_000.0).round() as i64)
+    }
+}
+
 impl MetSource {
+    /// Whether this source's timestamps include their time zone (`CitCsvV1` and `ExtScriptV1`), as
+    /// opposed to sources whose times must be interpreted in a time zone given when reading them.
+    fn timestamps_have_timezone(&self) -> bool {
+        match self {
+            MetSource::LegacyFileV1 { .. }
+            | MetSource::JplVaisalaV1 { .. }
+            | MetSource::ZenoV1 { .. } => false,
+            MetSource::CitCsvV1 { .. } | MetSource::ExtScriptV1 { .. } => true,
+        }
+    }
+
     /// Create a `MetSource` instance from a JSON file.
     ///
     /// Because different sources of met data may have different numbers and types of inputs
@@ -448,6 +588,9 @@ impl MetSource {
     /// and the path is `../met`, then the returned structure will contain the absolute path
     /// `/home/data/config/../met` i.e. `/home/data/met`.
     ///
+    /// Any options not specific to the met source (see [`MetConfig`]) are ignored; use
+    /// [`MetConfig::from_config_json`] if you need them.
+    ///
     /// # File examples
     ///
     /// A valid JSON for the `JplVaisalaV1` met source is:
@@ -471,46 +614,70 @@ impl MetSource {
     /// }
     /// ```
     pub fn from_config_json(config_file: &Path) -> Result<Self, MetErrorType> {
-        let reader = std::fs::File::open(config_file).map_err(|e| EncodingError::IoError(e))?;
-        let this: Self = serde_json::from_reader(reader)?;
-        match this {
+        let config = MetConfig::from_config_json(config_file)?;
+        Ok(config.source)
+    }
+
+    /// Convert any relative paths in this source to be relative to the directory
+    /// containing `config_file`.
+    fn resolve_relative_paths(self, config_file: &Path) -> Result<Self, ConfigPathError> {
+        let resolved = match self {
             MetSource::LegacyFileV1 { file } => {
-                let file = path_relative_to_config(config_file, file);
-                Ok(Self::LegacyFileV1 { file })
+                let file = path_relative_to_config(config_file, file)?;
+                Self::LegacyFileV1 { file }
             }
             MetSource::JplVaisalaV1 { file, utc_offset } => {
-                let file = path_relative_to_config(config_file, file);
-                Ok(Self::JplVaisalaV1 { file, utc_offset })
+                let file = path_relative_to_config(config_file, file)?;
+                Self::JplVaisalaV1 { file, utc_offset }
+            }
+            MetSource::ZenoV1 { file, utc_offset } => {
+                let file = path_relative_to_config(config_file, file)?;
+                Self::ZenoV1 { file, utc_offset }
             }
             MetSource::CitCsvV1 {
                 pres_file,
                 site,
                 temp_file,
                 humid_file,
+                strict_times,
             } => {
-                let pres_file = path_relative_to_config(config_file, pres_file);
-                let temp_file = temp_file.map(|p| path_relative_to_config(config_file, p));
-                let humid_file = humid_file.map(|p| path_relative_to_config(config_file, p));
-                Ok(Self::CitCsvV1 {
+                let pres_file = path_relative_to_config(config_file, pres_file)?;
+                let temp_file = temp_file
+                    .map(|p| path_relative_to_config(config_file, p))
+                    .transpose()?;
+                let humid_file = humid_file
+                    .map(|p| path_relative_to_config(config_file, p))
+                    .transpose()?;
+                Self::CitCsvV1 {
                     pres_file,
                     site,
                     temp_file,
                     humid_file,
-                })
+                    strict_times,
+                }
             }
             MetSource::ExtScriptV1 {
                 script,
                 args,
                 working_dir,
+                timeout_seconds,
+                pass_igram_times,
+                retries,
+                retry_delay_seconds,
             } => {
-                let working_dir = path_relative_to_config(config_file, working_dir);
-                Ok(Self::ExtScriptV1 {
+                let working_dir = path_relative_to_config(config_file, working_dir)?;
+                Self::ExtScriptV1 {
                     script,
                     args,
                     working_dir,
-                })
+                    timeout_seconds,
+                    pass_igram_times,
+                    retries,
+                    retry_delay_seconds,
+                }
             }
-        }
+        };
+        Ok(resolved)
     }
 
     /// This is a wrapper around `from_config_json` needed for parsing command line arguments.
@@ -542,17 +709,21 @@ impl MetSource {
                     .map(|o| format!(" UTC{:+.1}", o))
                     .unwrap_or_else(|| "".to_string())
             ),
+            MetSource::ZenoV1 { file, utc_offset } => format!(
+                "Zeno V1 (file {}{})",
+                file.display(),
+                utc_offset
+                    .map(|o| format!(" UTC{:+.1}", o))
+                    .unwrap_or_else(|| "".to_string())
+            ),
             MetSource::CitCsvV1 {
                 pres_file,
                 site,
                 temp_file: _,
                 humid_file: _,
+                strict_times: _,
             } => format!("CIT CSV V1 ({site}, pres_file = {})", pres_file.display()),
-            MetSource::ExtScriptV1 {
-                script,
-                args: _,
-                working_dir: _,
-            } => format!("External Script V1 ({script})"),
+            MetSource::ExtScriptV1 { script, .. } => format!("External Script V1 ({script})"),
         }
     }
 }
@@ -565,17 +736,15 @@ impl Display for MetSource {
                 file: _,
                 utc_offset: _,
             } => write!(f, "JplVaisalaV1"),
+            MetSource::ZenoV1 { .. } => write!(f, "ZenoV1"),
             MetSource::CitCsvV1 {
                 pres_file: _,
                 site: _,
                 temp_file: _,
                 humid_file: _,
+                strict_times: _,
             } => write!(f, "CitCsvV1"),
-            MetSource::ExtScriptV1 {
-                script: _,
-                args: _,
-                working_dir: _,
-            } => write!(f, "ExtScriptV1"),
+            MetSource::ExtScriptV1 { .. } => write!(f, "ExtScriptV1"),
         }
     }
 }
@@ -633,42 +802,47 @@ impl Timezones {
 ///
 /// # Inputs
 /// - `met_file`: path to the file to be read
+/// - `em27_zpd_times`: the ZPD times of the interferograms that need met data
+/// - `site_id`: the site ID of the EM27, if known. Only used by [`MetSource::ExtScriptV1`].
+/// - `detectors`: the detector set of the EM27, if known. Only used by [`MetSource::ExtScriptV1`].
+/// - `timezone`: if given, the time zone the met timestamps are in, overriding the source's default.
+///   For sources whose timestamps have no time zone (`LegacyFileV1`, `JplVaisalaV1`, and `ZenoV1`),
+///   this replaces the interferogram time zone or the `utc_offset` from the source configuration;
+///   for `LegacyFileV1`, it only applies to the CompSrlDate or CompDate/CompTime columns, since
+///   UTCDate/UTCTime are always UTC. For sources whose timestamps do have a time zone (`CitCsvV1`
+///   and `ExtScriptV1`), the local times read are reinterpreted as being in this time zone.
 pub fn read_met_file(
     met_type: &MetSource,
     em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
+    site_id: Option<&str>,
+    detectors: Option<DetectorSet>,
+    timezone: Option<MetTimezone>,
 ) -> error_stack::Result<Vec<MetEntry>, MetError> {
-    match met_type {
-        MetSource::LegacyFileV1 { file } => {
-            let em27_tz_offset =
-                Timezones::check_consistent_timezones(em27_zpd_times.into_iter().map(|t| *t));
-            let tz = get_em27_tz(em27_tz_offset, met_type)?;
-            legacy::read_legacy_met_csv(file, tz).change_context_lazy(|| MetError {
-                met_source_type: met_type.to_owned(),
-                reason: MetErrorType::Stack,
-            })
-        }
+    let tz_override = timezone
+        .map(|tz| tz.resolve(em27_zpd_times, met_type))
+        .transpose()?;
 
-        MetSource::JplVaisalaV1 { file, utc_offset } => {
-            let tz = if let Some(offset_hours) = utc_offset {
-                let secs = (offset_hours * 3600.0).round() as i32;
-                FixedOffset::east_opt(secs).ok_or_else(|| MetError {
-                    met_source_type: met_type.to_owned(),
-                    reason: MetErrorType::ConfigError(format!(
-                        "UTC offset {offset_hours:+.2} is out of the allowed range (-24 to +24"
-                    )),
-                })?
+    let entries = match met_type {
+        MetSource::LegacyFileV1 { file } => {
+            let tz = if let Some(tz) = tz_override {
+                tz
             } else {
                 let em27_tz_offset =
-                    Timezones::check_consistent_timezones(em27_zpd_times.into_iter().map(|t| *t));
+                    Timezones::check_consistent_timezones(em27_zpd_times.iter().copied());
                 get_em27_tz(em27_tz_offset, met_type)?
             };
-            jpl_vaisala::read_jpl_vaisala_met(file, tz).map_err(|e| {
-                MetError {
-                    met_source_type: met_type.to_owned(),
-                    reason: e.into(),
-                }
-                .into()
-            })
+            legacy::read_legacy_met_csv(file, tz).change_context_lazy(|| MetError::new(met_type))
+        }
+
+        MetSource::JplVaisalaV1 { file, utc_offset } => {
+            let tz = get_met_tz(tz_override, *utc_offset, em27_zpd_times, met_type)?;
+            jpl_vaisala::read_jpl_vaisala_met(file, tz)
+                .change_context_lazy(|| MetError::new(met_type))
+        }
+
+        MetSource::ZenoV1 { file, utc_offset } => {
+            let tz = get_met_tz(tz_override, *utc_offset, em27_zpd_times, met_type)?;
+            zeno::read_zeno_met(file, tz).change_context_lazy(|| MetError::new(met_type))
         }
 
         MetSource::CitCsvV1 {
@@ -676,46 +850,137 @@ pub fn read_met_file(
             site,
             temp_file,
             humid_file,
-        } => {
-            cit_csv::read_cit_csv_met(pres_file, site, temp_file.as_deref(), humid_file.as_deref())
-                .map_err(|e| {
-                    MetError {
-                        met_source_type: met_type.to_owned(),
-                        reason: e.into(),
-                    }
-                    .into()
-                })
-        }
+            strict_times,
+        } => cit_csv::read_cit_csv_met(
+            pres_file,
+            site,
+            temp_file.as_deref(),
+            humid_file.as_deref(),
+            *strict_times,
+        )
+        .change_context_lazy(|| MetError::new(met_type)),
 
         MetSource::ExtScriptV1 {
             script,
             args,
             working_dir,
+            timeout_seconds,
+            pass_igram_times,
+            retries,
+            retry_delay_seconds,
         } => {
-            let (first_time, last_time) =
-                get_igram_time_span(em27_zpd_times).unwrap_or_else(|| {
-                    (
-                        chrono::DateTime::from_timestamp_nanos(0).into(),
-                        chrono::DateTime::from_timestamp_nanos(0).into(),
-                    )
-                });
-            external_script::read_met_with_script(script, args, working_dir, first_time, last_time)
-                .change_context_lazy(|| MetError {
-                    met_source_type: met_type.to_owned(),
-                    reason: MetErrorType::Stack,
+            let retry_delay = std::time::Duration::try_from_secs_f64(*retry_delay_seconds)
+                .map_err(|e| {
+                    MetErrorType::ConfigError(format!(
+                        "retry_delay_seconds must be a non-negative number: {e}"
+                    ))
+                })
+                .change_context_lazy(|| MetError::new(met_type))?;
+            // A timeout of 0 would stop the script on the first check of whether it had finished
+            let timeout = timeout_seconds
+                .map(|secs| {
+                    if secs > 0.0 {
+                        std::time::Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+                    } else {
+                        Err(format!("got {secs}"))
+                    }
+                })
+                .transpose()
+                .map_err(|e| {
+                    MetErrorType::ConfigError(format!(
+                        "timeout_seconds must be a positive number: {e}"
+                    ))
                 })
+                .change_context_lazy(|| MetError::new(met_type))?;
+            external_script::read_met_with_script(
+                script,
+                args,
+                working_dir,
+                em27_zpd_times,
+                site_id,
+                detectors,
+                external_script::ScriptRunOptions {
+                    timeout,
+                    pass_igram_times: *pass_igram_times,
+                    retries: *retries,
+                    retry_delay,
+                },
+            )
+            .change_context_lazy(|| MetError::new(met_type))
+        }
+    }?;
+
+    // Sources without time zones in their timestamps were already read in the override time zone
+    // (and LegacyFileV1's UTC columns must not be changed); the rest need their times reinterpreted.
+    let reinterpret_tz = tz_override.filter(|_| met_type.timestamps_have_timezone());
+    if let Some(tz) = reinterpret_tz {
+        entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.datetime = reinterpret_in_timezone(entry.datetime, tz)
+                    .change_context_lazy(|| MetError::new(met_type))?;
+                Ok(entry)
+            })
+            .collect()
+    } else {
+        Ok(entries)
+    }
+}
+
+/// Return a datetime with the same local (wall clock) time as `datetime`, but in the time zone `tz`.
+fn reinterpret_in_timezone(
+    datetime: DateTime<FixedOffset>,
+    tz: FixedOffset,
+) -> Result<DateTime<FixedOffset>, MetErrorType> {
+    datetime
+        .naive_local()
+        .and_local_timezone(tz)
+        .single()
+        .ok_or(MetErrorType::BadTimezoneError)
+}
+
+/// Get the time zone for a met file without time zones in its timestamps: from `tz_override` (the
+/// met configuration's timezone) if given, then `utc_offset` (in hours) if given, otherwise the time
+/// zone of the interferograms, which must all be the same.
+fn get_met_tz(
+    tz_override: Option<FixedOffset>,
+    utc_offset: Option<f32>,
+    em27_zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
+    met_type: &MetSource,
+) -> error_stack::Result<FixedOffset, MetError> {
+    if let Some(tz) = tz_override {
+        if utc_offset.is_some() {
+            log::warn!(
+                "Both timezone and utc_offset given for a {met_type} met source, using timezone"
+            );
         }
+        Ok(tz)
+    } else if let Some(offset_hours) = utc_offset {
+        let secs = (offset_hours * 3600.0).round() as i32;
+        FixedOffset::east_opt(secs)
+            .ok_or_else(|| {
+                MetErrorType::ConfigError(format!(
+                    "UTC offset {offset_hours:+.2} is out of the allowed range (-24 to +24)"
+                ))
+            })
+            .change_context_lazy(|| MetError::new(met_type))
+    } else {
+        let em27_tz_offset = Timezones::check_consistent_timezones(em27_zpd_times.iter().copied());
+        get_em27_tz(em27_tz_offset, met_type)
     }
 }
 
-fn get_em27_tz(em27_tz_offset: Timezones, met_type: &MetSource) -> Result<FixedOffset, MetError> {
-    em27_tz_offset.try_unwrap_one().map_err(|reason| MetError {
-        met_source_type: met_type.to_owned(),
-        reason,
-    })
+fn get_em27_tz(
+    em27_tz_offset: Timezones,
+    met_type: &MetSource,
+) -> error_stack::Result<FixedOffset, MetError> {
+    em27_tz_offset
+        .try_unwrap_one()
+        .change_context_lazy(|| MetError::new(met_type))
 }
 
-fn get_igram_time_span(
+/// Get the earliest and latest of a set of times, or `None` if `zpd_times` is empty.
+pub(crate) fn get_igram_time_span(
     zpd_times: &[chrono::DateTime<chrono::FixedOffset>],
 ) -> Option<(
     chrono::DateTime<chrono::FixedOffset>,
@@ -726,13 +991,23 @@ fn get_igram_time_span(
     Some((first, last))
 }
 
+fn default_retry_delay_seconds() -> f64 {
+    5.0
+}
+
 fn curr_dir() -> PathBuf {
     PathBuf::from(".")
 }
 
+fn default_true() -> bool {
+    true
+}
+
 #[cfg(test)]
 mod tests {
-    use super::MetEntry;
+    use chrono::FixedOffset;
+
+    use super::{read_met_file, MetConfig, MetEntry, MetSource, MetTimezone};
 
     #[test]
     fn test_met_entry_de() {
@@ -742,4 +1017,104 @@ mod tests {
         .unwrap();
         dbg!(entry);
     }
+
+    #[test]
+    fn test_met_config_de() {
+        let config: MetConfig = serde_json::from_str(
+            r#"{"type": "JplVaisalaV1", "file": "met.txt", "max_extrapolation_minutes": 1.5}"#,
+        )
+        .unwrap();
+        assert!(matches!(config.source, MetSource::JplVaisalaV1 { .. }));
+        assert_eq!(config.max_extrapolation(), chrono::Duration::seconds(90));
+
+        let config: MetConfig =
+            serde_json::from_str(r#"{"type": "LegacyFileV1", "file": "met.txt"}"#).unwrap();
+        assert_eq!(config.max_extrapolation(), chrono::Duration::zero());
+        assert_eq!(config.timezone, None);
+    }
+
+    #[test]
+    fn test_met_timezone_de() {
+        let tz = |s: &str| {
+            let json = format!(r#"{{"type": "LegacyFileV1", "file": "met.txt", "timezone": {s}}}"#);
+            serde_json::from_str::<MetConfig>(&json).map(|c| c.timezone)
+        };
+        assert_eq!(tz(r#""utc""#).unwrap(), Some(MetTimezone::Utc));
+        assert_eq!(tz(r#""IGRAM""#).unwrap(), Some(MetTimezone::Igram));
+        assert_eq!(
+            tz("-7").unwrap(),
+            Some(MetTimezone::Offset(
+                FixedOffset::west_opt(7 * 3600).unwrap()
+            ))
+        );
+        assert_eq!(
+            tz("5.5").unwrap(),
+            Some(MetTimezone::Offset(
+                FixedOffset::east_opt(5 * 3600 + 1800).unwrap()
+            ))
+        );
+        assert!(tz(r#""local""#).is_err());
+        assert!(tz("30").is_err());
+    }
+
+    #[test]
+    fn test_legacy_timezone_override() {
+        let tmp = tempfile::tempdir().unwrap();
+        let met_file = tmp.path().join("met.txt");
+        std::fs::write(
+            &met_file,
+            "CompDate, CompTime, Pout\n2024/04/01, 18:00:00, 985.0\n",
+        )
+        .unwrap();
+        let source = MetSource::LegacyFileV1 {
+            file: met_file.clone(),
+        };
+        let zpd_times =
+            [chrono::DateTime::parse_from_rfc3339("2024-04-01T11:00:00-07:00").unwrap()];
+
+        let default = read_met_file(&source, &zpd_times, None, None, None);
+        let utc = read_met_file(&source, &zpd_times, None, None, Some(MetTimezone::Utc));
+
+        assert_eq!(
+            default.unwrap()[0].datetime,
+            chrono::DateTime::parse_from_rfc3339("2024-04-01T18:00:00-07:00").unwrap()
+        );
+        assert_eq!(
+            utc.unwrap()[0].datetime,
+            chrono::DateTime::parse_from_rfc3339("2024-04-01T18:00:00Z").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_met_error_chain() {
+        let source = MetSource::JplVaisalaV1 {
+            file: "egi_no_such_met_file.txt".into(),
+            utc_offset: Some(0.0),
+        };
+        let err = read_met_file(&source, &[], None, None, None).unwrap_err();
+        let msg = format!("{err:?}");
+        assert!(msg.contains("JPL Vaisala V1 (file egi_no_such_met_file.txt"));
+        assert!(msg.contains("Could not read egi_no_such_met_file.txt"));
+    }
+
+    #[test]
+    fn test_ext_script_zero_timeout() {
+        for timeout in [0.0, -1.0] {
+            let source = MetSource::ExtScriptV1 {
+                script: "true".to_string(),
+                args: vec![],
+                working_dir: ".".into(),
+                timeout_seconds: Some(timeout),
+                pass_igram_times: false,
+                retries: 2,
+                retry_delay_seconds: 0.0,
+            };
+            let err = read_met_file(&source, &[], None, None, None).unwrap_err();
+            let msg = format!("{err:?}");
+            assert!(
+                msg.contains("timeout_seconds must be a positive number"),
+                "{msg}"
+            );
+        }
+    }
 }
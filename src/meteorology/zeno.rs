@@ -0,0 +1,175 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone};
+use error_stack::ResultExt;
+use itertools::Itertools;
+
+use ggg_rs::utils::read_unknown_encoding_file;
+
+use super::MetEntry;
+
+#[derive(Debug, thiserror::Error)]
+pub(super) enum ZenoMetError {
+    #[error("Could not read {}", .0.display())]
+    ReadError(PathBuf),
+    #[error("Zeno file line {0} is missing the {1} column")]
+    LineTooShort(usize, Col),
+    #[error("Zeno file line {0} has a malformed {1} column: {2}")]
+    ParsingError(usize, Col, String),
+    #[error("Zeno file has a line with date/time {0} {1} that cannot be converted to {2} as it is either an invalid or ambiguous time for that timezone")]
+    InvalidTime(NaiveDate, NaiveTime, FixedOffset),
+}
+
+/// Read a file of records written by a Zeno data logger.
+///
+/// Each record is a comma-separated line with the date (YYYY/MM/DD), time (HH:MM:SS),
+/// pressure (hPa), temperature (deg C), and relative humidity (%) in that order; any further
+/// columns are ignored. Lines that do not start with a digit (such as the header and logger
+/// status messages) are skipped. Empty temperature or humidity fields are treated as missing.
+pub(super) fn read_zeno_met(
+    met_file: &Path,
+    tz_offset: FixedOffset,
+) -> error_stack::Result<Vec<MetEntry>, ZenoMetError> {
+    let contents = read_unknown_encoding_file(met_file)
+        .change_context_lazy(|| ZenoMetError::ReadError(met_file.to_path_buf()))?;
+    let mut met_data = vec![];
+
+    for (line_num, line) in contents.as_str().lines().enumerate() {
+        let line_num = line_num + 1;
+        if !line.trim_start().starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let parts = line.split(',').map(|s| s.trim()).collect_vec();
+
+        let datetime = parse_line_datetime(&parts, line_num, tz_offset)?;
+        let pressure = parse_line_value(&parts, line_num, Col::Pres)?.ok_or_else(|| {
+            ZenoMetError::ParsingError(line_num, Col::Pres, "value is empty".to_string())
+        })?;
+        let temperature = parse_line_value(&parts, line_num, Col::Temp)?;
+        let humidity = parse_line_value(&parts, line_num, Col::RH)?;
+
+        met_data.push(MetEntry {
+            datetime,
+            temperature,
+            pressure,
+            humidity,
+        })
+    }
+
+    Ok(met_data)
+}
+
+fn parse_line_datetime(
+    parts: &[&str],
+    line_num: usize,
+    offset: FixedOffset,
+) -> Result<DateTime<FixedOffset>, ZenoMetError> {
+    let date_str = get_part(parts, line_num, Col::Date)?;
+    let time_str = get_part(parts, line_num, Col::Time)?;
+
+    let date = NaiveDate::parse_from_str(date_str, "%Y/%m/%d").map_err(|e| {
+        ZenoMetError::ParsingError(
+            line_num,
+            Col::Date,
+            format!("expected YYYY/MM/DD, got {date_str}. Parsing error was {e}"),
+        )
+    })?;
+    let time = NaiveTime::parse_from_str(time_str, "%H:%M:%S").map_err(|e| {
+        ZenoMetError::ParsingError(
+            line_num,
+            Col::Time,
+            format!("expected HH:MM:SS, got {time_str}. Parsing error was {e}"),
+        )
+    })?;
+
+    match offset.from_local_datetime(&date.and_time(time)) {
+        chrono::LocalResult::Single(t) => Ok(t),
+        chrono::LocalResult::None | chrono::LocalResult::Ambiguous(_, _) => {
+            Err(ZenoMetError::InvalidTime(date, time, offset))
+        }
+    }
+}
+
+fn parse_line_value(
+    parts: &[&str],
+    line_num: usize,
+    col: Col,
+) -> Result<Option<f64>, ZenoMetError> {
+    let s = get_part(parts, line_num, col)?;
+    if s.is_empty() {
+        return Ok(None);
+    }
+    s.parse::<f64>()
+        .map(Some)
+        .map_err(|e| ZenoMetError::ParsingError(line_num, col, format!("{s} ({e})")))
+}
+
+fn get_part<'a>(parts: &[&'a str], line_num: usize, col: Col) -> Result<&'a str, ZenoMetError> {
+    parts
+        .get(col.index())
+        .copied()
+        .ok_or(ZenoMetError::LineTooShort(line_num, col))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Col {
+    Date,
+    Time,
+    Pres,
+    Temp,
+    RH,
+}
+
+impl Col {
+    /// The index of this column in a Zeno record
+    fn index(&self) -> usize {
+        match self {
+            Col::Date => 0,
+            Col::Time => 1,
+            Col::Pres => 2,
+            Col::Temp => 3,
+            Col::RH => 4,
+        }
+    }
+}
+
+impl Display for Col {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Col::Date => write!(f, "date"),
+            Col::Time => write!(f, "time"),
+            Col::Pres => write!(f, "pressure"),
+            Col::Temp => write!(f, "temperature"),
+            Col::RH => write!(f, "humidity"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_read_zeno_met() {
+        let crate_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let met_file = crate_root.join("test_inputs").join("zeno_met.txt");
+        let tz = FixedOffset::west_opt(7 * 3600).unwrap();
+        let entries = read_zeno_met(&met_file, tz).unwrap();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0].datetime,
+            DateTime::parse_from_rfc3339("2023-08-26T16:15:00-07:00").unwrap()
+        );
+        assert_eq!(entries[0].pressure, 972.7);
+        assert_eq!(entries[0].temperature, Some(26.8));
+        assert_eq!(entries[0].humidity, Some(39.3));
+        assert_eq!(entries[2].temperature, None);
+        assert_eq!(entries[2].humidity, Some(40.1));
+    }
+}
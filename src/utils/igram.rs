@@ -0,0 +1,217 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use error_stack::ResultExt;
+use ggg_rs::opus::IgramHeader;
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, thiserror::Error)]
+pub enum IgramHeaderError {
+    #[error("Could not decompress gzipped interferogram {}", .0.display())]
+    Decompress(PathBuf),
+    #[error("Could not read the header of interferogram {}", .0.display())]
+    Header(PathBuf),
+    #[error("Could not read the block directory of interferogram {}", .0.display())]
+    Directory(PathBuf),
+}
+
+/// The magic number at the start of every OPUS file.
+const OPUS_MAGIC: u32 = 0xFEFE0A0A;
+
+/// One entry in the block directory of an OPUS file, see [`read_opus_directory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpusBlockEntry {
+    /// The Bruker block type code.
+    pub block_type: u32,
+    /// The length of the block, in 4-byte words.
+    pub length: u32,
+    /// The offset of the block from the start of the file, in bytes.
+    pub offset: u32,
+}
+
+impl OpusBlockEntry {
+    /// Whether this is a non-empty interferogram data block (rather than a parameter block or
+    /// another kind of data, such as a spectrum).
+    ///
+    /// Bits 4-9 of the block type give the parameter block kind (0 for data blocks) and
+    /// bits 10-16 the data type (2 for interferograms).
+    pub fn is_igram_data(&self) -> bool {
+        let param_kind = (self.block_type >> 4) & 0x3f;
+        let data_type = (self.block_type >> 10) & 0x7f;
+        param_kind == 0 && data_type == 2 && self.length > 0
+    }
+}
+
+/// Read the block directory of an interferogram (which may be gzipped), i.e. the type, size,
+/// and location of every data and parameter block in the file.
+///
+/// Unlike [`read_igram_header`], this does not interpret the blocks, so it lists blocks that
+/// EGI does not otherwise know about (e.g. extra detector channels).
+pub fn read_opus_directory(
+    igram: &Path,
+) -> error_stack::Result<Vec<OpusBlockEntry>, IgramHeaderError> {
+    let f = std::fs::File::open(igram)
+        .change_context_lazy(|| IgramHeaderError::Directory(igram.to_path_buf()))?;
+    let reader: Box<dyn Read> = if is_gzipped(igram) {
+        Box::new(flate2::read::GzDecoder::new(f))
+    } else {
+        Box::new(std::io::BufReader::new(f))
+    };
+    read_opus_directory_from(reader)
+        .change_context_lazy(|| IgramHeaderError::Directory(igram.to_path_buf()))
+}
+
+fn read_opus_directory_from<R: Read>(mut reader: R) -> std::io::Result<Vec<OpusBlockEntry>> {
+    let mut file_header = [0u8; 24];
+    reader.read_exact(&mut file_header)?;
+    let word = |buf: &[u8], i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+
+    if word(&file_header, 0) != OPUS_MAGIC {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not an OPUS file (wrong magic number)",
+        ));
+    }
+    // Bytes 4-11 are the program version, then the directory pointer, maximum number of
+    // directory entries, and number of entries in use.
+    let dir_start = word(&file_header, 12) as u64;
+    let n_entries = word(&file_header, 20) as usize;
+    if dir_start < 24 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("directory pointer ({dir_start}) is inside the file header"),
+        ));
+    }
+
+    std::io::copy(
+        &mut reader.by_ref().take(dir_start - 24),
+        &mut std::io::sink(),
+    )?;
+    let mut directory = vec![0u8; 12 * n_entries];
+    reader.read_exact(&mut directory)?;
+    let entries = directory
+        .chunks_exact(12)
+        .map(|entry| OpusBlockEntry {
+            block_type: word(entry, 0),
+            length: word(entry, 4),
+            offset: word(entry, 8),
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Returns `true` if `path` has a ".gz" extension (case insensitive).
+pub fn is_gzipped(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// Read the full header of an interferogram, which may be gzipped.
+///
+/// If `igram` has a ".gz" extension, it is decompressed to a temporary file which is
+/// read and then deleted. Otherwise, the header is read directly from `igram`.
+pub fn read_igram_header(igram: &Path) -> error_stack::Result<IgramHeader, IgramHeaderError> {
+    if !is_gzipped(igram) {
+        return IgramHeader::read_full_igram_header(igram)
+            .change_context_lazy(|| IgramHeaderError::Header(igram.to_path_buf()));
+    }
+
+    let tmp = DecompressedIgram::new(igram)
+        .change_context_lazy(|| IgramHeaderError::Decompress(igram.to_path_buf()))?;
+    IgramHeader::read_full_igram_header(&tmp.path)
+        .change_context_lazy(|| IgramHeaderError::Header(igram.to_path_buf()))
+}
+
+/// A temporary, decompressed copy of a gzipped interferogram that is removed when dropped.
+struct DecompressedIgram {
+    path: PathBuf,
+}
+
+impl DecompressedIgram {
+    fn new(gz_file: &Path) -> std::io::Result<Self> {
+        // Keep the original name (minus the ".gz") in case anything downstream cares about it,
+        // but include the process ID and a counter so that parallel runs don't collide.
+        let stem = gz_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "igram".to_string());
+        let n = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("egi_gunzip_{}_{n}_{stem}", std::process::id()));
+
+        let mut decoder = flate2::read::GzDecoder::new(std::fs::File::open(gz_file)?);
+        let mut out = std::fs::File::create(&path)?;
+        // Construct self before copying so that a partially written file still gets removed.
+        let tmp = Self { path };
+        std::io::copy(&mut decoder, &mut out)?;
+        Ok(tmp)
+    }
+}
+
+impl Drop for DecompressedIgram {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_read_opus_directory() {
+        let igram = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_inputs")
+            .join("catalog")
+            .join("xx20240401s0e00a.0001");
+        let entries = read_opus_directory(&igram).unwrap();
+        let types: Vec<u32> = entries.iter().map(|e| e.block_type).collect();
+        assert_eq!(types, [32, 2071, 2055]);
+        let igram_blocks: Vec<u32> = entries
+            .iter()
+            .filter(|e| e.is_igram_data())
+            .map(|e| e.block_type)
+            .collect();
+        assert_eq!(igram_blocks, [2055]);
+        assert_eq!(entries[2].length, 16);
+
+        assert!(read_opus_directory_from(&b"not an interferogram at all"[..]).is_err());
+    }
+
+    #[test]
+    fn test_decompress_igram() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz_file = dir.path().join("ma20230826s0e00a.0001.gz");
+        let contents = b"not really an interferogram";
+        {
+            let f = std::fs::File::create(&gz_file).unwrap();
+            let mut enc = flate2::write::GzEncoder::new(f, flate2::Compression::default());
+            enc.write_all(contents).unwrap();
+            enc.finish().unwrap();
+        }
+
+        let tmp = DecompressedIgram::new(&gz_file).unwrap();
+        let tmp_path = tmp.path.clone();
+        let mut buf = vec![];
+        std::fs::File::open(&tmp_path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        let tmp_name = tmp_path.file_name().unwrap().to_string_lossy().to_string();
+        drop(tmp);
+        let removed = !tmp_path.exists();
+
+        assert_eq!(buf, contents);
+        assert!(tmp_name.ends_with("ma20230826s0e00a.0001"));
+        assert!(removed);
+        assert!(is_gzipped(&gz_file));
+        assert!(is_gzipped(Path::new("igram.GZ")));
+        assert!(!is_gzipped(Path::new("ma20230826s0e00a.0001")));
+    }
+}
@@ -0,0 +1,117 @@
+//! Logging setup shared by the EGI programs.
+//!
+//! Each program flattens [`LogArgs`] into its command line interface and calls
+//! [`LogArgs::init_logger`] at startup. Besides the usual human-readable format, the
+//! log can be written as JSON lines for log aggregators, see [`LogFormat`].
+use std::io::Write;
+
+use clap::{Args, ValueEnum};
+use clap_verbosity_flag::{Verbosity, WarnLevel};
+use log::kv::{Key, Value, VisitSource};
+
+/// The environmental variable that sets the log format if `--log-format` is not given.
+pub const LOG_FORMAT_ENV_VAR: &str = "EGI_LOG_FORMAT";
+
+/// How to format log messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// The usual human-readable format.
+    #[default]
+    Text,
+    /// One JSON object per line with the timestamp, level, target, message,
+    /// and any key-value fields (such as the date or site being processed).
+    Json,
+}
+
+/// Command line arguments that control logging.
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    #[command(flatten)]
+    pub verbose: Verbosity<WarnLevel>,
+
+    /// How to format log messages: "text" (the default) or "json" for one JSON
+    /// object per line. If not given, the EGI_LOG_FORMAT environmental variable
+    /// is used, if set.
+    #[clap(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+}
+
+impl LogArgs {
+    /// The log format to use: `--log-format` if given, otherwise the value of
+    /// [`LOG_FORMAT_ENV_VAR`], otherwise [`LogFormat::Text`].
+    pub fn format(&self) -> LogFormat {
+        if let Some(fmt) = self.log_format {
+            return fmt;
+        }
+
+        let Ok(value) = std::env::var(LOG_FORMAT_ENV_VAR) else {
+            return LogFormat::default();
+        };
+        LogFormat::from_str(&value, true).unwrap_or_else(|_| {
+            eprintln!("Unknown log format '{value}' in {LOG_FORMAT_ENV_VAR}, using text");
+            LogFormat::default()
+        })
+    }
+
+    /// Set up the global logger. This must only be called once.
+    pub fn init_logger(&self) {
+        let mut builder = env_logger::Builder::new();
+        builder.filter_level(self.verbose.log_level_filter());
+        if self.format() == LogFormat::Json {
+            builder.format(|buf, record| writeln!(buf, "{}", json_log_record(record)));
+        }
+        builder.init();
+    }
+}
+
+/// Convert a log record to a JSON object, see [`LogFormat::Json`].
+fn json_log_record(record: &log::Record) -> serde_json::Value {
+    let mut fields = serde_json::Map::new();
+    fields.insert(
+        "timestamp".to_string(),
+        chrono::Utc::now()
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+            .into(),
+    );
+    fields.insert("level".to_string(), record.level().as_str().into());
+    fields.insert("target".to_string(), record.target().into());
+    fields.insert("message".to_string(), record.args().to_string().into());
+
+    let mut visitor = JsonFieldVisitor(&mut fields);
+    // Our visitor never returns an error, so neither can this.
+    let _ = record.key_values().visit(&mut visitor);
+    serde_json::Value::Object(fields)
+}
+
+struct JsonFieldVisitor<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs, 'a> VisitSource<'kvs> for JsonFieldVisitor<'a> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.0.insert(key.to_string(), value.to_string().into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_log_record() {
+        let kvs = [("date", "2024-04-01"), ("site", "xx")];
+        let record = log::Record::builder()
+            .level(log::Level::Info)
+            .target("em27_i2s_prep")
+            .args(format_args!("Preparing I2S run"))
+            .key_values(&kvs)
+            .build();
+
+        let json = json_log_record(&record);
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["target"], "em27_i2s_prep");
+        assert_eq!(json["message"], "Preparing I2S run");
+        assert_eq!(json["date"], "2024-04-01");
+        assert_eq!(json["site"], "xx");
+        assert!(json["timestamp"].is_string());
+    }
+}
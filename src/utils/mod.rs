@@ -1,20 +1,250 @@
 use std::{
     io::{BufRead, BufReader, Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+use clap::ValueEnum;
 use itertools::Itertools;
+use serde::Deserialize;
 
+pub mod igram;
+pub mod logging;
 pub mod pattern_replacement;
+#[cfg(feature = "progress")]
+pub mod progress;
 
-pub fn ensure_trailing_path_sep(p: &Path) -> Option<String> {
-    let mut s = p.to_str()?.to_string();
-    if !s.ends_with(std::path::MAIN_SEPARATOR_STR) {
-        s.push(std::path::MAIN_SEPARATOR);
+/// The window file for EM27 retrievals, which `em27-init` adds to the ground windows menu.
+pub const EM27_WINDOW_FILE: &str = "em27.gnd";
+
+/// The linelist that EM27 retrievals use; gsetup's spectral region menu lists the linelists.
+pub const EM27_LINELIST: &str = "atm.161";
+
+#[derive(Debug, thiserror::Error)]
+#[error("Path {} is not valid UTF-8", .0.display())]
+pub struct NonUtf8PathError(pub PathBuf);
+
+/// An error from [`find_em27_window_index`].
+#[derive(Debug, thiserror::Error)]
+pub enum Em27WindowError {
+    #[error("Could not read {}", .0.display())]
+    ReadMenu(PathBuf, #[source] std::io::Error),
+    #[error("Could not find '{EM27_WINDOW_FILE}' in the windows menu file {}; have you run em27-init on the current GGG installation?", .0.display())]
+    NotFound(PathBuf),
+}
+
+/// An error from [`find_em27_linelist_index`].
+#[derive(Debug, thiserror::Error)]
+pub enum Em27LinelistError {
+    #[error("Could not read {}", .0.display())]
+    ReadMenu(PathBuf, #[source] std::io::Error),
+    #[error("Could not find '{EM27_LINELIST}' in the spectral region menu file {}; check that the GGG installation is complete", .0.display())]
+    NotFound(PathBuf),
+}
+
+/// Which line terminator to use when writing files for GGG programs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// Unix-style line endings ("\n"), the default.
+    #[default]
+    Lf,
+    /// Windows-style line endings ("\r\n"), for GGG builds on Windows.
+    Crlf,
+}
+
+/// A [`Write`] wrapper that converts line endings to those given by a [`LineEnding`].
+///
+/// With [`LineEnding::Crlf`], each "\n" not already preceded by "\r" is written as "\r\n".
+/// With [`LineEnding::Lf`], everything is passed through unchanged.
+pub struct LineEndingWriter<W: Write> {
+    inner: W,
+    ending: LineEnding,
+    last_was_cr: bool,
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    pub fn new(inner: W, ending: LineEnding) -> Self {
+        Self {
+            inner,
+            ending,
+            last_was_cr: false,
+        }
+    }
+}
+
+impl<W: Write> Write for LineEndingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.ending == LineEnding::Lf {
+            return self.inner.write(buf);
+        }
+
+        let mut start = 0;
+        for (i, &b) in buf.iter().enumerate() {
+            let prev_is_cr = if i == 0 {
+                self.last_was_cr
+            } else {
+                buf[i - 1] == b'\r'
+            };
+            if b == b'\n' && !prev_is_cr {
+                self.inner.write_all(&buf[start..i])?;
+                self.inner.write_all(b"\r")?;
+                start = i;
+            }
+        }
+        self.inner.write_all(&buf[start..])?;
+
+        if let Some(&b) = buf.last() {
+            self.last_was_cr = b == b'\r';
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
     }
-    Some(s)
 }
 
+/// Return a copy of `p` that ends in a path separator, adding one if needed.
+///
+/// This works on the path directly, so it cannot fail even if `p` is not valid UTF-8.
+pub fn with_trailing_path_sep(p: &Path) -> PathBuf {
+    // Joining an empty component adds a trailing separator only if there isn't one already.
+    p.join("")
+}
+
+/// Return `p` as a string that ends in a path separator, adding one if needed.
+///
+/// # Errors
+/// - if `p` is not valid UTF-8; the error contains `p` so the caller can report it.
+pub fn ensure_trailing_path_sep(p: &Path) -> Result<String, NonUtf8PathError> {
+    with_trailing_path_sep(p)
+        .into_os_string()
+        .into_string()
+        .map_err(|_| NonUtf8PathError(p.to_path_buf()))
+}
+
+/// Get the path to `to` relative to the directory `from_dir`, e.g. `../igrams/` for
+/// `to = /data/igrams/` and `from_dir = /data/runs/`.
+///
+/// Both paths are made absolute relative to the current directory first. This works on the paths
+/// lexically, i.e. `..` components are resolved without following symbolic links.
+///
+/// # Errors
+/// - if either path cannot be made absolute, or
+/// - if the paths have different roots (e.g. are on different drives on Windows), so `to` cannot
+///   be reached from `from_dir` with a relative path.
+pub fn relative_path(from_dir: &Path, to: &Path) -> std::io::Result<PathBuf> {
+    let from_dir = normalize_lexically(&std::path::absolute(from_dir)?);
+    let to = normalize_lexically(&std::path::absolute(to)?);
+
+    let n_common = from_dir
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if n_common == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "{} and {} do not have a common root",
+                from_dir.display(),
+                to.display()
+            ),
+        ));
+    }
+
+    let mut rel = PathBuf::new();
+    for _ in from_dir.components().skip(n_common) {
+        rel.push("..");
+    }
+    rel.extend(to.components().skip(n_common));
+    if rel.as_os_str().is_empty() {
+        rel.push(".");
+    }
+    Ok(rel)
+}
+
+/// Remove `.` components from `p` and resolve `..` components against the preceding one.
+fn normalize_lexically(p: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in p.components() {
+        match comp {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                out.pop();
+            }
+            _ => out.push(comp),
+        }
+    }
+    out
+}
+
+/// Expand a leading `~` (the current user's home directory) or `~user` (that user's home directory) in `p`.
+///
+/// Paths that do not start with a tilde, or whose home directory cannot be found, are returned
+/// unchanged. A tilde anywhere other than the start of the first component is never expanded.
+pub fn expand_user(p: &Path) -> PathBuf {
+    expand_user_with(p, home::home_dir, other_user_home_dir)
+}
+
+fn expand_user_with<H, U>(p: &Path, home_dir: H, user_home_dir: U) -> PathBuf
+where
+    H: FnOnce() -> Option<PathBuf>,
+    U: FnOnce(&str) -> Option<PathBuf>,
+{
+    let mut components = p.components();
+    let Some(user) = components
+        .next()
+        .and_then(|c| c.as_os_str().to_str())
+        .and_then(|c| c.strip_prefix('~'))
+    else {
+        return p.to_path_buf();
+    };
+
+    let home = if user.is_empty() {
+        home_dir()
+    } else {
+        user_home_dir(user)
+    };
+
+    if let Some(home) = home {
+        home.join(components.as_path())
+    } else {
+        log::debug!(
+            "Could not find the home directory to expand {}",
+            p.display()
+        );
+        p.to_path_buf()
+    }
+}
+
+/// Look up the home directory of `user` in the password file.
+#[cfg(unix)]
+fn other_user_home_dir(user: &str) -> Option<PathBuf> {
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() >= 6 && fields[0] == user {
+            Some(PathBuf::from(fields[5]))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(not(unix))]
+fn other_user_home_dir(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+/// Read the entries of a GGG menu file (e.g. `runlogs.men` or `windows.men`).
+///
+/// The first line is the header and is skipped, as are blank lines. Each other line is
+/// a value, then whitespace (spaces or tabs, however many are used to align the columns),
+/// then an optional description. Runs of whitespace that include a tab inside the
+/// description (e.g. between tab-separated columns) are replaced by a single space, and the
+/// description is trimmed; a description that is empty after that is `None`. The original
+/// line is kept in [`MenuEntry::raw`].
 pub fn read_menu_file(menu_file: &Path) -> Result<Vec<MenuEntry>, std::io::Error> {
     let f = std::fs::File::open(menu_file)?;
     let mut rdr = BufReader::new(f);
@@ -26,31 +256,102 @@ pub fn read_menu_file(menu_file: &Path) -> Result<Vec<MenuEntry>, std::io::Error
     let mut index = 0;
     for line in rdr.lines() {
         let line = line?;
-        let mut parts = line.splitn(2, |c: char| c.is_whitespace());
-        let value = if let Some(p) = parts.next() {
-            p.trim().to_string()
-        } else {
-            // probably a blank line, just skip it.
+        let Some((value, description)) = parse_menu_line(&line) else {
+            // a blank line, just skip it.
             continue;
         };
 
-        // also probably a blank line, just skip it
-        if value.is_empty() {
-            continue;
-        }
-
-        let description = parts.next().map(|p| p.trim().to_string());
-
         index += 1;
         entries.push(MenuEntry {
             index,
             value,
             description,
+            raw: line,
         });
     }
     Ok(entries)
 }
 
+/// Find the entry with the given value in a GGG menu file, see [`read_menu_file`].
+///
+/// Returns `Ok(None)` if the menu has no such entry, and an error if it cannot be read.
+pub fn find_menu_entry(menu_file: &Path, value: &str) -> Result<Option<MenuEntry>, std::io::Error> {
+    let entry = read_menu_file(menu_file)?
+        .into_iter()
+        .find(|entry| entry.value == value);
+    Ok(entry)
+}
+
+/// The path to the ground-based windows menu in the GGG installation at `ggg_path`.
+pub fn ground_windows_menu(ggg_path: &Path) -> PathBuf {
+    ggg_path.join("windows").join("gnd").join("windows.men")
+}
+
+/// Get the index of [`EM27_WINDOW_FILE`] in the ground-based windows menu of the GGG
+/// installation at `ggg_path`, i.e. the answer to give gsetup to use the EM27 windows.
+///
+/// # Errors
+/// - if the windows menu cannot be read, or
+/// - if it does not have the EM27 window file, usually because `em27-init` was not run.
+pub fn find_em27_window_index(ggg_path: &Path) -> Result<usize, Em27WindowError> {
+    let menu_file = ground_windows_menu(ggg_path);
+    match find_menu_entry(&menu_file, EM27_WINDOW_FILE) {
+        Ok(Some(entry)) => Ok(entry.index),
+        Ok(None) => Err(Em27WindowError::NotFound(menu_file)),
+        Err(e) => Err(Em27WindowError::ReadMenu(menu_file, e)),
+    }
+}
+
+/// The path to the spectral region (linelist) menu in the GGG installation at `ggg_path`.
+pub fn linelist_menu(ggg_path: &Path) -> PathBuf {
+    ggg_path.join("linelist").join("linelist.men")
+}
+
+/// Get the index of [`EM27_LINELIST`] in the spectral region menu of the GGG
+/// installation at `ggg_path`, i.e. the answer to give gsetup's spectral region prompt.
+///
+/// # Errors
+/// - if the spectral region menu cannot be read, or
+/// - if it does not have the EM27 linelist.
+pub fn find_em27_linelist_index(ggg_path: &Path) -> Result<usize, Em27LinelistError> {
+    let menu_file = linelist_menu(ggg_path);
+    match find_menu_entry(&menu_file, EM27_LINELIST) {
+        Ok(Some(entry)) => Ok(entry.index),
+        Ok(None) => Err(Em27LinelistError::NotFound(menu_file)),
+        Err(e) => Err(Em27LinelistError::ReadMenu(menu_file, e)),
+    }
+}
+
+/// Split a menu file line into its value and description, see [`read_menu_file`].
+/// Returns `None` for a blank line.
+fn parse_menu_line(line: &str) -> Option<(String, Option<String>)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    let (value, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    let mut description = String::new();
+    let mut pending_ws = String::new();
+    for c in rest.trim_start().chars() {
+        if c.is_whitespace() {
+            pending_ws.push(c);
+            continue;
+        }
+        if pending_ws.contains('\t') {
+            description.push(' ');
+        } else {
+            description.push_str(&pending_ws);
+        }
+        pending_ws.clear();
+        description.push(c);
+    }
+
+    let description = (!description.is_empty()).then_some(description);
+    Some((value.to_string(), description))
+}
+
 pub fn get_user_menu_selection(entries: &[MenuEntry]) -> Result<usize, inquire::InquireError> {
     let mut min_index = 1;
     let mut max_index = 1;
@@ -75,10 +376,15 @@ pub fn get_user_menu_selection(entries: &[MenuEntry]) -> Result<usize, inquire::
     }
 }
 
+/// One entry of a GGG menu file, as read by [`read_menu_file`].
+#[derive(Debug, Clone)]
 pub struct MenuEntry {
+    /// The 1-based position of this entry in the menu (not counting the header or blank lines).
     pub index: usize,
     pub value: String,
     pub description: Option<String>,
+    /// The line as it appears in the menu file, for rewriting the menu without changing its formatting.
+    pub raw: String,
 }
 
 pub fn add_menu_entry(file: &Path, value: &str, description: Option<&str>) -> std::io::Result<()> {
@@ -88,9 +394,9 @@ pub fn add_menu_entry(file: &Path, value: &str, description: Option<&str>) -> st
         f.read_to_string(&mut current_contents)?;
     }
 
-    // Try to figure out where the description should start - assume that the first line
-    // is the header and that it has description or similar as the second word
-    let desc_start_index = find_nth_word_index(&current_contents, 1);
+    // Try to figure out where the description should start so that the new line lines up
+    // with the existing ones.
+    let desc_start_index = find_description_column(&current_contents);
     let mut new_line = String::from(value);
     if let Some(desc) = description {
         if let Some(idesc) = desc_start_index {
@@ -125,19 +431,92 @@ pub fn add_menu_entry(file: &Path, value: &str, description: Option<&str>) -> st
         log::warn!("Adding entry to empty menu file, {}", file.display());
     }
 
+    backup_menu_file(file)?;
+    let mut f = std::fs::File::create(&file)?;
+    for line in lines {
+        writeln!(&mut f, "{line}")?;
+    }
+
+    Ok(())
+}
+
+/// Remove the entry with the given value from a GGG menu file.
+///
+/// The first line is assumed to be the header and is always kept; all other lines
+/// are written back unchanged. As with [`add_menu_entry`], the original file is
+/// moved to a backup with ".bak" appended to its extension. Returns `true` if an
+/// entry was removed and `false` (without modifying the file) if no entry matched.
+pub fn remove_menu_entry(file: &Path, value: &str) -> std::io::Result<bool> {
+    let mut current_contents = String::new();
+    {
+        let mut f = std::fs::File::open(file)?;
+        f.read_to_string(&mut current_contents)?;
+    }
+
+    let mut removed = false;
+    let mut lines = vec![];
+    for (iline, line) in current_contents.lines().enumerate() {
+        if iline > 0 && line.split_whitespace().next() == Some(value) {
+            removed = true;
+        } else {
+            lines.push(line);
+        }
+    }
+
+    if !removed {
+        return Ok(false);
+    }
+
+    backup_menu_file(file)?;
+    let mut f = std::fs::File::create(file)?;
+    for line in lines {
+        writeln!(&mut f, "{line}")?;
+    }
+
+    Ok(true)
+}
+
+/// Move a menu file to a backup with ".bak" appended to its extension
+/// (e.g. "windows.men" to "windows.men.bak") before it is rewritten.
+fn backup_menu_file(file: &Path) -> std::io::Result<()> {
     let mut ext = file
         .extension()
         .map(|ext| ext.to_string_lossy().to_string())
-        .unwrap_or_else(|| String::new());
+        .unwrap_or_default();
     ext.push_str(".bak");
     let backup = file.with_extension(ext);
-    std::fs::rename(&file, &backup)?;
-    let mut f = std::fs::File::create(&file)?;
+    std::fs::rename(file, backup)
+}
+
+/// Find the character index where the description column of a menu file starts.
+///
+/// This uses the most common start of the second column among the existing entries,
+/// since the header's first column label may be more than one word. If there are no
+/// entries with a description, this falls back to assuming that the description starts
+/// at the second word of the header.
+fn find_description_column(contents: &str) -> Option<usize> {
+    let mut lines = contents.lines();
+    let header = lines.next()?;
+
+    let mut counts: Vec<(usize, usize)> = vec![];
     for line in lines {
-        writeln!(&mut f, "{line}")?;
+        let Some(idesc) = find_nth_word_index(line, 1) else {
+            continue;
+        };
+        if let Some(entry) = counts.iter_mut().find(|(i, _)| *i == idesc) {
+            entry.1 += 1;
+        } else {
+            counts.push((idesc, 1));
+        }
     }
 
-    Ok(())
+    // max_by_key returns the last of equal elements, so reverse to prefer the earliest row in a tie
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, n)| *n)
+        .map(|(i, _)| i)
+        .or_else(|| find_nth_word_index(header, 1))
 }
 
 fn find_nth_word_index(s: &str, n: usize) -> Option<usize> {
@@ -161,7 +540,197 @@ fn find_nth_word_index(s: &str, n: usize) -> Option<usize> {
 
 #[cfg(test)]
 mod tests {
-    use super::find_nth_word_index;
+    use std::path::{Path, PathBuf};
+
+    use std::io::Write;
+
+    use super::{
+        add_menu_entry, ensure_trailing_path_sep, find_description_column,
+        find_em27_linelist_index, find_em27_window_index, find_menu_entry, find_nth_word_index,
+        read_menu_file, relative_path, remove_menu_entry, with_trailing_path_sep, LineEnding,
+        LineEndingWriter,
+    };
+
+    #[test]
+    fn test_expand_user() {
+        let expand = |p: &str| {
+            super::expand_user_with(
+                Path::new(p),
+                || Some(PathBuf::from("/home/me")),
+                |user| (user == "alice").then(|| PathBuf::from("/home/alice")),
+            )
+        };
+
+        assert_eq!(expand("~/met/xa.txt"), PathBuf::from("/home/me/met/xa.txt"));
+        assert_eq!(expand("~"), PathBuf::from("/home/me"));
+        assert_eq!(expand("~alice/x"), PathBuf::from("/home/alice/x"));
+        assert_eq!(expand("~bob/x"), PathBuf::from("~bob/x"));
+        assert_eq!(expand("/data/~/x"), PathBuf::from("/data/~/x"));
+        assert_eq!(expand("met~1.txt"), PathBuf::from("met~1.txt"));
+        assert_eq!(expand("/data/met.txt"), PathBuf::from("/data/met.txt"));
+    }
+
+    static SAMPLE_MENU: &str = "Filename          Description
+tccon.gnd         Standard TCCON windows
+em27.gnd          Subset of standard windows for an EM27
+extra.gnd         Extra windows
+";
+
+    static MULTIWORD_HEADER_MENU: &str = "Window file     Description of window
+tccon.gnd       Standard TCCON windows
+em27.gnd        Subset of standard windows for an EM27
+";
+
+    fn write_sample_menu(dir: &Path, name: &str) -> PathBuf {
+        write_menu(dir, name, SAMPLE_MENU)
+    }
+
+    fn write_menu(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let menu_file = dir.join(name);
+        std::fs::write(&menu_file, contents).unwrap();
+        menu_file
+    }
+
+    #[test]
+    fn test_trailing_path_sep() {
+        let s = ensure_trailing_path_sep(Path::new("/data/xx/spectra")).unwrap();
+        assert_eq!(s, "/data/xx/spectra/");
+        let s = ensure_trailing_path_sep(Path::new("/data/xx/spectra/")).unwrap();
+        assert_eq!(s, "/data/xx/spectra/");
+        let s = ensure_trailing_path_sep(Path::new("spectra")).unwrap();
+        assert_eq!(s, "spectra/");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_path() {
+        let rel = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to)).unwrap();
+        assert_eq!(
+            rel("/data/runs/20240401", "/data/igrams/20240401/"),
+            PathBuf::from("../../igrams/20240401")
+        );
+        assert_eq!(
+            rel("/data/runs/20240401", "/data/runs/20240401/spectra"),
+            PathBuf::from("spectra")
+        );
+        assert_eq!(rel("/data/runs/", "/data/runs"), PathBuf::from("."));
+        assert_eq!(
+            rel("/data/./runs/../runs/x", "/data/igrams"),
+            PathBuf::from("../../igrams")
+        );
+    }
+
+    #[test]
+    fn test_line_ending_writer() {
+        let mut buf = vec![];
+        let mut w = LineEndingWriter::new(&mut buf, LineEnding::Crlf);
+        w.write_all(b"a\nb\r").unwrap();
+        w.write_all(b"\nc\n\n").unwrap();
+        assert_eq!(buf, b"a\r\nb\r\nc\r\n\r\n");
+
+        let mut buf = vec![];
+        let mut w = LineEndingWriter::new(&mut buf, LineEnding::Lf);
+        w.write_all(b"a\nb\r\n").unwrap();
+        assert_eq!(buf, b"a\nb\r\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_trailing_path_sep_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let p = Path::new(OsStr::from_bytes(b"/data/\xff"));
+        assert_eq!(
+            with_trailing_path_sep(p).as_os_str().as_bytes(),
+            b"/data/\xff/"
+        );
+        let err = ensure_trailing_path_sep(p).unwrap_err();
+        assert_eq!(err.0, p);
+    }
+
+    #[test]
+    fn test_find_menu_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let menu_file = write_sample_menu(tmp.path(), "find.men");
+        let entry = find_menu_entry(&menu_file, "extra.gnd").unwrap().unwrap();
+        assert_eq!(entry.index, 3);
+        assert_eq!(entry.description.as_deref(), Some("Extra windows"));
+        assert!(find_menu_entry(&menu_file, "Filename").unwrap().is_none());
+
+        let ggg_path = tmp.path().join("find_ggg");
+        let windows_dir = ggg_path.join("windows").join("gnd");
+        std::fs::create_dir_all(&windows_dir).unwrap();
+        assert!(matches!(
+            find_em27_window_index(&ggg_path),
+            Err(super::Em27WindowError::ReadMenu(..))
+        ));
+        std::fs::write(windows_dir.join("windows.men"), MULTIWORD_HEADER_MENU).unwrap();
+        assert_eq!(find_em27_window_index(&ggg_path).unwrap(), 2);
+        std::fs::write(windows_dir.join("windows.men"), "Filename\ntccon.gnd\n").unwrap();
+        assert!(matches!(
+            find_em27_window_index(&ggg_path),
+            Err(super::Em27WindowError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_em27_linelist_index() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let ggg_path = tmp.path().join("linelist_ggg");
+        let linelist_dir = ggg_path.join("linelist");
+        std::fs::create_dir_all(&linelist_dir).unwrap();
+        assert!(matches!(
+            find_em27_linelist_index(&ggg_path),
+            Err(super::Em27LinelistError::ReadMenu(..))
+        ));
+        std::fs::write(
+            linelist_dir.join("linelist.men"),
+            "Linelist   Description\natm.101     Old atmospheric linelist\natm.161     Atmospheric linelist\n",
+        )
+        .unwrap();
+        assert_eq!(find_em27_linelist_index(&ggg_path).unwrap(), 2);
+        std::fs::write(linelist_dir.join("linelist.men"), "Linelist\natm.101\n").unwrap();
+        assert!(matches!(
+            find_em27_linelist_index(&ggg_path),
+            Err(super::Em27LinelistError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_padded_menu() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let menu_file = write_menu(
+            tmp.path(),
+            "padded.men",
+            concat!(
+                "Filename\tDescription\n",
+                "tccon.gnd                Standard TCCON windows   \n",
+                "em27.gnd\t\tSubset of windows\tfor an EM27\n",
+                "\n",
+                "   extra.gnd \t Extra  windows\n",
+                "bare.gnd  \t \n",
+            ),
+        );
+        let entries = read_menu_file(&menu_file).unwrap();
+        let parsed: Vec<(usize, &str, Option<&str>)> = entries
+            .iter()
+            .map(|e| (e.index, e.value.as_str(), e.description.as_deref()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                (1, "tccon.gnd", Some("Standard TCCON windows")),
+                (2, "em27.gnd", Some("Subset of windows for an EM27")),
+                (3, "extra.gnd", Some("Extra  windows")),
+                (4, "bare.gnd", None),
+            ]
+        );
+        assert_eq!(entries[1].raw, "em27.gnd\t\tSubset of windows\tfor an EM27");
+        assert_eq!(entries[2].raw, "   extra.gnd \t Extra  windows");
+    }
 
     #[test]
     fn test_nth_word_index() {
@@ -175,4 +744,76 @@ mod tests {
         let i4 = find_nth_word_index(s, 3);
         assert_eq!(i4, None);
     }
+
+    #[test]
+    fn test_remove_menu_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let menu_file = write_sample_menu(tmp.path(), "remove.men");
+        let removed = remove_menu_entry(&menu_file, "em27.gnd").unwrap();
+        assert!(removed);
+
+        let contents = std::fs::read_to_string(&menu_file).unwrap();
+        assert_eq!(
+            contents,
+            "Filename          Description
+tccon.gnd         Standard TCCON windows
+extra.gnd         Extra windows
+"
+        );
+        let entries = read_menu_file(&menu_file).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].index, 2);
+        assert_eq!(entries[1].value, "extra.gnd");
+
+        let backup = std::fs::read_to_string(menu_file.with_extension("men.bak")).unwrap();
+        assert_eq!(backup, SAMPLE_MENU);
+    }
+
+    #[test]
+    fn test_remove_menu_entry_not_present() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let menu_file = write_sample_menu(tmp.path(), "not_present.men");
+        // The header should never be treated as an entry
+        for value in ["missing.gnd", "em27", "Filename"] {
+            let removed = remove_menu_entry(&menu_file, value).unwrap();
+            assert!(!removed);
+        }
+
+        let contents = std::fs::read_to_string(&menu_file).unwrap();
+        assert_eq!(contents, SAMPLE_MENU);
+        assert!(!menu_file.with_extension("men.bak").exists());
+    }
+
+    #[test]
+    fn test_description_column() {
+        assert_eq!(find_description_column(SAMPLE_MENU), Some(18));
+        assert_eq!(find_description_column(MULTIWORD_HEADER_MENU), Some(16));
+
+        // With no entries, fall back on the header
+        assert_eq!(
+            find_description_column("Window  Description of window\n"),
+            Some(8)
+        );
+
+        // Entries without descriptions don't say where the column starts
+        assert_eq!(
+            find_description_column("Window  Description of window\ntccon.gnd\n"),
+            Some(8)
+        );
+    }
+
+    #[test]
+    fn test_add_menu_entry_multiword_header() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let menu_file = write_menu(tmp.path(), "multiword.men", MULTIWORD_HEADER_MENU);
+        add_menu_entry(&menu_file, "new.gnd", Some("New windows")).unwrap();
+        let contents = std::fs::read_to_string(&menu_file).unwrap();
+        assert_eq!(
+            contents,
+            format!("{MULTIWORD_HEADER_MENU}new.gnd         New windows\n")
+        );
+    }
 }
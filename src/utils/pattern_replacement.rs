@@ -14,6 +14,16 @@ mod met_ext_script;
 pub enum PatternError {
     #[error("Unknown key '{0}' in pattern string")]
     UnknownKey(String),
+    #[error(
+        "The ENV key in a pattern string must be followed by a variable name, e.g. {{ENV:HOME}}"
+    )]
+    MissingEnvVarName,
+    #[error(
+        "Environmental variable '{0}' used in pattern string is not set or is not valid unicode"
+    )]
+    EnvVarUnavailable(String),
+    #[error("The {0} key cannot be used in this pattern string because its value is not known")]
+    ValueUnavailable(String),
 }
 
 pub(super) trait PatternReplacer {
@@ -44,6 +54,51 @@ pub(super) trait PatternReplacer {
             .next()
             .expect("Should always be able to get at least one substring out of a format string");
         let fmt = split.next();
+        if key == "ENV" {
+            // Environmental variables are available to every pattern, so handle them here
+            // rather than in each implementation of `get_replacement_value`.
+            let var_name = fmt.ok_or(PatternError::MissingEnvVarName)?;
+            let value = std::env::var(var_name)
+                .map_err(|_| PatternError::EnvVarUnavailable(var_name.to_string()))?;
+            return Ok(value.into());
+        }
         self.get_replacement_value(key, fmt)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestReplacer;
+
+    impl PatternReplacer for TestReplacer {
+        fn get_replacement_value(
+            &self,
+            key: &str,
+            _fmt: Option<&str>,
+        ) -> Result<Cow<'_, str>, PatternError> {
+            match key {
+                "SITE_ID" => Ok("xx".into()),
+                _ => Err(PatternError::UnknownKey(key.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_env_pattern() {
+        std::env::set_var("EGI_TEST_PATTERN_DATA_ROOT", "/data/em27");
+        let p1 = TestReplacer
+            .render_pattern("{ENV:EGI_TEST_PATTERN_DATA_ROOT}/{SITE_ID}/igms")
+            .unwrap();
+        assert_eq!(p1, "/data/em27/xx/igms");
+
+        let e = TestReplacer.render_pattern("{ENV:EGI_TEST_PATTERN_UNSET_VARIABLE}/{SITE_ID}");
+        assert!(
+            matches!(e, Err(PatternError::EnvVarUnavailable(v)) if v == "EGI_TEST_PATTERN_UNSET_VARIABLE")
+        );
+
+        let e = TestReplacer.render_pattern("{ENV}/{SITE_ID}");
+        assert!(matches!(e, Err(PatternError::MissingEnvVarName)));
+    }
+}
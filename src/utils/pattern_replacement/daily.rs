@@ -1,14 +1,31 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, path::Path};
 
 use super::{PatternError, PatternReplacer};
+use crate::utils::expand_user;
 
+/// Render a pattern for one date, replacing the {DATE}, {YEAR}, {MONTH}, {DOY}, {SITE_ID},
+/// and {ENV:NAME} placeholders.
+///
+/// If the rendered pattern starts with a `~`, that is expanded to the home directory
+/// (see [`expand_user`]), as the shell would for a path given on the command line.
 pub fn render_daily_pattern(
     pattern: &str,
     date: chrono::NaiveDate,
     site_id: &str,
 ) -> Result<String, PatternError> {
     let rep = DailyPatternReplacer { date, site_id };
-    rep.render_pattern(pattern)
+    let rendered = rep.render_pattern(pattern)?;
+    if !rendered.starts_with('~') {
+        return Ok(rendered);
+    }
+
+    match expand_user(Path::new(&rendered))
+        .into_os_string()
+        .into_string()
+    {
+        Ok(expanded) => Ok(expanded),
+        Err(_) => Ok(rendered),
+    }
 }
 
 struct DailyPatternReplacer<'a> {
@@ -28,6 +45,9 @@ impl<'a> PatternReplacer for DailyPatternReplacer<'a> {
                 let datestr = self.date.format(fmt).to_string();
                 Ok(datestr.into())
             }
+            "YEAR" => Ok(self.date.format("%Y").to_string().into()),
+            "MONTH" => Ok(self.date.format("%m").to_string().into()),
+            "DOY" => Ok(self.date.format("%j").to_string().into()),
             "SITE_ID" => Ok(self.site_id.into()),
             _ => Err(PatternError::UnknownKey(key.to_string()).into()),
         }
@@ -65,6 +85,26 @@ mod tests {
         assert_eq!(p5, "/data/4.1");
     }
 
+    #[test]
+    fn test_date_shorthand_pattern() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+        let p1 = "/data/{YEAR}/{MONTH}/{DOY}";
+        let p1 = render_daily_pattern(p1, date, "").unwrap();
+        assert_eq!(p1, "/data/2024/02/034");
+
+        let p2 = "/data/{SITE_ID}{YEAR}{DOY}/{DATE}";
+        let p2 = render_daily_pattern(p2, date, "xx").unwrap();
+        assert_eq!(p2, "/data/xx2024034/2024-02-03");
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let p3 = render_daily_pattern("{DOY}", date, "").unwrap();
+        assert_eq!(p3, "366");
+
+        let p4 = "/data/{year}";
+        let e = render_daily_pattern(p4, date, "");
+        assert!(e.is_err());
+    }
+
     #[test]
     fn test_site_id_pattern() {
         let date = chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
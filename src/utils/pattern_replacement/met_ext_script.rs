@@ -2,28 +2,41 @@ use std::borrow::Cow;
 
 use chrono::{DateTime, FixedOffset};
 
+use crate::config::DetectorSet;
+
 use super::{PatternError, PatternReplacer};
 
 const DEFAULT_TIME_FMT: &'static str = "%Y-%m-%dT%H:%M:%S%z";
 
+/// Render an argument for an external met script.
+///
+/// `site_id` and `detectors` may be `None` if they are not known (e.g. when creating a
+/// catalog outside of a daily run); in that case, using `{SITE_ID}` or `{DETECTORS}` in
+/// the pattern is an error.
 pub fn render_met_script_arg_pattern(
     pattern: &str,
     first_igram_time: DateTime<FixedOffset>,
     last_igram_time: DateTime<FixedOffset>,
+    site_id: Option<&str>,
+    detectors: Option<DetectorSet>,
 ) -> Result<String, PatternError> {
     let rep = MetArgReplacer {
         first_igram_time,
         last_igram_time,
+        site_id,
+        detectors,
     };
     rep.render_pattern(pattern)
 }
 
-struct MetArgReplacer {
+struct MetArgReplacer<'a> {
     first_igram_time: DateTime<FixedOffset>,
     last_igram_time: DateTime<FixedOffset>,
+    site_id: Option<&'a str>,
+    detectors: Option<DetectorSet>,
 }
 
-impl PatternReplacer for MetArgReplacer {
+impl<'a> PatternReplacer for MetArgReplacer<'a> {
     fn get_replacement_value(
         &self,
         key: &str,
@@ -40,6 +53,14 @@ impl PatternReplacer for MetArgReplacer {
                 let timestr = self.last_igram_time.format(fmt).to_string();
                 Ok(timestr.into())
             }
+            "SITE_ID" => self
+                .site_id
+                .map(Cow::Borrowed)
+                .ok_or_else(|| PatternError::ValueUnavailable(key.to_string())),
+            "DETECTORS" => self
+                .detectors
+                .map(|d| d.config_name().into())
+                .ok_or_else(|| PatternError::ValueUnavailable(key.to_string())),
             _ => Err(PatternError::UnknownKey(key.to_string())),
         }
     }
@@ -54,9 +75,9 @@ mod tests {
         let t1 = DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
         let t2 = DateTime::parse_from_rfc3339("2025-03-01T18:00:00Z").unwrap();
 
-        let p1 = render_met_script_arg_pattern("-s{FIRST_IGRAM_TIME}", t1, t2).unwrap();
+        let p1 = render_met_script_arg_pattern("-s{FIRST_IGRAM_TIME}", t1, t2, None, None).unwrap();
         assert_eq!(p1, "-s2025-03-01T06:00:00+0000");
-        let p2 = render_met_script_arg_pattern("-e{LAST_IGRAM_TIME}", t1, t2).unwrap();
+        let p2 = render_met_script_arg_pattern("-e{LAST_IGRAM_TIME}", t1, t2, None, None).unwrap();
         assert_eq!(p2, "-e2025-03-01T18:00:00+0000");
     }
 
@@ -69,8 +90,47 @@ mod tests {
             "{FIRST_IGRAM_TIME:%y/%m/%d/%H/%M%:::z},{LAST_IGRAM_TIME:%y/%m/%d/%H/%M%:::z}",
             t1,
             t2,
+            None,
+            None,
         )
         .unwrap();
         assert_eq!(p1, "25/03/01/06/00+00,25/03/01/18/00+00");
     }
+
+    #[test]
+    fn test_site_and_detector_patterns() {
+        let t1 = DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
+        let t2 = DateTime::parse_from_rfc3339("2025-03-01T18:00:00Z").unwrap();
+
+        let p = render_met_script_arg_pattern(
+            "--table={SITE_ID}_{DETECTORS}",
+            t1,
+            t2,
+            Some("xx"),
+            Some(DetectorSet::Dual),
+        )
+        .unwrap();
+        assert_eq!(p, "--table=xx_dual");
+
+        let p = render_met_script_arg_pattern(
+            "{SITE_ID}:{FIRST_IGRAM_TIME:%Y%m%d}",
+            t1,
+            t2,
+            Some("xx"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(p, "xx:20250301");
+    }
+
+    #[test]
+    fn test_unavailable_site_and_detectors() {
+        let t1 = DateTime::parse_from_rfc3339("2025-03-01T06:00:00Z").unwrap();
+        let t2 = DateTime::parse_from_rfc3339("2025-03-01T18:00:00Z").unwrap();
+
+        let e = render_met_script_arg_pattern("{SITE_ID}", t1, t2, None, None).unwrap_err();
+        assert!(matches!(e, PatternError::ValueUnavailable(k) if k == "SITE_ID"));
+        let e = render_met_script_arg_pattern("{DETECTORS}", t1, t2, Some("xx"), None).unwrap_err();
+        assert!(matches!(e, PatternError::ValueUnavailable(k) if k == "DETECTORS"));
+    }
 }
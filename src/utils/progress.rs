@@ -0,0 +1,89 @@
+//! Progress reporting for the command line programs.
+//!
+//! Library functions that can take a long time (e.g. [`crate::i2s_catalog::make_catalog_entries`])
+//! accept a progress callback rather than drawing anything themselves; [`ProgressReporter`] is the
+//! callback the EGI programs use. It is only available with the (default) "progress" feature.
+use std::io::IsTerminal;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Report progress through a list of items: as a progress bar if stderr is a terminal,
+/// otherwise as a log line every 10% of the items.
+pub struct ProgressReporter {
+    bar: Option<ProgressBar>,
+    label: String,
+    next_log: usize,
+}
+
+impl ProgressReporter {
+    /// Create a new reporter. `label` describes what is being done, e.g. "Cataloging interferograms".
+    pub fn new(label: &str) -> Self {
+        let bar = if std::io::stderr().is_terminal() {
+            let style = ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} ({eta})")
+                .expect("progress bar template should be valid")
+                .progress_chars("=> ");
+            Some(
+                ProgressBar::hidden()
+                    .with_style(style)
+                    .with_message(label.to_string()),
+            )
+        } else {
+            None
+        };
+
+        Self {
+            bar,
+            label: label.to_string(),
+            next_log: 0,
+        }
+    }
+
+    /// Record that `done` out of `total` items are finished.
+    pub fn update(&mut self, done: usize, total: usize) {
+        if let Some(bar) = &self.bar {
+            if bar.is_hidden() {
+                bar.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+            }
+            bar.set_length(total as u64);
+            bar.set_position(done as u64);
+        } else if done >= self.next_log || done == total {
+            log::info!("{}: {done}/{total}", self.label);
+            self.next_log = done + (total / 10).max(1);
+        }
+    }
+
+    /// Remove the progress bar (if any) once all items are done.
+    pub fn finish(self) {
+        if let Some(bar) = self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressReporter;
+
+    #[test]
+    fn test_log_interval() {
+        let mut progress = ProgressReporter {
+            bar: None,
+            label: "Testing".to_string(),
+            next_log: 0,
+        };
+
+        let mut logged_at = vec![];
+        for done in 0..=25 {
+            let before = progress.next_log;
+            progress.update(done, 25);
+            if progress.next_log != before {
+                logged_at.push(done);
+            }
+        }
+        assert_eq!(
+            logged_at,
+            [0, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 25]
+        );
+        progress.finish();
+    }
+}
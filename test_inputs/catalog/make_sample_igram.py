@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+"""Write the minimal OPUS interferogram used by the catalog tests in src/i2s_catalog.rs.
+
+The file has only what EGI reads from an interferogram header: the instrument status block
+(with the TSC instrument temperature and INS instrument name), the data status block of the
+primary channel (with NPT, DAT, and TIM), and a short primary interferogram data block.
+
+Run this from this directory to regenerate xx20240401s0e00a.0001 if the test needs different
+header values; the expected values in the test must then be updated to match.
+"""
+import struct
+
+MAGIC = 0xFEFE0A0A
+PROGRAM_VERSION = 920622.0
+DIRECTORY_START = 24
+
+# Bruker block type codes
+INSTRUMENT_STATUS = 32
+IGRAM_PRIMARY_STATUS = 2071
+IGRAM_PRIMARY_DATA = 2055
+
+# Bruker parameter type codes
+INT32, REAL64, STRING = 0, 1, 2
+
+NPT = 16
+
+
+def param(name, value):
+    """Encode one parameter: 4-byte name, type, size in 16-bit words, then the value."""
+    if isinstance(value, int):
+        ptype, data = INT32, struct.pack("<i", value)
+    elif isinstance(value, float):
+        ptype, data = REAL64, struct.pack("<d", value)
+    else:
+        data = value.encode("ascii") + b"\0"
+        # Pad strings so that each parameter (and so each block) stays 4-byte aligned
+        data += b"\0" * (-len(data) % 4)
+        ptype = STRING
+    return name.encode("ascii").ljust(4, b"\0") + struct.pack("<hh", ptype, len(data) // 2) + data
+
+
+def param_block(params):
+    end = b"END\0" + struct.pack("<hh", INT32, 0)
+    return b"".join(param(k, v) for k, v in params) + end
+
+
+def main():
+    blocks = [
+        (INSTRUMENT_STATUS, param_block([("INS", "EM27/SUN"), ("TSC", 30.5)])),
+        (
+            IGRAM_PRIMARY_STATUS,
+            param_block(
+                [
+                    ("DPF", 1),
+                    ("NPT", NPT),
+                    ("FXV", 15798.0),
+                    ("LXV", 0.0),
+                    ("CSF", 1.0),
+                    ("MXY", 1.0),
+                    ("MNY", -1.0),
+                    ("DAT", "01/04/2024"),
+                    ("TIM", "18:05:00.000 (GMT+0)"),
+                ]
+            ),
+        ),
+        (
+            IGRAM_PRIMARY_DATA,
+            struct.pack(f"<{NPT}f", *[(-1.0) ** i / (1 + abs(i - NPT // 2)) for i in range(NPT)]),
+        ),
+    ]
+
+    offset = DIRECTORY_START + 12 * len(blocks)
+    directory = b""
+    contents = b""
+    for block_type, data in blocks:
+        assert len(data) % 4 == 0
+        directory += struct.pack("<Iii", block_type, len(data) // 4, offset + len(contents))
+        contents += data
+
+    header = struct.pack("<Idiii", MAGIC, PROGRAM_VERSION, DIRECTORY_START, len(blocks), len(blocks))
+    assert len(header) == DIRECTORY_START
+    with open("xx20240401s0e00a.0001", "wb") as f:
+        f.write(header + directory + contents)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,9 @@
+#!/usr/bin/env python3
+import json
+import sys
+
+# Echo back one met entry per interferogram ZPD time given on stdin
+for line in sys.stdin:
+    t = line.strip()
+    if t:
+        print(json.dumps({'datetime': t, 'pressure': 1000.0}))